@@ -1,9 +1,16 @@
-use crate::account::AccountError::{AccountLocked, Arithmetic};
+use crate::account::AccountError::{
+	AccountLocked, Arithmetic, BalanceCapExceeded, HeldFundsRemaining, NotLocked,
+};
 use log::debug;
 use AccountError::InsufficientFunds;
 
+use std::collections::BTreeMap;
+
+use rusty_money::iso::Currency;
+use rusty_money::Money;
+
 use crate::amount::{Amount, AmountError};
-use crate::config::ClientId;
+use crate::config::{base_currency, ClientId, TransactionId};
 
 /// Represents the different errors that can occur with an account.
 #[derive(Debug, PartialEq)]
@@ -12,6 +19,12 @@ pub enum AccountError {
 	AccountLocked,
 	/// The account has insufficient funds for the requested operation.
 	InsufficientFunds,
+	/// The operation would take the account's total balance above its maximum.
+	BalanceCapExceeded,
+	/// The account is not locked, so it cannot be unlocked.
+	NotLocked,
+	/// The account still holds funds, so it cannot be closed.
+	HeldFundsRemaining,
 
 	/// An unknown error occurred.
 	Arithmetic(AmountError),
@@ -21,16 +34,41 @@ impl From<AmountError> for AccountError {
 	/// Converts an `AmountError` into a corresponding `AccountError`.
 	///
 	/// This is used to handle cases where an operation on an `Amount` results in an error
-	/// that needs to be represented as an `AccountError`.
+	/// that needs to be represented as an `AccountError`. A negative value is not a shortage of
+	/// funds but an amount that could not be represented, as balances within an overdraft are
+	/// taken below zero without it.
 	fn from(value: AmountError) -> Self {
 		match value {
-			AmountError::NegativeValue(money) => unreachable!("{} Should not be negative", money),
 			AmountError::SubtractToNegative(_, _) => InsufficientFunds,
-			AmountError::InvalidAmount(_) => Arithmetic(value),
+			AmountError::NegativeValue(_)
+			| AmountError::InvalidAmount(_)
+			| AmountError::Overflow(_, _)
+			| AmountError::CurrencyMismatch(_, _) => Arithmetic(value),
 		}
 	}
 }
 
+/// Represents what happens to a deposit that would exceed an account's maximum balance.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum CapPolicy {
+	/// The whole deposit is rejected.
+	#[default]
+	Reject,
+	/// Only the part of the deposit up to the maximum balance is applied.
+	PartiallyApply,
+}
+
+/// The kind of an account, which determines how its balances are read.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum AccountKind {
+	/// An account holding the client's funds.
+	#[default]
+	Debit,
+	/// A credit line, whose balance below zero is the outstanding amount the client owes, up to
+	/// the credit limit in [`Account::overdraft`]. Deposits repay the outstanding amount.
+	Credit,
+}
+
 /// Represents a financial account with available, held, and total balances.
 #[derive(Debug, serde::Serialize, Clone)]
 pub struct Account {
@@ -40,6 +78,56 @@ pub struct Account {
 	pub held: Amount,
 	pub total: Amount,
 	pub locked: bool,
+	/// Whether the account is closed and rejects all further activity, maintained by the
+	/// processor.
+	#[serde(skip)]
+	pub closed: bool,
+	/// How far below zero withdrawals and holds may take the `available` balance, the credit limit
+	/// of a credit account, set by the processor.
+	#[serde(skip)]
+	pub overdraft: Amount,
+	/// Whether the account is a debit account or a credit line, set by the processor.
+	#[serde(skip)]
+	pub kind: AccountKind,
+	/// What happened to the account, maintained by the processor.
+	#[serde(skip)]
+	pub activity: AccountActivity,
+	/// The sub-balances in currencies other than the base currency, by currency code, maintained
+	/// by conversions and by transactions in those currencies.
+	#[serde(skip)]
+	pub balances: BTreeMap<&'static str, CurrencyBalance>,
+}
+
+/// The available and held balances of an account in a currency other than the base currency.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CurrencyBalance {
+	pub available: Amount,
+	pub held: Amount,
+}
+
+impl CurrencyBalance {
+	/// Returns zero balances in `currency`.
+	pub fn zero(currency: &'static Currency) -> Self {
+		CurrencyBalance { available: Amount::zero(currency), held: Amount::zero(currency) }
+	}
+
+	/// Returns the total balance (`available` + `held`), saturating as reported.
+	pub fn total(&self) -> Amount {
+		self.available.saturating_plus(&self.held)
+	}
+}
+
+/// The activity of an account, explaining how it arrived at its state.
+#[derive(Debug, PartialEq, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct AccountActivity {
+	/// The number of deposits applied.
+	pub deposits: u32,
+	/// The number of withdrawals applied.
+	pub withdrawals: u32,
+	/// The number of deposits and withdrawals currently disputed.
+	pub open_disputes: u32,
+	/// The id of the charged back transaction that locked the account.
+	pub locked_by: Option<TransactionId>,
 }
 
 impl Account {
@@ -52,9 +140,77 @@ impl Account {
 	/// * `held` - The initial held balance of the account.
 	/// * `locked` - Whether the account is initially locked.
 	pub fn new(client_id: ClientId, available: Amount, held: Amount, locked: bool) -> Self {
-		let mut total_money = available.clone();
-		total_money.add_assign(held.clone());
-		Self { client_id, available, held, total: total_money, locked }
+		Self {
+			client_id,
+			total: available.saturating_plus(&held),
+			available,
+			held,
+			locked,
+			closed: false,
+			overdraft: Amount::default(),
+			kind: AccountKind::Debit,
+			activity: AccountActivity::default(),
+			balances: BTreeMap::new(),
+		}
+	}
+
+	/// Returns the sub-balances in `currency`, which is not the base currency, starting at zero.
+	pub fn balance_in(&mut self, currency: &'static Currency) -> &mut CurrencyBalance {
+		self.balances
+			.entry(currency.iso_alpha_code)
+			.or_insert_with(|| CurrencyBalance::zero(currency))
+	}
+
+	/// Returns copies of the available and held balances in the currency of `amount`: the
+	/// `available` and `held` balances for the base currency, otherwise its sub-balances in
+	/// `balances`, zero if there are none.
+	fn balances_for(&self, amount: &Amount) -> (Amount, Amount) {
+		if amount.is_base_currency() {
+			return (self.available.clone(), self.held.clone());
+		}
+		let currency = amount.value().currency();
+		match self.balances.get(currency.iso_alpha_code) {
+			Some(balance) => (balance.available.clone(), balance.held.clone()),
+			None => (Amount::zero(currency), Amount::zero(currency)),
+		}
+	}
+
+	/// Sets the available and held balances in the currency of `amount`, see
+	/// [`Self::balances_for`].
+	fn set_balances_for(&mut self, amount: &Amount, available: Amount, held: Amount) {
+		if amount.is_base_currency() {
+			self.available = available;
+			self.held = held;
+		} else {
+			let balance = self.balance_in(amount.value().currency());
+			balance.available = available;
+			balance.held = held;
+		}
+	}
+
+	/// Applies `change` to copies of the available and held balances in the currency of `amount`,
+	/// and only keeps them if it succeeds. An operation failing halfway, such as a hold whose
+	/// held balance would overflow, thus leaves the account unchanged, so that callers can change
+	/// it in place.
+	fn change_balances(
+		&mut self,
+		amount: &Amount,
+		change: impl FnOnce(&mut Amount, &mut Amount) -> Result<(), AmountError>,
+	) -> Result<(), AccountError> {
+		let (mut available, mut held) = self.balances_for(amount);
+		change(&mut available, &mut held)?;
+		self.set_balances_for(amount, available, held);
+		Ok(())
+	}
+
+	/// Returns how far below zero the available balance in the currency of `amount` may go. Only
+	/// the base currency has an overdraft.
+	fn overdraft_for(&self, amount: &Amount) -> Amount {
+		if amount.is_base_currency() {
+			self.overdraft.clone()
+		} else {
+			Amount::default()
+		}
 	}
 
 	/// Deposits an `amount` into the account's `available` balance.
@@ -62,65 +218,180 @@ impl Account {
 	/// # Errors
 	///
 	/// Returns [`AccountLocked`] if the account is locked.
-	pub fn deposit(&mut self, amount: Amount) -> Result<(), AccountError> {
+	/// Returns [`Arithmetic`] if the balance would overflow.
+	pub fn deposit(&mut self, amount: &Amount) -> Result<(), AccountError> {
 		if self.locked {
 			Err(AccountLocked)
 		} else {
 			debug!("Depositing {:?} to account {:?}", amount, self.client_id);
-			self.available.add_assign(amount.clone());
+			self.change_balances(amount, |available, _| available.add_assign(amount))?;
 			debug!("Current account state after deposit: {:?}", self);
 			Ok(())
 		}
 	}
 
+	/// Deposits an `amount` into the account's `available` balance, keeping the total balance at
+	/// most `cap`, and returns the amount actually deposited.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is locked.
+	/// Returns [`BalanceCapExceeded`] if the deposit would exceed `cap` and `policy` is
+	/// [`CapPolicy::Reject`], or if the balance is already at `cap`.
+	/// Returns [`Arithmetic`] if the total balance or the balance would overflow.
+	pub fn deposit_capped(
+		&mut self,
+		amount: &Amount,
+		cap: &Amount,
+		policy: CapPolicy,
+	) -> Result<Amount, AccountError> {
+		// The cap is in the base currency, which deposits in other currencies do not count toward.
+		if !amount.is_base_currency() {
+			self.deposit(amount)?;
+			return Ok(amount.clone());
+		}
+		let headroom = cap.minus_checked(&self.checked_total()?).unwrap_or_default();
+		if amount.value() <= headroom.value() {
+			self.deposit(amount)?;
+			Ok(amount.clone())
+		} else if policy == CapPolicy::PartiallyApply && headroom != Amount::default() {
+			debug!(
+				"Capping deposit of {:?} to {:?} for account {:?}",
+				amount, headroom, self.client_id
+			);
+			self.deposit(&headroom)?;
+			Ok(headroom)
+		} else if self.locked {
+			Err(AccountLocked)
+		} else {
+			Err(BalanceCapExceeded)
+		}
+	}
+
 	/// Withdraws an `amount` from the account's `available` balance.
 	///
 	/// # Errors
 	///
 	/// Returns [`AccountLocked`] if the account is locked.
-	/// Returns [`InsufficientFunds`] if the withdrawal would result in a negative balance.
-	pub fn withdraw(&mut self, amount: Amount) -> Result<(), AccountError> {
+	/// Returns [`InsufficientFunds`] if the withdrawal would take the balance below its overdraft.
+	pub fn withdraw(&mut self, amount: &Amount) -> Result<(), AccountError> {
 		if self.locked {
 			Err(AccountLocked)
 		} else {
 			debug!("Withdrawing {:?} from account {:?}", amount, self.client_id);
-			self.available.checked_sub_assign(amount.clone())?;
+			let overdraft = self.overdraft_for(amount);
+			self.change_balances(amount, |available, _| {
+				available.checked_sub_assign_down_to(amount, &overdraft)
+			})?;
 			debug!("Current account state after withdraw: {:?}", self);
 			Ok(())
 		}
 	}
 
+	/// Converts `amount` from the sub-balance of its currency into `converted` in the sub-balance of
+	/// its currency. The base currency sub-balance is the `available` balance, which may go down to
+	/// its overdraft; the others are kept in `balances` and cannot go negative.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is locked.
+	/// Returns [`InsufficientFunds`] if the sub-balance of `amount` does not cover it.
+	pub fn convert(&mut self, amount: &Amount, converted: &Amount) -> Result<(), AccountError> {
+		if self.locked {
+			return Err(AccountLocked);
+		}
+		debug!("Converting {:?} to {:?} for account {:?}", amount, converted, self.client_id);
+		// Both sub-balances are changed on copies, so a failed credit leaves the debit undone.
+		let overdraft = self.overdraft_for(amount);
+		let (mut from, from_held) = self.balances_for(amount);
+		from.checked_sub_assign_down_to(amount, &overdraft)?;
+		let (mut to, to_held) = if converted.value().currency() == amount.value().currency() {
+			(from.clone(), from_held.clone())
+		} else {
+			self.balances_for(converted)
+		};
+		to.add_assign(converted)?;
+		self.set_balances_for(amount, from, from_held);
+		self.set_balances_for(converted, to, to_held);
+		Ok(())
+	}
+
+	/// Credits an `amount` to the account's `available` balance as an administrative correction,
+	/// even if the account is locked.
+	///
+	/// # Errors
+	///
+	/// Returns [`Arithmetic`] if the balance would overflow.
+	pub fn adjust_credit(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		debug!("Adjusting account {:?} by +{:?}", self.client_id, amount);
+		self.change_balances(amount, |available, _| available.add_assign(amount))?;
+		Ok(())
+	}
+
+	/// Debits an `amount` from the account's `available` balance as an administrative correction,
+	/// even if the account is locked.
+	///
+	/// # Errors
+	///
+	/// Returns [`InsufficientFunds`] if the adjustment would result in a negative balance.
+	pub fn adjust_debit(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		debug!("Adjusting account {:?} by -{:?}", self.client_id, amount);
+		self.change_balances(amount, |available, _| available.checked_sub_assign(amount))?;
+		Ok(())
+	}
+
 	/// Holds an `amount` from the account's `available` balance, transferring it to the `held` balance.
 	///
 	/// # Errors
 	///
 	/// Returns [`AccountLocked`] if the account is locked.
-	/// Returns [`InsufficientFunds`] if the hold would result in a negative available balance.
-	pub fn hold(&mut self, amount: Amount) -> Result<(), AccountError> {
+	/// Returns [`InsufficientFunds`] if the hold would take the available balance below its
+	/// overdraft.
+	pub fn hold(&mut self, amount: &Amount) -> Result<(), AccountError> {
 		if self.locked {
 			Err(AccountLocked)
 		} else {
 			debug!("Holding {:?} from account {:?}", amount, self.client_id);
-			self.held.add_assign(amount.clone());
-			self.available.checked_sub_assign(amount)?;
+			let overdraft = self.overdraft_for(amount);
+			self.change_balances(amount, |available, held| {
+				available.checked_sub_assign_down_to(amount, &overdraft)?;
+				held.add_assign(amount)
+			})?;
 			debug!("Current account state after hold: {:?}", self);
 			Ok(())
 		}
 	}
 
+	/// Releases the `amount` of a cleared deposit from the `held` to the `available` balance, even
+	/// if the account is locked.
+	///
+	/// # Errors
+	///
+	/// Returns [`InsufficientFunds`] if the release would result in a negative held balance.
+	pub fn clear(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		debug!("Clearing {:?} for account {:?}", amount, self.client_id);
+		self.change_balances(amount, |available, held| {
+			held.checked_sub_assign(amount)?;
+			available.add_assign(amount)
+		})?;
+		Ok(())
+	}
+
 	/// Releases a previously held `amount` back to the `available` balance.
 	///
 	/// # Errors
 	///
 	/// Returns [`AccountLocked`] if the account is locked.
 	/// Returns [`InsufficientFunds`] if the release would result in a negative held balance.
-	pub fn release(&mut self, amount: Amount) -> Result<(), AccountError> {
+	pub fn release(&mut self, amount: &Amount) -> Result<(), AccountError> {
 		if self.locked {
 			Err(AccountLocked)
 		} else {
 			debug!("Releasing {:?} from account {:?}", amount, self.client_id);
-			self.held.checked_sub_assign(amount.clone())?;
-			self.available.add_assign(amount);
+			self.change_balances(amount, |available, held| {
+				held.checked_sub_assign(amount)?;
+				available.add_assign(amount)
+			})?;
 			debug!("Current account state after release: {:?}", self);
 			Ok(())
 		}
@@ -132,24 +403,208 @@ impl Account {
 	///
 	/// Returns [`AccountLocked`] if the account is already locked.
 	/// Returns [`InsufficientFunds`] if the chargeback would result in a negative held balance.
-	pub fn chargeback(&mut self, amount: Amount) -> Result<(), AccountError> {
+	pub fn chargeback(&mut self, amount: &Amount) -> Result<(), AccountError> {
 		if self.locked {
 			Err(AccountLocked)
 		} else {
 			debug!("Charging back {:?} from account {:?}", amount, self.client_id);
-			self.held.checked_sub_assign(amount.clone())?;
+			self.change_balances(amount, |_, held| held.checked_sub_assign(amount))?;
 			self.locked = true;
 			debug!("Current account state after chargeback: {:?}", self);
 			Ok(())
 		}
 	}
 
-	/// Calculates and returns the total balance (`available` + `held`) of the account.
+	/// Provisionally credits the disputed `amount` of a withdrawal to the `held` balance, leaving
+	/// the `available` balance untouched.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is locked.
+	pub fn hold_withdrawal(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		if self.locked {
+			Err(AccountLocked)
+		} else {
+			debug!("Holding disputed withdrawal {:?} for account {:?}", amount, self.client_id);
+			self.change_balances(amount, |_, held| held.add_assign(amount))?;
+			debug!("Current account state after withdrawal hold: {:?}", self);
+			Ok(())
+		}
+	}
+
+	/// Drops the provisional credit of a resolved withdrawal dispute from the `held` balance, as
+	/// the withdrawal stands.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is locked.
+	/// Returns [`InsufficientFunds`] if the release would result in a negative held balance.
+	pub fn release_withdrawal(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		if self.locked {
+			Err(AccountLocked)
+		} else {
+			debug!("Releasing disputed withdrawal {:?} for account {:?}", amount, self.client_id);
+			self.change_balances(amount, |_, held| held.checked_sub_assign(amount))?;
+			debug!("Current account state after withdrawal release: {:?}", self);
+			Ok(())
+		}
+	}
+
+	/// Charges back a disputed withdrawal, returning its held `amount` to the `available` balance
+	/// and freezing the account.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is already locked.
+	/// Returns [`InsufficientFunds`] if the chargeback would result in a negative held balance.
+	pub fn chargeback_withdrawal(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		if self.locked {
+			Err(AccountLocked)
+		} else {
+			debug!("Charging back withdrawal {:?} for account {:?}", amount, self.client_id);
+			self.change_balances(amount, |available, held| {
+				held.checked_sub_assign(amount)?;
+				available.add_assign(amount)
+			})?;
+			self.locked = true;
+			debug!("Current account state after withdrawal chargeback: {:?}", self);
+			Ok(())
+		}
+	}
+
+	/// Re-opens a charged back `amount`, crediting it to the `held` balance again. Unlike a hold,
+	/// this is allowed while the account is locked, as it undoes the chargeback that locked it.
+	///
+	/// # Errors
+	///
+	/// Returns [`Arithmetic`] if the held balance would overflow.
+	pub fn reopen(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		debug!("Re-opening chargeback of {:?} for account {:?}", amount, self.client_id);
+		self.change_balances(amount, |_, held| held.add_assign(amount))?;
+		Ok(())
+	}
+
+	/// Re-opens a charged back withdrawal, moving its `amount` from the `available` balance back
+	/// to the `held` balance. Allowed while the account is locked, like [`Self::reopen`].
+	///
+	/// # Errors
+	///
+	/// Returns [`InsufficientFunds`] if the available balance no longer covers the amount.
+	pub fn reopen_withdrawal(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		debug!("Re-opening withdrawal chargeback of {:?} for account {:?}", amount, self.client_id);
+		self.change_balances(amount, |available, held| {
+			available.checked_sub_assign(amount)?;
+			held.add_assign(amount)
+		})?;
+		Ok(())
+	}
+
+	/// Captures a held `amount`, deducting it from the `held` balance as the withdrawal of an
+	/// authorization.
+	///
+	/// # Errors
+	///
+	/// Returns [`AccountLocked`] if the account is locked.
+	/// Returns [`InsufficientFunds`] if the capture would result in a negative held balance.
+	pub fn capture(&mut self, amount: &Amount) -> Result<(), AccountError> {
+		if self.locked {
+			Err(AccountLocked)
+		} else {
+			debug!("Capturing {:?} from account {:?}", amount, self.client_id);
+			self.change_balances(amount, |_, held| held.checked_sub_assign(amount))?;
+			debug!("Current account state after capture: {:?}", self);
+			Ok(())
+		}
+	}
+
+	/// Unfreezes a locked account, forgetting the chargeback that locked it.
+	///
+	/// # Errors
+	///
+	/// Returns [`NotLocked`] if the account is not locked.
+	pub fn unlock(&mut self) -> Result<(), AccountError> {
+		if self.locked {
+			debug!("Unlocking account {:?}", self.client_id);
+			self.locked = false;
+			self.activity.locked_by = None;
+			Ok(())
+		} else {
+			Err(NotLocked)
+		}
+	}
+
+	/// Closes the account, which then rejects all further activity.
+	///
+	/// # Errors
+	///
+	/// Returns [`HeldFundsRemaining`] if the `held` balance or a held sub-balance is not zero.
+	pub fn close(&mut self) -> Result<(), AccountError> {
+		if self.held != Amount::default()
+			|| self.balances.values().any(|balance| !balance.held.value().is_zero())
+		{
+			Err(HeldFundsRemaining)
+		} else {
+			debug!("Closing account {:?}", self.client_id);
+			self.closed = true;
+			Ok(())
+		}
+	}
+
+	/// Calculates and returns the total balance (`available` + `held`) of the account, saturating
+	/// at the largest decimal as reported. Checks against the balance use [`Self::checked_total`].
 	pub fn total(&self) -> Amount {
-		let mut total = Amount::default();
-		total.add_assign(self.available.clone());
-		total.add_assign(self.held.clone());
-		total
+		self.available.saturating_plus(&self.held)
+	}
+
+	/// Calculates the total balance like [`Self::total`], but fails instead of saturating.
+	///
+	/// # Errors
+	///
+	/// Returns [`Arithmetic`] if the total is out of range.
+	pub fn checked_total(&self) -> Result<Amount, AccountError> {
+		Ok(self.available.plus(&self.held)?)
+	}
+
+	/// Returns the amount by which the total balance is below zero, such as the amount owed on a
+	/// credit line, otherwise zero.
+	pub fn outstanding(&self) -> Amount {
+		let total = self.total();
+		let owed = -*total.value().amount();
+		Amount::try_from(Money::from_decimal(owed, base_currency())).unwrap_or_default()
+	}
+
+	/// Returns the credit limit of a credit account, otherwise `None`.
+	pub fn credit_limit(&self) -> Option<&Amount> {
+		(self.kind == AccountKind::Credit).then_some(&self.overdraft)
+	}
+
+	/// Returns the available sub-balances by currency code.
+	pub fn available_balances(&self) -> BTreeMap<&'static str, &Amount> {
+		self.balances
+			.iter()
+			.map(|(code, balance)| (*code, &balance.available))
+			.collect()
+	}
+
+	/// Returns the held sub-balances by currency code.
+	pub fn held_balances(&self) -> BTreeMap<&'static str, &Amount> {
+		self.balances.iter().map(|(code, balance)| (*code, &balance.held)).collect()
+	}
+
+	/// Returns an account for each sub-balance, in the currency of the sub-balance and with the
+	/// client, status and activity of this account, to report every currency of a client like the
+	/// base currency. Sub-balances have no overdraft.
+	pub fn currency_accounts(&self) -> impl Iterator<Item = Account> + '_ {
+		self.balances.values().map(|balance| Account {
+			available: balance.available.clone(),
+			held: balance.held.clone(),
+			total: balance.total(),
+			overdraft: Amount::zero(balance.available.value().currency()),
+			kind: AccountKind::Debit,
+			activity: self.activity.clone(),
+			balances: BTreeMap::new(),
+			..*self
+		})
 	}
 }
 
@@ -180,11 +635,54 @@ mod tests {
 		let mut account = Account::new(client_id, Amount::default(), Amount::default(), false);
 		let deposit_amount = Amount::try_from("50.0").unwrap();
 
-		account.deposit(deposit_amount.clone()).unwrap();
+		account.deposit(&deposit_amount).unwrap();
 
 		assert_eq!(account.available, deposit_amount);
 	}
 
+	#[test]
+	fn test_deposit_capped() {
+		let cap = Amount::try_from("100.0").unwrap();
+		let mut account = Account::new(
+			1,
+			Amount::try_from("60.0").unwrap(),
+			Amount::try_from("20.0").unwrap(),
+			false,
+		);
+
+		let deposited =
+			account.deposit_capped(&Amount::try_from("20.0").unwrap(), &cap, CapPolicy::Reject);
+		assert_eq!(deposited, Ok(Amount::try_from("20.0").unwrap()));
+		assert_eq!(
+			account.deposit_capped(&Amount::try_from("0.01").unwrap(), &cap, CapPolicy::Reject),
+			Err(BalanceCapExceeded)
+		);
+		assert_eq!(account.total(), cap);
+	}
+
+	#[test]
+	fn test_deposit_capped_partially_applied() {
+		let cap = Amount::try_from("100.0").unwrap();
+		let mut account =
+			Account::new(1, Amount::try_from("90.0").unwrap(), Amount::default(), false);
+
+		let deposited = account.deposit_capped(
+			&Amount::try_from("30.0").unwrap(),
+			&cap,
+			CapPolicy::PartiallyApply,
+		);
+		assert_eq!(deposited, Ok(Amount::try_from("10.0").unwrap()));
+		assert_eq!(account.available, cap);
+		assert_eq!(
+			account.deposit_capped(
+				&Amount::try_from("1.0").unwrap(),
+				&cap,
+				CapPolicy::PartiallyApply
+			),
+			Err(BalanceCapExceeded)
+		);
+	}
+
 	#[test]
 	fn test_withdraw() {
 		let client_id = 1;
@@ -192,7 +690,7 @@ mod tests {
 			Account::new(client_id, Amount::try_from("100.0").unwrap(), Amount::default(), false);
 		let withdraw_amount = Amount::try_from("30.0").unwrap();
 
-		account.withdraw(withdraw_amount.clone()).unwrap();
+		account.withdraw(&withdraw_amount).unwrap();
 
 		assert_eq!(account.available, Amount::try_from("70.0").unwrap());
 	}
@@ -204,7 +702,7 @@ mod tests {
 			Account::new(client_id, Amount::try_from("100.0").unwrap(), Amount::default(), false);
 		let hold_amount = Amount::try_from("20.0").unwrap();
 
-		account.hold(hold_amount.clone()).unwrap();
+		account.hold(&hold_amount).unwrap();
 
 		assert_eq!(account.held, hold_amount);
 	}
@@ -216,12 +714,71 @@ mod tests {
 			Account::new(client_id, Amount::try_from("100.0").unwrap(), Amount::default(), false);
 		let hold_amount = Amount::try_from("20.0").unwrap();
 
-		account.hold(hold_amount.clone()).unwrap();
-		account.release(hold_amount.clone()).unwrap();
+		account.hold(&hold_amount).unwrap();
+		account.release(&hold_amount).unwrap();
 
 		assert_eq!(account.held, Amount::default());
 	}
 
+	#[test]
+	fn test_clear() {
+		let amount = Amount::try_from("20.0").unwrap();
+		let mut account = Account::new(1, Amount::default(), amount.clone(), true);
+
+		assert_eq!(account.clear(&Amount::try_from("20.1").unwrap()), Err(InsufficientFunds));
+		account.clear(&amount).unwrap();
+		assert_eq!((account.available, account.held), (amount, Amount::default()));
+	}
+
+	#[test]
+	fn test_convert() {
+		let eur = |value: &str| {
+			let value = rust_decimal::Decimal::from_str_exact(value).unwrap();
+			Amount::try_from(Money::from_decimal(value, rusty_money::iso::EUR)).unwrap()
+		};
+		let mut account =
+			Account::new(1, Amount::try_from("10.0").unwrap(), Amount::default(), false);
+
+		assert_eq!(
+			account.convert(&eur("1.0"), &Amount::try_from("1.1").unwrap()),
+			Err(InsufficientFunds)
+		);
+		account.convert(&Amount::try_from("10.0").unwrap(), &eur("9.2")).unwrap();
+		account.convert(&eur("4.6"), &Amount::try_from("5.0").unwrap()).unwrap();
+		assert_eq!(account.available, Amount::try_from("5.0").unwrap());
+		assert_eq!(account.available_balances().get("EUR"), Some(&&eur("4.6")));
+		assert_eq!(account.total(), Amount::try_from("5.0").unwrap());
+
+		account.locked = true;
+		assert_eq!(
+			account.convert(&eur("4.6"), &Amount::try_from("5.0").unwrap()),
+			Err(AccountLocked)
+		);
+	}
+
+	#[test]
+	fn test_currency_accounts() {
+		let eur = |value: &str| {
+			let value = rust_decimal::Decimal::from_str_exact(value).unwrap();
+			Amount::try_from(Money::from_decimal(value, rusty_money::iso::EUR)).unwrap()
+		};
+		let mut account =
+			Account::new(1, Amount::try_from("10.0").unwrap(), Amount::default(), false);
+		account.convert(&Amount::try_from("10.0").unwrap(), &eur("9.2")).unwrap();
+		account.hold(&eur("2.2")).unwrap();
+		account.locked = true;
+
+		let accounts: Vec<Account> = account.currency_accounts().collect();
+		assert_eq!(accounts.len(), 1);
+		assert_eq!((accounts[0].client_id, accounts[0].locked), (1, true));
+		assert_eq!(
+			(&accounts[0].available, &accounts[0].held, accounts[0].total()),
+			(&eur("7.0"), &eur("2.2"), eur("9.2"))
+		);
+		assert!(accounts[0].balances.is_empty());
+		assert_eq!(account.held_balances().get("EUR"), Some(&&eur("2.2")));
+	}
+
 	#[test]
 	fn test_chargeback() {
 		let client_id = 1;
@@ -233,10 +790,94 @@ mod tests {
 		);
 		let chargeback_amount = Amount::try_from("20.0").unwrap();
 
-		account.chargeback(chargeback_amount.clone()).unwrap();
+		account.chargeback(&chargeback_amount).unwrap();
+
+		assert_eq!(account.held, Amount::default());
+		assert!(account.locked);
+
+		account.reopen(&chargeback_amount).unwrap();
+		assert_eq!(account.held, chargeback_amount);
+	}
+
+	#[test]
+	fn test_overdraft() {
+		let mut account =
+			Account::new(1, Amount::try_from("10.0").unwrap(), Amount::default(), false);
+		account.overdraft = Amount::try_from("25.0").unwrap();
+
+		account.withdraw(&Amount::try_from("30.0").unwrap()).unwrap();
+		assert_eq!(account.available.to_string(), "-20.0");
+		assert_eq!(account.hold(&Amount::try_from("5.01").unwrap()), Err(InsufficientFunds));
+		account.hold(&Amount::try_from("5.0").unwrap()).unwrap();
+		assert_eq!(account.total().to_string(), "-20.0");
+		assert_eq!(account.withdraw(&Amount::try_from("0.01").unwrap()), Err(InsufficientFunds));
+
+		// A negative balance adds normally, and a cap leaves room for the amount below zero.
+		account.deposit(&Amount::try_from("15.0").unwrap()).unwrap();
+		assert_eq!(account.available.to_string(), "-10.0");
+		let cap = Amount::try_from("10.0").unwrap();
+		let deposited =
+			account.deposit_capped(&Amount::try_from("15.0").unwrap(), &cap, CapPolicy::Reject);
+		assert_eq!(deposited, Ok(Amount::try_from("15.0").unwrap()));
+		assert_eq!(account.total(), cap);
+	}
+
+	#[test]
+	fn test_credit_line() {
+		let mut account = Account::new(1, Amount::default(), Amount::default(), false);
+		account.kind = AccountKind::Credit;
+		account.overdraft = Amount::try_from("100.0").unwrap();
+
+		account.withdraw(&Amount::try_from("80.0").unwrap()).unwrap();
+		account.hold(&Amount::try_from("20.0").unwrap()).unwrap();
+		assert_eq!(account.outstanding(), Amount::try_from("80.0").unwrap());
+		assert_eq!(account.withdraw(&Amount::try_from("0.01").unwrap()), Err(InsufficientFunds));
+		account.deposit(&Amount::try_from("30.0").unwrap()).unwrap();
+		assert_eq!(account.outstanding(), Amount::try_from("50.0").unwrap());
+		assert_eq!(account.credit_limit(), Some(&Amount::try_from("100.0").unwrap()));
+		account.deposit(&Amount::try_from("60.0").unwrap()).unwrap();
+		assert_eq!(account.outstanding(), Amount::default());
+	}
+
+	#[test]
+	fn test_withdrawal_dispute() {
+		let mut account =
+			Account::new(1, Amount::try_from("80.0").unwrap(), Amount::default(), false);
+		let withdrawn = Amount::try_from("20.0").unwrap();
+
+		account.hold_withdrawal(&withdrawn).unwrap();
+		assert_eq!(
+			(&account.available, &account.held),
+			(&Amount::try_from("80.0").unwrap(), &withdrawn)
+		);
+		account.release_withdrawal(&withdrawn).unwrap();
+		assert_eq!(account.total(), Amount::try_from("80.0").unwrap());
 
+		account.hold_withdrawal(&withdrawn).unwrap();
+		account.chargeback_withdrawal(&withdrawn).unwrap();
 		assert_eq!(account.held, Amount::default());
+		assert_eq!(account.available, Amount::try_from("100.0").unwrap());
 		assert!(account.locked);
+
+		account.reopen_withdrawal(&withdrawn).unwrap();
+		assert_eq!(
+			(&account.available, &account.held),
+			(&Amount::try_from("80.0").unwrap(), &withdrawn)
+		);
+	}
+
+	#[test]
+	fn test_capture() {
+		let mut account =
+			Account::new(1, Amount::try_from("100.0").unwrap(), Amount::default(), false);
+		let authorized = Amount::try_from("20.0").unwrap();
+
+		account.hold(&authorized).unwrap();
+		account.capture(&authorized).unwrap();
+
+		assert_eq!(account.held, Amount::default());
+		assert_eq!(account.total(), Amount::try_from("80.0").unwrap());
+		assert_eq!(account.capture(&authorized), Err(InsufficientFunds));
 	}
 
 	#[test]
@@ -264,10 +905,78 @@ mod tests {
 			true,
 		);
 
-		assert_eq!(account.deposit(Amount::try_from("10.0").unwrap()), Err(AccountLocked));
-		assert_eq!(account.withdraw(Amount::try_from("10.0").unwrap()), Err(AccountLocked));
-		assert_eq!(account.hold(Amount::try_from("10.0").unwrap()), Err(AccountLocked));
-		assert_eq!(account.release(Amount::try_from("10.0").unwrap()), Err(AccountLocked));
-		assert_eq!(account.chargeback(Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.deposit(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.withdraw(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.hold(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.release(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.chargeback(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+		assert_eq!(account.capture(&Amount::try_from("10.0").unwrap()), Err(AccountLocked));
+
+		account.adjust_credit(&Amount::try_from("10.0").unwrap()).unwrap();
+		account.adjust_debit(&Amount::try_from("50.0").unwrap()).unwrap();
+		assert_eq!(account.available, Amount::try_from("60.0").unwrap());
+		assert_eq!(
+			account.adjust_debit(&Amount::try_from("70.0").unwrap()),
+			Err(InsufficientFunds)
+		);
+
+		account.unlock().unwrap();
+		assert!(!account.locked);
+		assert_eq!(account.unlock(), Err(NotLocked));
+		account.deposit(&Amount::try_from("10.0").unwrap()).unwrap();
+	}
+
+	#[test]
+	fn test_close() {
+		let mut account = Account::new(
+			1,
+			Amount::try_from("10.0").unwrap(),
+			Amount::try_from("5.0").unwrap(),
+			false,
+		);
+
+		assert_eq!(account.close(), Err(HeldFundsRemaining));
+		assert!(!account.closed);
+		account.release(&Amount::try_from("5.0").unwrap()).unwrap();
+		account.close().unwrap();
+		assert!(account.closed);
+	}
+
+	#[test]
+	fn test_deposit_overflow() {
+		let max =
+			Amount::try_from(Money::from_decimal(rust_decimal::Decimal::MAX, base_currency()))
+				.unwrap();
+		let mut account = Account::new(1, max.clone(), Amount::default(), false);
+
+		assert!(matches!(
+			account.deposit(&Amount::try_from("1").unwrap()),
+			Err(Arithmetic(AmountError::Overflow(_, _)))
+		));
+		assert_eq!(account.available, max);
+	}
+
+	#[test]
+	fn test_failed_operation_leaves_account_unchanged() {
+		let max =
+			Amount::try_from(Money::from_decimal(rust_decimal::Decimal::MAX, base_currency()))
+				.unwrap();
+		let ten = Amount::try_from("10").unwrap();
+		let mut account = Account::new(1, ten.clone(), max.clone(), false);
+
+		assert!(matches!(account.hold(&ten), Err(Arithmetic(AmountError::Overflow(_, _)))));
+		assert_eq!((&account.available, &account.held), (&ten, &max));
+
+		let eur = ten.in_currency(rusty_money::iso::EUR);
+		assert_eq!(account.withdraw(&eur), Err(InsufficientFunds));
+		assert!(account.balances.is_empty());
+
+		account.balance_in(rusty_money::iso::EUR).available =
+			max.in_currency(rusty_money::iso::EUR);
+		assert!(matches!(
+			account.convert(&ten, &eur),
+			Err(Arithmetic(AmountError::Overflow(_, _)))
+		));
+		assert_eq!(account.available, ten);
 	}
 }
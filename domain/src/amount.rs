@@ -1,14 +1,26 @@
 use core::fmt;
-use std::ops::AddAssign;
-use std::ops::SubAssign;
+use std::cmp::Ordering;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
 
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 use rusty_money::iso::Currency;
 use rusty_money::{Money, MoneyError};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::amount::AmountError::{NegativeValue, SubtractToNegative};
-use crate::config::CURRENCY;
+use crate::amount::AmountError::{CurrencyMismatch, NegativeValue, Overflow, SubtractToNegative};
+use crate::config::{base_currency, max_decimal_places, rounding};
 
-/// Wrapper for Money, used to enforce positive values and handle deserialization of Money from strings
+/// Wrapper for Money, used to handle deserialization of Money from strings and to keep transaction
+/// amounts positive.
+///
+/// Transaction amounts are never negative: [`TryFrom`] and deserialization reject negative values,
+/// and subtraction fails with [`SubtractToNegative`] rather than going below zero. The one
+/// exception is an available balance, which withdrawals and holds within its overdraft may take
+/// below zero with [`Amount::checked_sub_assign_down_to`], and which is restored with
+/// [`Amount::deserialize_balance`]. Code reading a balance therefore must not assume it is
+/// positive; a negative available balance only fails later subtractions, adds normally and is
+/// reported as such. Values that are negative by nature are [`SignedAmount`]s.
 #[derive(PartialEq, Clone)]
 pub struct Amount {
 	value: Money<'static, Currency>,
@@ -21,6 +33,10 @@ pub enum AmountError {
 	NegativeValue(Money<'static, Currency>),
 	SubtractToNegative(Amount, Amount),
 	InvalidAmount(MoneyError),
+	/// The sum of the two amounts is out of the range of a decimal.
+	Overflow(Amount, Amount),
+	/// The two amounts are in different currencies.
+	CurrencyMismatch(Amount, Amount),
 }
 
 impl std::fmt::Debug for Amount {
@@ -31,13 +47,34 @@ impl std::fmt::Debug for Amount {
 
 impl Default for Amount {
 	fn default() -> Self {
-		Amount { value: Money::from_str("0.0", CURRENCY).expect("0.0 should be valid") }
+		// Not parsed, as the format of the base currency's locale may differ.
+		Amount { value: Money::from_decimal(Decimal::new(0, 1), base_currency()) }
 	}
 }
 
 impl Amount {
-	pub(crate) fn checked_sub_assign(&mut self, rhs: Amount) -> Result<(), AmountError> {
-		if self.value >= rhs.value {
+	pub(crate) fn checked_sub_assign(&mut self, rhs: &Amount) -> Result<(), AmountError> {
+		match self.partial_cmp(rhs) {
+			None => Err(CurrencyMismatch(self.clone(), rhs.clone())),
+			Some(Ordering::Less) => Err(SubtractToNegative(self.clone(), rhs.clone())),
+			Some(_) => {
+				self.value.sub_assign(rhs.value);
+				Ok(())
+			},
+		}
+	}
+
+	/// Subtracts `rhs` like [`Self::checked_sub_assign`], but allows the result to go negative down
+	/// to `-floor`, such as an available balance within its overdraft.
+	pub(crate) fn checked_sub_assign_down_to(
+		&mut self,
+		rhs: &Amount,
+		floor: &Amount,
+	) -> Result<(), AmountError> {
+		if self.value.currency() != rhs.value.currency() {
+			return Err(CurrencyMismatch(self.clone(), rhs.clone()));
+		}
+		if self.value.amount() + floor.value.amount() >= *rhs.value.amount() {
 			self.value.sub_assign(rhs.value);
 			Ok(())
 		} else {
@@ -45,8 +82,149 @@ impl Amount {
 		}
 	}
 
-	pub(crate) fn add_assign(&mut self, rhs: Amount) {
-		self.value.add_assign(rhs.value)
+	/// Deserializes a balance from a string. Unlike a transaction amount, a balance may be negative
+	/// when the account is overdrawn.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the value is not a decimal number.
+	pub fn deserialize_balance<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Self, D::Error> {
+		let value = String::deserialize(deserializer)?;
+		let decimal = Decimal::from_str_exact(value.trim()).map_err(de::Error::custom)?;
+		Ok(Amount { value: Money::from_decimal(decimal, base_currency()) })
+	}
+
+	/// Adds `rhs`, which must be in the same currency.
+	///
+	/// # Errors
+	///
+	/// Returns [`CurrencyMismatch`] if `rhs` is in another currency, or [`Overflow`] if the sum is
+	/// out of range, leaving the amount unchanged.
+	pub fn add_assign(&mut self, rhs: &Amount) -> Result<(), AmountError> {
+		if self.value.currency() != rhs.value.currency() {
+			return Err(CurrencyMismatch(self.clone(), rhs.clone()));
+		}
+		if self.value.amount().checked_add(*rhs.value.amount()).is_none() {
+			return Err(Overflow(self.clone(), rhs.clone()));
+		}
+		self.value.add_assign(rhs.value);
+		Ok(())
+	}
+
+	/// Adds `rhs` like [`Self::add_assign`], but saturates at the largest decimal instead of
+	/// overflowing, for running totals that are only reported, such as statistics. `rhs` must be in
+	/// the same currency, as the sum is kept in the currency of the amount.
+	pub fn saturating_add_assign(&mut self, rhs: &Amount) {
+		debug_assert_eq!(
+			self.value.currency(),
+			rhs.value.currency(),
+			"cannot add amounts in different currencies"
+		);
+		let sum = self.value.amount().saturating_add(*rhs.value.amount());
+		self.value = Money::from_decimal(sum, self.value.currency());
+	}
+
+	/// Returns a zero amount in `currency`.
+	pub fn zero(currency: &'static Currency) -> Amount {
+		Amount { value: Money::from_decimal(Decimal::ZERO, currency) }
+	}
+
+	/// Returns the same value in `currency`, such as an amount read before its currency was known.
+	pub fn in_currency(&self, currency: &'static Currency) -> Amount {
+		Amount { value: Money::from_decimal(*self.value.amount(), currency) }
+	}
+
+	/// Returns whether the amount is in the base currency.
+	pub fn is_base_currency(&self) -> bool {
+		self.value.currency() == base_currency()
+	}
+
+	/// Returns the value converted to `currency` at `rate`, the units of `currency` per unit of the
+	/// amount's currency, unrounded. Returns `None` if the result is out of range.
+	pub fn convert(&self, currency: &'static Currency, rate: Decimal) -> Option<Amount> {
+		let value = self.value.amount().checked_mul(rate)?;
+		Some(Amount { value: Money::from_decimal(value, currency) })
+	}
+
+	/// Returns an amount of `units` minor units of `currency`, such as cents for USD.
+	///
+	/// # Errors
+	///
+	/// Returns [`NegativeValue`] if `units` is negative.
+	pub fn from_minor_units(units: i64, currency: &'static Currency) -> AmountResult {
+		Amount::try_from(Money::from_decimal(Decimal::new(units, currency.exponent), currency))
+	}
+
+	/// Returns the value in minor units of the amount's currency, or `None` if it is not a whole
+	/// number of them or out of range.
+	pub fn minor_units(&self) -> Option<i64> {
+		let exponent = self.value.currency().exponent;
+		let units =
+			self.value.amount().checked_mul(Decimal::from(10_i64.checked_pow(exponent)?))?;
+		units.is_integer().then(|| units.to_i64()).flatten()
+	}
+
+	/// Returns the value rounded with `strategy` to at most `places` decimal places, as output.
+	pub fn rounded(&self, places: u8, strategy: RoundingStrategy) -> Decimal {
+		self.value.amount().round_dp_with_strategy(places.into(), strategy)
+	}
+
+	/// Returns the sum with `rhs` as a new amount.
+	///
+	/// # Errors
+	///
+	/// Returns [`Overflow`] if the sum is out of range.
+	pub fn plus(&self, rhs: &Amount) -> AmountResult {
+		let mut sum = self.clone();
+		sum.add_assign(rhs)?;
+		Ok(sum)
+	}
+
+	/// Returns the sum with `rhs` as a new amount, saturating like [`Self::saturating_add_assign`],
+	/// for totals that are only reported.
+	pub fn saturating_plus(&self, rhs: &Amount) -> Amount {
+		let mut sum = self.clone();
+		sum.saturating_add_assign(rhs);
+		sum
+	}
+
+	/// Returns the difference to `rhs` as a new amount.
+	///
+	/// # Errors
+	///
+	/// Returns [`SubtractToNegative`] if `rhs` is greater than the amount.
+	pub fn minus_checked(&self, rhs: &Amount) -> AmountResult {
+		let mut difference = self.clone();
+		difference.checked_sub_assign(rhs)?;
+		Ok(difference)
+	}
+
+	/// Returns the smaller of the amount and `other`.
+	///
+	/// # Errors
+	///
+	/// Returns [`CurrencyMismatch`] if `other` is in another currency.
+	pub fn min(&self, other: &Amount) -> AmountResult {
+		match self.partial_cmp(other) {
+			None => Err(CurrencyMismatch(self.clone(), other.clone())),
+			Some(Ordering::Greater) => Ok(other.clone()),
+			Some(_) => Ok(self.clone()),
+		}
+	}
+
+	/// Returns the greater of the amount and `other`.
+	///
+	/// # Errors
+	///
+	/// Returns [`CurrencyMismatch`] if `other` is in another currency.
+	pub fn max(&self, other: &Amount) -> AmountResult {
+		match self.partial_cmp(other) {
+			None => Err(CurrencyMismatch(self.clone(), other.clone())),
+			Some(Ordering::Less) => Ok(other.clone()),
+			Some(_) => Ok(self.clone()),
+		}
 	}
 }
 
@@ -55,7 +233,7 @@ impl TryFrom<&str> for Amount {
 
 	fn try_from(value: &str) -> Result<Self, Self::Error> {
 		Amount::try_from(
-			Money::from_str(value, CURRENCY).map_err(|e| AmountError::InvalidAmount(e))?,
+			Money::from_str(value, base_currency()).map_err(AmountError::InvalidAmount)?,
 		)
 	}
 }
@@ -78,6 +256,213 @@ impl Amount {
 	}
 }
 
+/// Sums amounts of the same currency, saturating like [`Amount::saturating_add_assign`]. The sum is
+/// in the currency of the first amount, or zero in the base currency if there are none.
+impl<'a> std::iter::Sum<&'a Amount> for Amount {
+	fn sum<I: Iterator<Item = &'a Amount>>(amounts: I) -> Self {
+		amounts.cloned().sum()
+	}
+}
+
+impl std::iter::Sum for Amount {
+	fn sum<I: Iterator<Item = Amount>>(mut amounts: I) -> Self {
+		let Some(first) = amounts.next() else {
+			return Amount::default();
+		};
+		amounts.fold(first, |mut sum, amount| {
+			sum.saturating_add_assign(&amount);
+			sum
+		})
+	}
+}
+
+/// Adds two amounts of the same currency, failing with [`Overflow`] if the sum is out of range.
+impl Add for Amount {
+	type Output = AmountResult;
+
+	fn add(mut self, rhs: Amount) -> AmountResult {
+		self.add_assign(&rhs)?;
+		Ok(self)
+	}
+}
+
+impl Add<&Amount> for &Amount {
+	type Output = AmountResult;
+
+	fn add(self, rhs: &Amount) -> AmountResult {
+		self.clone() + rhs.clone()
+	}
+}
+
+/// Subtracts an amount, failing with [`SubtractToNegative`] instead of going below zero.
+impl Sub for Amount {
+	type Output = AmountResult;
+
+	fn sub(mut self, rhs: Amount) -> AmountResult {
+		self.checked_sub_assign(&rhs)?;
+		Ok(self)
+	}
+}
+
+impl Sub<&Amount> for &Amount {
+	type Output = AmountResult;
+
+	fn sub(self, rhs: &Amount) -> AmountResult {
+		self.clone() - rhs.clone()
+	}
+}
+
+/// Orders amounts of the same currency by value. Amounts in different currencies are not
+/// comparable.
+impl PartialOrd for Amount {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		(self.value.currency() == other.value.currency())
+			.then(|| self.value.amount().cmp(other.value.amount()))
+	}
+}
+
+/// A money value that may be negative, such as a correction, a delta or a net position.
+///
+/// Converts from an [`Amount`] infallibly, and back only if it is not negative.
+#[derive(PartialEq, Clone)]
+pub struct SignedAmount {
+	value: Money<'static, Currency>,
+}
+
+impl SignedAmount {
+	/// Returns `lhs - rhs`, which must be in the same currency.
+	///
+	/// # Errors
+	///
+	/// Returns [`Overflow`] if the difference is out of range.
+	pub fn difference(lhs: &Amount, rhs: &Amount) -> Result<SignedAmount, AmountError> {
+		let difference = lhs
+			.value
+			.amount()
+			.checked_sub(*rhs.value.amount())
+			.ok_or_else(|| Overflow(lhs.clone(), rhs.clone()))?;
+		Ok(SignedAmount { value: Money::from_decimal(difference, lhs.value.currency()) })
+	}
+
+	/// Adds `rhs`, which must be in the same currency, or returns `None` if the sum is out of
+	/// range.
+	pub fn checked_add(&self, rhs: &SignedAmount) -> Option<SignedAmount> {
+		let sum = self.value.amount().checked_add(*rhs.value.amount())?;
+		Some(SignedAmount { value: Money::from_decimal(sum, self.value.currency()) })
+	}
+
+	pub fn value(&self) -> &Money<'static, Currency> {
+		&self.value
+	}
+
+	pub fn is_negative(&self) -> bool {
+		self.value.is_negative()
+	}
+
+	/// Returns the magnitude of the value, which is never negative.
+	pub fn abs(&self) -> Amount {
+		Amount { value: Money::from_decimal(self.value.amount().abs(), self.value.currency()) }
+	}
+}
+
+impl Default for SignedAmount {
+	fn default() -> Self {
+		Amount::default().into()
+	}
+}
+
+impl From<Amount> for SignedAmount {
+	fn from(amount: Amount) -> Self {
+		SignedAmount { value: amount.value }
+	}
+}
+
+impl TryFrom<SignedAmount> for Amount {
+	type Error = AmountError;
+
+	fn try_from(value: SignedAmount) -> AmountResult {
+		Amount::try_from(value.value)
+	}
+}
+
+impl Neg for SignedAmount {
+	type Output = SignedAmount;
+
+	fn neg(self) -> SignedAmount {
+		SignedAmount { value: Money::from_decimal(-*self.value.amount(), self.value.currency()) }
+	}
+}
+
+impl fmt::Debug for SignedAmount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.value.amount().to_string().as_str())
+	}
+}
+
+/// Formats a signed amount for output, rounded like an [`Amount`].
+impl fmt::Display for SignedAmount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let rounded = self
+			.value
+			.amount()
+			.round_dp_with_strategy(max_decimal_places().into(), rounding());
+		f.write_str(rounded.to_string().as_str())
+	}
+}
+
+impl Serialize for SignedAmount {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+/// Deserializes a signed amount in the base currency from a string, with at most
+/// [`max_decimal_places`].
+impl<'de> Deserialize<'de> for SignedAmount {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let value = String::deserialize(deserializer)?;
+		let decimal = Decimal::from_str_exact(value.trim()).map_err(de::Error::custom)?;
+		let max = max_decimal_places();
+		if decimal.scale() > u32::from(max) {
+			return Err(de::Error::custom(format!(
+				"Too many decimal places: {value}, max allowed: {max}"
+			)));
+		}
+		Ok(SignedAmount { value: Money::from_decimal(decimal, base_currency()) })
+	}
+}
+
+/// Reads a decimal number leniently: surrounding whitespace, `,` thousands separators and a
+/// leading or trailing symbol or code of `currency` are accepted, as in `$1,234.56` or
+/// `1,234.56 USD`. Separators must group the integer digits by three, so that a decimal comma such
+/// as in `1,5` is not mistaken for one. Returns `None` if the value is not a number.
+pub(crate) fn lenient_decimal(value: &str, currency: &Currency) -> Option<Decimal> {
+	let value = value.trim();
+	let value = [currency.symbol, currency.iso_alpha_code]
+		.into_iter()
+		.filter(|marker| !marker.is_empty())
+		.find_map(|marker| value.strip_prefix(marker).or_else(|| value.strip_suffix(marker)))
+		.unwrap_or(value)
+		.trim();
+	let (integer, fraction) = match value.split_once('.') {
+		Some((integer, fraction)) => (integer, Some(fraction)),
+		None => (value, None),
+	};
+	let mut groups = integer.split(',');
+	let mut digits = groups.next()?.to_string();
+	let leading_digits = digits.trim_start_matches('-').len();
+	for group in groups {
+		if !(1..=3).contains(&leading_digits) || group.len() != 3 {
+			return None;
+		}
+		digits.push_str(group);
+	}
+	if let Some(fraction) = fraction {
+		digits = format!("{digits}.{fraction}");
+	}
+	Decimal::from_str_exact(&digits).ok()
+}
+
 impl fmt::Display for AmountError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
@@ -86,35 +471,160 @@ impl fmt::Display for AmountError {
 				write!(f, "Subtraction results in negative amount: {} - {}", lhs.value, rhs.value)
 			},
 			AmountError::InvalidAmount(err) => write!(f, "Invalid amount: {}", err),
+			Overflow(lhs, rhs) => {
+				write!(f, "Addition overflows the amount range: {} + {}", lhs.value, rhs.value)
+			},
+			CurrencyMismatch(lhs, rhs) => {
+				write!(f, "Amounts are in different currencies: {} and {}", lhs.value, rhs.value)
+			},
 		}
 	}
 }
 #[cfg(test)]
 mod tests {
-	use rust_decimal::prelude::ToPrimitive;
-
 	use super::*;
 
+	#[test]
+	fn test_minor_units() {
+		let amount = Amount::from_minor_units(123456, base_currency()).unwrap();
+		assert_eq!(amount, Amount::try_from("1234.56").unwrap());
+		assert_eq!(amount.minor_units(), Some(123456));
+		assert_eq!(Amount::try_from("0.001").unwrap().minor_units(), None);
+		assert!(Amount::from_minor_units(-1, base_currency()).is_err());
+
+		let yen = Amount::from_minor_units(500, rusty_money::iso::JPY).unwrap();
+		assert_eq!(*yen.value().amount(), Decimal::from(500));
+	}
+
+	#[test]
+	fn test_non_mutating_arithmetic() {
+		let (five, ten) = (Amount::try_from("5.0").unwrap(), Amount::try_from("10.0").unwrap());
+
+		assert_eq!(five.plus(&ten), Ok(Amount::try_from("15.0").unwrap()));
+		let max = Amount::try_from(Money::from_decimal(Decimal::MAX, base_currency())).unwrap();
+		assert_eq!(max.plus(&five), Err(Overflow(max.clone(), five.clone())));
+		assert_eq!(max.saturating_plus(&five), max);
+		assert_eq!(ten.minus_checked(&five), Ok(five.clone()));
+		assert_eq!(five.minus_checked(&ten), Err(SubtractToNegative(five.clone(), ten.clone())));
+		assert_eq!((five.min(&ten), ten.min(&five)), (Ok(five.clone()), Ok(five.clone())));
+		assert_eq!((five.max(&ten), ten.max(&five)), (Ok(ten.clone()), Ok(ten.clone())));
+		let eur = five.in_currency(rusty_money::iso::EUR);
+		assert_eq!(five.min(&eur), Err(CurrencyMismatch(five.clone(), eur.clone())));
+		assert_eq!(five.max(&eur), Err(CurrencyMismatch(five.clone(), eur)));
+		assert_eq!(five, Amount::try_from("5.0").unwrap());
+	}
+
 	#[test]
 	fn test_checked_sub_assign() {
-		let mut amount1 = Amount::try_from(Money::from_str("10.0", CURRENCY).unwrap()).unwrap();
-		let amount2 = Amount::try_from(Money::from_str("5.0", CURRENCY).unwrap()).unwrap();
+		let mut amount1 =
+			Amount::try_from(Money::from_str("10.0", base_currency()).unwrap()).unwrap();
+		let amount2 = Amount::try_from(Money::from_str("5.0", base_currency()).unwrap()).unwrap();
 
-		amount1.checked_sub_assign(amount2.clone()).unwrap();
+		amount1.checked_sub_assign(&amount2).unwrap();
 
 		assert_eq!(amount1.value().amount().to_f32().unwrap(), 5.0);
 	}
 
 	#[test]
 	fn test_add_assign() {
-		let mut amount1 = Amount::try_from(Money::from_str("10.0", CURRENCY).unwrap()).unwrap();
-		let amount2 = Amount::try_from(Money::from_str("5.0", CURRENCY).unwrap()).unwrap();
+		let mut amount1 =
+			Amount::try_from(Money::from_str("10.0", base_currency()).unwrap()).unwrap();
+		let amount2 = Amount::try_from(Money::from_str("5.0", base_currency()).unwrap()).unwrap();
 
-		amount1.add_assign(amount2.clone());
+		amount1.add_assign(&amount2).unwrap();
 
 		assert_eq!(amount1.value().amount().to_f32().unwrap(), 15.0);
 	}
 
+	#[test]
+	fn test_add_assign_overflow() {
+		let max = Amount::try_from(Money::from_decimal(Decimal::MAX, base_currency())).unwrap();
+		let mut amount = max.clone();
+
+		assert_eq!(
+			amount.add_assign(&Amount::try_from("1").unwrap()),
+			Err(Overflow(max.clone(), Amount::try_from("1").unwrap()))
+		);
+		assert_eq!(amount, max);
+		amount.saturating_add_assign(&Amount::try_from("1").unwrap());
+		assert_eq!(amount, max);
+		assert_eq!([max.clone(), max.clone()].into_iter().sum::<Amount>(), max);
+	}
+
+	#[test]
+	fn test_add_assign_currency_mismatch() {
+		let usd = Amount::try_from("1").unwrap();
+		let eur = usd.in_currency(rusty_money::iso::EUR);
+		let mut amount = usd.clone();
+
+		assert_eq!(amount.add_assign(&eur), Err(CurrencyMismatch(usd.clone(), eur.clone())));
+		assert_eq!(amount, usd);
+		assert_eq!(usd.plus(&eur), Err(CurrencyMismatch(usd.clone(), eur)));
+	}
+
+	#[test]
+	fn test_sum_and_sub_other_currency() {
+		let eur = |value: &str| Amount::try_from(value).unwrap().in_currency(rusty_money::iso::EUR);
+
+		assert_eq!([eur("1"), eur("2.5")].into_iter().sum::<Amount>(), eur("3.5"));
+		assert_eq!([eur("1"), eur("2.5")].iter().sum::<Amount>(), eur("3.5"));
+		assert_eq!(std::iter::empty::<Amount>().sum::<Amount>(), Amount::default());
+		assert_eq!(eur("2") - eur("0.5"), Ok(eur("1.5")));
+		assert_eq!(
+			eur("2") - Amount::try_from("1").unwrap(),
+			Err(CurrencyMismatch(eur("2"), Amount::try_from("1").unwrap()))
+		);
+	}
+
+	#[test]
+	fn test_operators() {
+		let amount = |value: &str| Amount::try_from(value).unwrap();
+
+		assert_eq!(amount("10.0") + amount("5.5"), Ok(amount("15.5")));
+		assert_eq!(&amount("10.0") + &amount("5.5"), Ok(amount("15.5")));
+		assert_eq!(amount("10.0") - amount("5.5"), Ok(amount("4.5")));
+		assert_eq!(&amount("1") - &amount("2"), Err(SubtractToNegative(amount("1"), amount("2"))));
+		assert_eq!(["1", "2.5", "3"].map(amount).into_iter().sum::<Amount>(), amount("6.5"));
+		assert!(amount("2") > amount("1.5"));
+		let eur = Amount::zero(rusty_money::iso::EUR);
+		assert_eq!(eur.partial_cmp(&Amount::default()), None);
+	}
+
+	#[test]
+	fn test_signed_amount() {
+		let amount = |value: &str| Amount::try_from(value).unwrap();
+
+		let delta = SignedAmount::difference(&amount("2.5"), &amount("10")).unwrap();
+		assert!(delta.is_negative());
+		assert_eq!(delta.to_string(), "-7.5");
+		assert_eq!(delta.abs(), amount("7.5"));
+		assert!(matches!(Amount::try_from(delta.clone()), Err(NegativeValue(_))));
+		assert_eq!(Amount::try_from(-delta.clone()), Ok(amount("7.5")));
+		let net = delta.checked_add(&amount("10").into()).unwrap();
+		assert_eq!(Amount::try_from(net), Ok(amount("2.5")));
+		assert_eq!(SignedAmount::default(), amount("0").into());
+
+		let parsed: SignedAmount = serde_json::from_str(r#""-1.25""#).unwrap();
+		assert_eq!(serde_json::to_string(&parsed).unwrap(), r#""-1.25""#);
+		assert!(serde_json::from_str::<SignedAmount>(r#""-1.123456""#).is_err());
+	}
+
+	#[test]
+	fn test_lenient_decimal() {
+		let usd = rusty_money::iso::USD;
+		let lenient = |value: &str| lenient_decimal(value, usd).map(|decimal| decimal.to_string());
+
+		assert_eq!(lenient("1234.56"), Some("1234.56".into()));
+		assert_eq!(lenient(" 1,234.56 "), Some("1234.56".into()));
+		assert_eq!(lenient("$1,234,567"), Some("1234567".into()));
+		assert_eq!(lenient("1,234.50 USD"), Some("1234.50".into()));
+		assert_eq!(lenient("$ 0.5"), Some("0.5".into()));
+		assert_eq!(lenient("1,5"), None);
+		assert_eq!(lenient("1234,567"), None);
+		assert_eq!(lenient("1,234.56 EUR"), None);
+		assert_eq!(lenient(""), None);
+	}
+
 	#[test]
 	fn test_try_from_str() {
 		let amount = Amount::try_from("20.0").unwrap();
@@ -137,16 +647,16 @@ mod tests {
 
 	#[test]
 	fn test_try_from_money() {
-		let money = Money::from_str("30.0", CURRENCY).unwrap();
-		let amount = Amount::try_from(money.clone()).unwrap();
+		let money = Money::from_str("30.0", base_currency()).unwrap();
+		let amount = Amount::try_from(money).unwrap();
 
 		assert_eq!(amount.value().amount().to_f32().unwrap(), 30.0);
 	}
 
 	#[test]
 	fn test_try_from_negative_money() {
-		let money = Money::from_str("-30.0", CURRENCY).unwrap();
-		let amount = Amount::try_from(money.clone());
+		let money = Money::from_str("-30.0", base_currency()).unwrap();
+		let amount = Amount::try_from(money);
 
 		assert!(amount.is_err());
 		let error = amount.unwrap_err();
@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::stream;
+use lapin::acker::Acker;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions};
+use lapin::types::FieldTable;
+use lapin::{Channel, Connection, ConnectionProperties, Consumer};
+
+use crate::config::{GroupId, TransactionId};
+use crate::source::{handled, read_message, SourceError, TransactionSource};
+use crate::transaction::{Stream, StreamExt, Transaction};
+
+/// The number of messages the broker delivers ahead of the one being processed.
+const PREFETCH: u16 = 100;
+
+/// A source of the transaction messages of an AMQP queue, such as a RabbitMQ queue of payment
+/// events. Every message is a JSON object with the same fields as the CSV columns, like a line of
+/// [`Transaction::tx_stream_json`] input.
+///
+/// A message is acknowledged once its transaction was applied. `TransactionProcessor::process`
+/// handles every transaction before it reads the next one, and the transactions of a group once
+/// it reads a transaction of another group, so the messages are acknowledged as the next ones are
+/// read. The messages of the transactions reported to [`AmqpSource::rejections`], e.g. for
+/// insufficient funds, are rejected without requeueing instead, so that the broker dead-letters
+/// them. A message that cannot be read as a transaction is rejected the same way, unless
+/// configured to be requeued.
+///
+/// The stream ends when the broker cancels the consumer.
+pub struct AmqpSource {
+	/// The connection and channel of a consumer opened by [`AmqpSource::connect`], kept open while
+	/// consuming as they are closed when dropped.
+	_connection: Option<(Connection, Channel)>,
+	consumer: Consumer,
+	requeue_invalid: bool,
+	rejections: Rejections,
+}
+
+/// The ids of the transactions the processor rejected, whose messages an [`AmqpSource`] rejects
+/// rather than acknowledges. Clones share the same ids.
+#[derive(Clone, Debug, Default)]
+pub struct Rejections(Arc<Mutex<HashSet<TransactionId>>>);
+
+impl Rejections {
+	/// Records that the transaction `id` was rejected, such as from the error handler passed to
+	/// `TransactionProcessor::process`.
+	pub fn reject(&self, id: TransactionId) {
+		self.0.lock().unwrap_or_else(PoisonError::into_inner).insert(id);
+	}
+
+	/// Returns whether the transaction `id` was rejected, forgetting it.
+	fn take(&self, id: TransactionId) -> bool {
+		self.0.lock().unwrap_or_else(PoisonError::into_inner).remove(&id)
+	}
+}
+
+impl AmqpSource {
+	/// Creates a source of the messages of `consumer`, whose channel the caller keeps open.
+	pub fn new(consumer: Consumer) -> Self {
+		AmqpSource {
+			_connection: None,
+			consumer,
+			requeue_invalid: false,
+			rejections: Rejections::default(),
+		}
+	}
+
+	/// Consumes the messages of `queue` on the broker at `uri`, e.g. `amqp://localhost:5672/%2f`.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the broker cannot be connected to or the queue cannot be consumed.
+	pub async fn connect(uri: &str, queue: &str) -> io::Result<Self> {
+		let connection = Connection::connect(uri, ConnectionProperties::default())
+			.await
+			.map_err(io::Error::other)?;
+		let channel = connection.create_channel().await.map_err(io::Error::other)?;
+		channel
+			.basic_qos(PREFETCH, BasicQosOptions::default())
+			.await
+			.map_err(io::Error::other)?;
+		let consumer = channel
+			.basic_consume(
+				queue,
+				"transaction-processor",
+				BasicConsumeOptions::default(),
+				FieldTable::default(),
+			)
+			.await
+			.map_err(io::Error::other)?;
+		Ok(AmqpSource { _connection: Some((connection, channel)), ..AmqpSource::new(consumer) })
+	}
+
+	/// Requeues the messages that cannot be read as transactions when rejecting them, so that the
+	/// broker delivers them again instead of dead-lettering or dropping them.
+	pub fn with_requeue_invalid(mut self, requeue: bool) -> Self {
+		self.requeue_invalid = requeue;
+		self
+	}
+
+	/// Returns the rejected transactions to report, whose messages are rejected once handled.
+	pub fn rejections(&self) -> Rejections {
+		self.rejections.clone()
+	}
+}
+
+impl TransactionSource for AmqpSource {
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		stream::unfold((self, Vec::new()), |(mut source, mut pending)| async move {
+			if let Err(e) = ack_handled(&mut pending, &source.rejections).await {
+				return Some((Err(e), (source, pending)));
+			}
+			let delivery = match source.consumer.next().await? {
+				Ok(delivery) => delivery,
+				Err(e) => {
+					return Some((Err(SourceError::Io(io::Error::other(e))), (source, pending)))
+				},
+			};
+			let item = match read_message(&delivery.data) {
+				Ok(tx) => {
+					pending.push(((delivery.acker, tx.id()), tx.group_id()));
+					Ok(tx)
+				},
+				Err(reason) => {
+					let options = BasicNackOptions {
+						requeue: source.requeue_invalid,
+						..BasicNackOptions::default()
+					};
+					match delivery.acker.nack(options).await {
+						Ok(()) => Err(SourceError::InvalidRecord(reason)),
+						Err(e) => Err(SourceError::Io(io::Error::other(e))),
+					}
+				},
+			};
+			Some((item, (source, pending)))
+		})
+		.boxed()
+	}
+}
+
+/// Acknowledges the messages of the handled transactions, all the `pending` ones but a trailing
+/// group, which is handled once a transaction of another group is read. The messages of rejected
+/// transactions are rejected without requeueing them.
+async fn ack_handled(
+	pending: &mut Vec<((Acker, TransactionId), Option<GroupId>)>,
+	rejections: &Rejections,
+) -> Result<(), SourceError> {
+	let count = handled(pending);
+	for ((acker, id), _) in pending.drain(..count) {
+		let result = if rejections.take(id) {
+			acker
+				.nack(BasicNackOptions { requeue: false, ..BasicNackOptions::default() })
+				.await
+		} else {
+			acker.ack(BasicAckOptions::default()).await
+		};
+		result.map_err(|e| SourceError::Io(io::Error::other(e)))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rejections() {
+		let rejections = Rejections::default();
+		rejections.clone().reject(1);
+		assert!(!rejections.take(2));
+		assert!(rejections.take(1));
+		assert!(!rejections.take(1));
+	}
+}
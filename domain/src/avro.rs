@@ -0,0 +1,207 @@
+use std::io;
+
+use apache_avro::schema::Schema;
+use apache_avro::types::Value;
+use apache_avro::{AvroResult, Reader};
+use csv_async::StringRecord;
+use futures::io::AsyncReadExt;
+use futures::stream::{self, BoxStream};
+use itertools::Itertools;
+use serde::ser::Error;
+
+use crate::transaction::{AsyncRead, CsvError, StreamExt, Transaction, TransactionRow};
+
+impl Transaction {
+	/// Stream transactions from the given reader of an Avro object container file, including
+	/// errors.
+	///
+	/// Every record is one transaction, with fields named like the CSV columns. The writer schema
+	/// of the file is checked first: the records must have a `type` string or enum, `client` and
+	/// `tx` integers, an optional string or number `amount` and other fields of primitive types. A
+	/// file of other records is yielded as a single error. A record that cannot be read as a
+	/// transaction is yielded as an error and reading continues with the next one.
+	pub fn tx_stream_avro(
+		mut reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> BoxStream<'static, Result<Transaction, CsvError>> {
+		stream::once(async move {
+			let mut file = Vec::new();
+			if let Err(e) = reader.read_to_end(&mut file).await {
+				return stream::iter(vec![Err(CsvError::from(e))]).boxed();
+			}
+			let reader = match Reader::new(io::Cursor::new(file)) {
+				Ok(reader) => reader,
+				Err(e) => return stream::iter(vec![Err(read_error(e))]).boxed(),
+			};
+			let headers = match check_schema(reader.writer_schema()) {
+				Ok(headers) => headers,
+				Err(e) => {
+					let error = CsvError::custom(format!("Avro: {e}"));
+					return stream::iter(vec![Err(error)]).boxed();
+				},
+			};
+			stream::iter(reader.map(move |value| record(&headers, value))).boxed()
+		})
+		.flatten()
+		.boxed()
+	}
+}
+
+/// Checks that `schema` is the schema of transaction records and returns their field names.
+///
+/// Amounts are best written as strings, which keep their decimal places. Any field may be
+/// nullable.
+fn check_schema(schema: &Schema) -> Result<StringRecord, String> {
+	let Schema::Record(record) = schema else {
+		return Err("The writer schema is not a record".into());
+	};
+	for required in ["type", "client", "tx"] {
+		if !record.fields.iter().any(|field| field.name == required) {
+			return Err(format!("Missing field {required}"));
+		}
+	}
+	for field in &record.fields {
+		let schemas = match &field.schema {
+			Schema::Union(union) => union.variants().iter().collect_vec(),
+			schema => vec![schema],
+		};
+		let types = schemas
+			.into_iter()
+			.filter(|schema| !matches!(schema, Schema::Null))
+			.collect_vec();
+		let valid = match (field.name.as_str(), &types[..]) {
+			("type", [schema]) => matches!(schema, Schema::String | Schema::Enum(_)),
+			("client" | "tx", [schema]) => matches!(schema, Schema::Int | Schema::Long),
+			("amount", [schema]) => matches!(
+				schema,
+				Schema::String | Schema::Int | Schema::Long | Schema::Float | Schema::Double
+			),
+			(_, [schema]) => matches!(
+				schema,
+				Schema::Boolean
+					| Schema::Int | Schema::Long
+					| Schema::Float | Schema::Double
+					| Schema::String
+					| Schema::Enum(_)
+			),
+			_ => false,
+		};
+		if !valid {
+			return Err(format!("Unexpected type of field {}", field.name));
+		}
+	}
+	Ok(StringRecord::from(record.fields.iter().map(|field| field.name.as_str()).collect_vec()))
+}
+
+/// Reads a record of a file with a checked schema as a transaction.
+fn record(headers: &StringRecord, value: AvroResult<Value>) -> Result<Transaction, CsvError> {
+	let fields = match value {
+		Ok(Value::Record(fields)) => fields,
+		Ok(value) => return Err(CsvError::custom(format!("Avro: Not a record: {value:?}"))),
+		Err(e) => return Err(read_error(e)),
+	};
+	let values = fields.into_iter().map(|(_, value)| text(value)).collect_vec();
+	let record = StringRecord::from(values);
+	Transaction::try_from(TransactionRow::from_record(&record, headers))
+}
+
+/// Returns the text of a field value, empty for nulls. Numbers are read through their shortest
+/// representation.
+fn text(value: Value) -> String {
+	match value {
+		Value::String(text) | Value::Enum(_, text) => text,
+		Value::Boolean(value) => value.to_string(),
+		Value::Int(value) => value.to_string(),
+		Value::Long(value) => value.to_string(),
+		Value::Float(value) => value.to_string(),
+		Value::Double(value) => value.to_string(),
+		Value::Union(_, value) => text(*value),
+		// Other values are not in records of a checked schema.
+		_ => String::new(),
+	}
+}
+
+/// Returns the error of a file that cannot be read.
+fn read_error(e: apache_avro::Error) -> CsvError {
+	CsvError::from(io::Error::other(e))
+}
+
+#[cfg(test)]
+mod tests {
+	use apache_avro::types::Record;
+	use apache_avro::Writer;
+	use futures::io::Cursor;
+
+	use crate::amount::Amount;
+
+	use super::*;
+
+	const SCHEMA: &str = r#"{
+		"type": "record",
+		"name": "transaction",
+		"fields": [
+			{
+				"name": "type",
+				"type": {"type": "enum", "name": "kind", "symbols": ["deposit", "dispute"]}
+			},
+			{"name": "client", "type": "int"},
+			{"name": "tx", "type": "long"},
+			{"name": "amount", "type": ["null", "string"]}
+		]
+	}"#;
+
+	async fn read(
+		schema: &str,
+		records: &[(&str, i32, i64, Option<&str>)],
+	) -> Vec<Result<Transaction, CsvError>> {
+		let schema = Schema::parse_str(schema).unwrap();
+		let mut writer = Writer::new(&schema, Vec::new());
+		for (tx_type, client, tx, amount) in records {
+			let mut record = Record::new(writer.schema()).unwrap();
+			record.put("type", Value::Enum(u32::from(*tx_type == "dispute"), tx_type.to_string()));
+			record.put("client", *client);
+			record.put("tx", *tx);
+			record.put("amount", amount.map(String::from));
+			writer.append(record).unwrap();
+		}
+		let file = writer.into_inner().unwrap();
+		Transaction::tx_stream_avro(Cursor::new(file)).collect().await
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_avro() {
+		let vec = read(
+			SCHEMA,
+			&[
+				("deposit", 1, 1, Some("1.5")),
+				("dispute", 1, 1, None),
+				("deposit", 1, 2, Some("x")),
+			],
+		)
+		.await;
+
+		assert_eq!(vec.len(), 3);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(vec[2].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_avro_schema() {
+		let schema = SCHEMA.replace(r#"{"name": "client", "type": "int"},"#, "");
+		let vec = read(&schema, &[]).await;
+		assert_eq!(vec.len(), 1);
+		assert!(vec[0].as_ref().unwrap_err().to_string().contains("Missing field client"));
+
+		let schema = SCHEMA.replace(r#""type": "long""#, r#""type": "string""#);
+		let vec = read(&schema, &[]).await;
+		assert!(vec[0].as_ref().unwrap_err().to_string().contains("Unexpected type of field tx"));
+
+		let vec: Vec<_> = Transaction::tx_stream_avro(Cursor::new(b"type,client,tx\n".to_vec()))
+			.collect()
+			.await;
+		assert!(vec[0].is_err());
+	}
+}
@@ -1,9 +1,232 @@
+use std::future::Future;
+use std::sync::Mutex;
+
 use rust_decimal::RoundingStrategy;
-use rusty_money::iso::{Currency, USD};
+use rusty_money::iso::{self, Currency, USD};
+use rusty_money::Locale;
 
 pub type ClientId = i16;
 pub type TransactionId = i32;
+/// Identifies an acquirer's settlement batch.
+pub type BatchId = i32;
+/// Identifies a group of transactions applied atomically.
+pub type GroupId = i32;
+/// Seconds since the Unix epoch.
+pub type Timestamp = i64;
+
+/// The maximum decimal places of amounts unless configured in an [`AmountConfig`].
+pub const DEFAULT_MAX_DECIMAL_PLACES: u8 = 4;
+/// The rounding of output amounts unless configured in an [`AmountConfig`].
+pub const DEFAULT_ROUNDING: RoundingStrategy = RoundingStrategy::MidpointAwayFromZero;
+
+static CURRENCIES: Mutex<Vec<&'static Currency>> = Mutex::new(Vec::new());
+
+tokio::task_local! {
+	static AMOUNT_CONFIG: AmountConfig;
+}
+
+/// How input amounts are parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmountParsing {
+	/// Only plain decimal numbers, such as `1234.56`.
+	#[default]
+	Strict,
+	/// Also numbers with surrounding whitespace, `,` thousands separators and the symbol or code
+	/// of the base currency, such as `$ 1,234.56` or `1,234.56 USD`.
+	Lenient,
+	/// Only integers counting the minor units of the currency of the amount, such as `123456`
+	/// cents for 1234.56 USD.
+	MinorUnits,
+}
+
+/// Configures how amounts are read, validated and written: their base currency, decimal places,
+/// rounding and parsing.
+///
+/// Readers and processors are given a configuration with their `with_amount_config` option, and
+/// serializers round as configured with their `with_max_decimal_places` and `with_rounding`
+/// options. A configuration is in scope while they read, process or write amounts, including in
+/// the deserializers and `Display` implementations of amounts, which read it through
+/// [`base_currency`] and the other functions of this module. Components without one use the
+/// configuration in scope of their caller, and outside of any scope the defaults apply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmountConfig {
+	/// The currency of the balances and of amounts that do not state one, USD by default.
+	pub base_currency: &'static Currency,
+	/// The maximum decimal places of input amounts, to which output amounts are also rounded.
+	pub max_decimal_places: u8,
+	/// The strategy rounding output amounts to the maximum decimal places.
+	pub rounding: RoundingStrategy,
+	/// How input amounts are parsed.
+	pub parsing: AmountParsing,
+}
+
+impl Default for AmountConfig {
+	fn default() -> Self {
+		Self {
+			base_currency: USD,
+			max_decimal_places: DEFAULT_MAX_DECIMAL_PLACES,
+			rounding: DEFAULT_ROUNDING,
+			parsing: AmountParsing::default(),
+		}
+	}
+}
+
+impl AmountConfig {
+	/// Sets the base currency. As amounts in different currencies cannot be mixed, the readers,
+	/// processor and serializers of the same amounts must have the same one.
+	pub fn with_base_currency(mut self, currency: &'static Currency) -> Self {
+		self.base_currency = currency;
+		self
+	}
+
+	/// Sets the maximum decimal places, such as 8 for crypto currencies.
+	pub fn with_max_decimal_places(mut self, places: u8) -> Self {
+		self.max_decimal_places = places;
+		self
+	}
+
+	/// Sets the rounding strategy, such as [`RoundingStrategy::MidpointNearestEven`] for banker's
+	/// rounding.
+	pub fn with_rounding(mut self, rounding: RoundingStrategy) -> Self {
+		self.rounding = rounding;
+		self
+	}
+
+	/// Sets how input amounts are parsed.
+	pub fn with_parsing(mut self, parsing: AmountParsing) -> Self {
+		self.parsing = parsing;
+		self
+	}
+
+	/// Returns the configuration in scope, or the default one outside of any scope.
+	pub fn current() -> Self {
+		AMOUNT_CONFIG.try_with(|config| *config).unwrap_or_default()
+	}
+
+	/// Runs `future` with this configuration in scope.
+	pub async fn scope<F: Future>(self, future: F) -> F::Output {
+		AMOUNT_CONFIG.scope(self, future).await
+	}
+
+	/// Runs `f` with this configuration in scope.
+	pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+		AMOUNT_CONFIG.sync_scope(self, f)
+	}
+}
+
+/// Returns the base currency, the currency of the balances and of amounts that do not state one,
+/// of the [`AmountConfig`] in scope.
+pub fn base_currency() -> &'static Currency {
+	AmountConfig::current().base_currency
+}
+
+/// Returns the maximum decimal places of input amounts, to which output amounts are also rounded,
+/// of the [`AmountConfig`] in scope.
+pub fn max_decimal_places() -> u8 {
+	AmountConfig::current().max_decimal_places
+}
+
+/// Returns the strategy rounding output amounts to [`max_decimal_places`] of the [`AmountConfig`]
+/// in scope.
+pub fn rounding() -> RoundingStrategy {
+	AmountConfig::current().rounding
+}
+
+/// Returns how input amounts are parsed by the [`AmountConfig`] in scope.
+pub fn amount_parsing() -> AmountParsing {
+	AmountConfig::current().parsing
+}
+
+/// Finds the currency of a code, either an ISO 4217 currency or one registered with
+/// [`register_currency`].
+pub fn find_currency(code: &str) -> Option<&'static Currency> {
+	iso::find(code).or_else(|| {
+		let currencies = CURRENCIES.lock().unwrap_or_else(|e| e.into_inner());
+		currencies.iter().copied().find(|currency| currency.iso_alpha_code == code)
+	})
+}
+
+/// Registers a currency outside the ISO 4217 set, such as the crypto currencies `BTC` with 8 or
+/// `ETH` with 18 decimal places. Registering a known currency with its exponent does nothing.
+///
+/// # Errors
+///
+/// Returns the known currency of the code if it has a different exponent.
+pub fn register_currency(
+	code: &str,
+	exponent: u32,
+) -> Result<&'static Currency, &'static Currency> {
+	let mut currencies = CURRENCIES.lock().unwrap_or_else(|e| e.into_inner());
+	let known = iso::find(code)
+		.or_else(|| currencies.iter().copied().find(|currency| currency.iso_alpha_code == code));
+	if let Some(currency) = known {
+		return if currency.exponent == exponent { Ok(currency) } else { Err(currency) };
+	}
+	let code: &'static str = Box::leak(code.to_string().into_boxed_str());
+	let currency: &'static Currency = Box::leak(Box::new(Currency {
+		exponent,
+		iso_alpha_code: code,
+		iso_numeric_code: "",
+		locale: Locale::EnUs,
+		minor_units: 1,
+		name: code,
+		symbol: code,
+		symbol_first: false,
+	}));
+	currencies.push(currency);
+	Ok(currency)
+}
+
+#[cfg(test)]
+mod tests {
+	use rusty_money::iso::EUR;
+
+	use super::*;
+
+	#[test]
+	fn test_amount_config_sync_scope() {
+		assert_eq!(AmountConfig::current(), AmountConfig::default());
+		let config = AmountConfig::default()
+			.with_base_currency(EUR)
+			.with_max_decimal_places(8)
+			.with_rounding(RoundingStrategy::MidpointNearestEven)
+			.with_parsing(AmountParsing::Lenient);
+
+		config.sync_scope(|| {
+			assert_eq!(base_currency(), EUR);
+			assert_eq!(max_decimal_places(), 8);
+			assert_eq!(rounding(), RoundingStrategy::MidpointNearestEven);
+			assert_eq!(amount_parsing(), AmountParsing::Lenient);
+			AmountConfig::default().sync_scope(|| assert_eq!(base_currency(), USD));
+			assert_eq!(base_currency(), EUR);
+		});
+		assert_eq!(base_currency(), USD);
+		assert_eq!(max_decimal_places(), DEFAULT_MAX_DECIMAL_PLACES);
+	}
+
+	#[tokio::test]
+	async fn test_amount_config_scope() {
+		let config = AmountConfig::default().with_base_currency(EUR);
+		let currency = config
+			.scope(async {
+				tokio::task::yield_now().await;
+				base_currency()
+			})
+			.await;
+
+		assert_eq!(currency, EUR);
+		assert_eq!(base_currency(), USD);
+	}
 
-pub const CURRENCY: &Currency = USD;
-pub const MAX_DECIMAL_PLACES: u8 = 4;
-pub const ROUNDING: RoundingStrategy = RoundingStrategy::MidpointAwayFromZero;
+	#[test]
+	fn test_register_currency() {
+		assert!(find_currency("BTC").is_none());
+		let btc = register_currency("BTC", 8).unwrap();
+		assert_eq!(btc.exponent, 8);
+		assert_eq!(find_currency("BTC"), Some(btc));
+		assert_eq!(register_currency("BTC", 8), Ok(btc));
+		assert_eq!(register_currency("BTC", 18), Err(btc));
+		assert_eq!(register_currency("EUR", 2), Ok(EUR));
+		assert_eq!(register_currency("EUR", 8), Err(EUR));
+	}
+}
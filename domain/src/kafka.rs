@@ -0,0 +1,91 @@
+use std::io;
+
+use futures::stream;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::error::KafkaError;
+use rdkafka::message::Message;
+
+use crate::config::GroupId;
+use crate::source::{handled, read_message, SourceError, TransactionSource};
+use crate::transaction::{Stream, StreamExt, Transaction};
+
+/// The consumer group of a [`KafkaSource`] unless configured otherwise.
+pub const DEFAULT_GROUP: &str = "transaction-processor";
+
+/// A source of the transaction messages of a Kafka topic, consumed as a member of a consumer
+/// group. Every message is a JSON object with the same fields as the CSV columns, like a line of
+/// [`Transaction::tx_stream_json`] input.
+///
+/// The offset of a message is only committed once its transaction was handled, so that a restarted
+/// consumer continues with the first message that was not. `TransactionProcessor::process` handles
+/// every transaction before it reads the next one, and the transactions of a group once it reads a
+/// transaction of another group, so the offsets are stored as the next messages are read and
+/// committed in the background. Unlike the messages of a queue, those of a partition cannot be
+/// settled one by one, so the offsets of rejected transactions, e.g. for insufficient funds, and of
+/// messages that cannot be read as transactions are committed as well: reading them again would
+/// reject them again. The processor reports them as rejects.
+///
+/// The stream does not end by itself, but when reading the topic fails.
+pub struct KafkaSource {
+	consumer: StreamConsumer,
+	topic: String,
+}
+
+impl KafkaSource {
+	/// Consumes `topic` on the brokers `bootstrap_servers`, e.g. `localhost:9092`, in the consumer
+	/// group `group`. A group without committed offsets starts at the earliest message.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the consumer cannot be created or subscribe to the topic.
+	pub fn connect(bootstrap_servers: &str, topic: &str, group: &str) -> io::Result<Self> {
+		let consumer: StreamConsumer = ClientConfig::new()
+			.set("bootstrap.servers", bootstrap_servers)
+			.set("group.id", group)
+			.set("auto.offset.reset", "earliest")
+			.set("enable.auto.commit", "true")
+			// Offsets are stored explicitly once handled, and only stored offsets are committed.
+			.set("enable.auto.offset.store", "false")
+			.create()
+			.map_err(io::Error::other)?;
+		consumer.subscribe(&[topic]).map_err(io::Error::other)?;
+		Ok(KafkaSource { consumer, topic: topic.to_string() })
+	}
+
+	/// Stores the offsets of the handled messages, all the `pending` ones but a trailing group, to
+	/// be committed.
+	fn store_handled(
+		&self,
+		pending: &mut Vec<((i32, i64), Option<GroupId>)>,
+	) -> Result<(), KafkaError> {
+		let count = handled(pending);
+		for ((partition, offset), _) in pending.drain(..count) {
+			self.consumer.store_offset(&self.topic, partition, offset)?;
+		}
+		Ok(())
+	}
+}
+
+impl TransactionSource for KafkaSource {
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		stream::unfold((self, Vec::new()), |(source, mut pending)| async move {
+			if let Err(e) = source.store_handled(&mut pending) {
+				return Some((Err(SourceError::Io(io::Error::other(e))), (source, pending)));
+			}
+			let (item, position) = match source.consumer.recv().await {
+				Ok(message) => {
+					let item = read_message(message.payload().unwrap_or_default());
+					(item, (message.partition(), message.offset()))
+				},
+				Err(e) => {
+					return Some((Err(SourceError::Io(io::Error::other(e))), (source, pending)))
+				},
+			};
+			let group = item.as_ref().ok().and_then(Transaction::group_id);
+			pending.push((position, group));
+			Some((item.map_err(SourceError::InvalidRecord), (source, pending)))
+		})
+		.boxed()
+	}
+}
@@ -4,8 +4,27 @@ extern crate enum_display_derive;
 
 pub mod account;
 pub mod amount;
+#[cfg(feature = "amqp")]
+pub mod amqp;
+#[cfg(feature = "avro")]
+mod avro;
 pub mod config;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "object-store")]
+pub mod object_store;
+pub mod output;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+mod protobuf;
+pub mod sink;
+pub mod source;
+#[cfg(feature = "postgres")]
+pub mod sql;
 pub mod transaction;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+mod xml;
 
 #[cfg(test)]
 mod tests {
@@ -13,16 +32,16 @@ mod tests {
 	use serde::ser::Error;
 
 	use crate::amount::{Amount, AmountResult};
-	use crate::config::CURRENCY;
+	use crate::config::base_currency;
 	use crate::transaction::Transaction;
 	use crate::transaction::{CsvError, CsvResult, TransactionRow, TransactionRowType};
 
 	fn amount() -> Option<Amount> {
-		Some(Amount::try_from(Money::from_str("0.1", CURRENCY).unwrap()).unwrap())
+		Some(Amount::try_from(Money::from_str("0.1", base_currency()).unwrap()).unwrap())
 	}
 
 	fn amount_of(value: &str) -> AmountResult {
-		Amount::try_from(Money::from_str(value, CURRENCY).unwrap())
+		Amount::try_from(Money::from_str(value, base_currency()).unwrap())
 	}
 
 	fn row(tx_type: TransactionRowType, with_amount: bool) -> CsvResult<TransactionRow> {
@@ -31,6 +50,18 @@ mod tests {
 			tx_id: 1,
 			tx_type,
 			amount: if with_amount { amount() } else { None },
+			to: None,
+			reference: None,
+			reason: None,
+			idempotency_key: None,
+			timestamp: None,
+			group_id: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			currency: None,
+			to_currency: None,
+			rate: None,
 		})
 	}
 	#[test]
@@ -45,14 +76,22 @@ mod tests {
 		assert!(Transaction::try_from(row(TransactionRowType::Deposit, true)).is_ok());
 		assert!(Transaction::try_from(row(TransactionRowType::Withdrawal, true)).is_ok());
 		assert!(Transaction::try_from(row(TransactionRowType::Dispute, false)).is_ok());
+		assert!(Transaction::try_from(row(TransactionRowType::Dispute, true)).is_ok());
 		assert!(Transaction::try_from(row(TransactionRowType::Resolve, false)).is_ok());
 		assert!(Transaction::try_from(row(TransactionRowType::Chargeback, false)).is_ok());
+		assert!(Transaction::try_from(row(TransactionRowType::Fee, true)).is_ok());
+		assert!(Transaction::try_from(row(TransactionRowType::Reversal, false)).is_ok());
+		assert!(Transaction::try_from(row(TransactionRowType::Authorize, true)).is_ok());
+		assert!(Transaction::try_from(row(TransactionRowType::Capture, false)).is_ok());
 
 		assert!(Transaction::try_from(row(TransactionRowType::Deposit, false)).is_err());
 		assert!(Transaction::try_from(row(TransactionRowType::Withdrawal, false)).is_err());
-		assert!(Transaction::try_from(row(TransactionRowType::Dispute, true)).is_err());
 		assert!(Transaction::try_from(row(TransactionRowType::Resolve, true)).is_err());
 		assert!(Transaction::try_from(row(TransactionRowType::Chargeback, true)).is_err());
+		assert!(Transaction::try_from(row(TransactionRowType::Fee, false)).is_err());
+		assert!(Transaction::try_from(row(TransactionRowType::Reversal, true)).is_err());
+		assert!(Transaction::try_from(row(TransactionRowType::Authorize, false)).is_err());
+		assert!(Transaction::try_from(row(TransactionRowType::Capture, true)).is_err());
 	}
 
 	#[test]
@@ -61,13 +100,27 @@ mod tests {
 			client: 2,
 			tx_id: 1,
 			tx_type: TransactionRowType::Deposit,
-			amount: Some(Amount::try_from(Money::from_str("0.1", CURRENCY).unwrap()).unwrap()),
+			amount: Some(
+				Amount::try_from(Money::from_str("0.1", base_currency()).unwrap()).unwrap(),
+			),
+			to: None,
+			reference: None,
+			reason: None,
+			idempotency_key: None,
+			timestamp: None,
+			group_id: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			currency: None,
+			to_currency: None,
+			rate: None,
 		};
 		assert_eq!(
 			Transaction::try_from(Ok(row)).unwrap(),
 			Transaction::deposit(
 				1,
-				Amount::try_from(Money::from_str("0.1", CURRENCY).unwrap()).unwrap(),
+				Amount::try_from(Money::from_str("0.1", base_currency()).unwrap()).unwrap(),
 				2
 			)
 		);
@@ -76,9 +129,30 @@ mod tests {
 			client: 2,
 			tx_id: 1,
 			tx_type: TransactionRowType::Dispute,
-			amount: Some(Amount::try_from(Money::from_str("0.1", CURRENCY).unwrap()).unwrap()),
+			amount: Some(
+				Amount::try_from(Money::from_str("0.1", base_currency()).unwrap()).unwrap(),
+			),
+			to: None,
+			reference: None,
+			reason: None,
+			idempotency_key: None,
+			timestamp: None,
+			group_id: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			currency: None,
+			to_currency: None,
+			rate: None,
 		};
-		assert!(Transaction::try_from(Ok(row)).is_err());
+		assert_eq!(
+			Transaction::try_from(Ok(row)).unwrap(),
+			Transaction::partial_dispute(
+				1,
+				2,
+				Amount::try_from(Money::from_str("0.1", base_currency()).unwrap()).unwrap()
+			)
+		);
 
 		assert!(Transaction::try_from(Err(CsvError::custom("whatever".to_string()))).is_err());
 	}
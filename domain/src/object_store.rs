@@ -0,0 +1,67 @@
+use std::io;
+
+use ::object_store::path::Path;
+use ::object_store::ObjectStoreScheme;
+use futures::TryStreamExt;
+use url::Url;
+
+use crate::transaction::AsyncRead;
+
+/// Returns whether `path` is the URL of an object in a remote object store, such as
+/// `s3://bucket/transactions.csv`, `gs://`, `az://` or `https://` URLs, rather than a local path.
+pub fn is_object_url(path: &str) -> bool {
+	object_url(path).is_some()
+}
+
+/// Parses the URL of an object in a remote object store.
+fn object_url(path: &str) -> Option<(Url, Path)> {
+	let url = Url::parse(path).ok()?;
+	match ObjectStoreScheme::parse(&url).ok()? {
+		(ObjectStoreScheme::Local | ObjectStoreScheme::Memory, _) => None,
+		(_, path) => Some((url, path)),
+	}
+}
+
+/// Opens the object at `url` for reading, streaming its content as it is read rather than
+/// downloading it first. Any [`TransactionSource`](crate::source::TransactionSource) reading it
+/// reads the object.
+///
+/// The store is configured from the environment, with the keys of its builder in upper case, e.g.
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION` and `AWS_ENDPOINT` for S3.
+///
+/// # Errors
+///
+/// Returns an I/O error if `url` is not the URL of an object in a remote object store, or if the
+/// object cannot be read.
+pub async fn open(url: &str) -> io::Result<impl AsyncRead + Unpin + Send + 'static> {
+	let (url, path) = object_url(url).ok_or_else(|| {
+		io::Error::new(io::ErrorKind::InvalidInput, format!("Not an object store URL: {url}"))
+	})?;
+	let options = std::env::vars_os().filter_map(|(key, value)| {
+		Some((key.into_string().ok()?.to_ascii_lowercase(), value.into_string().ok()?))
+	});
+	let (store, _) = ::object_store::parse_url_opts(&url, options).map_err(io::Error::other)?;
+	let object = store.get(&path).await.map_err(io::Error::other)?;
+	Ok(object.into_stream().map_err(io::Error::other).into_async_read())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_is_object_url() {
+		assert!(is_object_url("s3://bucket/transactions.csv"));
+		assert!(is_object_url("gs://bucket/2024/transactions.csv.gz"));
+		assert!(is_object_url("https://example.com/transactions.csv"));
+		assert!(!is_object_url("file:///tmp/transactions.csv"));
+		assert!(!is_object_url("transactions.csv"));
+		assert!(!is_object_url("/tmp/transactions.csv"));
+	}
+
+	#[tokio::test]
+	async fn test_open_local_path() {
+		let error = open("transactions.csv").await.err().unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+	}
+}
@@ -0,0 +1,779 @@
+use core::fmt;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use itertools::Itertools;
+use rust_decimal::{Decimal, RoundingStrategy};
+use rusty_money::iso::Currency;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::account::Account;
+use crate::amount::Amount;
+use crate::config::{max_decimal_places, rounding, ClientId, TransactionId};
+use crate::output::OutputConfigError::{DuplicateColumn, NoColumns, UnknownColumn};
+
+/// Represents the columns of the accounts output.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum AccountColumn {
+	Client,
+	Available,
+	Held,
+	Total,
+	Locked,
+	/// The number of deposits applied.
+	Deposits,
+	/// The number of withdrawals applied.
+	Withdrawals,
+	/// The number of deposits and withdrawals currently disputed.
+	OpenDisputes,
+	/// The id of the charged back transaction that locked the account, empty if unlocked.
+	LockedBy,
+	/// Whether the account is closed.
+	Closed,
+	/// The amount by which the total balance is below zero, such as the amount owed on a credit
+	/// line.
+	Outstanding,
+	/// The credit limit of a credit line, empty for other accounts.
+	CreditLimit,
+	/// The available sub-balances in other currencies than the base currency, as
+	/// `currency=amount` pairs separated by `;`, e.g. `EUR=9.2;GBP=3`.
+	Balances,
+	/// The held sub-balances in other currencies than the base currency, like [`Self::Balances`].
+	HeldBalances,
+	/// The currency of the available, held and total balances, which is the base currency unless
+	/// the sub-balances are written as rows of their own, see [`Account::currency_accounts`].
+	Currency,
+	/// The total of all balances and sub-balances converted into the reporting currency, see
+	/// [`AccountSerializer::with_reporting_currency`]. Empty without a reporting currency or if a
+	/// sub-balance has no rate to it.
+	ReportingTotal,
+}
+
+impl AccountColumn {
+	/// All columns, in the default output order.
+	pub const ALL: [AccountColumn; 5] = [
+		AccountColumn::Client,
+		AccountColumn::Available,
+		AccountColumn::Held,
+		AccountColumn::Total,
+		AccountColumn::Locked,
+	];
+
+	/// The opt-in columns explaining how an account arrived at its state.
+	pub const ACTIVITY: [AccountColumn; 5] = [
+		AccountColumn::Deposits,
+		AccountColumn::Withdrawals,
+		AccountColumn::OpenDisputes,
+		AccountColumn::LockedBy,
+		AccountColumn::Closed,
+	];
+
+	/// The opt-in columns of credit lines.
+	pub const CREDIT: [AccountColumn; 2] = [AccountColumn::Outstanding, AccountColumn::CreditLimit];
+
+	/// The opt-in columns of currency sub-balances.
+	pub const FX: [AccountColumn; 4] = [
+		AccountColumn::Balances,
+		AccountColumn::HeldBalances,
+		AccountColumn::Currency,
+		AccountColumn::ReportingTotal,
+	];
+
+	/// Returns the default header name of the column.
+	pub fn default_header(&self) -> &'static str {
+		match self {
+			AccountColumn::Client => "client",
+			AccountColumn::Available => "available",
+			AccountColumn::Held => "held",
+			AccountColumn::Total => "total",
+			AccountColumn::Locked => "locked",
+			AccountColumn::Deposits => "deposits",
+			AccountColumn::Withdrawals => "withdrawals",
+			AccountColumn::OpenDisputes => "open_disputes",
+			AccountColumn::LockedBy => "locked_by",
+			AccountColumn::Closed => "closed",
+			AccountColumn::Outstanding => "outstanding",
+			AccountColumn::CreditLimit => "credit_limit",
+			AccountColumn::Balances => "balances",
+			AccountColumn::HeldBalances => "held_balances",
+			AccountColumn::Currency => "currency",
+			AccountColumn::ReportingTotal => "reporting_total",
+		}
+	}
+
+	/// Returns the formatted value of the column for the given `account`, with balances rounded
+	/// by `serializer`.
+	pub(crate) fn value(&self, account: &Account, serializer: &AccountSerializer) -> String {
+		let format = |amount: &Amount| serializer.format_amount(amount);
+		match self {
+			AccountColumn::Client => account.client_id.to_string(),
+			AccountColumn::Available => format(&account.available),
+			AccountColumn::Held => format(&account.held),
+			AccountColumn::Total => format(&account.total()),
+			AccountColumn::Locked => account.locked.to_string(),
+			AccountColumn::Deposits => account.activity.deposits.to_string(),
+			AccountColumn::Withdrawals => account.activity.withdrawals.to_string(),
+			AccountColumn::OpenDisputes => account.activity.open_disputes.to_string(),
+			AccountColumn::LockedBy => {
+				account.activity.locked_by.map(|id| id.to_string()).unwrap_or_default()
+			},
+			AccountColumn::Closed => account.closed.to_string(),
+			AccountColumn::Outstanding => format(&account.outstanding()),
+			AccountColumn::CreditLimit => account.credit_limit().map(format).unwrap_or_default(),
+			AccountColumn::Balances => format_balances(&account.available_balances(), serializer),
+			AccountColumn::HeldBalances => format_balances(&account.held_balances(), serializer),
+			AccountColumn::Currency => currency(account).to_string(),
+			AccountColumn::ReportingTotal => serializer
+				.reporting
+				.as_ref()
+				.and_then(|reporting| reporting.total(account))
+				.map(|total| format(&total))
+				.unwrap_or_default(),
+		}
+	}
+}
+
+/// Converts the balances of accounts into a single reporting currency, to compare and sum accounts
+/// holding several currencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportingCurrency {
+	pub currency: &'static Currency,
+	/// The units of the reporting currency per unit of each other currency, by currency code.
+	pub rates: BTreeMap<String, Decimal>,
+}
+
+impl ReportingCurrency {
+	/// Returns the total of all balances and sub-balances of `account` in the reporting currency,
+	/// or `None` if a sub-balance has no rate to it or the total is out of range.
+	pub fn total(&self, account: &Account) -> Option<Amount> {
+		let balances = account.balances.values().map(|balance| balance.total());
+		let mut total = Amount::zero(self.currency);
+		for balance in std::iter::once(account.total()).chain(balances) {
+			let code = balance.value().currency().iso_alpha_code;
+			let rate = if code == self.currency.iso_alpha_code {
+				Decimal::ONE
+			} else {
+				*self.rates.get(code)?
+			};
+			total.add_assign(&balance.convert(self.currency, rate)?).ok()?;
+		}
+		Some(total)
+	}
+}
+
+/// Returns the currency code of the balances of `account`.
+fn currency(account: &Account) -> &'static str {
+	account.available.value().currency().iso_alpha_code
+}
+
+/// Formats currency sub-balances as `currency=amount` pairs separated by `;`, rounded by
+/// `serializer`.
+fn format_balances(
+	balances: &BTreeMap<&'static str, &Amount>,
+	serializer: &AccountSerializer,
+) -> String {
+	balances
+		.iter()
+		.map(|(currency, balance)| format!("{currency}={}", serializer.format_amount(balance)))
+		.join(";")
+}
+
+impl FromStr for AccountColumn {
+	type Err = OutputConfigError;
+
+	/// Parses a column from its default header name.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		AccountColumn::ALL
+			.into_iter()
+			.chain(AccountColumn::ACTIVITY)
+			.chain(AccountColumn::CREDIT)
+			.chain(AccountColumn::FX)
+			.find(|column| column.default_header() == s.trim())
+			.ok_or_else(|| UnknownColumn(s.to_string()))
+	}
+}
+
+/// Represents the different errors that can occur when configuring the accounts output.
+#[derive(Debug, PartialEq)]
+pub enum OutputConfigError {
+	/// The column name does not match any column.
+	UnknownColumn(String),
+	/// The configured column order is empty.
+	NoColumns,
+	/// The column appears more than once in the configured column order.
+	DuplicateColumn(AccountColumn),
+}
+
+impl fmt::Display for OutputConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			UnknownColumn(name) => write!(f, "Unknown account column: {name}"),
+			NoColumns => write!(f, "At least one account column is required"),
+			DuplicateColumn(column) => {
+				write!(f, "Duplicate account column: {}", column.default_header())
+			},
+		}
+	}
+}
+
+impl std::error::Error for OutputConfigError {}
+
+/// Represents an output record that does not match the declared accounts output schema.
+#[derive(Debug, PartialEq)]
+pub enum OutputValidationError {
+	/// The record does not have one value per column.
+	ColumnCount { expected: usize, found: usize },
+	/// The value cannot be parsed as the column's type.
+	InvalidValue(AccountColumn, String),
+	/// The balance has more than the given maximum decimal places.
+	TooManyDecimalPlaces(AccountColumn, String, u8),
+	/// The balance is negative.
+	NegativeBalance(AccountColumn, String),
+}
+
+impl fmt::Display for OutputValidationError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			OutputValidationError::ColumnCount { expected, found } => {
+				write!(f, "Expected {expected} account columns, found {found}")
+			},
+			OutputValidationError::InvalidValue(column, value) => {
+				write!(f, "Invalid {} value: {value}", column.default_header())
+			},
+			OutputValidationError::TooManyDecimalPlaces(column, value, max) => write!(
+				f,
+				"Too many decimal places in {} value: {value}, max allowed: {max}",
+				column.default_header()
+			),
+			OutputValidationError::NegativeBalance(column, value) => {
+				write!(f, "Negative {} balance: {value}", column.default_header())
+			},
+		}
+	}
+}
+
+impl std::error::Error for OutputValidationError {}
+
+/// Configures how accounts are serialized for output: the schema of which columns are emitted, in
+/// which order, and under which header names.
+///
+/// The default emits `client,available,held,total,locked`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountSerializer {
+	/// The output columns in order, each with its header name.
+	columns: Vec<(AccountColumn, String)>,
+	/// Whether available and total balances may be negative, as with overdrafts.
+	overdrafts: bool,
+	/// The currency of [`AccountColumn::ReportingTotal`], if configured.
+	reporting: Option<ReportingCurrency>,
+	/// The rounding of output balances, the [`rounding`] in scope unless configured.
+	rounding: Option<RoundingStrategy>,
+	/// The decimal places output balances are rounded to, the [`max_decimal_places`] in scope
+	/// unless configured.
+	max_decimal_places: Option<u8>,
+}
+
+impl Default for AccountSerializer {
+	fn default() -> Self {
+		Self {
+			columns: AccountColumn::ALL
+				.into_iter()
+				.map(|column| (column, column.default_header().to_string()))
+				.collect(),
+			overdrafts: false,
+			reporting: None,
+			rounding: None,
+			max_decimal_places: None,
+		}
+	}
+}
+
+impl AccountSerializer {
+	/// Creates a serializer emitting only the given `columns`, in order, under their default
+	/// headers.
+	///
+	/// # Errors
+	///
+	/// Returns [`NoColumns`] if `columns` is empty, or [`DuplicateColumn`] if a column appears more
+	/// than once.
+	pub fn with_columns(columns: Vec<AccountColumn>) -> Result<Self, OutputConfigError> {
+		if columns.is_empty() {
+			return Err(NoColumns);
+		}
+		if let Some(duplicate) = columns.iter().duplicates().next() {
+			return Err(DuplicateColumn(*duplicate));
+		}
+		Ok(Self {
+			columns: columns
+				.into_iter()
+				.map(|column| (column, column.default_header().to_string()))
+				.collect(),
+			overdrafts: false,
+			reporting: None,
+			rounding: None,
+			max_decimal_places: None,
+		})
+	}
+
+	/// Renames the header of `column` to `header`.
+	pub fn with_header(mut self, column: AccountColumn, header: impl Into<String>) -> Self {
+		let header = header.into();
+		self.columns
+			.iter_mut()
+			.filter(|(c, _)| *c == column)
+			.for_each(|(_, h)| *h = header.clone());
+		self
+	}
+
+	/// Accepts negative available and total balances in [`Self::validate`], for accounts with an
+	/// overdraft.
+	pub fn with_overdrafts(mut self) -> Self {
+		self.overdrafts = true;
+		self
+	}
+
+	/// Converts all balances into `reporting` for [`AccountColumn::ReportingTotal`].
+	pub fn with_reporting_currency(mut self, reporting: ReportingCurrency) -> Self {
+		self.reporting = Some(reporting);
+		self
+	}
+
+	/// Rounds output balances with `rounding` instead of the [`rounding`] in scope, such as
+	/// [`RoundingStrategy::ToZero`] to truncate them on statements. Only the output is rounded, the
+	/// balances keep their precision.
+	pub fn with_rounding(mut self, rounding: RoundingStrategy) -> Self {
+		self.rounding = Some(rounding);
+		self
+	}
+
+	/// Rounds output balances to `places` decimal places instead of the [`max_decimal_places`] in
+	/// scope, such as 8 for crypto currencies, and accepts as many in [`Self::validate`].
+	pub fn with_max_decimal_places(mut self, places: u8) -> Self {
+		self.max_decimal_places = Some(places);
+		self
+	}
+
+	/// Returns the rounding of output balances.
+	fn rounding(&self) -> RoundingStrategy {
+		self.rounding.unwrap_or_else(rounding)
+	}
+
+	/// Returns the decimal places of output balances.
+	pub(crate) fn max_decimal_places(&self) -> u8 {
+		self.max_decimal_places.unwrap_or_else(max_decimal_places)
+	}
+
+	/// Returns `amount` formatted for output, rounded like the balances of [`Self::record`].
+	pub fn format_amount(&self, amount: &Amount) -> String {
+		self.round_amount(amount).to_string()
+	}
+
+	/// Returns the value of `amount` rounded like the balances of [`Self::record`].
+	pub fn round_amount(&self, amount: &Amount) -> Decimal {
+		amount.rounded(self.max_decimal_places(), self.rounding())
+	}
+
+	/// Returns the output columns in order.
+	pub fn columns(&self) -> impl Iterator<Item = AccountColumn> + '_ {
+		self.columns.iter().map(|(column, _)| *column)
+	}
+
+	/// Returns the header row.
+	pub fn header(&self) -> Vec<&str> {
+		self.columns.iter().map(|(_, header)| header.as_str()).collect()
+	}
+
+	/// Returns the output row of the given `account`.
+	pub fn record(&self, account: &Account) -> Vec<String> {
+		self.columns.iter().map(|(column, _)| column.value(account, self)).collect()
+	}
+
+	/// Returns the output record of the given `account` as a map from header to typed value, for
+	/// self-describing formats such as JSON: client ids are numbers, locked flags booleans and
+	/// balances strings rounded like in the CSV output, so no float conversion is involved.
+	pub fn typed_record<'a>(&'a self, account: &'a Account) -> impl Serialize + 'a {
+		TypedRecord { serializer: self, account }
+	}
+
+	/// Checks an output row against the declared schema: one value per column, ids, counts and
+	/// locked flags of their type, and non-negative balances with at most the decimal places of
+	/// the output. With overdrafts, available and total balances may be negative.
+	///
+	/// # Errors
+	///
+	/// Returns the first [`OutputValidationError`] found in the row.
+	pub fn validate(&self, record: &[String]) -> Result<(), OutputValidationError> {
+		if record.len() != self.columns.len() {
+			return Err(OutputValidationError::ColumnCount {
+				expected: self.columns.len(),
+				found: record.len(),
+			});
+		}
+		let max_decimal_places = self.max_decimal_places();
+		for ((column, _), value) in self.columns.iter().zip(record) {
+			let invalid = || OutputValidationError::InvalidValue(*column, value.clone());
+			match column {
+				AccountColumn::Client => {
+					value.parse::<ClientId>().map_err(|_| invalid())?;
+				},
+				AccountColumn::Locked | AccountColumn::Closed => {
+					value.parse::<bool>().map_err(|_| invalid())?;
+				},
+				AccountColumn::Deposits
+				| AccountColumn::Withdrawals
+				| AccountColumn::OpenDisputes => {
+					value.parse::<u32>().map_err(|_| invalid())?;
+				},
+				AccountColumn::LockedBy => {
+					if !value.is_empty() {
+						value.parse::<TransactionId>().map_err(|_| invalid())?;
+					}
+				},
+				AccountColumn::CreditLimit | AccountColumn::ReportingTotal if value.is_empty() => {
+				},
+				AccountColumn::Currency => {
+					if value.is_empty() {
+						return Err(invalid());
+					}
+				},
+				AccountColumn::Balances | AccountColumn::HeldBalances => {
+					for balance in value.split(';').filter(|balance| !balance.is_empty()) {
+						let (_, amount) = balance.split_once('=').ok_or_else(invalid)?;
+						let amount = Decimal::from_str(amount).map_err(|_| invalid())?;
+						if amount.scale() > u32::from(max_decimal_places) {
+							return Err(OutputValidationError::TooManyDecimalPlaces(
+								*column,
+								value.clone(),
+								max_decimal_places,
+							));
+						}
+						if amount.is_sign_negative() && !amount.is_zero() {
+							return Err(OutputValidationError::NegativeBalance(
+								*column,
+								value.clone(),
+							));
+						}
+					}
+				},
+				AccountColumn::Available
+				| AccountColumn::Held
+				| AccountColumn::Total
+				| AccountColumn::Outstanding
+				| AccountColumn::CreditLimit
+				| AccountColumn::ReportingTotal => {
+					let balance = Decimal::from_str(value).map_err(|_| invalid())?;
+					if balance.scale() > u32::from(max_decimal_places) {
+						return Err(OutputValidationError::TooManyDecimalPlaces(
+							*column,
+							value.clone(),
+							max_decimal_places,
+						));
+					}
+					let may_be_negative = self.overdrafts
+						&& matches!(
+							column,
+							AccountColumn::Available
+								| AccountColumn::Total | AccountColumn::ReportingTotal
+						);
+					if balance.is_sign_negative() && !balance.is_zero() && !may_be_negative {
+						return Err(OutputValidationError::NegativeBalance(*column, value.clone()));
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+}
+
+/// An account serialized as a map of its configured columns, in order.
+struct TypedRecord<'a> {
+	serializer: &'a AccountSerializer,
+	account: &'a Account,
+}
+
+impl Serialize for TypedRecord<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let (account, columns) = (self.account, &self.serializer.columns);
+		let format = |amount: &Amount| self.serializer.format_amount(amount);
+		let format_all = |balances: BTreeMap<&'static str, &Amount>| {
+			balances
+				.into_iter()
+				.map(|(code, balance)| (code, format(balance)))
+				.collect::<BTreeMap<_, _>>()
+		};
+		let mut map = serializer.serialize_map(Some(columns.len()))?;
+		for (column, header) in columns {
+			match column {
+				AccountColumn::Client => map.serialize_entry(header, &account.client_id)?,
+				AccountColumn::Available => {
+					map.serialize_entry(header, &format(&account.available))?
+				},
+				AccountColumn::Held => map.serialize_entry(header, &format(&account.held))?,
+				AccountColumn::Total => map.serialize_entry(header, &format(&account.total()))?,
+				AccountColumn::Locked => map.serialize_entry(header, &account.locked)?,
+				AccountColumn::Deposits => {
+					map.serialize_entry(header, &account.activity.deposits)?
+				},
+				AccountColumn::Withdrawals => {
+					map.serialize_entry(header, &account.activity.withdrawals)?
+				},
+				AccountColumn::OpenDisputes => {
+					map.serialize_entry(header, &account.activity.open_disputes)?
+				},
+				AccountColumn::LockedBy => {
+					map.serialize_entry(header, &account.activity.locked_by)?
+				},
+				AccountColumn::Closed => map.serialize_entry(header, &account.closed)?,
+				AccountColumn::Outstanding => {
+					map.serialize_entry(header, &format(&account.outstanding()))?
+				},
+				AccountColumn::CreditLimit => {
+					map.serialize_entry(header, &account.credit_limit().map(format))?
+				},
+				AccountColumn::Balances => {
+					map.serialize_entry(header, &format_all(account.available_balances()))?
+				},
+				AccountColumn::HeldBalances => {
+					map.serialize_entry(header, &format_all(account.held_balances()))?
+				},
+				AccountColumn::Currency => map.serialize_entry(header, currency(account))?,
+				AccountColumn::ReportingTotal => {
+					let total = self
+						.serializer
+						.reporting
+						.as_ref()
+						.and_then(|reporting| reporting.total(account));
+					map.serialize_entry(header, &total.as_ref().map(format))?
+				},
+			}
+		}
+		map.end()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::account::{AccountKind, CurrencyBalance};
+
+	fn account() -> Account {
+		Account::new(1, Amount::try_from("1.5").unwrap(), Amount::try_from("0.5").unwrap(), true)
+	}
+
+	#[test]
+	fn test_default_serializer() {
+		let serializer = AccountSerializer::default();
+
+		assert_eq!(serializer.header(), vec!["client", "available", "held", "total", "locked"]);
+		assert_eq!(serializer.record(&account()), vec!["1", "1.5", "0.5", "2.0", "true"]);
+	}
+
+	#[test]
+	fn test_reordered_and_renamed_columns() {
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Locked,
+			AccountColumn::Client,
+			AccountColumn::Total,
+			AccountColumn::Held,
+			AccountColumn::Available,
+		])
+		.unwrap()
+		.with_header(AccountColumn::Client, "client_id")
+		.with_header(AccountColumn::Locked, "frozen");
+
+		assert_eq!(serializer.header(), vec!["frozen", "client_id", "total", "held", "available"]);
+		assert_eq!(serializer.record(&account()), vec!["true", "1", "2.0", "0.5", "1.5"]);
+	}
+
+	#[test]
+	fn test_typed_record() {
+		let serializer = AccountSerializer::default().with_header(AccountColumn::Client, "id");
+		let account =
+			Account::new(1, Amount::try_from("1.12345").unwrap(), Amount::default(), false);
+
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"id":1,"available":"1.1235","held":"0.0","total":"1.1235","locked":false}"#
+		);
+	}
+
+	#[test]
+	fn test_column_subset() {
+		let serializer =
+			AccountSerializer::with_columns(vec![AccountColumn::Client, AccountColumn::Total])
+				.unwrap();
+
+		assert_eq!(serializer.header(), vec!["client", "total"]);
+		assert_eq!(serializer.record(&account()), vec!["1", "2.0"]);
+		assert_eq!(serializer.validate(&serializer.record(&account())), Ok(()));
+	}
+
+	#[test]
+	fn test_activity_columns() {
+		let mut account = account();
+		account.activity.deposits = 2;
+		account.activity.open_disputes = 1;
+		account.activity.locked_by = Some(7);
+		let columns = "client,deposits,withdrawals,open_disputes,locked_by".split(',');
+		let serializer =
+			AccountSerializer::with_columns(columns.map(|c| c.parse().unwrap()).collect()).unwrap();
+
+		assert_eq!(serializer.record(&account), vec!["1", "2", "0", "1", "7"]);
+		assert_eq!(serializer.validate(&serializer.record(&account)), Ok(()));
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"client":1,"deposits":2,"withdrawals":0,"open_disputes":1,"locked_by":7}"#
+		);
+	}
+
+	#[test]
+	fn test_credit_columns() {
+		let mut account = Account::new(1, Amount::default(), Amount::try_from("5").unwrap(), false);
+		account.available = Amount::deserialize_balance(serde_json::json!("-25")).unwrap();
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::Outstanding,
+			AccountColumn::CreditLimit,
+		])
+		.unwrap();
+
+		assert_eq!(serializer.record(&account), vec!["1", "20.00", ""]);
+		assert_eq!(serializer.validate(&serializer.record(&account)), Ok(()));
+		account.kind = AccountKind::Credit;
+		account.overdraft = Amount::try_from("50").unwrap();
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"client":1,"outstanding":"20.00","credit_limit":"50.00"}"#
+		);
+	}
+
+	#[test]
+	fn test_balances_column() {
+		let mut account = account();
+		let serializer =
+			AccountSerializer::with_columns(vec![AccountColumn::Client, AccountColumn::Balances])
+				.unwrap();
+
+		assert_eq!(serializer.record(&account), vec!["1", ""]);
+		for (currency, value) in [(rusty_money::iso::EUR, "9.2"), (rusty_money::iso::GBP, "3")] {
+			let balance =
+				rusty_money::Money::from_decimal(Decimal::from_str(value).unwrap(), currency);
+			let balance = CurrencyBalance {
+				available: Amount::try_from(balance).unwrap(),
+				held: Amount::zero(currency),
+			};
+			account.balances.insert(currency.iso_alpha_code, balance);
+		}
+		assert_eq!(serializer.record(&account), vec!["1", "EUR=9.2;GBP=3"]);
+		assert_eq!(serializer.validate(&serializer.record(&account)), Ok(()));
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"client":1,"balances":{"EUR":"9.2","GBP":"3"}}"#
+		);
+		assert_eq!(
+			serializer.validate(&["1".to_string(), "EUR".to_string()]),
+			Err(OutputValidationError::InvalidValue(AccountColumn::Balances, "EUR".to_string()))
+		);
+
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::Currency,
+			AccountColumn::Available,
+		])
+		.unwrap();
+		let records = std::iter::once(account.clone())
+			.chain(account.currency_accounts())
+			.map(|account| serializer.record(&account))
+			.collect_vec();
+		assert_eq!(
+			records,
+			vec![vec!["1", "USD", "1.5"], vec!["1", "EUR", "9.2"], vec!["1", "GBP", "3"]]
+		);
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"client":1,"currency":"USD","available":"1.5"}"#
+		);
+	}
+
+	#[test]
+	fn test_reporting_total_column() {
+		let mut account = account();
+		let eur = rusty_money::Money::from_decimal(Decimal::from(10), rusty_money::iso::EUR);
+		let balance = CurrencyBalance {
+			available: Amount::try_from(eur).unwrap(),
+			held: Amount::zero(rusty_money::iso::EUR),
+		};
+		account.balances.insert("EUR", balance);
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::ReportingTotal,
+		])
+		.unwrap();
+
+		assert_eq!(serializer.record(&account), vec!["1", ""]);
+		let reporting = |rates: &[(&str, &str)]| ReportingCurrency {
+			currency: rusty_money::iso::GBP,
+			rates: rates
+				.iter()
+				.map(|(code, rate)| (code.to_string(), Decimal::from_str(rate).unwrap()))
+				.collect(),
+		};
+		let partial = serializer.clone().with_reporting_currency(reporting(&[("USD", "0.8")]));
+		assert_eq!(partial.record(&account), vec!["1", ""]);
+		let serializer =
+			serializer.with_reporting_currency(reporting(&[("USD", "0.8"), ("EUR", "0.85")]));
+		assert_eq!(serializer.record(&account), vec!["1", "10.10"]);
+		assert_eq!(serializer.validate(&serializer.record(&account)), Ok(()));
+		assert_eq!(
+			serde_json::to_string(&serializer.typed_record(&account)).unwrap(),
+			r#"{"client":1,"reporting_total":"10.10"}"#
+		);
+	}
+
+	#[test]
+	fn test_invalid_columns() {
+		use AccountColumn::*;
+
+		assert_eq!(AccountSerializer::with_columns(vec![]), Err(NoColumns));
+		assert_eq!(
+			AccountSerializer::with_columns(vec![Client, Client, Available, Held, Total, Locked]),
+			Err(DuplicateColumn(Client))
+		);
+		assert_eq!("frozen".parse::<AccountColumn>(), Err(UnknownColumn("frozen".to_string())));
+	}
+
+	#[test]
+	fn test_validate() {
+		let serializer = AccountSerializer::default();
+		let record = |values: [&str; 5]| values.map(String::from).to_vec();
+
+		assert_eq!(serializer.validate(&serializer.record(&account())), Ok(()));
+		assert_eq!(
+			serializer.validate(&record(["1", "1.5", "0.5", "2.0", "true"])[..4]),
+			Err(OutputValidationError::ColumnCount { expected: 5, found: 4 })
+		);
+		assert_eq!(
+			serializer.validate(&record(["1", "1.12345", "0", "1.12345", "false"])),
+			Err(OutputValidationError::TooManyDecimalPlaces(
+				AccountColumn::Available,
+				"1.12345".into(),
+				4
+			))
+		);
+		assert_eq!(
+			serializer.validate(&record(["1", "1", "-1", "0", "false"])),
+			Err(OutputValidationError::NegativeBalance(AccountColumn::Held, "-1".into()))
+		);
+		let overdrawn = record(["1", "-5", "2", "-3", "false"]);
+		assert_eq!(
+			serializer.validate(&overdrawn),
+			Err(OutputValidationError::NegativeBalance(AccountColumn::Available, "-5".into()))
+		);
+		assert_eq!(serializer.clone().with_overdrafts().validate(&overdrawn), Ok(()));
+		assert_eq!(
+			serializer.validate(&record(["x", "1", "0", "1", "false"])),
+			Err(OutputValidationError::InvalidValue(AccountColumn::Client, "x".into()))
+		);
+	}
+}
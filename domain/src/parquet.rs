@@ -0,0 +1,352 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ::parquet::arrow::{ArrowWriter, ParquetRecordBatchStreamBuilder};
+use ::parquet::errors::ParquetError;
+use arrow_array::{
+	ArrayRef, BooleanArray, Decimal128Array, Int16Array, Int32Array, RecordBatch, StringArray,
+	UInt32Array,
+};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use csv_async::StringRecord;
+use futures::io::AsyncSeek;
+use futures::stream::{self, BoxStream};
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::ser::Error;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use crate::account::Account;
+use crate::output::{AccountColumn, AccountSerializer};
+use crate::sink::AccountSink;
+use crate::transaction::{AsyncRead, CsvError, StreamExt, Transaction, TransactionRow};
+
+/// The precision of decimal balance columns, the most digits a 128-bit decimal holds.
+const DECIMAL_PRECISION: u8 = 38;
+
+impl Transaction {
+	/// Stream transactions from the given reader of a Parquet file, including errors.
+	///
+	/// Every row is one record, with columns named like the CSV columns. The file is read one
+	/// record batch at a time, so it does not have to fit in memory. Values are read through their
+	/// text, so that decimal amounts keep their decimal places, and nulls are missing values. A
+	/// row that cannot be read is yielded as an error and reading continues with the next one; a
+	/// file that cannot be read ends the stream with an error.
+	pub fn tx_stream_parquet(
+		reader: impl AsyncRead + AsyncSeek + Unpin + Send + 'static,
+	) -> BoxStream<'static, Result<Transaction, CsvError>> {
+		stream::once(async move {
+			match ParquetRecordBatchStreamBuilder::new(reader.compat())
+				.await
+				.and_then(|builder| builder.build())
+			{
+				Ok(batches) => batches
+					.flat_map(|batch| {
+						stream::iter(match batch {
+							Ok(batch) => records(&batch),
+							Err(e) => vec![Err(read_error(e))],
+						})
+					})
+					.boxed(),
+				Err(e) => stream::iter(vec![Err(read_error(e))]).boxed(),
+			}
+		})
+		.flatten()
+		.boxed()
+	}
+}
+
+/// Reads the rows of `batch` as transactions.
+fn records(batch: &RecordBatch) -> Vec<Result<Transaction, CsvError>> {
+	let schema = batch.schema();
+	let headers =
+		StringRecord::from(schema.fields().iter().map(|field| field.name()).collect_vec());
+	let options = FormatOptions::default();
+	let formatters = match batch
+		.columns()
+		.iter()
+		.map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+		.collect::<Result<Vec<_>, _>>()
+	{
+		Ok(formatters) => formatters,
+		Err(e) => return vec![Err(CsvError::custom(format!("Parquet: {e}")))],
+	};
+	(0..batch.num_rows())
+		.map(|row| {
+			let record = StringRecord::from(
+				formatters
+					.iter()
+					.map(|formatter| formatter.value(row).to_string())
+					.collect_vec(),
+			);
+			Transaction::try_from(TransactionRow::from_record(&record, &headers))
+		})
+		.collect()
+}
+
+/// Returns the error of a file that cannot be read, which ends the stream.
+fn read_error(e: ParquetError) -> CsvError {
+	CsvError::from(io::Error::other(e))
+}
+
+/// Writes the accounts as a Parquet file with a column per configured output column, so that they
+/// can be loaded into a warehouse without inferring the column types: client ids, counts and
+/// transaction ids are integers, locked and closed flags booleans, and balances decimals with the
+/// configured decimal places. The sub-balances and the currency are text as in the CSV output, and
+/// empty credit limits, reporting totals and locking transactions are nulls.
+///
+/// The file is only complete once [`AccountSink::finish`] wrote its footer.
+pub struct ParquetSink<W: Write + Send> {
+	writer: Option<ArrowWriter<W>>,
+	schema: SchemaRef,
+	serializer: AccountSerializer,
+}
+
+impl<W: Write + Send> ParquetSink<W> {
+	/// Creates a sink writing a Parquet file to `writer`, with the columns of `serializer`.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the start of the file cannot be written.
+	pub fn new(writer: W, serializer: AccountSerializer) -> io::Result<Self> {
+		let scale = serializer.max_decimal_places();
+		let fields = serializer
+			.columns()
+			.zip(serializer.header())
+			.map(|(column, header)| {
+				let (data_type, nullable) = data_type(column, scale);
+				Field::new(header, data_type, nullable)
+			})
+			.collect_vec();
+		let schema = Arc::new(Schema::new(fields));
+		let writer =
+			ArrowWriter::try_new(writer, schema.clone(), None).map_err(io::Error::other)?;
+		Ok(ParquetSink { writer: Some(writer), schema, serializer })
+	}
+
+	/// Returns the values of `column` for `accounts`.
+	fn array(&self, column: AccountColumn, accounts: &[Account]) -> io::Result<ArrayRef> {
+		let text = |account: &Account| column.value(account, &self.serializer);
+		let array: ArrayRef = match column {
+			AccountColumn::Client => {
+				Arc::new(Int16Array::from_iter_values(accounts.iter().map(|a| a.client_id)))
+			},
+			AccountColumn::Locked => {
+				Arc::new(BooleanArray::from(accounts.iter().map(|a| a.locked).collect_vec()))
+			},
+			AccountColumn::Closed => {
+				Arc::new(BooleanArray::from(accounts.iter().map(|a| a.closed).collect_vec()))
+			},
+			AccountColumn::Deposits => Arc::new(UInt32Array::from_iter_values(
+				accounts.iter().map(|a| a.activity.deposits),
+			)),
+			AccountColumn::Withdrawals => Arc::new(UInt32Array::from_iter_values(
+				accounts.iter().map(|a| a.activity.withdrawals),
+			)),
+			AccountColumn::OpenDisputes => Arc::new(UInt32Array::from_iter_values(
+				accounts.iter().map(|a| a.activity.open_disputes),
+			)),
+			AccountColumn::LockedBy => Arc::new(Int32Array::from(
+				accounts.iter().map(|a| a.activity.locked_by).collect_vec(),
+			)),
+			AccountColumn::Balances | AccountColumn::HeldBalances | AccountColumn::Currency => {
+				Arc::new(StringArray::from_iter_values(accounts.iter().map(text)))
+			},
+			// The balances are rounded like in the CSV output, and their text read back exactly.
+			AccountColumn::Available
+			| AccountColumn::Held
+			| AccountColumn::Total
+			| AccountColumn::Outstanding
+			| AccountColumn::CreditLimit
+			| AccountColumn::ReportingTotal => {
+				let scale = self.serializer.max_decimal_places();
+				let values = accounts
+					.iter()
+					.map(|account| mantissa(&text(account), scale))
+					.collect::<io::Result<Vec<_>>>()?;
+				let array = Decimal128Array::from(values)
+					.with_precision_and_scale(DECIMAL_PRECISION, scale as i8)
+					.map_err(io::Error::other)?;
+				Arc::new(array)
+			},
+		};
+		Ok(array)
+	}
+}
+
+impl<W: Write + Send> AccountSink for ParquetSink<W> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		let columns = self
+			.serializer
+			.columns()
+			.map(|column| self.array(column, accounts))
+			.collect::<io::Result<Vec<_>>>()?;
+		let batch = RecordBatch::try_new(self.schema.clone(), columns).map_err(io::Error::other)?;
+		match &mut self.writer {
+			Some(writer) => writer.write(&batch).map_err(io::Error::other),
+			None => Err(io::Error::other("The Parquet file was finished already")),
+		}
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		match self.writer.take() {
+			Some(writer) => writer.into_inner().map_err(io::Error::other)?.flush(),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Returns the type of the values of `column`, and whether they may be null.
+fn data_type(column: AccountColumn, scale: u8) -> (DataType, bool) {
+	let decimal = DataType::Decimal128(DECIMAL_PRECISION, scale as i8);
+	match column {
+		AccountColumn::Client => (DataType::Int16, false),
+		AccountColumn::Available
+		| AccountColumn::Held
+		| AccountColumn::Total
+		| AccountColumn::Outstanding => (decimal, false),
+		AccountColumn::CreditLimit | AccountColumn::ReportingTotal => (decimal, true),
+		AccountColumn::Locked | AccountColumn::Closed => (DataType::Boolean, false),
+		AccountColumn::Deposits | AccountColumn::Withdrawals | AccountColumn::OpenDisputes => {
+			(DataType::UInt32, false)
+		},
+		AccountColumn::LockedBy => (DataType::Int32, true),
+		AccountColumn::Balances | AccountColumn::HeldBalances | AccountColumn::Currency => {
+			(DataType::Utf8, false)
+		},
+	}
+}
+
+/// Returns the unscaled value of the decimal `text` at `scale`, `None` if it is empty.
+fn mantissa(text: &str, scale: u8) -> io::Result<Option<i128>> {
+	if text.is_empty() {
+		return Ok(None);
+	}
+	let mut decimal = Decimal::from_str(text).map_err(io::Error::other)?;
+	decimal.rescale(scale.into());
+	Ok(Some(decimal.mantissa()))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use arrow_array::Array;
+	use futures::io::Cursor;
+
+	use crate::amount::Amount;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_tx_stream_parquet() {
+		let batch = RecordBatch::try_from_iter([
+			(
+				"type",
+				Arc::new(StringArray::from(vec!["deposit", "dispute", "withdrawal", "deposit"]))
+					as ArrayRef,
+			),
+			("client", Arc::new(Int32Array::from(vec![1, 1, 1, 70_000])) as ArrayRef),
+			("tx", Arc::new(Int32Array::from(vec![1, 1, 2, 3])) as ArrayRef),
+			(
+				"amount",
+				Arc::new(
+					Decimal128Array::from(vec![Some(15_000), None, Some(5_000), Some(1)])
+						.with_precision_and_scale(10, 4)
+						.unwrap(),
+				) as ArrayRef,
+			),
+		])
+		.unwrap();
+		let mut file = Vec::new();
+		let mut writer = ArrowWriter::try_new(&mut file, batch.schema(), None).unwrap();
+		writer.write(&batch).unwrap();
+		writer.close().unwrap();
+
+		let vec: Vec<_> = Transaction::tx_stream_parquet(Cursor::new(file)).collect().await;
+		assert_eq!(vec.len(), 4);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert_eq!(
+			vec[2].as_ref().unwrap(),
+			&Transaction::withdrawal(2, Amount::try_from("0.5").unwrap(), 1)
+		);
+		// The client id is out of range.
+		assert!(vec[3].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_parquet_invalid_file() {
+		let vec: Vec<_> =
+			Transaction::tx_stream_parquet(Cursor::new(b"type,client,tx,amount".to_vec()))
+				.collect()
+				.await;
+		assert_eq!(vec.len(), 1);
+		assert!(vec[0].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_parquet_sink() {
+		let accounts = vec![
+			Account::new(1, Amount::try_from("1.10010").unwrap(), Amount::default(), false),
+			Account::new(2, Amount::default(), Amount::try_from("2").unwrap(), true),
+		];
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::Available,
+			AccountColumn::Held,
+			AccountColumn::Locked,
+			AccountColumn::LockedBy,
+			AccountColumn::CreditLimit,
+		])
+		.unwrap();
+		let mut file = Vec::new();
+		let mut sink = ParquetSink::new(&mut file, serializer).unwrap();
+		sink.write(&accounts[..1]).await.unwrap();
+		sink.write(&accounts[1..]).await.unwrap();
+		sink.finish().await.unwrap();
+
+		let batches: Vec<_> = ParquetRecordBatchStreamBuilder::new(Cursor::new(file).compat())
+			.await
+			.unwrap()
+			.build()
+			.unwrap()
+			.map(Result::unwrap)
+			.collect()
+			.await;
+		// The batches are buffered into a single row group, read back as a single batch.
+		assert_eq!(batches.len(), 1);
+		let batch = &batches[0];
+		let types = batch
+			.schema()
+			.fields()
+			.iter()
+			.map(|field| field.data_type().clone())
+			.collect_vec();
+		let decimal = DataType::Decimal128(DECIMAL_PRECISION, 4);
+		assert_eq!(
+			types,
+			vec![
+				DataType::Int16,
+				decimal.clone(),
+				decimal.clone(),
+				DataType::Boolean,
+				DataType::Int32,
+				decimal
+			]
+		);
+		let held = batch.column(2).as_any().downcast_ref::<Decimal128Array>().unwrap();
+		let available = batch.column(1).as_any().downcast_ref::<Decimal128Array>().unwrap();
+		assert_eq!(
+			(available.value_as_string(0), held.value_as_string(1)),
+			("1.1001".to_string(), "2.0000".to_string())
+		);
+		assert!(batch.column(3).as_any().downcast_ref::<BooleanArray>().unwrap().value(1));
+		assert_eq!((batch.column(4).null_count(), batch.column(5).null_count()), (2, 2));
+	}
+}
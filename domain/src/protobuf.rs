@@ -0,0 +1,266 @@
+use std::io;
+
+use futures::io::{AsyncReadExt, BufReader};
+use futures::stream::{self, BoxStream};
+use serde::ser::Error;
+
+use crate::transaction::{AsyncRead, CsvError, StreamExt, Transaction, TransactionRow};
+
+/// The largest record accepted, guarding against allocating for a corrupt length prefix.
+const MAX_RECORD_LENGTH: u64 = 64 * 1024;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+impl Transaction {
+	/// Stream transactions from the given reader of length-delimited protobuf
+	/// `TransactionRecord` messages, as declared in `proto/transaction.proto`, including errors.
+	///
+	/// A record that cannot be decoded is yielded as an error and streaming continues with the
+	/// next one; a truncated stream or an implausible length prefix is yielded as an IO error.
+	pub fn tx_stream_protobuf(
+		reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> BoxStream<'static, Result<Transaction, CsvError>> {
+		stream::unfold(Some((BufReader::new(reader), 1)), |state| async move {
+			let (mut reader, index) = state?;
+			match read_record(&mut reader).await {
+				Ok(Some(bytes)) => {
+					let tx = decode_record(&bytes)
+						.map_err(|e| CsvError::custom(format!("Record {index}: {e}")))
+						.and_then(|row| Transaction::try_from(Ok(row)));
+					Some((tx, Some((reader, index + 1))))
+				},
+				Ok(None) => None,
+				Err(e) => Some((Err(CsvError::from(e)), None)),
+			}
+		})
+		.boxed()
+	}
+}
+
+/// Reads the next length-delimited record, `None` if the stream ends before it.
+async fn read_record(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+	let Some(length) = read_varint(reader).await? else {
+		return Ok(None);
+	};
+	if length > MAX_RECORD_LENGTH {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("Record length {length} exceeds the maximum of {MAX_RECORD_LENGTH}"),
+		));
+	}
+	let mut bytes = vec![0; length as usize];
+	reader.read_exact(&mut bytes).await?;
+	Ok(Some(bytes))
+}
+
+/// Reads a varint, `None` if the stream ends before its first byte.
+async fn read_varint(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<u64>> {
+	let mut value = 0;
+	for (i, shift) in (0..64).step_by(7).enumerate() {
+		let mut byte = [0];
+		if reader.read(&mut byte).await? == 0 {
+			return if i == 0 { Ok(None) } else { Err(io::ErrorKind::UnexpectedEof.into()) };
+		}
+		value |= u64::from(byte[0] & 0x7f) << shift;
+		if byte[0] & 0x80 == 0 {
+			return Ok(Some(value));
+		}
+	}
+	Err(io::Error::new(io::ErrorKind::InvalidData, "Varint too long"))
+}
+
+/// Takes a varint off the front of `bytes`.
+fn take_varint(bytes: &mut &[u8]) -> Result<u64, String> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let (byte, rest) = bytes.split_first().ok_or("Truncated varint")?;
+		*bytes = rest;
+		value |= u64::from(byte & 0x7f) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err("Varint too long".to_string())
+}
+
+/// Takes `length` bytes off the front of `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], length: u64) -> Result<&'a [u8], String> {
+	let length = usize::try_from(length).map_err(|e| e.to_string())?;
+	if length > bytes.len() {
+		return Err("Truncated field".to_string());
+	}
+	let (field, rest) = bytes.split_at(length);
+	*bytes = rest;
+	Ok(field)
+}
+
+/// Decodes a `TransactionRecord` message into a transaction row, skipping unknown fields.
+fn decode_record(mut bytes: &[u8]) -> Result<TransactionRow, String> {
+	let (mut tx_type, mut client, mut tx_id, mut amount) = (None, 0, 0, None);
+	let (mut to, mut reference, mut reason, mut idempotency_key) = (None, None, None, None);
+	let (mut memo, mut merchant, mut external_reference, mut timestamp) = (None, None, None, None);
+	let (mut group_id, mut currency, mut to_currency, mut rate) = (None, None, None, None);
+	while !bytes.is_empty() {
+		let key = take_varint(&mut bytes)?;
+		match (key >> 3, key & 0x7) {
+			(1, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				tx_type = Some(utf8(take(&mut bytes, length)?)?);
+			},
+			(2, WIRE_VARINT) => {
+				client = take_varint(&mut bytes)?.try_into().map_err(|_| "Client out of range")?
+			},
+			(3, WIRE_VARINT) => {
+				tx_id = take_varint(&mut bytes)?.try_into().map_err(|_| "Tx out of range")?
+			},
+			(4, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				amount = Some(utf8(take(&mut bytes, length)?)?);
+			},
+			(5, WIRE_VARINT) => {
+				to = Some(take_varint(&mut bytes)?.try_into().map_err(|_| "To out of range")?)
+			},
+			(6, WIRE_VARINT) => {
+				reference =
+					Some(take_varint(&mut bytes)?.try_into().map_err(|_| "Ref out of range")?)
+			},
+			(7, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				reason = Some(utf8(take(&mut bytes, length)?)?);
+			},
+			(8, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				idempotency_key = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			// An int64, whose negative values are encoded as ten byte varints.
+			(12, WIRE_VARINT) => timestamp = Some(take_varint(&mut bytes)? as i64),
+			(13, WIRE_VARINT) => {
+				group_id =
+					Some(take_varint(&mut bytes)?.try_into().map_err(|_| "Group out of range")?)
+			},
+			(9, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				memo = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(10, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				merchant = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(11, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				external_reference = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(14, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				currency = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(15, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				to_currency = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(16, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				rate = Some(utf8(take(&mut bytes, length)?)?.to_string());
+			},
+			(_, WIRE_VARINT) => {
+				take_varint(&mut bytes)?;
+			},
+			(_, WIRE_LENGTH_DELIMITED) => {
+				let length = take_varint(&mut bytes)?;
+				take(&mut bytes, length)?;
+			},
+			(_, WIRE_FIXED64) => {
+				take(&mut bytes, 8)?;
+			},
+			(_, WIRE_FIXED32) => {
+				take(&mut bytes, 4)?;
+			},
+			(field, wire_type) => {
+				return Err(format!("Unsupported wire type {wire_type} of field {field}"))
+			},
+		}
+	}
+	let tx_type = tx_type.ok_or("Missing type")?;
+	let mut row =
+		TransactionRow::from_fields(tx_type, client, tx_id, amount, to, reference, reason)?;
+	row.idempotency_key = idempotency_key;
+	row.timestamp = timestamp;
+	row.group_id = group_id;
+	row.memo = memo;
+	row.merchant = merchant;
+	row.external_reference = external_reference;
+	row.currency = currency;
+	row.to_currency = to_currency;
+	row.rate = rate;
+	Ok(row)
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, String> {
+	std::str::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::io::Cursor;
+
+	use crate::amount::Amount;
+
+	use super::*;
+
+	/// Encodes a `TransactionRecord` with a length prefix.
+	fn record(tx_type: &str, client: u8, tx: u8, amount: Option<&str>) -> Vec<u8> {
+		let mut message = vec![0x0a, tx_type.len() as u8];
+		message.extend(tx_type.as_bytes());
+		message.extend([0x10, client, 0x18, tx]);
+		if let Some(amount) = amount {
+			message.extend([0x22, amount.len() as u8]);
+			message.extend(amount.as_bytes());
+		}
+		[vec![message.len() as u8], message].concat()
+	}
+
+	async fn decode(input: Vec<u8>) -> Vec<Result<Transaction, CsvError>> {
+		Transaction::tx_stream_protobuf(Cursor::new(input)).collect().await
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_protobuf() {
+		let input = [
+			record("deposit", 1, 1, Some("1.5")),
+			record("dispute", 1, 1, None),
+			record("rebate", 1, 2, None),
+			record("withdrawal", 1, 3, Some("1.12345")),
+			record("withdrawal", 1, 4, Some("0.5")),
+		]
+		.concat();
+
+		let vec = decode(input).await;
+
+		assert_eq!(vec.len(), 5);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(vec[2].as_ref().unwrap_err().to_string().contains("Record 3:"));
+		assert!(vec[3].is_err());
+		assert_eq!(
+			vec[4].as_ref().unwrap(),
+			&Transaction::withdrawal(4, Amount::try_from("0.5").unwrap(), 1)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_protobuf_truncated() {
+		let mut input = record("deposit", 1, 1, Some("1.5"));
+		input.pop();
+
+		let vec = decode(input).await;
+
+		assert_eq!(vec.len(), 1);
+		assert!(vec[0].as_ref().unwrap_err().is_io_error());
+	}
+}
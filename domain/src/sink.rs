@@ -0,0 +1,369 @@
+use std::future::Future;
+use std::io::{self, Stdout, Write};
+
+use itertools::Itertools;
+
+use crate::account::Account;
+use crate::amount::Amount;
+use crate::config::ClientId;
+use crate::output::{AccountColumn, AccountSerializer};
+
+/// A destination for the resulting account states, such as a file or a message bus.
+///
+/// Accounts are written in batches, followed by a single call to [`AccountSink::finish`]. Sinks
+/// can be fanned out by writing to a pair of sinks, e.g. `(CsvSink::new(file, serializer), bus)`.
+pub trait AccountSink {
+	/// Writes the next batch of accounts.
+	fn write(&mut self, accounts: &[Account]) -> impl Future<Output = io::Result<()>> + Send;
+
+	/// Completes the output after the last batch.
+	fn finish(&mut self) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+impl<S: AccountSink + Send> AccountSink for &mut S {
+	fn write(&mut self, accounts: &[Account]) -> impl Future<Output = io::Result<()>> + Send {
+		(**self).write(accounts)
+	}
+
+	fn finish(&mut self) -> impl Future<Output = io::Result<()>> + Send {
+		(**self).finish()
+	}
+}
+
+impl<A: AccountSink + Send, B: AccountSink + Send> AccountSink for (A, B) {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		self.0.write(accounts).await?;
+		self.1.write(accounts).await
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		self.0.finish().await?;
+		self.1.finish().await
+	}
+}
+
+/// Collects the accounts.
+impl AccountSink for Vec<Account> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		self.extend_from_slice(accounts);
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Writes every account to the inner sink as one row per currency: the account itself, followed by
+/// an account for each of its currency sub-balances, see [`Account::currency_accounts`]. Add
+/// [`AccountColumn::Currency`] to the output columns to tell the rows of a client apart.
+pub struct CurrencyRowsSink<S: AccountSink>(pub S);
+
+impl<S: AccountSink + Send> AccountSink for CurrencyRowsSink<S> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		let rows = accounts
+			.iter()
+			.flat_map(|account| std::iter::once(account.clone()).chain(account.currency_accounts()))
+			.collect_vec();
+		self.0.write(&rows).await
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		self.0.finish().await
+	}
+}
+
+/// Writes the accounts as CSV with a header row, as configured by an [`AccountSerializer`].
+///
+/// The default writes `client,available,held,total,locked` to stdout.
+pub struct CsvSink<W: Write> {
+	writer: csv::Writer<W>,
+	serializer: AccountSerializer,
+	header_written: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+	pub fn new(writer: W, serializer: AccountSerializer) -> Self {
+		CsvSink { writer: csv::Writer::from_writer(writer), serializer, header_written: false }
+	}
+
+	fn write_header(&mut self) -> io::Result<()> {
+		if !self.header_written {
+			self.writer.write_record(self.serializer.header())?;
+			self.header_written = true;
+		}
+		Ok(())
+	}
+}
+
+impl Default for CsvSink<Stdout> {
+	fn default() -> Self {
+		CsvSink::new(io::stdout(), AccountSerializer::default())
+	}
+}
+
+impl<W: Write + Send> AccountSink for CsvSink<W> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		self.write_header()?;
+		for account in accounts {
+			self.writer.write_record(self.serializer.record(account))?;
+		}
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		self.write_header()?;
+		self.writer.flush()
+	}
+}
+
+/// Writes the accounts as a JSON array of objects keyed by the configured column headers, see
+/// [`AccountSerializer::typed_record`].
+pub struct JsonSink<W: Write> {
+	writer: W,
+	serializer: AccountSerializer,
+	written: usize,
+}
+
+impl<W: Write> JsonSink<W> {
+	pub fn new(writer: W, serializer: AccountSerializer) -> Self {
+		JsonSink { writer, serializer, written: 0 }
+	}
+}
+
+impl<W: Write + Send> AccountSink for JsonSink<W> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		for account in accounts {
+			self.writer.write_all(if self.written == 0 { b"[" } else { b"," })?;
+			serde_json::to_writer(&mut self.writer, &self.serializer.typed_record(account))?;
+			self.written += 1;
+		}
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		if self.written == 0 {
+			self.writer.write_all(b"[")?;
+		}
+		self.writer.write_all(b"]\n")?;
+		self.writer.flush()
+	}
+}
+
+/// Writes the accounts as JSON lines, one object keyed by the configured column headers per line.
+pub struct JsonLinesSink<W: Write> {
+	writer: W,
+	serializer: AccountSerializer,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+	pub fn new(writer: W, serializer: AccountSerializer) -> Self {
+		JsonLinesSink { writer, serializer }
+	}
+}
+
+impl<W: Write + Send> AccountSink for JsonLinesSink<W> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		for account in accounts {
+			serde_json::to_writer(&mut self.writer, &self.serializer.typed_record(account))?;
+			self.writer.write_all(b"\n")?;
+		}
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+/// Writes the accounts as a table with aligned columns and a row of base currency balance totals,
+/// for reading small results in a terminal.
+///
+/// The table is only written by [`AccountSink::finish`], sorted by client, as the column widths
+/// depend on every account.
+pub struct TableSink<W: Write> {
+	writer: W,
+	serializer: AccountSerializer,
+	rows: Vec<(ClientId, Vec<String>)>,
+	totals: [Amount; 3],
+}
+
+impl<W: Write> TableSink<W> {
+	pub fn new(writer: W, serializer: AccountSerializer) -> Self {
+		TableSink { writer, serializer, rows: Vec::new(), totals: Default::default() }
+	}
+
+	/// Returns the totals row: the sums of the balance columns, labelled in the client column.
+	fn totals_row(&self) -> Vec<String> {
+		self.serializer
+			.columns()
+			.map(|column| match column {
+				AccountColumn::Client => "total".to_string(),
+				AccountColumn::Available => self.serializer.format_amount(&self.totals[0]),
+				AccountColumn::Held => self.serializer.format_amount(&self.totals[1]),
+				AccountColumn::Total => self.serializer.format_amount(&self.totals[2]),
+				_ => String::new(),
+			})
+			.collect()
+	}
+}
+
+impl<W: Write + Send> AccountSink for TableSink<W> {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		for account in accounts {
+			if account.available.is_base_currency() {
+				self.totals[0].saturating_add_assign(&account.available);
+				self.totals[1].saturating_add_assign(&account.held);
+				self.totals[2].saturating_add_assign(&account.total());
+			}
+			self.rows.push((account.client_id, self.serializer.record(account)));
+		}
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		self.rows.sort_by_key(|(client, _)| *client);
+		let rows = self.rows.iter().map(|(_, row)| row).collect_vec();
+		let header = self.serializer.header().into_iter().map(String::from).collect_vec();
+		let totals = self.totals_row();
+		let widths = header
+			.iter()
+			.enumerate()
+			.map(|(i, name)| {
+				let values = rows.iter().copied().chain([&totals]).map(|row| row[i].len());
+				values.chain([name.len()]).max().unwrap_or_default()
+			})
+			.collect_vec();
+		let rule = widths.iter().map(|width| "-".repeat(*width)).collect_vec();
+		for row in [&header, &rule].into_iter().chain(rows).chain([&rule, &totals]) {
+			let mut cells =
+				self.serializer.columns().zip(row).zip(&widths).map(|((column, value), width)| {
+					match column {
+						AccountColumn::Locked => format!("{value:<width$}"),
+						_ => format!("{value:>width$}"),
+					}
+				});
+			writeln!(self.writer, "{}", cells.join("  ").trim_end())?;
+		}
+		self.writer.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rust_decimal::{Decimal, RoundingStrategy};
+	use rusty_money::Money;
+
+	use super::*;
+
+	fn accounts() -> Vec<Account> {
+		vec![
+			Account::new(1, Amount::try_from("1.10010").unwrap(), Amount::default(), false),
+			Account::new(2, Amount::default(), Amount::try_from("2").unwrap(), true),
+		]
+	}
+
+	async fn write_all(mut sink: impl AccountSink) {
+		let accounts = accounts();
+		sink.write(&accounts[..1]).await.unwrap();
+		sink.write(&accounts[1..]).await.unwrap();
+		sink.finish().await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_fan_out() {
+		let (mut csv, mut collected) = (Vec::new(), Vec::new());
+		write_all((CsvSink::new(&mut csv, AccountSerializer::default()), &mut collected)).await;
+
+		assert_eq!(
+			String::from_utf8(csv).unwrap(),
+			"client,available,held,total,locked\n1,1.1001,0.0,1.1001,false\n2,0.0,2.00,2.00,true\n"
+		);
+		assert_eq!(collected.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_csv_rounding() {
+		let available = Amount::try_from("1.00005").unwrap();
+		let account = Account::new(1, available, Amount::try_from("0.00015").unwrap(), false);
+		for (rounding, row) in [
+			(RoundingStrategy::MidpointAwayFromZero, "1,1.0001,0.0002,1.0002,false"),
+			(RoundingStrategy::MidpointNearestEven, "1,1.0000,0.0002,1.0002,false"),
+			(RoundingStrategy::ToZero, "1,1.0000,0.0001,1.0002,false"),
+		] {
+			let mut csv = Vec::new();
+			{
+				let serializer = AccountSerializer::default().with_rounding(rounding);
+				let mut sink = CsvSink::new(&mut csv, serializer);
+				sink.write(&[account.clone()]).await.unwrap();
+				sink.finish().await.unwrap();
+			}
+			assert_eq!(
+				String::from_utf8(csv).unwrap(),
+				format!("client,available,held,total,locked\n{row}\n")
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_currency_rows_sink() {
+		let mut account = accounts().remove(0);
+		let eur = Money::from_decimal(Decimal::new(92, 2), rusty_money::iso::EUR);
+		account
+			.convert(&Amount::try_from("1").unwrap(), &Amount::try_from(eur).unwrap())
+			.unwrap();
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::Currency,
+			AccountColumn::Available,
+		])
+		.unwrap();
+
+		let mut csv = Vec::new();
+		{
+			let mut sink = CurrencyRowsSink(CsvSink::new(&mut csv, serializer));
+			sink.write(&[account]).await.unwrap();
+			sink.finish().await.unwrap();
+		}
+		assert_eq!(
+			String::from_utf8(csv).unwrap(),
+			"client,currency,available\n1,USD,0.1001\n1,EUR,0.92\n"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_table_sink() {
+		let mut table = Vec::new();
+		let mut sink = TableSink::new(&mut table, AccountSerializer::default());
+		let accounts = accounts();
+		sink.write(&[accounts[1].clone(), accounts[0].clone()]).await.unwrap();
+		sink.finish().await.unwrap();
+
+		assert_eq!(
+			String::from_utf8(table).unwrap(),
+			"\
+client  available  held   total  locked
+------  ---------  ----  ------  ------
+     1     1.1001   0.0  1.1001  false
+     2        0.0  2.00    2.00  true
+------  ---------  ----  ------  ------
+ total     1.1001  2.00  3.1001
+"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_json_sinks() {
+		let (mut json, mut jsonl, mut empty) = (Vec::new(), Vec::new(), Vec::new());
+		write_all(JsonSink::new(&mut json, AccountSerializer::default())).await;
+		write_all(JsonLinesSink::new(&mut jsonl, AccountSerializer::default())).await;
+		JsonSink::new(&mut empty, AccountSerializer::default()).finish().await.unwrap();
+
+		let first =
+			r#"{"client":1,"available":"1.1001","held":"0.0","total":"1.1001","locked":false}"#;
+		let second = r#"{"client":2,"available":"0.0","held":"2.00","total":"2.00","locked":true}"#;
+		assert_eq!(String::from_utf8(json).unwrap(), format!("[{first},{second}]\n"));
+		assert_eq!(String::from_utf8(jsonl).unwrap(), format!("{first}\n{second}\n"));
+		assert_eq!(String::from_utf8(empty).unwrap(), "[]\n");
+	}
+}
@@ -0,0 +1,251 @@
+use core::fmt;
+use std::io;
+
+use csv_async::StringRecord;
+use futures::io::{AsyncReadExt, AsyncSeek, AsyncSeekExt, SeekFrom};
+
+#[cfg(any(feature = "amqp", feature = "kafka"))]
+use crate::config::GroupId;
+#[cfg(any(feature = "amqp", feature = "kafka"))]
+use crate::transaction::TransactionRow;
+use crate::transaction::{AsyncRead, CsvError, CsvFormat, Stream, StreamExt, Transaction};
+
+/// Represents errors reading transactions from a [`TransactionSource`].
+#[derive(Debug)]
+pub enum SourceError {
+	/// A record could not be read as a transaction; reading continues with the next record.
+	InvalidRecord(String),
+	/// The source could not be read; no further transactions can be read from it.
+	Io(io::Error),
+}
+
+impl fmt::Display for SourceError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SourceError::InvalidRecord(reason) => write!(f, "Invalid record: {reason}"),
+			SourceError::Io(err) => write!(f, "Could not read source: {err}"),
+		}
+	}
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<CsvError> for SourceError {
+	fn from(err: CsvError) -> Self {
+		if err.is_io_error() {
+			SourceError::Io(err.into())
+		} else {
+			SourceError::InvalidRecord(err.to_string())
+		}
+	}
+}
+
+/// A source of transactions to process, such as a transactions file or a message feed.
+///
+/// Readers are sources of CSV input with a header row. Other formats are read through
+/// [`CsvSource`] and [`StreamSource`], or by implementing this trait.
+pub trait TransactionSource {
+	/// Returns the transactions of this source in order, including the records that could not be
+	/// read as transactions.
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send;
+
+	/// Returns the transactions like [`Self::into_stream`], each with the byte offset of its
+	/// record in the source if the source knows it, which [`CsvSource::seek_to`] can resume from.
+	fn into_offset_stream(
+		self,
+	) -> impl Stream<Item = (Option<u64>, Result<Transaction, SourceError>)> + Unpin + Send
+	where
+		Self: Sized,
+	{
+		self.into_stream().map(|tx| (None, tx))
+	}
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> TransactionSource for R {
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		CsvSource::new(self, CsvFormat::default()).into_stream()
+	}
+
+	fn into_offset_stream(
+		self,
+	) -> impl Stream<Item = (Option<u64>, Result<Transaction, SourceError>)> + Unpin + Send {
+		CsvSource::new(self, CsvFormat::default()).into_offset_stream()
+	}
+}
+
+/// A source of CSV input in a configured [`CsvFormat`].
+pub struct CsvSource<R> {
+	reader: R,
+	format: CsvFormat,
+	/// The header row, if it was read before seeking to a record.
+	headers: Option<StringRecord>,
+	/// The byte offset of the input the reader was seeked to.
+	start: u64,
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> CsvSource<R> {
+	pub fn new(reader: R, format: CsvFormat) -> Self {
+		CsvSource { reader, format, headers: None, start: 0 }
+	}
+
+	/// Moves to the record at byte `offset` of the input like [`Self::seek_to`], but by reading
+	/// past the bytes before it, for input that cannot be seeked such as decompressed input.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the input cannot be read up to `offset`.
+	pub async fn skip_to(mut self, offset: u64) -> io::Result<Self> {
+		let mut skipped = (&mut self.reader).take(offset);
+		self.headers = self.format.read_headers(&mut skipped).await?;
+		futures::io::copy(&mut skipped, &mut futures::io::sink()).await?;
+		self.start = offset;
+		Ok(self)
+	}
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send + 'static> CsvSource<R> {
+	/// Moves to the record at byte `offset` of the input, such as the offset recorded by a
+	/// checkpoint, so that the records before it are not read again. The header row of input with
+	/// one is read first.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the header row cannot be read or the input cannot be seeked.
+	pub async fn seek_to(mut self, offset: u64) -> io::Result<Self> {
+		self.headers = self.format.read_headers(&mut self.reader).await?;
+		self.start = self.reader.seek(SeekFrom::Start(offset)).await?;
+		Ok(self)
+	}
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> TransactionSource for CsvSource<R> {
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		self.into_offset_stream().map(|(_, tx)| tx)
+	}
+
+	fn into_offset_stream(
+		self,
+	) -> impl Stream<Item = (Option<u64>, Result<Transaction, SourceError>)> + Unpin + Send {
+		let start = self.start;
+		self.format
+			.tx_stream_with_offsets(self.reader, self.headers)
+			.map(move |(offset, tx)| {
+				(offset.map(|offset| start + offset), tx.map_err(SourceError::from))
+			})
+	}
+}
+
+/// A source over a stream of transactions, such as [`Transaction::tx_stream_json`].
+pub struct StreamSource<S>(pub S);
+
+impl<S, E> TransactionSource for StreamSource<S>
+where
+	S: Stream<Item = Result<Transaction, E>> + Unpin + Send,
+	E: Into<SourceError>,
+{
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		self.0.map(|tx| tx.map_err(Into::into))
+	}
+}
+
+/// Returns the number of `pending` transactions of a message feed that the processor has handled,
+/// all but a trailing group. `TransactionProcessor::process` handles every transaction before it
+/// reads the next one, and the transactions of a group once it reads a transaction of another
+/// group, so a feed can settle the messages of the handled ones before yielding the next.
+#[cfg(any(feature = "amqp", feature = "kafka"))]
+pub(crate) fn handled<T>(pending: &[(T, Option<GroupId>)]) -> usize {
+	match pending.last() {
+		Some((_, Some(group))) => pending
+			.iter()
+			.rposition(|(_, other)| other.as_ref() != Some(group))
+			.map_or(0, |last_handled| last_handled + 1),
+		_ => pending.len(),
+	}
+}
+
+/// Reads the body of a message of a feed as a transaction, a JSON object like a line of
+/// [`Transaction::tx_stream_json`] input.
+#[cfg(any(feature = "amqp", feature = "kafka"))]
+pub(crate) fn read_message(body: &[u8]) -> Result<Transaction, String> {
+	let body = std::str::from_utf8(body).map_err(|e| format!("Message is not UTF-8: {e}"))?;
+	let row = serde_json::from_str::<TransactionRow>(body).map_err(|e| format!("Message: {e}"))?;
+	Transaction::try_from(Ok(row)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::io::Cursor;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_reader_source() {
+		let input =
+			Cursor::new(b"type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,x\n".to_vec());
+		let vec: Vec<_> = input.into_stream().collect().await;
+
+		assert!(vec[0].is_ok());
+		assert!(matches!(vec[1], Err(SourceError::InvalidRecord(_))));
+	}
+
+	#[tokio::test]
+	async fn test_csv_source_seek_to() {
+		let input = b"type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,2\n".to_vec();
+		let offsets: Vec<_> = Cursor::new(input.clone())
+			.into_offset_stream()
+			.map(|(offset, _)| offset)
+			.collect()
+			.await;
+		assert_eq!(offsets, vec![Some(22), Some(38)]);
+
+		let source = CsvSource::new(Cursor::new(input), CsvFormat::default()).seek_to(38).await;
+		let vec: Vec<_> = source.unwrap().into_offset_stream().collect().await;
+		assert_eq!(vec.len(), 1);
+		assert_eq!((vec[0].0, vec[0].1.as_ref().unwrap().id()), (Some(38), 2));
+	}
+
+	#[tokio::test]
+	async fn test_csv_source_skip_to() {
+		let input = b"type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,2\n".to_vec();
+		let source = CsvSource::new(Cursor::new(input), CsvFormat::default()).skip_to(38).await;
+		let vec: Vec<_> = source.unwrap().into_offset_stream().collect().await;
+		assert_eq!(vec.len(), 1);
+		assert_eq!((vec[0].0, vec[0].1.as_ref().unwrap().id()), (Some(38), 2));
+	}
+
+	#[tokio::test]
+	async fn test_stream_source() {
+		let source = StreamSource(futures::stream::iter([
+			Ok(Transaction::dispute(1, 1)),
+			Err(CsvError::from(io::Error::from(io::ErrorKind::UnexpectedEof))),
+		]));
+		let vec: Vec<_> = source.into_stream().collect().await;
+
+		assert_eq!(vec[0].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(matches!(vec[1], Err(SourceError::Io(_))));
+	}
+
+	#[cfg(any(feature = "amqp", feature = "kafka"))]
+	#[test]
+	fn test_read_message() {
+		let amount = crate::amount::Amount::try_from("1.5").unwrap();
+		assert_eq!(
+			read_message(br#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#),
+			Ok(Transaction::deposit(1, amount, 1))
+		);
+		assert!(read_message(br#"{"type": "deposit", "client": 1, "tx": 1}"#).is_err());
+		assert!(read_message(b"deposit,1,1,1.5").is_err());
+		assert!(read_message(b"\xff").is_err());
+	}
+
+	#[cfg(any(feature = "amqp", feature = "kafka"))]
+	#[test]
+	fn test_handled() {
+		assert_eq!(handled::<()>(&[]), 0);
+		assert_eq!(handled(&[((), None), ((), None)]), 2);
+		assert_eq!(handled(&[((), None), ((), Some(1)), ((), Some(1))]), 1);
+		assert_eq!(handled(&[((), Some(1)), ((), Some(2))]), 1);
+		assert_eq!(handled(&[((), Some(1)), ((), None)]), 2);
+		assert_eq!(handled(&[((), Some(2)), ((), Some(2))]), 0);
+	}
+}
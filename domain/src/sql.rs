@@ -0,0 +1,237 @@
+use std::io;
+
+use csv_async::StringRecord;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use sqlx::postgres::{PgPool, PgRow, Postgres};
+use sqlx::{Column, Decode, QueryBuilder, Row, Type};
+
+use crate::account::Account;
+use crate::output::AccountSerializer;
+use crate::sink::AccountSink;
+use crate::source::{SourceError, TransactionSource};
+use crate::transaction::{Stream, StreamExt, Transaction, TransactionRow};
+
+/// The number of accounts [`SqlSink`] upserts in one transaction by default.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// The largest number of accounts upserted in one statement, as Postgres accepts at most 65535
+/// bound parameters and each account binds 5.
+const MAX_BATCH_SIZE: usize = u16::MAX as usize / 5;
+
+/// A source of the rows of a SQL query on a Postgres database, such as transactions staged in a
+/// table, with columns named like the CSV columns.
+///
+/// The rows are streamed as the query returns them. Each row is read like a CSV record from the
+/// text of its values, so `NUMERIC` amounts keep their decimal places, and `NULL` values are
+/// missing values.
+pub struct SqlSource {
+	pool: PgPool,
+	query: String,
+}
+
+impl SqlSource {
+	/// Creates a source of the rows of `query` on the database of `pool`.
+	pub fn new(pool: PgPool, query: impl Into<String>) -> Self {
+		SqlSource { pool, query: query.into() }
+	}
+
+	/// Connects to the database at `url`, e.g. `postgres://user@localhost/payments`, to read the
+	/// rows of `query`.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if the database cannot be connected to.
+	pub async fn connect(url: &str, query: impl Into<String>) -> io::Result<Self> {
+		let pool = PgPool::connect(url).await.map_err(io::Error::other)?;
+		Ok(SqlSource::new(pool, query))
+	}
+}
+
+impl TransactionSource for SqlSource {
+	fn into_stream(self) -> impl Stream<Item = Result<Transaction, SourceError>> + Unpin + Send {
+		Box::pin(async_stream::stream! {
+			let mut rows = sqlx::query(&self.query).fetch(&self.pool);
+			let mut headers = None;
+			while let Some(row) = rows.next().await {
+				match row {
+					Ok(row) => {
+						let headers = headers.get_or_insert_with(|| {
+							StringRecord::from(
+								row.columns().iter().map(|column| column.name()).collect_vec(),
+							)
+						});
+						yield record(headers, &row);
+					},
+					Err(e) => {
+						yield Err(SourceError::Io(io::Error::other(e)));
+						break;
+					},
+				}
+			}
+		})
+	}
+}
+
+/// Reads a row as a transaction.
+fn record(headers: &StringRecord, row: &PgRow) -> Result<Transaction, SourceError> {
+	let values = (0..row.len())
+		.map(|index| {
+			text(row, index).ok_or_else(|| {
+				let name = row.columns()[index].name();
+				SourceError::InvalidRecord(format!("Unsupported type of column {name}"))
+			})
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	let record = StringRecord::from(values);
+	Ok(Transaction::try_from(TransactionRow::from_record(&record, headers))?)
+}
+
+/// Returns the text of the value at `index`, empty for `NULL`, or `None` if its type is not a
+/// text, number or boolean type.
+fn text(row: &PgRow, index: usize) -> Option<String> {
+	fn get<T>(row: &PgRow, index: usize) -> Option<String>
+	where
+		T: for<'r> Decode<'r, Postgres> + Type<Postgres> + ToString,
+	{
+		let value = row.try_get::<Option<T>, _>(index).ok()?;
+		Some(value.map(|value| value.to_string()).unwrap_or_default())
+	}
+
+	get::<String>(row, index)
+		.or_else(|| get::<i16>(row, index))
+		.or_else(|| get::<i32>(row, index))
+		.or_else(|| get::<i64>(row, index))
+		.or_else(|| get::<Decimal>(row, index))
+		.or_else(|| get::<f64>(row, index))
+		.or_else(|| get::<f32>(row, index))
+		.or_else(|| get::<bool>(row, index))
+}
+
+/// A sink upserting the accounts into a Postgres table with the columns `client`, `available`,
+/// `held`, `total` and `locked`, such as
+/// `CREATE TABLE accounts (client SMALLINT PRIMARY KEY, available NUMERIC, held NUMERIC, total
+/// NUMERIC, locked BOOLEAN)`.
+///
+/// The accounts are upserted on `client` in batches, each in its own database transaction, so an
+/// error leaves the batches before it written. The balances are rounded like the output, by an
+/// [`AccountSerializer`]; its columns are not used.
+pub struct SqlSink {
+	pool: PgPool,
+	table: String,
+	serializer: AccountSerializer,
+	batch_size: usize,
+}
+
+impl SqlSink {
+	/// Creates a sink upserting into `table`, optionally qualified by its schema, on the database
+	/// of `pool`.
+	///
+	/// # Errors
+	///
+	/// Returns an [`io::ErrorKind::InvalidInput`] error if `table` is not a plain SQL identifier.
+	pub fn new(pool: PgPool, table: &str, serializer: AccountSerializer) -> io::Result<Self> {
+		check_table(table)?;
+		Ok(SqlSink { pool, table: table.to_string(), serializer, batch_size: DEFAULT_BATCH_SIZE })
+	}
+
+	/// Connects to the database at `url`, e.g. `postgres://user@localhost/payments`, to upsert
+	/// into `table`.
+	///
+	/// # Errors
+	///
+	/// Returns an I/O error if `table` is not a plain SQL identifier or the database cannot be
+	/// connected to.
+	pub async fn connect(
+		url: &str,
+		table: &str,
+		serializer: AccountSerializer,
+	) -> io::Result<Self> {
+		check_table(table)?;
+		let pool = PgPool::connect(url).await.map_err(io::Error::other)?;
+		SqlSink::new(pool, table, serializer)
+	}
+
+	/// Upserts `batch_size` accounts per transaction instead of [`DEFAULT_BATCH_SIZE`], at least
+	/// one and at most as many as fit in one statement.
+	pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size.clamp(1, MAX_BATCH_SIZE);
+		self
+	}
+
+	/// Upserts `accounts` in one transaction.
+	async fn upsert(&self, accounts: &[Account]) -> Result<(), sqlx::Error> {
+		let mut query = QueryBuilder::<Postgres>::new(format!(
+			"INSERT INTO {} (client, available, held, total, locked) ",
+			self.table
+		));
+		query.push_values(accounts, |mut row, account| {
+			row.push_bind(account.client_id)
+				.push_bind(self.serializer.round_amount(&account.available))
+				.push_bind(self.serializer.round_amount(&account.held))
+				.push_bind(self.serializer.round_amount(&account.total()))
+				.push_bind(account.locked);
+		});
+		query.push(
+			" ON CONFLICT (client) DO UPDATE SET available = EXCLUDED.available, \
+			 held = EXCLUDED.held, total = EXCLUDED.total, locked = EXCLUDED.locked",
+		);
+		let mut transaction = self.pool.begin().await?;
+		query.build().execute(&mut *transaction).await?;
+		transaction.commit().await
+	}
+}
+
+impl AccountSink for SqlSink {
+	async fn write(&mut self, accounts: &[Account]) -> io::Result<()> {
+		for batch in accounts.chunks(self.batch_size) {
+			self.upsert(batch).await.map_err(io::Error::other)?;
+		}
+		Ok(())
+	}
+
+	async fn finish(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Checks that `table` is a plain SQL identifier, optionally qualified by a schema, as it is
+/// written into the upsert statement.
+fn check_table(table: &str) -> io::Result<()> {
+	let identifier = |name: &str| {
+		name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+			&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+	};
+	let valid = match table.split_once('.') {
+		Some((schema, name)) => identifier(schema) && identifier(name),
+		None => identifier(table),
+	};
+	if valid {
+		Ok(())
+	} else {
+		let error = format!("Not a plain SQL table name: {table}");
+		Err(io::Error::new(io::ErrorKind::InvalidInput, error))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_connect_invalid_url() {
+		assert!(SqlSource::connect("mysql://localhost/payments", "SELECT 1").await.is_err());
+	}
+
+	#[test]
+	fn test_check_table() {
+		assert!(check_table("accounts").is_ok());
+		assert!(check_table("payments.accounts_2024").is_ok());
+		assert!(check_table("_accounts").is_ok());
+		assert!(check_table("").is_err());
+		assert!(check_table("2024_accounts").is_err());
+		assert!(check_table("a.b.c").is_err());
+		assert!(check_table("accounts; DROP TABLE accounts").is_err());
+		assert!(check_table("\"accounts\"").is_err());
+	}
+}
@@ -2,24 +2,30 @@ use core::fmt;
 use std::fmt::Display;
 
 pub use async_std::fs::File;
-use csv_async::{AsyncReaderBuilder, DeserializeRecordsIntoStream, Trim};
+use csv_async::{AsyncReaderBuilder, Position, StringRecord, Trim};
 pub use csv_async::{Error as CsvError, Result as CsvResult};
+use futures::io::AsyncBufReadExt;
 pub use futures::stream::Map;
 pub use futures::stream::StreamExt;
 pub use futures::Stream;
 pub use futures_io::AsyncRead;
+use itertools::Itertools;
 use log::error;
 use rust_decimal::Decimal;
+use rusty_money::iso::Currency;
 use rusty_money::Money;
-use serde::de::Visitor;
+use serde::de::{IntoDeserializer, Visitor};
 use serde::ser::Error;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use TransactionError::{AccountFrozen, InsufficientFunds};
+use TransactionError::{AccountFrozen, BalanceCapExceeded, InsufficientFunds};
 
 use crate::account::AccountError;
-use crate::amount::Amount;
-use crate::config::{ClientId, TransactionId, CURRENCY, MAX_DECIMAL_PLACES, ROUNDING};
+use crate::amount::{lenient_decimal, Amount};
+use crate::config::{
+	amount_parsing, base_currency, max_decimal_places, rounding, AmountConfig, AmountParsing,
+	BatchId, ClientId, GroupId, Timestamp, TransactionId,
+};
 use crate::transaction::TransactionError::{
 	IllegalStateChange, InternalError, InvalidTransactionId,
 };
@@ -37,6 +43,40 @@ pub(crate) enum TransactionRowType {
 	Resolve,
 	#[serde(rename = "chargeback")]
 	Chargeback,
+	#[serde(rename = "transfer")]
+	Transfer,
+	#[serde(rename = "fee")]
+	Fee,
+	#[serde(rename = "refund")]
+	Refund,
+	#[serde(rename = "reversal")]
+	Reversal,
+	#[serde(rename = "authorize")]
+	Authorize,
+	#[serde(rename = "capture")]
+	Capture,
+	#[serde(rename = "credit_adjustment")]
+	CreditAdjustment,
+	#[serde(rename = "debit_adjustment")]
+	DebitAdjustment,
+	#[serde(rename = "unlock")]
+	Unlock,
+	#[serde(rename = "reopen")]
+	Reopen,
+	#[serde(rename = "close_account")]
+	CloseAccount,
+	#[serde(rename = "settlement")]
+	Settlement,
+	#[serde(rename = "convert")]
+	Convert,
+	#[serde(rename = "representment")]
+	Representment,
+	#[serde(rename = "pre_arbitration")]
+	PreArbitration,
+	#[serde(rename = "hold")]
+	Hold,
+	#[serde(rename = "release")]
+	Release,
 }
 impl TransactionRowType {
 	/// Checks if the transaction type should have an associated amount.
@@ -46,22 +86,364 @@ impl TransactionRowType {
 			TransactionRowType::Dispute
 				| TransactionRowType::Resolve
 				| TransactionRowType::Chargeback
+				| TransactionRowType::Reversal
+				| TransactionRowType::Capture
+				| TransactionRowType::Unlock
+				| TransactionRowType::Reopen
+				| TransactionRowType::CloseAccount
+				| TransactionRowType::Settlement
+				| TransactionRowType::Representment
+				| TransactionRowType::PreArbitration
+		)
+	}
+
+	/// Checks if the transaction type references a transaction or batch in the `ref` column.
+	pub(crate) fn has_reference(&self) -> bool {
+		matches!(self, TransactionRowType::Refund | TransactionRowType::Settlement)
+	}
+
+	/// Checks if the transaction type may be in a currency other than the base currency.
+	pub(crate) fn has_currency(&self) -> bool {
+		matches!(
+			self,
+			TransactionRowType::Deposit
+				| TransactionRowType::Withdrawal
+				| TransactionRowType::Dispute
+				| TransactionRowType::Convert
 		)
 	}
+
+	/// Checks if the transaction type is an administrative operation, an adjustment, an unlock, a
+	/// reopened chargeback or a manual hold or release, which requires a reason.
+	pub(crate) fn requires_reason(&self) -> bool {
+		matches!(
+			self,
+			TransactionRowType::CreditAdjustment
+				| TransactionRowType::DebitAdjustment
+				| TransactionRowType::Unlock
+				| TransactionRowType::Reopen
+				| TransactionRowType::Hold
+				| TransactionRowType::Release
+		)
+	}
+}
+
+tokio::task_local! {
+	/// In scope while reading a record of text fields, see [`TransactionRow::from_record`].
+	static TEXT_FIELDS: ();
 }
 
-/// Represents a row in the transaction CSV file.
+/// Represents a transaction input record, such as a row in the transaction CSV file.
 #[derive(Debug, Deserialize, PartialEq)]
-pub(crate) struct TransactionRow {
+pub struct TransactionRow {
 	#[serde(rename = "tx")]
 	pub(crate) tx_id: TransactionId,
 	#[serde(rename = "type")]
 	pub(crate) tx_type: TransactionRowType,
 	pub(crate) client: ClientId,
 	pub(crate) amount: Option<Amount>,
+	/// The destination client of a transfer.
+	#[serde(default)]
+	pub(crate) to: Option<ClientId>,
+	/// The original transaction of a refund, or the batch of a settlement.
+	#[serde(rename = "ref", default)]
+	pub(crate) reference: Option<TransactionId>,
+	/// The reason of an adjustment, unlock, reopened chargeback or manual hold or release.
+	#[serde(default)]
+	pub(crate) reason: Option<String>,
+	/// The key shared by the retries of a submission.
+	#[serde(default)]
+	pub(crate) idempotency_key: Option<String>,
+	/// When the sender recorded the transaction.
+	#[serde(default)]
+	pub(crate) timestamp: Option<Timestamp>,
+	/// The group of consecutive records applied together or not at all.
+	#[serde(default)]
+	pub(crate) group_id: Option<GroupId>,
+	/// A free-form note of the sender.
+	#[serde(default)]
+	pub(crate) memo: Option<String>,
+	/// The merchant of the transaction.
+	#[serde(default)]
+	pub(crate) merchant: Option<String>,
+	/// A free-form reference of the sender, such as the merchant's order number.
+	#[serde(rename = "reference", default)]
+	pub(crate) external_reference: Option<String>,
+	/// The currency of the amount, or of the transaction a dispute references, the base currency
+	/// if not given.
+	#[serde(default)]
+	pub(crate) currency: Option<String>,
+	/// The currency a conversion credits.
+	#[serde(default)]
+	pub(crate) to_currency: Option<String>,
+	/// The rate of a conversion, units of `to_currency` per unit of `currency`.
+	#[serde(default)]
+	pub(crate) rate: Option<String>,
+}
+
+/// Represents the columns of CSV transaction input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputColumn {
+	Type,
+	Client,
+	Tx,
+	Amount,
+	/// The destination client of transfers.
+	To,
+	/// The original transaction of refunds, or the batch of settlements.
+	Ref,
+	/// The reason of adjustments, unlocks, reopened chargebacks and manual holds and releases.
+	Reason,
+	/// The key shared by the retries of a submission.
+	IdempotencyKey,
+	/// When the sender recorded the transaction, in seconds since the Unix epoch.
+	Timestamp,
+	/// The group of consecutive records applied together or not at all.
+	GroupId,
+	/// A free-form note of the sender.
+	Memo,
+	/// The merchant of the transaction.
+	Merchant,
+	/// A free-form reference of the sender.
+	Reference,
+	/// The currency of the amount, or of the transaction disputes reference.
+	Currency,
+	/// The currency conversions credit.
+	ToCurrency,
+	/// The rate of conversions.
+	Rate,
+	/// A column that is not read.
+	Ignored,
+}
+
+impl InputColumn {
+	/// The columns every input must have.
+	const REQUIRED: [InputColumn; 3] = [InputColumn::Type, InputColumn::Client, InputColumn::Tx];
+
+	/// Returns the header name of the column, `_` for ignored columns.
+	pub fn header(&self) -> &'static str {
+		match self {
+			InputColumn::Type => "type",
+			InputColumn::Client => "client",
+			InputColumn::Tx => "tx",
+			InputColumn::Amount => "amount",
+			InputColumn::To => "to",
+			InputColumn::Ref => "ref",
+			InputColumn::Reason => "reason",
+			InputColumn::IdempotencyKey => "idempotency_key",
+			InputColumn::Timestamp => "timestamp",
+			InputColumn::GroupId => "group_id",
+			InputColumn::Memo => "memo",
+			InputColumn::Merchant => "merchant",
+			InputColumn::Reference => "reference",
+			InputColumn::Currency => "currency",
+			InputColumn::ToCurrency => "to_currency",
+			InputColumn::Rate => "rate",
+			InputColumn::Ignored => "_",
+		}
+	}
+}
+
+impl std::str::FromStr for InputColumn {
+	type Err = String;
+
+	/// Parses a column from its header name, `_` for ignored columns.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		[
+			InputColumn::Type,
+			InputColumn::Client,
+			InputColumn::Tx,
+			InputColumn::Amount,
+			InputColumn::To,
+			InputColumn::Ref,
+			InputColumn::Reason,
+			InputColumn::IdempotencyKey,
+			InputColumn::Timestamp,
+			InputColumn::GroupId,
+			InputColumn::Memo,
+			InputColumn::Merchant,
+			InputColumn::Reference,
+			InputColumn::Currency,
+			InputColumn::ToCurrency,
+			InputColumn::Rate,
+			InputColumn::Ignored,
+		]
+		.into_iter()
+		.find(|column| column.header() == s.trim())
+		.ok_or_else(|| format!("Unknown input column: {s}"))
+	}
+}
+
+/// Configures how transactions are read from CSV input.
+///
+/// The default reads comma separated input with a header row naming the columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvFormat {
+	/// The column order of input without a header row, `None` if the input has a header row.
+	columns: Option<Vec<InputColumn>>,
+	/// The field delimiter.
+	delimiter: u8,
+	/// How amounts are read, the configuration in scope of the reader's caller unless configured.
+	amount_config: Option<AmountConfig>,
+}
+
+impl Default for CsvFormat {
+	fn default() -> Self {
+		CsvFormat { columns: None, delimiter: b',', amount_config: None }
+	}
+}
+
+impl CsvFormat {
+	/// Reads fields separated by `delimiter`, e.g. `b'\t'` or `b'|'`.
+	pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+		self.delimiter = delimiter;
+		self
+	}
+
+	/// Reads input without a header row, taking the given `columns` in order.
+	///
+	/// # Errors
+	///
+	/// Returns an error naming the first of the `type`, `client` and `tx` columns that is missing.
+	pub fn with_columns(mut self, columns: Vec<InputColumn>) -> Result<Self, String> {
+		if let Some(missing) = InputColumn::REQUIRED.iter().find(|c| !columns.contains(c)) {
+			return Err(format!("Missing input column: {}", missing.header()));
+		}
+		self.columns = Some(columns);
+		Ok(self)
+	}
+
+	/// Reads amounts as configured by `config`, such as in another base currency or with more
+	/// decimal places, instead of by the configuration in scope.
+	pub fn with_amount_config(mut self, config: AmountConfig) -> Self {
+		self.amount_config = Some(config);
+		self
+	}
+
+	/// Stream transactions from the given reader, including errors
+	pub fn tx_stream(
+		&self,
+		reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> impl Stream<Item = Result<Transaction, CsvError>> {
+		self.tx_stream_with_offsets(reader, None).map(|(_, tx)| tx)
+	}
+
+	/// Streams transactions like [`Self::tx_stream`], each with the byte offset of its record in
+	/// the input if the record could be read. Input with a header row whose `headers` were read
+	/// before, such as input seeked to a record, is read from its first record.
+	pub(crate) fn tx_stream_with_offsets(
+		&self,
+		reader: impl AsyncRead + Unpin + Send + 'static,
+		headers: Option<StringRecord>,
+	) -> impl Stream<Item = (Option<u64>, Result<Transaction, CsvError>)> {
+		let columns = self.columns.as_ref().map(|columns| {
+			StringRecord::from(columns.iter().map(InputColumn::header).collect_vec())
+		});
+		let amount_config = self.amount_config;
+		let mut builder = AsyncReaderBuilder::new();
+		builder.trim(Trim::All).has_headers(false).delimiter(self.delimiter);
+		// Unless the headers are known, the first record is the header row naming the columns.
+		builder
+			.create_reader(reader)
+			.into_records()
+			.scan(columns.or(headers), move |headers, record| {
+				let item = match record {
+					Ok(record) => match headers {
+						Some(names) => {
+							let offset = record.position().map(Position::byte);
+							let config = amount_config.unwrap_or_else(AmountConfig::current);
+							let tx = config.sync_scope(|| {
+								Transaction::try_from(TransactionRow::from_record(&record, names))
+							});
+							Some((offset, tx))
+						},
+						None => {
+							*headers = Some(record);
+							None
+						},
+					},
+					Err(e) => Some((None, Err(e))),
+				};
+				futures::future::ready(Some(item))
+			})
+			.filter_map(futures::future::ready)
+	}
+
+	/// Reads the header row of `reader`, or returns `None` if the input has configured columns
+	/// instead.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the header row cannot be read.
+	pub(crate) async fn read_headers(
+		&self,
+		reader: impl AsyncRead + Unpin + Send,
+	) -> CsvResult<Option<StringRecord>> {
+		if self.columns.is_some() {
+			return Ok(None);
+		}
+		let mut builder = AsyncReaderBuilder::new();
+		builder.trim(Trim::All).delimiter(self.delimiter);
+		let mut reader = builder.create_reader(reader);
+		Ok(Some(reader.headers().await?.clone()))
+	}
+}
+
+impl TransactionRow {
+	/// Reads a record of text fields named by `headers`, such as a CSV record.
+	///
+	/// The amount is read from the text of its field. Deserializing the record as is would infer a
+	/// number from it, and lose decimal places of the input such as the trailing zeros of `1.50`.
+	pub(crate) fn from_record(record: &StringRecord, headers: &StringRecord) -> CsvResult<Self> {
+		TEXT_FIELDS.sync_scope((), || record.deserialize(Some(headers)))
+	}
+
+	/// Creates a record from the field values of a non-CSV input, reading the type and amount
+	/// the same way as CSV fields.
+	pub(crate) fn from_fields(
+		tx_type: &str,
+		client: ClientId,
+		tx_id: TransactionId,
+		amount: Option<&str>,
+		to: Option<ClientId>,
+		reference: Option<TransactionId>,
+		reason: Option<&str>,
+	) -> Result<Self, String> {
+		let tx_type = TransactionRowType::deserialize(tx_type.into_deserializer())
+			.map_err(|e: de::value::Error| e.to_string())?;
+		let amount = amount
+			.map(|amount| Amount::deserialize(amount.into_deserializer()))
+			.transpose()
+			.map_err(|e: de::value::Error| e.to_string())?;
+		let reason = reason.map(String::from);
+		Ok(TransactionRow {
+			tx_id,
+			tx_type,
+			client,
+			amount,
+			to,
+			reference,
+			reason,
+			idempotency_key: None,
+			timestamp: None,
+			group_id: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			currency: None,
+			to_currency: None,
+			rate: None,
+		})
+	}
 }
 
-/// Logic for deserializing an Amount from a string.
+/// Logic for deserializing an Amount from a decimal string, or from a number, such as a JSON
+/// number.
+///
+/// Integers are read exactly, and floats through their shortest representation, parsed like a
+/// string. A float is rejected if that representation has more significant digits than a float
+/// holds exactly. Records of text fields are read by [`TransactionRow::from_record`], which reads
+/// amounts as strings.
 impl<'de> Deserialize<'de> for Amount {
 	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
 	where
@@ -69,6 +451,28 @@ impl<'de> Deserialize<'de> for Amount {
 	{
 		struct AmountVisitor;
 
+		impl AmountVisitor {
+			/// Checks the decimal places and sign of the amount read from `input`.
+			fn amount<E: de::Error>(decimal: Decimal, input: impl Display) -> Result<Amount, E> {
+				let max = max_decimal_places();
+				if decimal.scale() > u32::from(max) {
+					return Err(de::Error::custom(format!(
+						"Too many decimal places: {input}, max allowed: {max}"
+					)));
+				};
+
+				let tx_amount = Amount::try_from(Money::from_decimal(decimal, base_currency()))
+					.map_err(|e| de::Error::custom(format!("Invalid amount: {e}")))?;
+				Ok(tx_amount)
+			}
+
+			/// Reads `units` minor units of the base currency, converted to those of the currency
+			/// of the record, if any, by [`TransactionRow`].
+			fn minor_units<E: de::Error>(units: i64, input: impl Display) -> Result<Amount, E> {
+				AmountVisitor::amount(Decimal::new(units, base_currency().exponent), input)
+			}
+		}
+
 		impl<'de> Visitor<'de> for AmountVisitor {
 			type Value = Amount;
 
@@ -80,21 +484,91 @@ impl<'de> Deserialize<'de> for Amount {
 			where
 				E: de::Error,
 			{
-				let decimal = Decimal::from_str_exact(v).map_err(de::Error::custom)?;
-				if decimal.scale() > MAX_DECIMAL_PLACES as u32 {
-					return Err(de::Error::custom(format!(
-						"Too many decimal places: {}, max allowed: {MAX_DECIMAL_PLACES}",
-						v
-					)));
+				let decimal = match amount_parsing() {
+					AmountParsing::MinorUnits => {
+						let units = v.trim().parse::<i64>().map_err(|_| {
+							de::Error::custom(format!("Invalid amount in minor units: {v}"))
+						})?;
+						return AmountVisitor::minor_units(units, v);
+					},
+					AmountParsing::Strict => {
+						Decimal::from_str_exact(v).map_err(de::Error::custom)?
+					},
+					AmountParsing::Lenient => lenient_decimal(v, base_currency())
+						.ok_or_else(|| de::Error::custom(format!("Invalid amount: {v}")))?,
 				};
+				AmountVisitor::amount(decimal, v)
+			}
 
-				let tx_amount = Amount::try_from(Money::from_decimal(decimal, CURRENCY))
-					.map_err(|e| de::Error::custom(format!("Invalid amount: {e}")))?;
-				Ok(tx_amount)
+			fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				if amount_parsing() == AmountParsing::MinorUnits {
+					let units = i64::try_from(v).map_err(|_| {
+						de::Error::custom(format!("Invalid amount in minor units: {v}"))
+					})?;
+					return AmountVisitor::minor_units(units, v);
+				}
+				AmountVisitor::amount(Decimal::from(v), v)
+			}
+
+			fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				if amount_parsing() == AmountParsing::MinorUnits {
+					return AmountVisitor::minor_units(v, v);
+				}
+				AmountVisitor::amount(Decimal::from(v), v)
+			}
+
+			fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				self.visit_str(&v.to_string())
+			}
+
+			fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				self.visit_str(&v.to_string())
 			}
+
+			fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				if !v.is_finite() {
+					return Err(de::Error::custom(format!("Invalid amount: {v}")));
+				}
+				// Display never uses an exponent, and prints the shortest representation.
+				let text = v.to_string();
+				let digits = text.chars().filter(char::is_ascii_digit).collect::<String>();
+				if digits.trim_matches('0').len() > f64::DIGITS as usize {
+					return Err(de::Error::custom(format!(
+						"Amount {v} has more significant digits than a number holds exactly"
+					)));
+				}
+				self.visit_str(&text)
+			}
+		}
+
+		if TEXT_FIELDS.try_with(|_| ()).is_ok() {
+			deserializer.deserialize_str(AmountVisitor)
+		} else {
+			deserializer.deserialize_any(AmountVisitor)
 		}
+	}
+}
 
-		deserializer.deserialize_str(AmountVisitor)
+/// Formats an Amount for output, rounded with the [`rounding`] in scope to at most its
+/// [`max_decimal_places`].
+impl Display for Amount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.rounded(max_decimal_places(), rounding()).to_string().as_str())
 	}
 }
 
@@ -103,11 +577,7 @@ impl Serialize for Amount {
 	where
 		S: Serializer,
 	{
-		let rounded = self
-			.value()
-			.amount()
-			.round_dp_with_strategy(MAX_DECIMAL_PLACES as u32, ROUNDING);
-		serializer.serialize_str(rounded.to_string().replace(CURRENCY.symbol, "").as_str())
+		serializer.collect_str(self)
 	}
 }
 
@@ -118,6 +588,8 @@ pub enum TransactionError {
 	TransactionNotFound(Transaction),
 	/// The transaction has already been processed.
 	DuplicateGlobalTransactionId(Transaction),
+	/// A record with the same idempotency key has already been processed.
+	DuplicateIdempotencyKey(Transaction),
 	/// The transaction id refers to a wrong type of transaction.
 	InvalidTransactionId(Transaction),
 	/// The account does not have enough funds to complete the transaction.
@@ -126,29 +598,323 @@ pub enum TransactionError {
 	IllegalStateChange(Transaction),
 	/// The referenced account has been frozen.
 	AccountFrozen(Transaction),
+	/// The transaction would take the account's balance above its maximum.
+	BalanceCapExceeded(Transaction),
+	/// The refunds of a transaction would exceed its amount.
+	RefundExceedsOriginal(Transaction),
+	/// The disputed portion exceeds the amount of the disputed transaction.
+	DisputeExceedsOriginal(Transaction),
+	/// The disputed transaction was applied before the dispute window.
+	DisputeWindowExpired(Transaction),
+	/// The referenced account has been closed.
+	AccountClosed(Transaction),
+	/// The account cannot be closed while it holds funds.
+	HeldFundsRemaining(Transaction),
+	/// Another transaction of the same group was rejected.
+	GroupRejected(Transaction),
+	/// The manual release exceeds the funds held manually.
+	ReleaseExceedsHold(Transaction),
+	/// The dispute or refund references a transaction in a different currency.
+	CurrencyMismatch(Transaction),
+	/// No exchange rate between the currencies of a conversion is known.
+	FxRateUnavailable(Transaction),
+	/// The rate of a conversion deviates from the rate table by more than the allowed slippage.
+	FxSlippageExceeded(Transaction),
+	/// The transfer names its own client as the destination.
+	SelfTransfer(Transaction),
+	/// The settlement names a batch of the client that was settled before.
+	BatchAlreadySettled(Transaction),
 	/// The transaction could not be processed due to an internal error.
-	InternalError(Transaction, String),
+	InternalError(Transaction, Box<str>),
+}
+
+impl TransactionError {
+	/// Returns a stable code of the rejection reason, for reports.
+	pub fn code(&self) -> &'static str {
+		match self {
+			TransactionError::TransactionNotFound(_) => "unknown_tx",
+			TransactionError::DuplicateGlobalTransactionId(_) => "duplicate_tx",
+			TransactionError::DuplicateIdempotencyKey(_) => "duplicate_idempotency_key",
+			InvalidTransactionId(_) => "invalid_tx_reference",
+			InsufficientFunds(_) => "insufficient_funds",
+			IllegalStateChange(_) => "illegal_state_change",
+			AccountFrozen(_) => "account_frozen",
+			BalanceCapExceeded(_) => "balance_cap_exceeded",
+			TransactionError::RefundExceedsOriginal(_) => "refund_exceeds_original",
+			TransactionError::DisputeExceedsOriginal(_) => "dispute_exceeds_original",
+			TransactionError::DisputeWindowExpired(_) => "dispute_window_expired",
+			TransactionError::AccountClosed(_) => "account_closed",
+			TransactionError::HeldFundsRemaining(_) => "held_funds_remaining",
+			TransactionError::GroupRejected(_) => "group_rejected",
+			TransactionError::ReleaseExceedsHold(_) => "release_exceeds_hold",
+			TransactionError::CurrencyMismatch(_) => "currency_mismatch",
+			TransactionError::FxRateUnavailable(_) => "fx_rate_unavailable",
+			TransactionError::FxSlippageExceeded(_) => "fx_slippage_exceeded",
+			TransactionError::SelfTransfer(_) => "self_transfer",
+			TransactionError::BatchAlreadySettled(_) => "batch_already_settled",
+			InternalError(..) => "internal_error",
+		}
+	}
+
+	/// Returns the transaction the error reports, the rejected one or the one it references.
+	pub fn transaction(&self) -> &Transaction {
+		match self {
+			TransactionError::TransactionNotFound(tx)
+			| TransactionError::DuplicateGlobalTransactionId(tx)
+			| TransactionError::DuplicateIdempotencyKey(tx)
+			| InvalidTransactionId(tx)
+			| InsufficientFunds(tx)
+			| IllegalStateChange(tx)
+			| AccountFrozen(tx)
+			| BalanceCapExceeded(tx)
+			| TransactionError::RefundExceedsOriginal(tx)
+			| TransactionError::DisputeExceedsOriginal(tx)
+			| TransactionError::DisputeWindowExpired(tx)
+			| TransactionError::AccountClosed(tx)
+			| TransactionError::HeldFundsRemaining(tx)
+			| TransactionError::GroupRejected(tx)
+			| TransactionError::ReleaseExceedsHold(tx)
+			| TransactionError::CurrencyMismatch(tx)
+			| TransactionError::FxRateUnavailable(tx)
+			| TransactionError::FxSlippageExceeded(tx)
+			| TransactionError::SelfTransfer(tx)
+			| TransactionError::BatchAlreadySettled(tx)
+			| InternalError(tx, _) => tx,
+		}
+	}
 }
 
 /// Represents the possible states of a transaction.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionState {
 	/// The transaction has been successfully processed.
 	Okay,
 	/// The transaction has been disputed.
 	Disputed,
+	/// The merchant has contested the dispute with evidence, the funds remain held.
+	Representment,
+	/// The cardholder's bank has escalated a contested dispute towards arbitration, the funds
+	/// remain held.
+	PreArbitration,
 	/// The transaction has been charged back.
 	ChargedBack,
+	/// The transaction has been reversed and can no longer be disputed.
+	Reversed,
+}
+
+impl TransactionState {
+	/// Returns the name of the state, as in snapshots.
+	pub fn name(&self) -> &'static str {
+		match self {
+			TransactionState::Okay => "okay",
+			TransactionState::Disputed => "disputed",
+			TransactionState::Representment => "representment",
+			TransactionState::PreArbitration => "pre_arbitration",
+			TransactionState::ChargedBack => "charged_back",
+			TransactionState::Reversed => "reversed",
+		}
+	}
+
+	/// Checks whether the transaction is in any stage of an open dispute, with its funds held.
+	pub fn is_disputed(&self) -> bool {
+		matches!(
+			self,
+			TransactionState::Disputed
+				| TransactionState::Representment
+				| TransactionState::PreArbitration
+		)
+	}
+}
+
+/// Attributes of an input record that do not affect how its transaction is applied.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RecordMetadata {
+	/// Identifies a submission and its retries, which may have different transaction ids.
+	pub idempotency_key: Option<Box<str>>,
+	/// When the sender recorded the transaction.
+	pub timestamp: Option<Timestamp>,
+	/// The group of transactions that apply together or not at all.
+	pub group_id: Option<GroupId>,
+	/// The free-form annotations of the record, boxed as most records have none.
+	pub annotations: Option<Box<Annotations>>,
+}
+
+/// Free-form attributes of an input record, passed through to the ledger and rejects.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Annotations {
+	/// A free-form note of the sender.
+	pub memo: Option<String>,
+	/// The merchant of the transaction.
+	pub merchant: Option<String>,
+	/// A free-form reference of the sender, such as the merchant's order number.
+	pub external_reference: Option<String>,
+}
+
+/// The amount and currencies of a conversion between the currency sub-balances of a client.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conversion {
+	/// The amount taken from the sub-balance of its currency.
+	pub amount: Amount,
+	/// The currency of the sub-balance credited.
+	pub to: &'static Currency,
+	/// The rate quoted by the sender, units of `to` per unit of the amount's currency. The rate
+	/// table of the processor is used if `None`.
+	pub rate: Option<Decimal>,
+}
+
+impl Conversion {
+	/// Returns the currency of the sub-balance debited.
+	pub fn from(&self) -> &'static Currency {
+		self.amount.value().currency()
+	}
 }
 
 /// Represents a financial transaction with an associated state.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Transaction {
-	Deposit { id: TransactionId, amount: Amount, client_id: ClientId, state: TransactionState },
-	Withdrawal { id: TransactionId, amount: Amount, client_id: ClientId, state: TransactionState },
-	Dispute { id: TransactionId, client: ClientId },
-	Resolve { id: TransactionId, client: ClientId },
-	Chargeback { id: TransactionId, client: ClientId },
+	Deposit {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		state: TransactionState,
+		disputed: Option<Amount>,
+		metadata: RecordMetadata,
+	},
+	Withdrawal {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		state: TransactionState,
+		disputed: Option<Amount>,
+		metadata: RecordMetadata,
+	},
+	Dispute {
+		id: TransactionId,
+		client: ClientId,
+		amount: Option<Amount>,
+		/// The currency of the disputed transaction, if stated by the dispute.
+		currency: Option<&'static Currency>,
+		metadata: RecordMetadata,
+	},
+	Resolve {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Chargeback {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Reversal {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Transfer {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		to: ClientId,
+		metadata: RecordMetadata,
+	},
+	Fee {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		metadata: RecordMetadata,
+	},
+	Refund {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		original: TransactionId,
+		metadata: RecordMetadata,
+	},
+	Authorization {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		/// The state of the withdrawal an authorization turned into once captured, `None` until
+		/// then.
+		captured: Option<TransactionState>,
+		disputed: Option<Amount>,
+		metadata: RecordMetadata,
+	},
+	Capture {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	CreditAdjustment {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	DebitAdjustment {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	Unlock {
+		id: TransactionId,
+		client: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	Reopen {
+		id: TransactionId,
+		client: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	CloseAccount {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Settlement {
+		id: TransactionId,
+		client: ClientId,
+		batch: BatchId,
+		metadata: RecordMetadata,
+	},
+	Representment {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Hold {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	Release {
+		id: TransactionId,
+		amount: Amount,
+		client_id: ClientId,
+		reason: String,
+		metadata: RecordMetadata,
+	},
+	PreArbitration {
+		id: TransactionId,
+		client: ClientId,
+		metadata: RecordMetadata,
+	},
+	Convert {
+		id: TransactionId,
+		client_id: ClientId,
+		/// Boxed to keep transactions small, as conversions are rare.
+		conversion: Box<Conversion>,
+		metadata: RecordMetadata,
+	},
 }
 
 impl TryFrom<CsvResult<TransactionRow>> for Transaction {
@@ -156,8 +922,11 @@ impl TryFrom<CsvResult<TransactionRow>> for Transaction {
 
 	/// Tries to convert a `TransactionRow` parsing result into a transaction.
 	fn try_from(row: CsvResult<TransactionRow>) -> Result<Self, CsvError> {
-		row.map(|transaction_row| {
-			if !transaction_row.tx_type.has_amount() && transaction_row.amount.is_some() {
+		row.map(|mut transaction_row| {
+			if !transaction_row.tx_type.has_amount()
+				&& transaction_row.tx_type != TransactionRowType::Dispute
+				&& transaction_row.amount.is_some()
+			{
 				Err(CsvError::custom(format!(
 					"Transaction with type {} cannot have an amount",
 					transaction_row.tx_type
@@ -167,20 +936,101 @@ impl TryFrom<CsvResult<TransactionRow>> for Transaction {
 					"Transaction with type {} must have an amount",
 					transaction_row.tx_type
 				)))
+			} else if transaction_row.tx_type != TransactionRowType::Transfer
+				&& transaction_row.to.is_some()
+			{
+				Err(CsvError::custom(format!(
+					"Transaction with type {} cannot have a destination client",
+					transaction_row.tx_type
+				)))
+			} else if !transaction_row.tx_type.has_reference()
+				&& transaction_row.reference.is_some()
+			{
+				Err(CsvError::custom(format!(
+					"Transaction with type {} cannot reference a transaction",
+					transaction_row.tx_type
+				)))
+			} else if !transaction_row.tx_type.requires_reason() && transaction_row.reason.is_some()
+			{
+				Err(CsvError::custom(format!(
+					"Transaction with type {} cannot have a reason",
+					transaction_row.tx_type
+				)))
+			} else if transaction_row.tx_type != TransactionRowType::Convert
+				&& (transaction_row.to_currency.is_some() || transaction_row.rate.is_some())
+			{
+				Err(CsvError::custom(format!(
+					"Transaction with type {} cannot have a target currency or rate",
+					transaction_row.tx_type
+				)))
+			} else if !transaction_row.tx_type.has_currency()
+				&& transaction_row
+					.currency
+					.as_deref()
+					.is_some_and(|currency| currency.trim() != base_currency().iso_alpha_code)
+			{
+				Err(CsvError::custom(format!(
+					"Transaction with type {} must be in {}",
+					transaction_row.tx_type,
+					base_currency().iso_alpha_code
+				)))
 			} else {
-				Ok(match transaction_row.tx_type {
+				let currency =
+					transaction_row.currency.as_deref().map(find_currency).transpose()?;
+				if let Some(currency) = currency {
+					transaction_row.amount = match (transaction_row.amount, amount_parsing()) {
+						// Minor units count in the units of the record's currency, such as yen
+						// instead of cents.
+						(Some(amount), AmountParsing::MinorUnits) => {
+							let units = amount.minor_units().ok_or_else(|| {
+								CsvError::custom(format!("Invalid amount: {amount}"))
+							})?;
+							Some(
+								Amount::from_minor_units(units, currency)
+									.map_err(CsvError::custom)?,
+							)
+						},
+						(amount, _) => amount.map(|amount| amount.in_currency(currency)),
+					};
+				}
+				let annotations = Annotations {
+					memo: transaction_row.memo,
+					merchant: transaction_row.merchant,
+					external_reference: transaction_row.external_reference,
+				};
+				let metadata = RecordMetadata {
+					idempotency_key: transaction_row.idempotency_key.map(String::into_boxed_str),
+					timestamp: transaction_row.timestamp,
+					group_id: transaction_row.group_id,
+					annotations: (annotations != Annotations::default())
+						.then(|| Box::new(annotations)),
+				};
+				let tx = match transaction_row.tx_type {
 					TransactionRowType::Deposit => Transaction::deposit(
 						transaction_row.tx_id,
-						transaction_row.amount.ok_or(CsvError::custom("Deposit must have an amount"))?,
+						transaction_row
+							.amount
+							.ok_or(CsvError::custom("Deposit must have an amount"))?,
 						transaction_row.client,
 					),
 					TransactionRowType::Withdrawal => Transaction::withdrawal(
 						transaction_row.tx_id,
-						transaction_row.amount.ok_or(CsvError::custom("Withdrawal must have an amount"))?,
+						transaction_row
+							.amount
+							.ok_or(CsvError::custom("Withdrawal must have an amount"))?,
 						transaction_row.client,
 					),
-					TransactionRowType::Dispute => {
-						Transaction::dispute(transaction_row.tx_id, transaction_row.client)
+					TransactionRowType::Dispute => match transaction_row.amount {
+						Some(amount) => Transaction::partial_dispute(
+							transaction_row.tx_id,
+							transaction_row.client,
+							amount,
+						),
+						None => Transaction::dispute_in(
+							transaction_row.tx_id,
+							transaction_row.client,
+							currency,
+						),
 					},
 					TransactionRowType::Resolve => {
 						Transaction::resolve(transaction_row.tx_id, transaction_row.client)
@@ -188,73 +1038,556 @@ impl TryFrom<CsvResult<TransactionRow>> for Transaction {
 					TransactionRowType::Chargeback => {
 						Transaction::chargeback(transaction_row.tx_id, transaction_row.client)
 					},
-				})
+					TransactionRowType::Reversal => {
+						Transaction::reversal(transaction_row.tx_id, transaction_row.client)
+					},
+					TransactionRowType::Representment => {
+						Transaction::representment(transaction_row.tx_id, transaction_row.client)
+					},
+					TransactionRowType::PreArbitration => {
+						Transaction::pre_arbitration(transaction_row.tx_id, transaction_row.client)
+					},
+					TransactionRowType::Authorize => Transaction::authorization(
+						transaction_row.tx_id,
+						transaction_row
+							.amount
+							.ok_or(CsvError::custom("Authorization must have an amount"))?,
+						transaction_row.client,
+					),
+					TransactionRowType::Capture => {
+						Transaction::capture(transaction_row.tx_id, transaction_row.client)
+					},
+					TransactionRowType::CreditAdjustment | TransactionRowType::DebitAdjustment => {
+						let amount = transaction_row
+							.amount
+							.ok_or(CsvError::custom("Adjustment must have an amount"))?;
+						let reason = transaction_row
+							.reason
+							.filter(|reason| !reason.trim().is_empty())
+							.ok_or(CsvError::custom("Adjustment must have a reason"))?;
+						let (id, client) = (transaction_row.tx_id, transaction_row.client);
+						match transaction_row.tx_type {
+							TransactionRowType::CreditAdjustment => {
+								Transaction::credit_adjustment(id, amount, client, reason)
+							},
+							_ => Transaction::debit_adjustment(id, amount, client, reason),
+						}
+					},
+					TransactionRowType::Hold | TransactionRowType::Release => {
+						let amount = transaction_row
+							.amount
+							.ok_or(CsvError::custom("Hold or release must have an amount"))?;
+						let reason = transaction_row
+							.reason
+							.filter(|reason| !reason.trim().is_empty())
+							.ok_or(CsvError::custom("Hold or release must have a reason"))?;
+						let (id, client) = (transaction_row.tx_id, transaction_row.client);
+						match transaction_row.tx_type {
+							TransactionRowType::Hold => {
+								Transaction::hold(id, amount, client, reason)
+							},
+							_ => Transaction::release(id, amount, client, reason),
+						}
+					},
+					TransactionRowType::Unlock => {
+						let reason = transaction_row
+							.reason
+							.filter(|reason| !reason.trim().is_empty())
+							.ok_or(CsvError::custom("Unlock must have a reason"))?;
+						Transaction::unlock(transaction_row.tx_id, transaction_row.client, reason)
+					},
+					TransactionRowType::Reopen => {
+						let reason = transaction_row
+							.reason
+							.filter(|reason| !reason.trim().is_empty())
+							.ok_or(CsvError::custom("Reopen must have a reason"))?;
+						Transaction::reopen(transaction_row.tx_id, transaction_row.client, reason)
+					},
+					TransactionRowType::CloseAccount => {
+						Transaction::close_account(transaction_row.tx_id, transaction_row.client)
+					},
+					TransactionRowType::Transfer => {
+						let to = transaction_row
+							.to
+							.ok_or(CsvError::custom("Transfer must have a destination client"))?;
+						if to == transaction_row.client {
+							return Err(CsvError::custom(
+								"Transfer must have a different destination client",
+							));
+						}
+						Transaction::transfer(
+							transaction_row.tx_id,
+							transaction_row
+								.amount
+								.ok_or(CsvError::custom("Transfer must have an amount"))?,
+							transaction_row.client,
+							to,
+						)
+					},
+					TransactionRowType::Fee => Transaction::fee(
+						transaction_row.tx_id,
+						transaction_row
+							.amount
+							.ok_or(CsvError::custom("Fee must have an amount"))?,
+						transaction_row.client,
+					),
+					TransactionRowType::Refund => Transaction::refund(
+						transaction_row.tx_id,
+						transaction_row
+							.amount
+							.ok_or(CsvError::custom("Refund must have an amount"))?,
+						transaction_row.client,
+						transaction_row
+							.reference
+							.ok_or(CsvError::custom("Refund must reference a transaction"))?,
+					),
+					TransactionRowType::Settlement => Transaction::settlement(
+						transaction_row.tx_id,
+						transaction_row.client,
+						transaction_row
+							.reference
+							.ok_or(CsvError::custom("Settlement must reference a batch"))?,
+					),
+					TransactionRowType::Convert => {
+						let amount = transaction_row
+							.amount
+							.ok_or(CsvError::custom("Convert must have an amount"))?;
+						let to = find_currency(
+							transaction_row
+								.to_currency
+								.as_deref()
+								.ok_or(CsvError::custom("Convert must have a target currency"))?,
+						)?;
+						if to == amount.value().currency() {
+							return Err(CsvError::custom(
+								"Convert must have a different target currency",
+							));
+						}
+						let rate = transaction_row
+							.rate
+							.map(|rate| match Decimal::from_str_exact(rate.trim()) {
+								Ok(rate) if rate.is_sign_positive() && !rate.is_zero() => Ok(rate),
+								_ => Err(CsvError::custom(format!("Invalid rate: {rate}"))),
+							})
+							.transpose()?;
+						Transaction::convert(
+							transaction_row.tx_id,
+							transaction_row.client,
+							Conversion { amount, to, rate },
+						)
+					},
+				};
+				Ok(tx.with_metadata(metadata))
 			}
-		})
-		.map_err(CsvError::from)?
+		})?
 	}
 }
 
+/// Finds the currency of an ISO 4217 or registered code.
+fn find_currency(code: &str) -> Result<&'static Currency, CsvError> {
+	crate::config::find_currency(code.trim())
+		.ok_or_else(|| CsvError::custom(format!("Unknown currency: {code}")))
+}
+
 impl From<(AccountError, Transaction)> for TransactionError {
 	fn from((err, tx): (AccountError, Transaction)) -> Self {
 		match err {
 			AccountError::InsufficientFunds => InsufficientFunds(tx),
 			AccountError::AccountLocked => AccountFrozen(tx),
-			AccountError::Arithmetic(e) => InternalError(tx, e.to_string()),
+			AccountError::BalanceCapExceeded => BalanceCapExceeded(tx),
+			AccountError::NotLocked => IllegalStateChange(tx),
+			AccountError::HeldFundsRemaining => TransactionError::HeldFundsRemaining(tx),
+			AccountError::Arithmetic(e) => InternalError(tx, e.to_string().into()),
+		}
+	}
+}
+impl Transaction {
+	/// Creates a new `Deposit` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount of the deposit.
+	/// * `client`: The client's ID.
+	pub fn deposit(id: TransactionId, amount: Amount, client: ClientId) -> Self {
+		Transaction::Deposit {
+			id,
+			amount,
+			client_id: client,
+			state: TransactionState::Okay,
+			disputed: None,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Withdrawal` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount of the withdrawal.
+	/// * `client`: The client's ID.
+	pub fn withdrawal(id: TransactionId, amount: Amount, client: ClientId) -> Self {
+		Transaction::Withdrawal {
+			id,
+			amount,
+			client_id: client,
+			state: TransactionState::Okay,
+			disputed: None,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Dispute` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the transaction being disputed.
+	/// * `client`: The client's ID initiating the dispute.
+	pub(crate) fn dispute(id: TransactionId, client: ClientId) -> Self {
+		Transaction::dispute_in(id, client, None)
+	}
+
+	/// Creates a new `Dispute` transaction of a transaction in the given currency, if stated.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the transaction being disputed.
+	/// * `client`: The client's ID initiating the dispute.
+	/// * `currency`: The currency of the transaction being disputed, `None` if not stated.
+	pub(crate) fn dispute_in(
+		id: TransactionId,
+		client: ClientId,
+		currency: Option<&'static Currency>,
+	) -> Self {
+		Transaction::Dispute {
+			id,
+			client,
+			amount: None,
+			currency,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Dispute` transaction of only part of the referenced transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the transaction being disputed.
+	/// * `client`: The client's ID for whom the dispute is being created.
+	/// * `amount`: The disputed portion, at most the amount of the disputed transaction.
+	pub(crate) fn partial_dispute(id: TransactionId, client: ClientId, amount: Amount) -> Self {
+		Transaction::Dispute {
+			id,
+			client,
+			currency: Some(amount.value().currency()),
+			amount: Some(amount),
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Resolve` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the transaction being resolved.
+	/// * `client`: The client's ID for whom the dispute is being resolved.
+	pub(crate) fn resolve(id: TransactionId, client: ClientId) -> Self {
+		Transaction::Resolve { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Chargeback` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the transaction being charged back.
+	/// * `client`: The client's ID initiating the chargeback.
+	pub(crate) fn chargeback(id: TransactionId, client: ClientId) -> Self {
+		Transaction::Chargeback { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Reversal` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the deposit or withdrawal being reversed.
+	/// * `client`: The client's ID whose transaction is being reversed.
+	pub(crate) fn reversal(id: TransactionId, client: ClientId) -> Self {
+		Transaction::Reversal { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Representment` transaction, recording that the merchant contested the
+	/// dispute of a transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the disputed transaction.
+	/// * `client`: The client's ID whose transaction is disputed.
+	pub(crate) fn representment(id: TransactionId, client: ClientId) -> Self {
+		Transaction::Representment { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `PreArbitration` transaction, recording that a contested dispute of a
+	/// transaction was escalated.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the disputed transaction.
+	/// * `client`: The client's ID whose transaction is disputed.
+	pub(crate) fn pre_arbitration(id: TransactionId, client: ClientId) -> Self {
+		Transaction::PreArbitration { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Authorization` transaction, which holds its amount until it is captured or
+	/// expires.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount authorized.
+	/// * `client`: The client's ID.
+	pub fn authorization(id: TransactionId, amount: Amount, client: ClientId) -> Self {
+		Transaction::Authorization {
+			id,
+			amount,
+			client_id: client,
+			captured: None,
+			disputed: None,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Capture` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier of the authorization being captured.
+	/// * `client`: The client's ID whose authorization is being captured.
+	pub fn capture(id: TransactionId, client: ClientId) -> Self {
+		Transaction::Capture { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `CreditAdjustment` transaction, an administrative correction that credits
+	/// the account even if it is locked.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount credited.
+	/// * `client`: The client's ID.
+	/// * `reason`: Why the balance is corrected, for the audit trail.
+	pub fn credit_adjustment(
+		id: TransactionId,
+		amount: Amount,
+		client: ClientId,
+		reason: impl Into<String>,
+	) -> Self {
+		Transaction::CreditAdjustment {
+			id,
+			amount,
+			client_id: client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `DebitAdjustment` transaction, an administrative correction that debits the
+	/// account even if it is locked.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount debited.
+	/// * `client`: The client's ID.
+	/// * `reason`: Why the balance is corrected, for the audit trail.
+	pub fn debit_adjustment(
+		id: TransactionId,
+		amount: Amount,
+		client: ClientId,
+		reason: impl Into<String>,
+	) -> Self {
+		Transaction::DebitAdjustment {
+			id,
+			amount,
+			client_id: client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
 		}
 	}
-}
-impl Transaction {
-	/// Creates a new `Deposit` transaction.
+
+	/// Creates a new `Hold` transaction, an administrative operation that moves an amount from the
+	/// available to the held balance, such as funds frozen by the risk team.
 	///
 	/// # Arguments
 	///
 	/// * `id`: The unique identifier for the transaction.
-	/// * `amount`: The amount of the deposit.
+	/// * `amount`: The amount held.
 	/// * `client`: The client's ID.
-	pub fn deposit(id: TransactionId, amount: Amount, client: ClientId) -> Self {
-		Transaction::Deposit { id, amount, client_id: client, state: TransactionState::Okay }
+	/// * `reason`: Who holds the funds and why, for the audit trail.
+	pub fn hold(
+		id: TransactionId,
+		amount: Amount,
+		client: ClientId,
+		reason: impl Into<String>,
+	) -> Self {
+		Transaction::Hold {
+			id,
+			amount,
+			client_id: client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
+		}
 	}
 
-	/// Creates a new `Withdrawal` transaction.
+	/// Creates a new `Release` transaction, an administrative operation that moves an amount held
+	/// by earlier `Hold` transactions back to the available balance.
 	///
 	/// # Arguments
 	///
 	/// * `id`: The unique identifier for the transaction.
-	/// * `amount`: The amount of the withdrawal.
+	/// * `amount`: The amount released.
 	/// * `client`: The client's ID.
-	pub fn withdrawal(id: TransactionId, amount: Amount, client: ClientId) -> Self {
-		Transaction::Withdrawal { id, amount, client_id: client, state: TransactionState::Okay }
+	/// * `reason`: Who releases the funds and why, for the audit trail.
+	pub fn release(
+		id: TransactionId,
+		amount: Amount,
+		client: ClientId,
+		reason: impl Into<String>,
+	) -> Self {
+		Transaction::Release {
+			id,
+			amount,
+			client_id: client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
+		}
 	}
 
-	/// Creates a new `Dispute` transaction.
+	/// Creates a new `Unlock` transaction, an administrative operation that unfreezes a locked
+	/// account once its chargeback investigation concludes.
 	///
 	/// # Arguments
 	///
-	/// * `id`: The unique identifier of the transaction being disputed.
-	/// * `client`: The client's ID initiating the dispute.
-	pub(crate) fn dispute(id: TransactionId, client: ClientId) -> Self {
-		Transaction::Dispute { id, client }
+	/// * `id`: The unique identifier for the transaction.
+	/// * `client`: The client's ID whose account is unlocked.
+	/// * `reason`: Who unlocks the account and why, for the audit trail.
+	pub fn unlock(id: TransactionId, client: ClientId, reason: impl Into<String>) -> Self {
+		Transaction::Unlock {
+			id,
+			client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
+		}
 	}
 
-	/// Creates a new `Resolve` transaction.
+	/// Creates a new `Reopen` transaction, an administrative operation that re-opens the dispute of
+	/// a charged back transaction, if the dispute policy allows it.
 	///
 	/// # Arguments
 	///
-	/// * `id`: The unique identifier of the transaction being resolved.
-	/// * `client`: The client's ID for whom the dispute is being resolved.
-	pub(crate) fn resolve(id: TransactionId, client: ClientId) -> Self {
-		Transaction::Resolve { id, client }
+	/// * `id`: The ID of the charged back transaction.
+	/// * `client`: The client's ID associated with the transaction.
+	/// * `reason`: Who re-opens the dispute and why, for the audit trail.
+	pub fn reopen(id: TransactionId, client: ClientId, reason: impl Into<String>) -> Self {
+		Transaction::Reopen {
+			id,
+			client,
+			reason: reason.into(),
+			metadata: RecordMetadata::default(),
+		}
 	}
 
-	/// Creates a new `Chargeback` transaction.
+	/// Creates a new `CloseAccount` transaction, which closes an account without held funds so
+	/// that it rejects all further activity.
 	///
 	/// # Arguments
 	///
-	/// * `id`: The unique identifier of the transaction being charged back.
-	/// * `client`: The client's ID initiating the chargeback.
-	pub(crate) fn chargeback(id: TransactionId, client: ClientId) -> Self {
-		Transaction::Chargeback { id, client }
+	/// * `id`: The unique identifier for the transaction.
+	/// * `client`: The client's ID whose account is closed.
+	pub fn close_account(id: TransactionId, client: ClientId) -> Self {
+		Transaction::CloseAccount { id, client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Settlement` transaction, which nets the transactions of a client applied
+	/// since its last settlement and moves the net amount in one operation.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `client`: The client's ID whose transactions are settled.
+	/// * `batch`: The acquirer's batch being settled.
+	pub fn settlement(id: TransactionId, client: ClientId, batch: BatchId) -> Self {
+		Transaction::Settlement { id, client, batch, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Transfer` transaction.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount to transfer.
+	/// * `client`: The client's ID the amount is taken from.
+	/// * `to`: The client's ID the amount is credited to.
+	pub fn transfer(id: TransactionId, amount: Amount, client: ClientId, to: ClientId) -> Self {
+		Transaction::Transfer {
+			id,
+			amount,
+			client_id: client,
+			to,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Fee` transaction, which debits the account and cannot be disputed.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount of the fee.
+	/// * `client`: The client's ID the fee is charged to.
+	pub fn fee(id: TransactionId, amount: Amount, client: ClientId) -> Self {
+		Transaction::Fee { id, amount, client_id: client, metadata: RecordMetadata::default() }
+	}
+
+	/// Creates a new `Refund` transaction, which credits the account with at most the amount of
+	/// the original transaction and cannot be disputed.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `amount`: The amount of the refund.
+	/// * `client`: The client's ID the refund is credited to.
+	/// * `original`: The unique identifier of the refunded deposit or withdrawal.
+	pub fn refund(
+		id: TransactionId,
+		amount: Amount,
+		client: ClientId,
+		original: TransactionId,
+	) -> Self {
+		Transaction::Refund {
+			id,
+			amount,
+			client_id: client,
+			original,
+			metadata: RecordMetadata::default(),
+		}
+	}
+
+	/// Creates a new `Convert` transaction, which moves value between two currency sub-balances
+	/// of the client.
+	///
+	/// # Arguments
+	///
+	/// * `id`: The unique identifier for the transaction.
+	/// * `client`: The client's ID whose sub-balances are converted.
+	/// * `conversion`: The amount converted and the currency it is converted to.
+	pub fn convert(id: TransactionId, client: ClientId, conversion: Conversion) -> Self {
+		Transaction::Convert {
+			id,
+			client_id: client,
+			conversion: Box::new(conversion),
+			metadata: RecordMetadata::default(),
+		}
 	}
 
 	/// Returns the transaction ID.
@@ -265,51 +1598,250 @@ impl Transaction {
 			Transaction::Dispute { id, .. } => *id,
 			Transaction::Resolve { id, .. } => *id,
 			Transaction::Chargeback { id, .. } => *id,
+			Transaction::Reversal { id, .. } => *id,
+			Transaction::Representment { id, .. } => *id,
+			Transaction::PreArbitration { id, .. } => *id,
+			Transaction::Authorization { id, .. } => *id,
+			Transaction::Capture { id, .. } => *id,
+			Transaction::CreditAdjustment { id, .. } => *id,
+			Transaction::DebitAdjustment { id, .. } => *id,
+			Transaction::Hold { id, .. } => *id,
+			Transaction::Release { id, .. } => *id,
+			Transaction::Unlock { id, .. } => *id,
+			Transaction::Reopen { id, .. } => *id,
+			Transaction::CloseAccount { id, .. } => *id,
+			Transaction::Settlement { id, .. } => *id,
+			Transaction::Transfer { id, .. } => *id,
+			Transaction::Fee { id, .. } => *id,
+			Transaction::Refund { id, .. } => *id,
+			Transaction::Convert { id, .. } => *id,
+		}
+	}
+
+	/// Returns the name of the transaction type, as in the `type` input column.
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Transaction::Deposit { .. } => "deposit",
+			Transaction::Withdrawal { .. } => "withdrawal",
+			Transaction::Dispute { .. } => "dispute",
+			Transaction::Resolve { .. } => "resolve",
+			Transaction::Chargeback { .. } => "chargeback",
+			Transaction::Reversal { .. } => "reversal",
+			Transaction::Representment { .. } => "representment",
+			Transaction::PreArbitration { .. } => "pre_arbitration",
+			Transaction::Authorization { .. } => "authorize",
+			Transaction::Capture { .. } => "capture",
+			Transaction::CreditAdjustment { .. } => "credit_adjustment",
+			Transaction::DebitAdjustment { .. } => "debit_adjustment",
+			Transaction::Hold { .. } => "hold",
+			Transaction::Release { .. } => "release",
+			Transaction::Unlock { .. } => "unlock",
+			Transaction::Reopen { .. } => "reopen",
+			Transaction::CloseAccount { .. } => "close_account",
+			Transaction::Settlement { .. } => "settlement",
+			Transaction::Transfer { .. } => "transfer",
+			Transaction::Fee { .. } => "fee",
+			Transaction::Refund { .. } => "refund",
+			Transaction::Convert { .. } => "convert",
 		}
 	}
 
-	/// Returns the transaction amount if applicable (`Deposit` or `Withdrawal`).
+	/// Returns the transaction amount if applicable (`Deposit`, `Withdrawal`, `Transfer`, `Fee`,
+	/// `Refund`, `Authorization`, an adjustment or a manual hold or release).
 	///
-	/// For `Dispute`, `Resolve`, and `Chargeback` transactions, returns `None`.
-	pub fn amount(&self) -> Option<Amount> {
+	/// For `Dispute`, `Resolve`, `Chargeback`, `Reversal` and `Capture` transactions, returns
+	/// `None`. The amount of a `Convert` may be in another currency, see [`Self::conversion`].
+	pub fn amount(&self) -> Option<&Amount> {
+		match self {
+			Transaction::Deposit { amount, .. } => Some(amount),
+			Transaction::Withdrawal { amount, .. } => Some(amount),
+			Transaction::Transfer { amount, .. } => Some(amount),
+			Transaction::Fee { amount, .. } => Some(amount),
+			Transaction::Refund { amount, .. } => Some(amount),
+			Transaction::Authorization { amount, .. } => Some(amount),
+			Transaction::CreditAdjustment { amount, .. } => Some(amount),
+			Transaction::DebitAdjustment { amount, .. } => Some(amount),
+			Transaction::Hold { amount, .. } => Some(amount),
+			Transaction::Release { amount, .. } => Some(amount),
+			_ => None,
+		}
+	}
+
+	/// Returns the reason of an adjustment, unlock, reopened chargeback or manual hold or release,
+	/// otherwise `None`.
+	pub fn reason(&self) -> Option<&str> {
+		match self {
+			Transaction::CreditAdjustment { reason, .. }
+			| Transaction::DebitAdjustment { reason, .. }
+			| Transaction::Hold { reason, .. }
+			| Transaction::Release { reason, .. }
+			| Transaction::Unlock { reason, .. }
+			| Transaction::Reopen { reason, .. } => Some(reason),
+			_ => None,
+		}
+	}
+
+	/// Returns the attributes of the input record of the transaction.
+	pub fn metadata(&self) -> &RecordMetadata {
+		match self {
+			Transaction::Deposit { metadata, .. }
+			| Transaction::Withdrawal { metadata, .. }
+			| Transaction::Dispute { metadata, .. }
+			| Transaction::Resolve { metadata, .. }
+			| Transaction::Chargeback { metadata, .. }
+			| Transaction::Reversal { metadata, .. }
+			| Transaction::Representment { metadata, .. }
+			| Transaction::PreArbitration { metadata, .. }
+			| Transaction::Transfer { metadata, .. }
+			| Transaction::Fee { metadata, .. }
+			| Transaction::Refund { metadata, .. }
+			| Transaction::Authorization { metadata, .. }
+			| Transaction::Capture { metadata, .. }
+			| Transaction::CreditAdjustment { metadata, .. }
+			| Transaction::DebitAdjustment { metadata, .. }
+			| Transaction::Hold { metadata, .. }
+			| Transaction::Release { metadata, .. }
+			| Transaction::Unlock { metadata, .. }
+			| Transaction::Reopen { metadata, .. }
+			| Transaction::CloseAccount { metadata, .. }
+			| Transaction::Settlement { metadata, .. }
+			| Transaction::Convert { metadata, .. } => metadata,
+		}
+	}
+
+	/// Sets the attributes of the input record of the transaction.
+	pub fn with_metadata(mut self, metadata: RecordMetadata) -> Self {
+		match &mut self {
+			Transaction::Deposit { metadata: m, .. }
+			| Transaction::Withdrawal { metadata: m, .. }
+			| Transaction::Dispute { metadata: m, .. }
+			| Transaction::Resolve { metadata: m, .. }
+			| Transaction::Chargeback { metadata: m, .. }
+			| Transaction::Reversal { metadata: m, .. }
+			| Transaction::Representment { metadata: m, .. }
+			| Transaction::PreArbitration { metadata: m, .. }
+			| Transaction::Transfer { metadata: m, .. }
+			| Transaction::Fee { metadata: m, .. }
+			| Transaction::Refund { metadata: m, .. }
+			| Transaction::Authorization { metadata: m, .. }
+			| Transaction::Capture { metadata: m, .. }
+			| Transaction::CreditAdjustment { metadata: m, .. }
+			| Transaction::DebitAdjustment { metadata: m, .. }
+			| Transaction::Hold { metadata: m, .. }
+			| Transaction::Release { metadata: m, .. }
+			| Transaction::Unlock { metadata: m, .. }
+			| Transaction::Reopen { metadata: m, .. }
+			| Transaction::CloseAccount { metadata: m, .. }
+			| Transaction::Settlement { metadata: m, .. }
+			| Transaction::Convert { metadata: m, .. } => *m = metadata,
+		}
+		self
+	}
+
+	/// Returns the free-form annotations of the input record, empty if it has none.
+	pub fn annotations(&self) -> Annotations {
+		self.metadata().annotations.as_deref().cloned().unwrap_or_default()
+	}
+
+	/// Returns when the sender recorded the transaction, if known.
+	pub fn timestamp(&self) -> Option<Timestamp> {
+		self.metadata().timestamp
+	}
+
+	/// Returns the group of transactions this one is applied with, if any.
+	pub fn group_id(&self) -> Option<GroupId> {
+		self.metadata().group_id
+	}
+
+	/// Returns the idempotency key of the input record, if any.
+	pub fn idempotency_key(&self) -> Option<&str> {
+		self.metadata().idempotency_key.as_deref()
+	}
+
+	/// Returns the original transaction of a `Refund` or the batch of a `Settlement`, otherwise
+	/// `None`.
+	pub fn reference(&self) -> Option<TransactionId> {
+		match self {
+			Transaction::Refund { original, .. } => Some(*original),
+			Transaction::Settlement { batch, .. } => Some(*batch),
+			_ => None,
+		}
+	}
+
+	/// Returns the amount and currencies of a `Convert`, otherwise `None`.
+	pub fn conversion(&self) -> Option<&Conversion> {
+		match self {
+			Transaction::Convert { conversion, .. } => Some(conversion),
+			_ => None,
+		}
+	}
+
+	/// Returns the currency of the transaction's amount, the currency a `Dispute` states for the
+	/// disputed transaction, if any, or the currency a `Convert` debits, otherwise `None`.
+	pub fn currency(&self) -> Option<&'static Currency> {
+		match self {
+			Transaction::Dispute { currency, .. } => *currency,
+			Transaction::Convert { conversion, .. } => Some(conversion.from()),
+			_ => self.amount().map(|amount| amount.value().currency()),
+		}
+	}
+
+	/// Returns the destination client of a `Transfer`, otherwise `None`.
+	pub fn destination(&self) -> Option<&ClientId> {
 		match self {
-			Transaction::Deposit { amount, .. } => Some(amount.clone()),
-			Transaction::Withdrawal { amount, .. } => Some(amount.clone()),
+			Transaction::Transfer { to, .. } => Some(to),
 			_ => None,
 		}
 	}
 
 	/// Returns the state of the transaction, if applicable.
 	///
-	/// Returns the state for `Deposit` and `Withdrawal` transactions; otherwise, returns `None`.
+	/// Returns the state for `Deposit` and `Withdrawal` transactions and captured `Authorization`s;
+	/// otherwise, returns `None`.
 	pub fn state(&self) -> Option<&TransactionState> {
 		match self {
-			Transaction::Deposit { state, .. } | Transaction::Withdrawal { state, .. } => {
-				Some(state)
-			},
+			Transaction::Deposit { state, .. }
+			| Transaction::Withdrawal { state, .. }
+			| Transaction::Authorization { captured: Some(state), .. } => Some(state),
 			_ => None,
 		}
 	}
 
-	/// Changes the state of a transaction based on the current state and the provided `transaction_state`.
+	/// Returns whether the transaction took its amount out of the account, a `Withdrawal` or a
+	/// captured `Authorization`, which are disputed alike.
+	pub fn is_withdrawal(&self) -> bool {
+		matches!(
+			self,
+			Transaction::Withdrawal { .. } | Transaction::Authorization { captured: Some(_), .. }
+		)
+	}
+
+	/// Checks whether the transaction may change from its current state to `transaction_state`,
+	/// without changing it.
 	///
 	/// # Errors
 	///
 	/// * Returns [`IllegalStateChange`] if the state transition is not allowed.
 	/// * Returns [`InvalidTransactionId`] if the transaction does not have a changeable state.
-	fn change_state(
-		&mut self,
+	pub fn check_state_change(
+		&self,
 		transaction_state: TransactionState,
 	) -> Result<(), TransactionError> {
 		match self {
-			Transaction::Deposit { state, .. } | Transaction::Withdrawal { state, .. } => {
+			Transaction::Deposit { state, .. }
+			| Transaction::Withdrawal { state, .. }
+			| Transaction::Authorization { captured: Some(state), .. } => {
 				match (*state, transaction_state) {
 					(TransactionState::Okay, TransactionState::Disputed)
-					| (TransactionState::Disputed, TransactionState::Okay)
-					| (TransactionState::Disputed, TransactionState::ChargedBack) => {
-						*state = transaction_state;
-						Ok(())
-					},
+					| (TransactionState::Disputed, TransactionState::Representment)
+					| (TransactionState::Representment, TransactionState::PreArbitration)
+					| (
+						TransactionState::Disputed
+						| TransactionState::Representment
+						| TransactionState::PreArbitration,
+						TransactionState::Okay | TransactionState::ChargedBack,
+					)
+					| (TransactionState::Okay, TransactionState::Reversed) => Ok(()),
 					_ => {
 						error!("Illegal state transition: {:?} -> {:?}", state, transaction_state);
 						Err(IllegalStateChange(self.clone()))
@@ -320,6 +1852,48 @@ impl Transaction {
 		}
 	}
 
+	/// Changes the state of a transaction based on the current state and the provided `transaction_state`.
+	///
+	/// # Errors
+	///
+	/// * Returns [`IllegalStateChange`] if the state transition is not allowed.
+	/// * Returns [`InvalidTransactionId`] if the transaction does not have a changeable state.
+	pub fn change_state(
+		&mut self,
+		transaction_state: TransactionState,
+	) -> Result<(), TransactionError> {
+		self.check_state_change(transaction_state)?;
+		if let Transaction::Deposit { state, .. }
+		| Transaction::Withdrawal { state, .. }
+		| Transaction::Authorization { captured: Some(state), .. } = self
+		{
+			*state = transaction_state;
+		}
+		Ok(())
+	}
+
+	/// Returns the disputed part of a partially disputed deposit, withdrawal or captured
+	/// authorization, `None` if it is disputed in full or not disputed.
+	pub fn disputed_portion(&self) -> Option<&Amount> {
+		match self {
+			Transaction::Deposit { disputed, .. }
+			| Transaction::Withdrawal { disputed, .. }
+			| Transaction::Authorization { disputed, .. } => disputed.as_ref(),
+			_ => None,
+		}
+	}
+
+	/// Sets the disputed part of a deposit, withdrawal or captured authorization, `None` if it is
+	/// disputed in full or no longer disputed. Does nothing for other transactions.
+	pub fn set_disputed_portion(&mut self, portion: Option<Amount>) {
+		if let Transaction::Deposit { disputed, .. }
+		| Transaction::Withdrawal { disputed, .. }
+		| Transaction::Authorization { captured: Some(_), disputed, .. } = self
+		{
+			*disputed = portion;
+		}
+	}
+
 	/// Sets the transaction state to `Disputed`.
 	pub fn set_disputed(&mut self) -> Result<(), TransactionError> {
 		self.change_state(TransactionState::Disputed)
@@ -335,6 +1909,36 @@ impl Transaction {
 		self.change_state(TransactionState::ChargedBack)
 	}
 
+	/// Sets the transaction state to `Reversed`.
+	pub fn set_reversed(&mut self) -> Result<(), TransactionError> {
+		self.change_state(TransactionState::Reversed)
+	}
+
+	/// Re-opens the dispute of a charged back transaction, setting its state back to `Disputed`.
+	/// This transition is not allowed by [`Self::change_state`], as only an administrative reopen
+	/// may undo a chargeback.
+	///
+	/// # Errors
+	///
+	/// * Returns [`IllegalStateChange`] if the transaction is not charged back.
+	/// * Returns [`InvalidTransactionId`] if the transaction does not have a changeable state.
+	pub fn reopen_chargeback(&mut self) -> Result<(), TransactionError> {
+		match self {
+			Transaction::Deposit { state, .. }
+			| Transaction::Withdrawal { state, .. }
+			| Transaction::Authorization { captured: Some(state), .. } => {
+				if *state == TransactionState::ChargedBack {
+					*state = TransactionState::Disputed;
+					Ok(())
+				} else {
+					error!("Illegal reopen of a transaction in state {:?}", state);
+					Err(IllegalStateChange(self.clone()))
+				}
+			},
+			_ => Err(InvalidTransactionId(self.clone())),
+		}
+	}
+
 	/// Returns the client ID.
 	pub fn client_id(&self) -> &ClientId {
 		match self {
@@ -343,6 +1947,23 @@ impl Transaction {
 			Transaction::Dispute { client, .. } => client,
 			Transaction::Resolve { client, .. } => client,
 			Transaction::Chargeback { client, .. } => client,
+			Transaction::Reversal { client, .. } => client,
+			Transaction::Representment { client, .. } => client,
+			Transaction::PreArbitration { client, .. } => client,
+			Transaction::Authorization { client_id: client, .. } => client,
+			Transaction::Capture { client, .. } => client,
+			Transaction::CreditAdjustment { client_id: client, .. } => client,
+			Transaction::DebitAdjustment { client_id: client, .. } => client,
+			Transaction::Hold { client_id: client, .. } => client,
+			Transaction::Release { client_id: client, .. } => client,
+			Transaction::Unlock { client, .. } => client,
+			Transaction::Reopen { client, .. } => client,
+			Transaction::CloseAccount { client, .. } => client,
+			Transaction::Settlement { client, .. } => client,
+			Transaction::Transfer { client_id: client, .. } => client,
+			Transaction::Fee { client_id: client, .. } => client,
+			Transaction::Refund { client_id: client, .. } => client,
+			Transaction::Convert { client_id: client, .. } => client,
 		}
 	}
 
@@ -350,13 +1971,29 @@ impl Transaction {
 	pub fn tx_stream(
 		reader: impl AsyncRead + Unpin + Send + 'static,
 	) -> impl Stream<Item = Result<Transaction, CsvError>> {
-		let csv_reader = AsyncReaderBuilder::new()
-			.trim(Trim::All)
-			.has_headers(true)
-			.create_deserializer(reader);
-		let iter: DeserializeRecordsIntoStream<_, TransactionRow> =
-			csv_reader.into_deserialize::<TransactionRow>();
-		iter.map(Transaction::try_from)
+		CsvFormat::default().tx_stream(reader)
+	}
+
+	/// Stream transactions from the given reader of newline-delimited JSON records, including
+	/// errors.
+	///
+	/// Every non-empty line holds one record with the same fields as the CSV columns, e.g.
+	/// `{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}`. Amounts may also be JSON
+	/// numbers, but only strings preserve their exact decimal places.
+	pub fn tx_stream_json(
+		reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> impl Stream<Item = Result<Transaction, CsvError>> {
+		futures::io::BufReader::new(reader)
+			.lines()
+			.enumerate()
+			.filter(|(_, line)| {
+				futures::future::ready(!matches!(line, Ok(line) if line.trim().is_empty()))
+			})
+			.map(|(index, line)| {
+				let row = serde_json::from_str::<TransactionRow>(&line?)
+					.map_err(|e| CsvError::custom(format!("Line {}: {e}", index + 1)))?;
+				Transaction::try_from(Ok(row))
+			})
 	}
 }
 
@@ -377,6 +2014,34 @@ mod tests {
 		assert!(!TransactionRowType::Dispute.has_amount());
 		assert!(!TransactionRowType::Resolve.has_amount());
 		assert!(!TransactionRowType::Chargeback.has_amount());
+		assert!(!TransactionRowType::Representment.has_amount());
+		assert!(!TransactionRowType::PreArbitration.has_amount());
+	}
+
+	#[test]
+	fn test_amount_from_number() {
+		use de::value::{Error, F64Deserializer, I64Deserializer, U64Deserializer};
+
+		let amount = |result: Result<Amount, Error>| result.map(|amount| amount.to_string());
+		assert_eq!(amount(Amount::deserialize(U64Deserializer::new(10))), Ok("10".into()));
+		assert_eq!(amount(Amount::deserialize(F64Deserializer::new(1.25))), Ok("1.25".into()));
+		assert_eq!(amount(Amount::deserialize(F64Deserializer::new(0.1))), Ok("0.1".into()));
+		assert!(Amount::deserialize(F64Deserializer::<Error>::new(0.12345)).is_err());
+		assert!(Amount::deserialize(I64Deserializer::<Error>::new(-1)).is_err());
+		assert!(Amount::deserialize(F64Deserializer::<Error>::new(f64::NAN)).is_err());
+		assert!(Amount::deserialize(F64Deserializer::<Error>::new(12345678901234.5)).is_ok());
+		assert!(Amount::deserialize(F64Deserializer::<Error>::new(1234567890123.4567)).is_err());
+	}
+
+	#[test]
+	fn test_amount_from_json_number() {
+		let row: TransactionRow =
+			serde_json::from_str(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1.5}"#)
+				.unwrap();
+		assert_eq!(row.amount.unwrap().to_string(), "1.5");
+		let row: Result<TransactionRow, _> =
+			serde_json::from_str(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 0.12345}"#);
+		assert!(row.is_err());
 	}
 
 	#[tokio::test]
@@ -427,6 +2092,278 @@ mod tests {
 		assert!(vec.first().unwrap().is_err())
 	}
 
+	#[tokio::test]
+	async fn test_tx_stream_without_header() {
+		use InputColumn::{Client, Ignored, Tx, Type};
+
+		let input = "1, deposit, 7, 1.5\n2, dispute, 7, \n3, dispute, 7, 1.0";
+		let format = CsvFormat::default()
+			.with_columns(vec![Tx, Type, Client, InputColumn::Amount])
+			.unwrap();
+		let vec: Vec<Result<Transaction, CsvError>> =
+			format.tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(vec.len(), 3);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 7)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(2, 7));
+		assert_eq!(
+			vec[2].as_ref().unwrap(),
+			&Transaction::partial_dispute(3, 7, Amount::try_from("1.0").unwrap())
+		);
+
+		let input = "deposit, x, 7, 1, 1.5";
+		let format = CsvFormat::default()
+			.with_columns(vec![Type, Ignored, Client, Tx, InputColumn::Amount])
+			.unwrap();
+		let vec: Vec<Result<Transaction, CsvError>> =
+			format.tx_stream(BufReader::new(input.as_bytes())).collect().await;
+		assert!(vec[0].is_ok());
+
+		assert_eq!(
+			CsvFormat::default().with_columns(vec![Type, Tx, InputColumn::Amount]),
+			Err("Missing input column: client".to_string())
+		);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_with_delimiter() {
+		let input = "type|client|tx|amount\ndeposit|1|1|1.5\ndispute|1|1|";
+		let format = CsvFormat::default().with_delimiter(b'|');
+		let vec: Vec<Result<Transaction, CsvError>> =
+			format.tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_keeps_decimal_places() {
+		let input = "type,client,tx,amount\ndeposit,1,1,1.50\ndeposit,1,2,2";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			CsvFormat::default().tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(vec[0].as_ref().unwrap().amount().unwrap().to_string(), "1.50");
+		assert_eq!(vec[1].as_ref().unwrap().amount().unwrap().to_string(), "2");
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_with_amount_config() {
+		let input =
+			"type,client,tx,amount\ndeposit,1,1,\"1,234.12345678 EUR\"\ndeposit,1,2,0.123456789";
+		let config = AmountConfig::default()
+			.with_base_currency(rusty_money::iso::EUR)
+			.with_max_decimal_places(8)
+			.with_parsing(AmountParsing::Lenient);
+		let format = CsvFormat::default().with_amount_config(config);
+		let vec: Vec<Result<Transaction, CsvError>> =
+			format.tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let decimal = Decimal::from_str_exact("1234.12345678").unwrap();
+		let amount = Amount::try_from(Money::from_decimal(decimal, rusty_money::iso::EUR)).unwrap();
+		assert_eq!(vec[0].as_ref().unwrap(), &Transaction::deposit(1, amount, 1));
+		assert!(vec[1].is_err());
+
+		// The same input is read with the default configuration otherwise.
+		let vec: Vec<Result<Transaction, CsvError>> =
+			CsvFormat::default().tx_stream(BufReader::new(input.as_bytes())).collect().await;
+		assert!(vec[0].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_transfer() {
+		let input =
+			"type,client,tx,amount,to\ntransfer,1,1,1.5,2\ntransfer,1,2,1.5,\ntransfer,1,3,1.5,1\n\
+			deposit,1,4,1.5,2\ndeposit,1,5,1.5,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::transfer(1, Amount::try_from("1.5").unwrap(), 1, 2)
+		);
+		assert!(vec[1].is_err());
+		assert!(vec[2].is_err());
+		assert!(vec[3].is_err());
+		assert_eq!(
+			vec[4].as_ref().unwrap(),
+			&Transaction::deposit(5, Amount::try_from("1.5").unwrap(), 1)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_adjustment() {
+		let input = "type,client,tx,amount,reason\ndebit_adjustment,1,1,1.5,duplicate payout\n\
+			credit_adjustment,1,2,1.5,\ndeposit,1,3,1.5,bonus";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::debit_adjustment(
+				1,
+				Amount::try_from("1.5").unwrap(),
+				1,
+				"duplicate payout"
+			)
+		);
+		assert!(vec[1].is_err());
+		assert!(vec[2].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_hold_and_release() {
+		let input = "type,client,tx,amount,reason\nhold,1,1,1.5,risk case 12\n\
+			release,1,2,1.5,risk case 12 closed\nhold,1,3,1.5,\nrelease,1,4,,risk case 12 closed";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let amount = Amount::try_from("1.5").unwrap();
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::hold(1, amount.clone(), 1, "risk case 12")
+		);
+		assert_eq!(
+			vec[1].as_ref().unwrap(),
+			&Transaction::release(2, amount, 1, "risk case 12 closed")
+		);
+		assert!(vec[2].is_err());
+		assert!(vec[3].is_err());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_idempotency_key() {
+		let input =
+			"type,client,tx,amount,idempotency_key\ndeposit,1,1,1.5,req-7\ndeposit,1,2,1.5,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let deposit = vec[0].as_ref().unwrap();
+		assert_eq!(deposit.idempotency_key(), Some("req-7"));
+		assert_eq!(deposit.id(), 1);
+		assert_eq!(vec[1].as_ref().unwrap().idempotency_key(), None);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_timestamp() {
+		let input = "type,client,tx,amount,timestamp\ndeposit,1,1,1.5,1714521600\ndeposit,1,2,1.5,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(vec[0].as_ref().unwrap().timestamp(), Some(1_714_521_600));
+		assert_eq!(vec[1].as_ref().unwrap().timestamp(), None);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_group_id() {
+		let input =
+			"type,client,tx,amount,group_id\nwithdrawal,1,1,1.5,7\ndeposit,2,2,1.5,7\nfee,1,3,0.1,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let groups = vec.iter().map(|tx| tx.as_ref().unwrap().group_id()).collect_vec();
+		assert_eq!(groups, vec![Some(7), Some(7), None]);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_convert() {
+		let input = "type,client,tx,amount,currency,to_currency,rate
+convert,1,1,10.0,,EUR,0.92
+convert,1,2,5.0,EUR,USD,
+convert,1,3,5.0,EUR,EUR,
+convert,1,4,5.0,,EUR,-1
+convert,1,5,5.0,,XYZ,
+fee,1,6,5.0,EUR,,
+deposit,1,7,5.0,,,0.92
+deposit,1,8,5.0,USD,,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let conversion = vec[0].as_ref().unwrap().conversion().unwrap();
+		assert_eq!((conversion.from(), conversion.to), (base_currency(), rusty_money::iso::EUR));
+		assert_eq!(conversion.rate, Some(Decimal::from_str_exact("0.92").unwrap()));
+		assert_eq!(conversion.amount.to_string(), "10.0");
+		let conversion = vec[1].as_ref().unwrap().conversion().unwrap();
+		assert_eq!(
+			(conversion.from(), conversion.to, conversion.rate),
+			(rusty_money::iso::EUR, base_currency(), None)
+		);
+		assert!(vec[2..7].iter().all(Result::is_err));
+		assert!(vec[7].is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_currency() {
+		let input = "type,client,tx,amount,currency
+deposit,1,1,5.0,EUR
+withdrawal,1,2,1.5,
+dispute,1,1,,EUR
+dispute,1,1,2.0,EUR
+dispute,1,2,,
+resolve,1,1,,EUR
+deposit,1,3,5.0,XYZ";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		let eur = rusty_money::iso::EUR;
+		let currencies = vec[..5].iter().map(|tx| tx.as_ref().unwrap().currency()).collect_vec();
+		assert_eq!(currencies, vec![Some(eur), Some(base_currency()), Some(eur), Some(eur), None]);
+		assert_eq!(vec[0].as_ref().unwrap().amount().unwrap().to_string(), "5.0");
+		assert!(vec[5..].iter().all(Result::is_err));
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_metadata() {
+		let input = "type,client,tx,amount,memo,merchant,reference
+deposit,1,1,1.5,\"refund, partial\",ACME Ltd,order-81
+withdrawal,1,2,1.5,,,";
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(
+			vec[0].as_ref().unwrap().metadata(),
+			&RecordMetadata {
+				annotations: Some(Box::new(Annotations {
+					memo: Some("refund, partial".into()),
+					merchant: Some("ACME Ltd".into()),
+					external_reference: Some("order-81".into()),
+				})),
+				..RecordMetadata::default()
+			}
+		);
+		assert_eq!(vec[1].as_ref().unwrap().metadata(), &RecordMetadata::default());
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_json() {
+		let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.1234"}
+
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": 0.5}
+{"type": "dispute", "client": 1, "tx": 1, "amount": null}
+{"type": "deposit", "client": 1, "tx": 3, "amount": "1.12345"}
+{"type": "deposit", "client": 1"#;
+		let vec: Vec<Result<Transaction, CsvError>> =
+			Transaction::tx_stream_json(BufReader::new(input.as_bytes())).collect().await;
+
+		assert_eq!(vec.len(), 5);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.1234").unwrap(), 1)
+		);
+		assert_eq!(
+			vec[1].as_ref().unwrap(),
+			&Transaction::withdrawal(2, Amount::try_from("0.5").unwrap(), 1)
+		);
+		assert_eq!(vec[2].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(vec[3].is_err());
+		assert!(vec[4].as_ref().unwrap_err().to_string().contains("Line 6:"));
+	}
+
 	#[tokio::test]
 	async fn test_change_state_deposit_open_to_disputed() {
 		let mut transaction = Transaction::Deposit {
@@ -434,6 +2371,8 @@ mod tests {
 			amount: Amount::try_from("50").unwrap(),
 			client_id: 1,
 			state: TransactionState::Okay,
+			disputed: None,
+			metadata: RecordMetadata::default(),
 		};
 
 		let result = transaction.change_state(TransactionState::Disputed);
@@ -449,6 +2388,8 @@ mod tests {
 			amount: Amount::try_from("50").unwrap(),
 			client_id: 1,
 			state: TransactionState::Disputed,
+			disputed: None,
+			metadata: RecordMetadata::default(),
 		};
 
 		let result = transaction.change_state(TransactionState::Okay);
@@ -464,6 +2405,8 @@ mod tests {
 			amount: Amount::try_from("50").unwrap(),
 			client_id: 1,
 			state: TransactionState::ChargedBack,
+			disputed: None,
+			metadata: RecordMetadata::default(),
 		};
 
 		let result = transaction.change_state(TransactionState::Okay);
@@ -472,4 +2415,54 @@ mod tests {
 		// State shouldn't have changed
 		assert_eq!(transaction.state().unwrap(), &TransactionState::ChargedBack);
 	}
+
+	#[tokio::test]
+	async fn test_change_state_dispute_escalation() {
+		let mut transaction = Transaction::deposit(1, Amount::try_from("50").unwrap(), 1);
+
+		assert_eq!(
+			transaction.change_state(TransactionState::Representment),
+			Err(IllegalStateChange(transaction.clone()))
+		);
+		for state in [
+			TransactionState::Disputed,
+			TransactionState::Representment,
+			TransactionState::PreArbitration,
+			TransactionState::ChargedBack,
+		] {
+			transaction.change_state(state).unwrap();
+			assert_eq!(transaction.state(), Some(&state));
+		}
+
+		let mut transaction = Transaction::deposit(2, Amount::try_from("50").unwrap(), 1);
+		transaction.set_disputed().unwrap();
+		assert_eq!(
+			transaction.change_state(TransactionState::PreArbitration),
+			Err(IllegalStateChange(transaction.clone()))
+		);
+		transaction.change_state(TransactionState::Representment).unwrap();
+		assert!(transaction.state().unwrap().is_disputed());
+		transaction.set_resolved().unwrap();
+		assert!(!transaction.state().unwrap().is_disputed());
+	}
+
+	#[tokio::test]
+	async fn test_reopen_chargeback() {
+		let mut transaction = Transaction::Deposit {
+			id: 1,
+			amount: Amount::try_from("50").unwrap(),
+			client_id: 1,
+			state: TransactionState::ChargedBack,
+			disputed: None,
+			metadata: RecordMetadata::default(),
+		};
+
+		assert_eq!(
+			transaction.change_state(TransactionState::Disputed),
+			Err(IllegalStateChange(transaction.clone()))
+		);
+		assert!(transaction.reopen_chargeback().is_ok());
+		assert_eq!(transaction.state().unwrap(), &TransactionState::Disputed);
+		assert_eq!(transaction.reopen_chargeback(), Err(IllegalStateChange(transaction.clone())));
+	}
 }
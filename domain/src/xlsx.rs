@@ -0,0 +1,134 @@
+use std::io::Cursor;
+
+use calamine::{open_workbook_from_rs, Data, Range, Reader, Xlsx};
+use csv_async::StringRecord;
+use futures::io::AsyncReadExt;
+use futures::stream::{self, BoxStream};
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::ser::Error;
+
+use crate::config::{max_decimal_places, rounding};
+use crate::transaction::{AsyncRead, CsvError, StreamExt, Transaction, TransactionRow};
+
+impl Transaction {
+	/// Stream transactions from the given reader of an XLSX workbook, including errors.
+	///
+	/// The first sheet is read, with a header row naming the same columns as CSV input. Every
+	/// other row is one record, read like a CSV record from the text of its cells, and empty rows
+	/// are skipped. Number cells are read at their value rounded to the configured decimal places. A row that cannot be read is yielded as an error with its row number and
+	/// reading continues with the next one; a workbook that cannot be read is yielded as a single
+	/// error.
+	pub fn tx_stream_xlsx(
+		mut reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> BoxStream<'static, Result<Transaction, CsvError>> {
+		stream::once(async move {
+			let mut workbook = Vec::new();
+			let txs = match reader.read_to_end(&mut workbook).await {
+				Ok(_) => match first_sheet(workbook) {
+					Ok(sheet) => records(&sheet),
+					Err(e) => vec![Err(CsvError::custom(format!("XLSX: {e}")))],
+				},
+				Err(e) => vec![Err(CsvError::from(e))],
+			};
+			stream::iter(txs)
+		})
+		.flatten()
+		.boxed()
+	}
+}
+
+/// Returns the cells of the first sheet of `workbook`.
+fn first_sheet(workbook: Vec<u8>) -> Result<Range<Data>, String> {
+	let mut workbook: Xlsx<_> =
+		open_workbook_from_rs(Cursor::new(workbook)).map_err(|e| e.to_string())?;
+	match workbook.worksheet_range_at(0) {
+		Some(sheet) => sheet.map_err(|e| e.to_string()),
+		None => Err("The workbook has no sheets".into()),
+	}
+}
+
+/// Reads the rows of `sheet` after its header row as transactions.
+fn records(sheet: &Range<Data>) -> Vec<Result<Transaction, CsvError>> {
+	let mut rows = sheet.rows().map(|row| {
+		StringRecord::from(row.iter().map(|cell| cell_text(cell).trim().to_string()).collect_vec())
+	});
+	let Some(headers) = rows.next() else {
+		return Vec::new();
+	};
+	// Rows are numbered from 1 as in spreadsheets, and the first record follows the header row.
+	let first_row = sheet.start().map_or(1, |(row, _)| row + 2);
+	rows.zip(first_row..)
+		.filter(|(record, _)| record.iter().any(|field| !field.is_empty()))
+		.map(|(record, row)| {
+			Transaction::try_from(
+				TransactionRow::from_record(&record, &headers)
+					.map_err(|e| CsvError::custom(format!("Row {row}: {e}"))),
+			)
+		})
+		.collect()
+}
+
+/// Returns the text of `cell`. Spreadsheets store numbers as floats, such as `0.30000000000000004`
+/// for the sum of `0.1` and `0.2`, so they are rounded to the configured decimal places instead of
+/// written out in full.
+fn cell_text(cell: &Data) -> String {
+	match cell {
+		Data::Float(float) => match Decimal::from_f64_retain(*float) {
+			Some(decimal) => decimal
+				.round_dp_with_strategy(max_decimal_places().into(), rounding())
+				.normalize()
+				.to_string(),
+			None => float.to_string(),
+		},
+		cell => cell.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::io::Cursor;
+
+	use crate::amount::Amount;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_tx_stream_xlsx() {
+		// The rows of the sheet: a deposit, a dispute, an empty row, a deposit with an invalid
+		// amount and a deposit whose amount is the formula `0.1+0.2`.
+		let file = include_bytes!("../testdata/transactions.xlsx").to_vec();
+
+		let vec: Vec<_> = Transaction::tx_stream_xlsx(Cursor::new(file)).collect().await;
+		assert_eq!(vec.len(), 4);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(vec[2].as_ref().unwrap_err().to_string().contains("Row 5"));
+		assert_eq!(
+			vec[3].as_ref().unwrap(),
+			&Transaction::deposit(3, Amount::try_from("0.3").unwrap(), 1)
+		);
+	}
+
+	#[test]
+	fn test_cell_text() {
+		assert_eq!(cell_text(&Data::Float(1.0)), "1");
+		assert_eq!(cell_text(&Data::Float(0.1 + 0.2)), "0.3");
+		assert_eq!(cell_text(&Data::Float(1.23456789)), "1.2346");
+		assert_eq!(cell_text(&Data::Int(7)), "7");
+		assert_eq!(cell_text(&Data::String("deposit".into())), "deposit");
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_xlsx_invalid_workbook() {
+		let vec: Vec<_> =
+			Transaction::tx_stream_xlsx(Cursor::new(b"type,client,tx,amount\n".to_vec()))
+				.collect()
+				.await;
+		assert_eq!(vec.len(), 1);
+		assert!(vec[0].as_ref().unwrap_err().to_string().contains("XLSX"));
+	}
+}
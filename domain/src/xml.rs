@@ -0,0 +1,292 @@
+use futures::io::AsyncReadExt;
+use futures::stream::{self, BoxStream};
+use serde::ser::Error;
+
+use crate::transaction::{AsyncRead, CsvError, StreamExt, Transaction, TransactionRow};
+
+/// The root element of XML transaction documents.
+const ROOT: &str = "transactions";
+/// The element of a single transaction record.
+const RECORD: &str = "tx";
+
+impl Transaction {
+	/// Stream transactions from the given reader of an XML document of the form
+	/// `<transactions><tx type="deposit" client="1" tx="1" amount="1.5"/></transactions>`,
+	/// including errors.
+	///
+	/// Every `tx` element is one record, with the same attributes as the CSV columns. A record
+	/// that cannot be read is yielded as an error and reading continues with the next one; a
+	/// malformed document ends the stream with an error after the records before it.
+	pub fn tx_stream_xml(
+		mut reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> BoxStream<'static, Result<Transaction, CsvError>> {
+		stream::once(async move {
+			let mut document = String::new();
+			let txs = match reader.read_to_string(&mut document).await {
+				Ok(_) => parse_document(&document)
+					.into_iter()
+					.map(|row| {
+						Transaction::try_from(
+							row.map_err(|e| CsvError::custom(format!("XML: {e}"))),
+						)
+					})
+					.collect(),
+				Err(e) => vec![Err(CsvError::from(e))],
+			};
+			stream::iter(txs)
+		})
+		.flatten()
+		.boxed()
+	}
+}
+
+#[derive(Debug, PartialEq)]
+enum TagKind {
+	Open,
+	Close,
+	Empty,
+}
+
+#[derive(Debug)]
+struct Tag<'a> {
+	name: &'a str,
+	kind: TagKind,
+	attributes: Vec<(&'a str, String)>,
+}
+
+impl Tag<'_> {
+	fn attribute(&self, name: &str) -> Option<&str> {
+		self.attributes
+			.iter()
+			.find(|(n, _)| *n == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Reads the records of a document, ending with an error if the document is malformed.
+fn parse_document(document: &str) -> Vec<Result<TransactionRow, String>> {
+	let mut rows = Vec::new();
+	let mut tags = Tags { rest: document };
+	let mut next = || tags.next().unwrap_or_else(|| Err("Unexpected end of document".into()));
+	match next() {
+		Ok(Tag { name: ROOT, kind: TagKind::Open, .. }) => {},
+		Ok(Tag { name: ROOT, kind: TagKind::Empty, .. }) => return rows,
+		Ok(tag) => return vec![Err(format!("Expected <{ROOT}>, found <{}>", tag.name))],
+		Err(e) => return vec![Err(e)],
+	}
+	loop {
+		match next() {
+			Ok(tag) if tag.name == RECORD && tag.kind != TagKind::Close => {
+				let index = rows.len() + 1;
+				rows.push(record(&tag).map_err(|e| format!("Record {index}: {e}")));
+				if tag.kind == TagKind::Open {
+					match next() {
+						Ok(Tag { name: RECORD, kind: TagKind::Close, .. }) => {},
+						Ok(tag) => {
+							rows.push(Err(format!("Expected </{RECORD}>, found <{}>", tag.name)));
+							return rows;
+						},
+						Err(e) => {
+							rows.push(Err(e));
+							return rows;
+						},
+					}
+				}
+			},
+			Ok(Tag { name: ROOT, kind: TagKind::Close, .. }) => break,
+			Ok(tag) => {
+				rows.push(Err(format!("Unexpected element <{}>", tag.name)));
+				return rows;
+			},
+			Err(e) => {
+				rows.push(Err(e));
+				return rows;
+			},
+		}
+	}
+	if let Some(tag) = tags.next() {
+		rows.push(Err(
+			tag.map_or_else(|e| e, |tag| format!("Content after </{ROOT}>: <{}>", tag.name))
+		));
+	}
+	rows
+}
+
+/// Reads a record from the attributes of a `tx` element.
+fn record(tag: &Tag) -> Result<TransactionRow, String> {
+	let attribute = |name| tag.attribute(name).ok_or(format!("Missing attribute {name}"));
+	let client = attribute("client")?;
+	let tx = attribute("tx")?;
+	let to = tag
+		.attribute("to")
+		.map(|to| to.trim().parse().map_err(|e| format!("Invalid to {to}: {e}")))
+		.transpose()?;
+	let reference = tag
+		.attribute("ref")
+		.map(|reference| {
+			reference.trim().parse().map_err(|e| format!("Invalid ref {reference}: {e}"))
+		})
+		.transpose()?;
+	let mut row = TransactionRow::from_fields(
+		attribute("type")?,
+		client.trim().parse().map_err(|e| format!("Invalid client {client}: {e}"))?,
+		tx.trim().parse().map_err(|e| format!("Invalid tx {tx}: {e}"))?,
+		tag.attribute("amount").map(str::trim),
+		to,
+		reference,
+		tag.attribute("reason"),
+	)?;
+	row.idempotency_key = tag.attribute("idempotency_key").map(String::from);
+	row.timestamp = tag
+		.attribute("timestamp")
+		.map(|ts| ts.trim().parse().map_err(|e| format!("Invalid timestamp {ts}: {e}")))
+		.transpose()?;
+	row.group_id = tag
+		.attribute("group_id")
+		.map(|group| group.trim().parse().map_err(|e| format!("Invalid group_id {group}: {e}")))
+		.transpose()?;
+	row.memo = tag.attribute("memo").map(String::from);
+	row.merchant = tag.attribute("merchant").map(String::from);
+	row.external_reference = tag.attribute("reference").map(String::from);
+	row.currency = tag.attribute("currency").map(String::from);
+	row.to_currency = tag.attribute("to_currency").map(String::from);
+	row.rate = tag.attribute("rate").map(String::from);
+	Ok(row)
+}
+
+/// Iterates the tags of a document, skipping the XML declaration, processing instructions,
+/// comments, doctype declarations and whitespace.
+struct Tags<'a> {
+	rest: &'a str,
+}
+
+impl<'a> Iterator for Tags<'a> {
+	type Item = Result<Tag<'a>, String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			self.rest = self.rest.trim_start();
+			if self.rest.is_empty() {
+				return None;
+			}
+			let skipped = [("<?", "?>"), ("<!--", "-->"), ("<!", ">")]
+				.into_iter()
+				.find(|(start, _)| self.rest.starts_with(start));
+			if let Some((_, end)) = skipped {
+				match self.rest.find(end) {
+					Some(i) => self.rest = &self.rest[i + end.len()..],
+					None => return Some(Err("Unterminated declaration or comment".into())),
+				}
+				continue;
+			}
+			if !self.rest.starts_with('<') {
+				let text = self.rest.split('<').next().unwrap_or_default();
+				return Some(Err(format!("Unexpected text {}", text.trim())));
+			}
+			return Some(self.tag());
+		}
+	}
+}
+
+impl<'a> Tags<'a> {
+	/// Reads the tag at the start of the remaining document.
+	fn tag(&mut self) -> Result<Tag<'a>, String> {
+		if let Some(rest) = self.rest.strip_prefix("</") {
+			let end = rest.find('>').ok_or("Unterminated closing tag")?;
+			self.rest = &rest[end + 1..];
+			return Ok(Tag { name: rest[..end].trim(), kind: TagKind::Close, attributes: vec![] });
+		}
+		let rest = &self.rest[1..];
+		let name_end = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>');
+		let (name, mut rest) = rest.split_at(name_end.ok_or("Unterminated tag")?);
+		let mut attributes = Vec::new();
+		loop {
+			rest = rest.trim_start();
+			if let Some(after) = rest.strip_prefix("/>") {
+				self.rest = after;
+				return Ok(Tag { name, kind: TagKind::Empty, attributes });
+			}
+			if let Some(after) = rest.strip_prefix('>') {
+				self.rest = after;
+				return Ok(Tag { name, kind: TagKind::Open, attributes });
+			}
+			let (attribute, after) =
+				rest.split_once('=').ok_or(format!("Malformed tag <{name}>"))?;
+			let after = after.trim_start();
+			let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'');
+			let quote = quote.ok_or(format!("Unquoted attribute {}", attribute.trim()))?;
+			let (value, after) = after[1..]
+				.split_once(quote)
+				.ok_or(format!("Unterminated attribute {}", attribute.trim()))?;
+			attributes.push((attribute.trim(), unescape(value)));
+			rest = after;
+		}
+	}
+}
+
+/// Replaces the predefined XML entities.
+fn unescape(value: &str) -> String {
+	value
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::io::Cursor;
+
+	use crate::amount::Amount;
+
+	use super::*;
+
+	async fn read(document: &str) -> Vec<Result<Transaction, CsvError>> {
+		Transaction::tx_stream_xml(Cursor::new(document.as_bytes().to_vec()))
+			.collect()
+			.await
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_xml() {
+		let vec = read(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+			<!-- exported by the legacy bank -->
+			<transactions>
+				<tx type="deposit" client="1" tx="1" amount="1.5"/>
+				<tx type='dispute' client="1" tx="1"></tx>
+				<tx type="deposit" client="1" tx="2"/>
+				<tx type="withdrawal" client="1" tx="3" amount=" 0.5 " note="a &amp; b"/>
+			</transactions>"#,
+		)
+		.await;
+
+		assert_eq!(vec.len(), 4);
+		assert_eq!(
+			vec[0].as_ref().unwrap(),
+			&Transaction::deposit(1, Amount::try_from("1.5").unwrap(), 1)
+		);
+		assert_eq!(vec[1].as_ref().unwrap(), &Transaction::dispute(1, 1));
+		assert!(vec[2].is_err());
+		assert_eq!(
+			vec[3].as_ref().unwrap(),
+			&Transaction::withdrawal(3, Amount::try_from("0.5").unwrap(), 1)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_tx_stream_xml_malformed() {
+		let vec =
+			read(r#"<transactions><tx type="deposit" client="1" tx="1" amount="1"/><tx"#).await;
+		assert_eq!(vec.len(), 2);
+		assert!(vec[0].is_ok());
+		assert!(vec[1].as_ref().unwrap_err().to_string().contains("Unterminated tag"));
+
+		let vec = read("<accounts/>").await;
+		assert!(vec[0].as_ref().unwrap_err().to_string().contains("Expected <transactions>"));
+
+		assert!(read("<transactions/>").await.is_empty());
+		assert!(read("<transactions></transactions> trailing").await[0].is_err());
+	}
+}
@@ -0,0 +1,16 @@
+use std::process::Command;
+
+/// Exposes the git commit the engine is built from as `ENGINE_BUILD_HASH`, when available.
+fn main() {
+	println!("cargo:rerun-if-changed=../.git/HEAD");
+	println!("cargo:rerun-if-changed=../.git/refs");
+	let hash = Command::new("git")
+		.args(["rev-parse", "--short", "HEAD"])
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.and_then(|output| String::from_utf8(output.stdout).ok());
+	if let Some(hash) = hash {
+		println!("cargo:rustc-env=ENGINE_BUILD_HASH={}", hash.trim());
+	}
+}
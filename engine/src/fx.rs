@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use rusty_money::iso::Currency;
+use rusty_money::Money;
+use serde::{de, Deserialize, Deserializer};
+
+use domain::amount::Amount;
+use domain::config::{find_currency, max_decimal_places};
+use domain::transaction::Conversion;
+
+/// Configures the exchange rates of conversions.
+///
+/// A conversion without a rate uses the rate of the table, or the inverse of the opposite rate. A
+/// rate quoted by the sender is used as is, unless it deviates from the table by more than the
+/// maximum slippage. Either way, the converted amount is rounded toward zero to the minor unit of
+/// its currency, so that a conversion never credits more than its rate is worth.
+#[derive(Debug, Default, Clone)]
+pub struct RateTable {
+	/// The units of the second currency per unit of the first, by ISO 4217 codes.
+	pub rates: HashMap<(String, String), Decimal>,
+	/// The largest relative deviation of a quoted rate from the table, e.g. `0.01` for 1%. Quoted
+	/// rates are not checked if `None`, and are rejected without a table rate otherwise.
+	pub max_slippage: Option<Decimal>,
+}
+
+/// Represents the different errors that can occur converting an amount.
+#[derive(Debug, PartialEq)]
+pub enum FxError {
+	/// No rate was quoted and the table has none.
+	RateUnavailable,
+	/// The quoted rate deviates from the table by more than the maximum slippage.
+	SlippageExceeded,
+	/// The converted amount is out of range.
+	Overflow,
+}
+
+/// A row of a rates file: `from,to,rate`.
+#[derive(Deserialize)]
+struct RateRow {
+	from: String,
+	to: String,
+	#[serde(deserialize_with = "deserialize_rate")]
+	rate: Decimal,
+}
+
+/// Deserializes a positive decimal rate.
+fn deserialize_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+	let value = String::deserialize(deserializer)?;
+	let rate = Decimal::from_str_exact(value.trim()).map_err(de::Error::custom)?;
+	if rate <= Decimal::ZERO {
+		return Err(de::Error::custom(format!("Rate must be positive, got {rate}")));
+	}
+	Ok(rate)
+}
+
+impl RateTable {
+	/// Loads the rates of a CSV file with a `from,to,rate` header row, such as `USD,EUR,0.92`, the
+	/// units of `to` per unit of `from`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if a row is not a pair of currency codes with a positive rate.
+	pub fn from_csv(reader: impl Read) -> Result<Self, csv::Error> {
+		let mut rates = HashMap::new();
+		for row in csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader).deserialize()
+		{
+			let RateRow { from, to, rate } = row?;
+			rates.insert((from.to_uppercase(), to.to_uppercase()), rate);
+		}
+		Ok(RateTable { rates, max_slippage: None })
+	}
+
+	/// Returns the rate from `from` to `to`, if known.
+	pub fn rate(&self, from: &Currency, to: &Currency) -> Option<Decimal> {
+		let key = |from: &Currency, to: &Currency| {
+			(from.iso_alpha_code.to_string(), to.iso_alpha_code.to_string())
+		};
+		self.rates.get(&key(from, to)).copied().or_else(|| {
+			self.rates.get(&key(to, from)).and_then(|rate| Decimal::ONE.checked_div(*rate))
+		})
+	}
+
+	/// Returns `amount` converted to `to` at the table's rate, unrounded, to aggregate amounts in
+	/// different currencies.
+	///
+	/// # Errors
+	///
+	/// Returns [`FxError::RateUnavailable`] if the table has no rate between the currencies.
+	pub fn convert_amount(
+		&self,
+		amount: &Amount,
+		to: &'static Currency,
+	) -> Result<Amount, FxError> {
+		let from = amount.value().currency();
+		let rate = if from == to {
+			Decimal::ONE
+		} else {
+			self.rate(from, to).ok_or(FxError::RateUnavailable)?
+		};
+		amount.convert(to, rate).ok_or(FxError::Overflow)
+	}
+
+	/// Returns the rates from every currency of the table to `to`, by currency code, to report
+	/// balances in `to`.
+	pub fn rates_to(&self, to: &Currency) -> BTreeMap<String, Decimal> {
+		self.rates
+			.keys()
+			.flat_map(|(first, second)| [first, second])
+			.filter(|code| code.as_str() != to.iso_alpha_code)
+			.filter_map(|code| {
+				let from = find_currency(code)?;
+				Some((code.clone(), self.rate(from, to)?))
+			})
+			.collect()
+	}
+
+	/// Returns the amount `conversion` credits, in its target currency.
+	///
+	/// # Errors
+	///
+	/// Returns an [`FxError`] if the rate is unknown or not acceptable.
+	pub fn convert(&self, conversion: &Conversion) -> Result<Amount, FxError> {
+		let rate = match (conversion.rate, self.rate(conversion.from(), conversion.to)) {
+			(Some(quoted), Some(table)) => {
+				let slippage = ((quoted - table) / table).abs();
+				if self.max_slippage.is_some_and(|max| slippage > max) {
+					return Err(FxError::SlippageExceeded);
+				}
+				quoted
+			},
+			(Some(quoted), None) if self.max_slippage.is_none() => quoted,
+			(None, Some(table)) => table,
+			_ => return Err(FxError::RateUnavailable),
+		};
+		let places = conversion.to.exponent.min(max_decimal_places().into());
+		let converted = conversion
+			.amount
+			.convert(conversion.to, rate)
+			.ok_or(FxError::Overflow)?
+			.value()
+			.amount()
+			.round_dp_with_strategy(places, RoundingStrategy::ToZero);
+		Amount::try_from(Money::from_decimal(converted, conversion.to))
+			.map_err(|_| FxError::RateUnavailable)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rusty_money::iso::{EUR, GBP, JPY, USD};
+
+	use super::*;
+
+	fn decimal(value: &str) -> Decimal {
+		Decimal::from_str_exact(value).unwrap()
+	}
+
+	fn conversion(amount: &str, from: &'static Currency, to: &'static Currency) -> Conversion {
+		let amount = Amount::try_from(Money::from_decimal(decimal(amount), from)).unwrap();
+		Conversion { amount, to, rate: None }
+	}
+
+	#[test]
+	fn test_convert() {
+		let table = RateTable {
+			rates: HashMap::from([
+				(("USD".into(), "EUR".into()), decimal("0.92")),
+				(("USD".into(), "JPY".into()), decimal("151.237")),
+			]),
+			max_slippage: Some(decimal("0.01")),
+		};
+
+		let converted = |conversion: Conversion| table.convert(&conversion).map(|a| a.to_string());
+		assert_eq!(converted(conversion("10.0", USD, EUR)), Ok("9.20".into()));
+		assert_eq!(converted(conversion("9.20", EUR, USD)), Ok("10.00".into()));
+		// Rounded toward zero to the minor unit of the target currency.
+		assert_eq!(converted(conversion("1.0", USD, JPY)), Ok("151".into()));
+		assert_eq!(converted(conversion("0.01", EUR, USD)), Ok("0.01".into()));
+		assert_eq!(converted(conversion("1.0", EUR, JPY)), Err(FxError::RateUnavailable));
+
+		let quoted =
+			|rate: &str| Conversion { rate: Some(decimal(rate)), ..conversion("10.0", USD, EUR) };
+		assert_eq!(converted(quoted("0.925")), Ok("9.25".into()));
+		assert_eq!(converted(quoted("0.93")), Err(FxError::SlippageExceeded));
+		let unchecked = RateTable::default();
+		assert_eq!(unchecked.convert(&quoted("0.93")).unwrap().to_string(), "9.30");
+		assert_eq!(unchecked.convert(&conversion("1", USD, EUR)), Err(FxError::RateUnavailable));
+	}
+
+	#[test]
+	fn test_from_csv() {
+		let table =
+			RateTable::from_csv("from,to,rate\nUSD,EUR,0.92\neur, gbp ,0.85\n".as_bytes()).unwrap();
+
+		let amount = |value: &str, currency| {
+			Amount::try_from(Money::from_decimal(decimal(value), currency)).unwrap()
+		};
+		assert_eq!(table.convert_amount(&amount("10", EUR), GBP), Ok(amount("8.50", GBP)));
+		assert_eq!(table.convert_amount(&amount("10", EUR), EUR), Ok(amount("10", EUR)));
+		assert_eq!(table.convert_amount(&amount("10", USD), GBP), Err(FxError::RateUnavailable));
+		assert_eq!(
+			table.rates_to(EUR),
+			BTreeMap::from([
+				("GBP".into(), Decimal::ONE / decimal("0.85")),
+				("USD".into(), decimal("0.92"))
+			])
+		);
+		assert!(RateTable::from_csv("from,to,rate\nUSD,EUR,-1\n".as_bytes()).is_err());
+	}
+}
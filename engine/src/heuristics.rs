@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+
+use domain::amount::Amount;
+use domain::config::{ClientId, TransactionId};
+use domain::transaction::Transaction;
+
+/// A transaction that looks like an accidental resubmission of an earlier one.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SuspectedDuplicate {
+	#[serde(rename = "client")]
+	pub client_id: ClientId,
+	#[serde(rename = "type")]
+	pub tx_type: &'static str,
+	#[serde(rename = "tx")]
+	pub tx_id: TransactionId,
+	#[serde(rename = "previous_tx")]
+	pub previous_tx_id: TransactionId,
+	pub amount: Amount,
+}
+
+/// Flags likely accidental double submissions: deposits or withdrawals of the same client with
+/// the same amount whose transaction ids are within a window of each other.
+///
+/// The detector only observes applied transactions and never affects processing.
+#[derive(Debug)]
+pub struct DuplicateSubmissionDetector {
+	/// The maximal distance between the ids of two transactions considered adjacent.
+	window: u32,
+	/// The most recent deposits and withdrawals of each client, oldest first.
+	recent: HashMap<ClientId, VecDeque<Transaction>>,
+	findings: Vec<SuspectedDuplicate>,
+}
+
+impl DuplicateSubmissionDetector {
+	/// Creates a new detector comparing transactions whose ids are at most `window` apart.
+	pub fn new(window: u32) -> Self {
+		Self { window, recent: HashMap::new(), findings: Vec::new() }
+	}
+
+	/// Returns the maximal id distance of transactions compared.
+	pub fn window(&self) -> u32 {
+		self.window
+	}
+
+	/// Observes an applied transaction, recording a finding if it matches a recent one.
+	///
+	/// Only deposits and withdrawals are considered.
+	pub fn observe(&mut self, tx: &Transaction) {
+		let (tx_type, amount) = match tx {
+			Transaction::Deposit { amount, .. } => ("deposit", amount),
+			Transaction::Withdrawal { amount, .. } => ("withdrawal", amount),
+			_ => return,
+		};
+		let recent = self.recent.entry(*tx.client_id()).or_default();
+		recent.retain(|previous| previous.id().abs_diff(tx.id()) <= self.window);
+
+		let previous = recent.iter().rev().find(|previous| {
+			std::mem::discriminant(*previous) == std::mem::discriminant(tx)
+				&& previous.amount() == Some(amount)
+		});
+		if let Some(previous) = previous {
+			self.findings.push(SuspectedDuplicate {
+				client_id: *tx.client_id(),
+				tx_type,
+				tx_id: tx.id(),
+				previous_tx_id: previous.id(),
+				amount: amount.clone(),
+			});
+		}
+		recent.push_back(tx.clone());
+	}
+
+	/// Returns the suspected duplicates found so far, in the order they were observed.
+	pub fn findings(&self) -> &[SuspectedDuplicate] {
+		&self.findings
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn amount(value: &str) -> Amount {
+		Amount::try_from(value).unwrap()
+	}
+
+	#[test]
+	fn test_flags_adjacent_same_amount() {
+		let mut detector = DuplicateSubmissionDetector::new(2);
+
+		detector.observe(&Transaction::deposit(1, amount("10"), 1));
+		detector.observe(&Transaction::deposit(2, amount("10"), 1));
+
+		assert_eq!(
+			detector.findings(),
+			&[SuspectedDuplicate {
+				client_id: 1,
+				tx_type: "deposit",
+				tx_id: 2,
+				previous_tx_id: 1,
+				amount: amount("10"),
+			}]
+		);
+	}
+
+	#[test]
+	fn test_ignores_other_clients_types_amounts_and_distant_ids() {
+		let mut detector = DuplicateSubmissionDetector::new(2);
+
+		detector.observe(&Transaction::deposit(1, amount("10"), 1));
+		detector.observe(&Transaction::deposit(2, amount("10"), 2));
+		detector.observe(&Transaction::withdrawal(3, amount("10"), 1));
+		detector.observe(&Transaction::deposit(4, amount("11"), 1));
+		detector.observe(&Transaction::deposit(9, amount("10"), 1));
+
+		assert!(detector.findings().is_empty());
+	}
+}
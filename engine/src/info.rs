@@ -0,0 +1,90 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use rusty_money::iso::Currency;
+
+use domain::amount::Amount;
+
+use crate::limits::BalanceCap;
+
+/// Counts of the records and transactions handled by a processor.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProcessorCounters {
+	/// Input records read, including those that could not be parsed.
+	pub records_read: u64,
+	/// Input records that could not be parsed into a transaction.
+	pub parse_errors: u64,
+	/// Transactions applied to an account.
+	pub applied: u64,
+	/// Transactions rejected while processing.
+	pub rejected: u64,
+	/// Transactions applied, by transaction type.
+	pub applied_by_type: BTreeMap<&'static str, u64>,
+	/// Transactions rejected, by [`TransactionError::code`](domain::transaction::TransactionError::code).
+	pub rejected_by_reason: BTreeMap<&'static str, u64>,
+	/// The sum of the applied deposits.
+	pub deposited: Amount,
+	/// The sum of the applied withdrawals.
+	pub withdrawn: Amount,
+	/// The sum of the applied refunds.
+	pub refunded: Amount,
+	/// Authorizations released for not being captured in time.
+	pub expired_authorizations: u64,
+	/// The sum of the interest accrued.
+	pub interest: Amount,
+}
+
+/// Describes a processor for diagnostics: its build, effective configuration and counters.
+#[derive(Debug, Clone)]
+pub struct ProcessorInfo {
+	/// The engine crate version.
+	pub version: &'static str,
+	/// The git commit the engine was built from, `unknown` if it was not built from git.
+	pub build_hash: &'static str,
+	/// The currency of the balances.
+	pub base_currency: &'static Currency,
+	/// The maximum balances enforced on deposits.
+	pub balance_cap: BalanceCap,
+	/// The window of the duplicate submission heuristics, if enabled.
+	pub duplicate_window: Option<u32>,
+	pub counters: ProcessorCounters,
+	/// The number of accounts tracked.
+	pub accounts: usize,
+	/// The number of locked accounts.
+	pub locked_accounts: usize,
+	/// The sum of the held balances of all accounts.
+	pub held: Amount,
+}
+
+impl fmt::Display for ProcessorInfo {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let max_balance = self.balance_cap.global.as_ref().map_or("none".into(), |m| m.to_string());
+		let client_max_balances = self
+			.balance_cap
+			.per_client
+			.iter()
+			.sorted_by_key(|(client, _)| **client)
+			.map(|(client, max)| format!("{client}={max}"))
+			.join(",");
+		let client_max_balances =
+			if client_max_balances.is_empty() { "none".into() } else { client_max_balances };
+		let duplicate_window =
+			self.duplicate_window.map_or("disabled".into(), |window| window.to_string());
+
+		writeln!(f, "version: {}", self.version)?;
+		writeln!(f, "build: {}", self.build_hash)?;
+		writeln!(f, "base_currency: {}", self.base_currency.iso_alpha_code)?;
+		writeln!(f, "max_balance: {max_balance}")?;
+		writeln!(f, "client_max_balances: {client_max_balances}")?;
+		writeln!(f, "cap_policy: {:?}", self.balance_cap.policy)?;
+		writeln!(f, "duplicate_window: {duplicate_window}")?;
+		writeln!(f, "records_read: {}", self.counters.records_read)?;
+		writeln!(f, "parse_errors: {}", self.counters.parse_errors)?;
+		writeln!(f, "applied: {}", self.counters.applied)?;
+		writeln!(f, "rejected: {}", self.counters.rejected)?;
+		writeln!(f, "accounts: {}", self.accounts)?;
+		writeln!(f, "locked_accounts: {}", self.locked_accounts)?;
+		writeln!(f, "held: {}", self.held)
+	}
+}
@@ -0,0 +1,124 @@
+use rust_decimal::Decimal;
+use rusty_money::Money;
+
+use domain::amount::Amount;
+use domain::config::{base_currency, max_decimal_places, rounding, Timestamp};
+
+/// The number of seconds in a year of interest, by the actual/365 convention.
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+/// Annual interest rates, each effective from its timestamp until the next one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RateSchedule {
+	/// The rates with the timestamps they take effect, in chronological order.
+	rates: Vec<(Timestamp, Decimal)>,
+}
+
+impl RateSchedule {
+	/// Creates a schedule of annual rates, such as `0.05` for 5%, taking effect at their timestamps.
+	pub fn new(rates: impl IntoIterator<Item = (Timestamp, Decimal)>) -> Self {
+		let mut rates: Vec<_> = rates.into_iter().collect();
+		rates.sort_by_key(|(from, _)| *from);
+		Self { rates }
+	}
+
+	/// Returns the rate in effect at `timestamp`, zero before the first rate takes effect.
+	pub fn rate_at(&self, timestamp: Timestamp) -> Decimal {
+		let effective = self.rates.partition_point(|(from, _)| *from <= timestamp);
+		effective.checked_sub(1).map_or(Decimal::ZERO, |i| self.rates[i].1)
+	}
+}
+
+/// Accrues interest on available balances at the end of each period of a fixed length, at the rate
+/// in effect at the start of the period.
+///
+/// Periods are aligned to the Unix epoch, e.g. a period of a day ends at every midnight UTC.
+#[derive(Debug, Clone)]
+pub struct InterestAccrual {
+	schedule: RateSchedule,
+	/// The length of a period in seconds.
+	period: i64,
+	/// The latest time the accrual was advanced to, if any.
+	clock: Option<Timestamp>,
+}
+
+impl InterestAccrual {
+	/// Creates an accrual of the rates of `schedule` over periods of `period` seconds.
+	///
+	/// # Panics
+	///
+	/// Panics if `period` is not positive.
+	pub fn new(schedule: RateSchedule, period: i64) -> Self {
+		assert!(period > 0, "interest period must be positive");
+		Self { schedule, period, clock: None }
+	}
+
+	/// Advances the clock to `timestamp`, returning the starts of the periods that ended since the
+	/// previous time, in chronological order. The first time only starts the clock, and time never
+	/// moves backwards.
+	pub(crate) fn advance(&mut self, timestamp: Timestamp) -> Vec<Timestamp> {
+		let Some(previous) = self.clock else {
+			self.clock = Some(timestamp);
+			return Vec::new();
+		};
+		if timestamp <= previous {
+			return Vec::new();
+		}
+		self.clock = Some(timestamp);
+		let first = previous.div_euclid(self.period);
+		let last = timestamp.div_euclid(self.period);
+		(first..last).map(|period| period * self.period).collect()
+	}
+
+	/// Returns the interest accrued on `balance` over the period starting at `start`, rounded to
+	/// [`max_decimal_places`], or `None` if there is none.
+	pub fn interest(&self, balance: &Amount, start: Timestamp) -> Option<Amount> {
+		let interest =
+			balance.value().amount() * self.schedule.rate_at(start) * Decimal::from(self.period)
+				/ Decimal::from(SECONDS_PER_YEAR);
+		let interest = interest.round_dp_with_strategy(max_decimal_places().into(), rounding());
+		if interest.is_sign_positive() && !interest.is_zero() {
+			Amount::try_from(Money::from_decimal(interest, base_currency())).ok()
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DAY: i64 = 24 * 60 * 60;
+
+	#[test]
+	fn test_rate_at() {
+		let schedule = RateSchedule::new([(10 * DAY, Decimal::new(3, 2)), (0, Decimal::new(5, 2))]);
+
+		assert_eq!(schedule.rate_at(-1), Decimal::ZERO);
+		assert_eq!(schedule.rate_at(0), Decimal::new(5, 2));
+		assert_eq!(schedule.rate_at(10 * DAY - 1), Decimal::new(5, 2));
+		assert_eq!(schedule.rate_at(10 * DAY), Decimal::new(3, 2));
+	}
+
+	#[test]
+	fn test_advance() {
+		let mut accrual = InterestAccrual::new(RateSchedule::default(), DAY);
+
+		assert_eq!(accrual.advance(DAY / 2), Vec::<Timestamp>::new());
+		assert_eq!(accrual.advance(DAY - 1), Vec::<Timestamp>::new());
+		assert_eq!(accrual.advance(DAY), vec![0]);
+		assert_eq!(accrual.advance(0), Vec::<Timestamp>::new());
+		assert_eq!(accrual.advance(3 * DAY + 1), vec![DAY, 2 * DAY]);
+	}
+
+	#[test]
+	fn test_interest() {
+		let accrual = InterestAccrual::new(RateSchedule::new([(0, Decimal::new(365, 4))]), DAY);
+		let amount = |value| Amount::try_from(value).unwrap();
+
+		assert_eq!(accrual.interest(&amount("1000"), 0), Some(amount("0.1")));
+		assert_eq!(accrual.interest(&amount("0.01"), 0), None);
+		assert_eq!(accrual.interest(&amount("1000"), -DAY), None);
+	}
+}
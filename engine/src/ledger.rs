@@ -0,0 +1,96 @@
+use serde::Serialize;
+
+use domain::account::Account;
+use domain::amount::Amount;
+use domain::config::{ClientId, Timestamp, TransactionId};
+
+use crate::sequencer::SequenceNumber;
+
+/// An applied transaction or interest accrual with the resulting balances of its account, a line of
+/// the running ledger showing how each account arrived at its final state.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LedgerEntry {
+	/// The sequence number of the input record.
+	pub sequence: SequenceNumber,
+	#[serde(rename = "type")]
+	pub tx_type: &'static str,
+	pub client: ClientId,
+	/// The transaction applied, `None` for an interest accrual.
+	pub tx: Option<TransactionId>,
+	/// When the sender recorded the transaction, if the input has timestamps.
+	pub timestamp: Option<Timestamp>,
+	/// The amount applied by a deposit, withdrawal, transfer, settlement or interest accrual, which
+	/// is the capped amount of a partially applied deposit and the net amount of a settlement.
+	/// Disputes, resolves, chargebacks, reversals and captures move the amount of the referenced
+	/// `tx`.
+	pub amount: Option<Amount>,
+	pub available: Amount,
+	pub held: Amount,
+	pub total: Amount,
+	pub locked: bool,
+	/// The reason of an adjustment or unlock.
+	pub reason: Option<String>,
+	/// The original transaction of a refund, or the batch of a settlement.
+	#[serde(rename = "ref")]
+	pub reference: Option<TransactionId>,
+	/// The memo, merchant and reference of the input record.
+	pub memo: Option<String>,
+	pub merchant: Option<String>,
+	#[serde(rename = "reference")]
+	pub external_reference: Option<String>,
+	/// The amounts and currencies of a conversion, e.g. `10.0 USD -> 9.20 EUR`. Its `amount` is
+	/// in the currency converted from, the balances are in the base currency.
+	pub conversion: Option<String>,
+}
+
+impl LedgerEntry {
+	/// Creates the entry of the transaction `tx` of the given type, resulting in `account`.
+	pub fn new(
+		sequence: SequenceNumber,
+		tx_type: &'static str,
+		tx: TransactionId,
+		amount: Option<Amount>,
+		account: &Account,
+	) -> Self {
+		Self {
+			sequence,
+			tx_type,
+			client: account.client_id,
+			tx: Some(tx),
+			timestamp: None,
+			amount,
+			available: account.available.clone(),
+			held: account.held.clone(),
+			total: account.total(),
+			locked: account.locked,
+			reason: None,
+			reference: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			conversion: None,
+		}
+	}
+
+	/// Creates the entry of interest accrued onto `account` after the input record `sequence`.
+	pub fn accrual(sequence: SequenceNumber, amount: Amount, account: &Account) -> Self {
+		Self {
+			sequence,
+			tx_type: "interest",
+			client: account.client_id,
+			tx: None,
+			timestamp: None,
+			amount: Some(amount),
+			available: account.available.clone(),
+			held: account.held.clone(),
+			total: account.total(),
+			locked: account.locked,
+			reason: None,
+			reference: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			conversion: None,
+		}
+	}
+}
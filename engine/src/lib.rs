@@ -1 +1,16 @@
+pub mod fx;
+pub mod heuristics;
+pub mod info;
+pub mod interest;
+pub mod ledger;
+pub mod limits;
+pub mod permissions;
+pub mod policy;
 pub mod processor;
+pub mod recurring;
+pub mod rejects;
+pub mod report;
+pub mod scenario;
+pub mod sequencer;
+pub mod settlement;
+pub mod snapshot;
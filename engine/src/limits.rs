@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use domain::account::{Account, AccountKind, CapPolicy};
+use domain::amount::Amount;
+use domain::config::ClientId;
+
+/// Configures the maximum total balance of accounts.
+///
+/// A per-client maximum takes precedence over the global one; clients with neither are uncapped.
+#[derive(Debug, Default, Clone)]
+pub struct BalanceCap {
+	/// The maximum balance of every client without a per-client maximum.
+	pub global: Option<Amount>,
+	/// The maximum balance of specific clients.
+	pub per_client: HashMap<ClientId, Amount>,
+	/// What happens to deposits that would exceed the maximum.
+	pub policy: CapPolicy,
+}
+
+impl BalanceCap {
+	/// Returns the maximum balance of the given client, if any.
+	pub fn cap_for(&self, client_id: &ClientId) -> Option<&Amount> {
+		self.per_client.get(client_id).or(self.global.as_ref())
+	}
+}
+
+/// Configures how far below zero withdrawals and holds may take the available balance of
+/// accounts.
+///
+/// A per-client limit takes precedence over the global one; clients with neither have no
+/// overdraft. Clients with a credit line have credit accounts, whose credit limit takes precedence
+/// over both.
+#[derive(Debug, Default, Clone)]
+pub struct Overdraft {
+	/// The overdraft limit of every client without a per-client limit.
+	pub global: Option<Amount>,
+	/// The overdraft limits of specific clients.
+	pub per_client: HashMap<ClientId, Amount>,
+	/// The credit limits of the clients with credit accounts.
+	pub credit_lines: HashMap<ClientId, Amount>,
+}
+
+impl Overdraft {
+	/// Returns the overdraft limit of the given client, if any.
+	pub fn limit_for(&self, client_id: &ClientId) -> Option<&Amount> {
+		self.per_client.get(client_id).or(self.global.as_ref())
+	}
+
+	/// Sets the kind and overdraft limit of the given account.
+	pub fn configure(&self, account: &mut Account) {
+		if let Some(limit) = self.credit_lines.get(&account.client_id) {
+			account.kind = AccountKind::Credit;
+			account.overdraft = limit.clone();
+		} else if let Some(limit) = self.limit_for(&account.client_id) {
+			account.overdraft = limit.clone();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_cap_for() {
+		let global = Amount::try_from("100").unwrap();
+		let client = Amount::try_from("10").unwrap();
+		let cap = BalanceCap {
+			global: Some(global.clone()),
+			per_client: HashMap::from([(2, client.clone())]),
+			..BalanceCap::default()
+		};
+
+		assert_eq!(cap.cap_for(&1), Some(&global));
+		assert_eq!(cap.cap_for(&2), Some(&client));
+		assert_eq!(BalanceCap::default().cap_for(&1), None);
+	}
+
+	#[test]
+	fn test_limit_for() {
+		let client = Amount::try_from("50").unwrap();
+		let overdraft =
+			Overdraft { per_client: HashMap::from([(2, client.clone())]), ..Overdraft::default() };
+
+		assert_eq!(overdraft.limit_for(&1), None);
+		assert_eq!(overdraft.limit_for(&2), Some(&client));
+	}
+
+	#[test]
+	fn test_configure() {
+		let limit = Amount::try_from("50").unwrap();
+		let credit = Amount::try_from("1000").unwrap();
+		let overdraft = Overdraft {
+			global: Some(limit.clone()),
+			credit_lines: HashMap::from([(2, credit.clone())]),
+			..Overdraft::default()
+		};
+		let mut accounts =
+			[1, 2].map(|client| Account::new(client, Amount::default(), Amount::default(), false));
+		accounts.iter_mut().for_each(|account| overdraft.configure(account));
+
+		assert_eq!((accounts[0].kind, &accounts[0].overdraft), (AccountKind::Debit, &limit));
+		assert_eq!((accounts[1].kind, &accounts[1].overdraft), (AccountKind::Credit, &credit));
+	}
+}
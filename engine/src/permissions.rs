@@ -0,0 +1,180 @@
+use std::fmt;
+
+use domain::account::Account;
+use domain::transaction::{Transaction, TransactionError};
+
+use crate::processor::TransactionProcessor;
+
+/// Configures which transaction types a [`RestrictedHandle`] may submit.
+///
+/// The default permits no transactions at all, giving a read-only handle.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Permissions {
+	pub deposit: bool,
+	pub withdrawal: bool,
+	pub dispute: bool,
+	pub resolve: bool,
+	pub chargeback: bool,
+	pub reversal: bool,
+	pub transfer: bool,
+	pub fee: bool,
+	pub refund: bool,
+	pub authorize: bool,
+	pub capture: bool,
+	pub adjustment: bool,
+	pub unlock: bool,
+	pub reopen: bool,
+	pub close_account: bool,
+	pub settlement: bool,
+	pub convert: bool,
+	pub hold: bool,
+}
+
+impl Permissions {
+	/// Permits only reading the account states.
+	pub fn read_only() -> Self {
+		Permissions::default()
+	}
+
+	/// Permits only deposits.
+	pub fn deposits_only() -> Self {
+		Permissions { deposit: true, ..Permissions::default() }
+	}
+
+	/// Permits every transaction type.
+	pub fn all() -> Self {
+		Permissions {
+			deposit: true,
+			withdrawal: true,
+			dispute: true,
+			resolve: true,
+			chargeback: true,
+			reversal: true,
+			transfer: true,
+			fee: true,
+			refund: true,
+			authorize: true,
+			capture: true,
+			adjustment: true,
+			unlock: true,
+			reopen: true,
+			close_account: true,
+			settlement: true,
+			convert: true,
+			hold: true,
+		}
+	}
+
+	/// Returns whether the given transaction may be submitted.
+	pub fn allows(&self, tx: &Transaction) -> bool {
+		match tx {
+			Transaction::Deposit { .. } => self.deposit,
+			Transaction::Withdrawal { .. } => self.withdrawal,
+			Transaction::Dispute { .. }
+			| Transaction::Representment { .. }
+			| Transaction::PreArbitration { .. } => self.dispute,
+			Transaction::Resolve { .. } => self.resolve,
+			Transaction::Chargeback { .. } => self.chargeback,
+			Transaction::Reversal { .. } => self.reversal,
+			Transaction::Transfer { .. } => self.transfer,
+			Transaction::Fee { .. } => self.fee,
+			Transaction::Refund { .. } => self.refund,
+			Transaction::Authorization { .. } => self.authorize,
+			Transaction::Capture { .. } => self.capture,
+			Transaction::CreditAdjustment { .. } | Transaction::DebitAdjustment { .. } => {
+				self.adjustment
+			},
+			Transaction::Unlock { .. } => self.unlock,
+			Transaction::Reopen { .. } => self.reopen,
+			Transaction::CloseAccount { .. } => self.close_account,
+			Transaction::Settlement { .. } => self.settlement,
+			Transaction::Convert { .. } => self.convert,
+			Transaction::Hold { .. } | Transaction::Release { .. } => self.hold,
+		}
+	}
+}
+
+/// Represents errors submitting a transaction through a [`RestrictedHandle`].
+#[derive(Debug, PartialEq)]
+pub enum RestrictedHandleError {
+	/// The handle is not permitted to submit transactions of this type.
+	NotPermitted(Transaction),
+	/// The transaction was permitted but rejected by the processor.
+	TransactionError(TransactionError),
+}
+
+impl fmt::Display for RestrictedHandleError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RestrictedHandleError::NotPermitted(tx) => {
+				write!(f, "Transaction not permitted: {tx:?}")
+			},
+			RestrictedHandleError::TransactionError(e) => write!(f, "Transaction rejected: {e:?}"),
+		}
+	}
+}
+
+/// A handle over a [`TransactionProcessor`] that can only submit the transaction types its
+/// [`Permissions`] allow, for handing limited capabilities to embedded plugins.
+pub struct RestrictedHandle<'a> {
+	processor: &'a mut TransactionProcessor,
+	permissions: Permissions,
+}
+
+impl<'a> RestrictedHandle<'a> {
+	/// Creates a handle over `processor` restricted to the given `permissions`.
+	pub fn new(processor: &'a mut TransactionProcessor, permissions: Permissions) -> Self {
+		RestrictedHandle { processor, permissions }
+	}
+
+	/// Returns the permissions of this handle.
+	pub fn permissions(&self) -> Permissions {
+		self.permissions
+	}
+
+	/// Submits a transaction to the processor, if this handle is permitted to.
+	///
+	/// # Errors
+	///
+	/// * Returns [`RestrictedHandleError::NotPermitted`] if the transaction type is not permitted.
+	/// * Returns [`RestrictedHandleError::TransactionError`] if the processor rejects the transaction.
+	pub async fn submit(&mut self, tx: Transaction) -> Result<(), RestrictedHandleError> {
+		if !self.permissions.allows(&tx) {
+			return Err(RestrictedHandleError::NotPermitted(tx));
+		}
+		self.processor
+			.handle_transaction(tx)
+			.await
+			.map_err(RestrictedHandleError::TransactionError)
+	}
+
+	/// Retrieves all accounts of the processor.
+	pub async fn get_accounts(&self) -> Vec<Account> {
+		self.processor.get_accounts().await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use domain::amount::Amount;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_submit_enforces_permissions() {
+		let mut processor = TransactionProcessor::default();
+		let amount = Amount::try_from("5").unwrap();
+
+		let mut deposits = RestrictedHandle::new(&mut processor, Permissions::deposits_only());
+		deposits.submit(Transaction::deposit(1, amount.clone(), 1)).await.unwrap();
+		let withdrawal = Transaction::withdrawal(2, amount.clone(), 1);
+		assert_eq!(
+			deposits.submit(withdrawal.clone()).await,
+			Err(RestrictedHandleError::NotPermitted(withdrawal))
+		);
+
+		let mut read_only = RestrictedHandle::new(&mut processor, Permissions::read_only());
+		assert!(read_only.submit(Transaction::deposit(3, amount.clone(), 1)).await.is_err());
+		assert_eq!(read_only.get_accounts().await[0].available, amount);
+	}
+}
@@ -0,0 +1,16 @@
+/// Configures the dispute rules of the scheme a processor operates under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisputePolicy {
+	/// Whether a transaction that was disputed, resolved and disputed again may be charged back.
+	pub chargeback_after_redispute: bool,
+	/// Whether a charged back transaction may be re-opened by an administrative `reopen`, which
+	/// requires keeping charged back transactions in the history.
+	pub reopen_chargebacks: bool,
+}
+
+impl Default for DisputePolicy {
+	/// Allows charging back re-disputed transactions, but not re-opening chargebacks.
+	fn default() -> Self {
+		Self { chargeback_after_redispute: true, reopen_chargebacks: false }
+	}
+}
@@ -1,17 +1,46 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
 use std::sync::Arc;
 
 use itertools::Itertools;
-use log::debug;
+use log::{debug, warn};
 use tokio::sync::Mutex;
 
-use domain::account::Account;
+use domain::account::{Account, AccountError, AccountKind, CapPolicy};
 use domain::amount::Amount;
-use domain::config::{ClientId, TransactionId};
+use domain::config::{base_currency, AmountConfig, BatchId, ClientId, Timestamp, TransactionId};
+use domain::output::OutputValidationError;
+use domain::sink::AccountSink;
+use domain::source::{SourceError, TransactionSource};
 use domain::transaction::TransactionError::*;
-use domain::transaction::{CsvError, StreamExt, Transaction, TransactionError};
+use domain::transaction::TransactionState::{
+	ChargedBack, Disputed, Okay, PreArbitration, Representment, Reversed,
+};
+use domain::transaction::{StreamExt, Transaction, TransactionError, TransactionState};
 
-type Accounts = HashMap<ClientId, (Account, HashMap<TransactionId, Transaction>)>;
+use crate::fx::{FxError, RateTable};
+use crate::heuristics::{DuplicateSubmissionDetector, SuspectedDuplicate};
+use crate::info::{ProcessorCounters, ProcessorInfo};
+use crate::interest::InterestAccrual;
+use crate::ledger::LedgerEntry;
+use crate::limits::{BalanceCap, Overdraft};
+use crate::permissions::{Permissions, RestrictedHandle};
+use crate::policy::DisputePolicy;
+use crate::recurring::Scheduler;
+use crate::rejects::RejectedRecord;
+use crate::report::ProcessingReport;
+use crate::sequencer::{SequenceNumber, Sequencer};
+use crate::settlement::{NetAmount, UnsettledBatch};
+use crate::snapshot::{AccountSnapshot, ProcessorSnapshot};
+
+/// The number of accounts passed to an [`AccountSink`] at a time.
+pub const SINK_BATCH_SIZE: usize = 1024;
+
+/// An account with its transaction history.
+type AccountEntry = (Account, HashMap<TransactionId, Transaction>);
+type Accounts = HashMap<ClientId, AccountEntry>;
+type Operation = fn(&mut Account, &Amount) -> Result<(), AccountError>;
 /// Processes and manages transactions for multiple accounts.
 #[derive(Default)]
 pub struct TransactionProcessor {
@@ -21,371 +50,3701 @@ pub struct TransactionProcessor {
 	accounts: Arc<Mutex<Accounts>>,
 	/// Set of globally unique transaction IDs to prevent duplicates.
 	global_tx_ids: Arc<Mutex<HashSet<TransactionId>>>,
+	/// Optional heuristics flagging likely accidental double submissions.
+	duplicate_detector: Option<DuplicateSubmissionDetector>,
+	/// Sequence number of the last input record processed, 0 if none.
+	last_sequence: SequenceNumber,
+	/// The byte offset of the last input record processed in its source, if the source knows it.
+	last_offset: Option<u64>,
+	/// The maximum balances enforced on deposits.
+	balance_cap: BalanceCap,
+	/// Counts of the records and transactions processed so far.
+	counters: ProcessorCounters,
+	/// Input records up to this sequence number were processed before the restored snapshot was
+	/// taken, and are skipped.
+	resume_after: SequenceNumber,
+	/// Optional periodic checkpoints of the processor state.
+	checkpoints: Option<Checkpoints>,
+	/// The running ledger of applied transactions, if enabled.
+	ledger: Option<Vec<LedgerEntry>>,
+	/// The skipped input records, if enabled.
+	rejects: Option<Vec<RejectedRecord>>,
+	/// The state of each account before the first transaction of this run touched it, if change
+	/// tracking is enabled.
+	originals: Option<HashMap<ClientId, Account>>,
+	/// The number of accounts restored from a snapshot.
+	restored_accounts: usize,
+	/// The number of input records after which uncaptured authorizations expire, if enabled.
+	authorization_expiry: Option<u64>,
+	/// The authorizations that may expire, with the sequence numbers of their input records, in
+	/// input order.
+	authorizations: VecDeque<(SequenceNumber, ClientId, TransactionId)>,
+	/// The number of seconds after which uncaptured authorizations with a timestamp expire, if
+	/// enabled.
+	authorization_window: Option<i64>,
+	/// The authorizations with a timestamp that may expire, with their timestamps, in input order.
+	timed_authorizations: VecDeque<(Timestamp, ClientId, TransactionId)>,
+	/// Accrues interest on available balances as the clock advances, if enabled.
+	interest: Option<InterestAccrual>,
+	/// The current time, advanced by [`Self::advance_clock`], if it was ever set.
+	clock: Option<Timestamp>,
+	/// The number of seconds after which a transaction can no longer be disputed, if enabled.
+	dispute_window: Option<i64>,
+	/// The time each deposit and withdrawal was applied, if the dispute window and the clock are
+	/// set.
+	applied_at: HashMap<TransactionId, Timestamp>,
+	/// The transactions of each client applied since its last settlement.
+	unsettled: HashMap<ClientId, UnsettledBatch>,
+	/// The net amounts moved by the applied settlements.
+	settled: HashMap<TransactionId, Amount>,
+	/// The batches settled for each client, which cannot be settled again.
+	settled_batches: HashSet<(ClientId, BatchId)>,
+	/// Expands recurring payments as the clock advances, if enabled.
+	recurring: Option<Scheduler>,
+	/// The dispute rules of the scheme the processor operates under.
+	dispute_policy: DisputePolicy,
+	/// The deposits and withdrawals whose disputes were resolved before.
+	resolved: HashSet<TransactionId>,
+	/// The idempotency keys of the records applied so far.
+	idempotency_keys: HashSet<String>,
+	/// The overdraft limits of withdrawals and holds.
+	overdraft: Overdraft,
+	/// The transactions of the group being read, with the sequence numbers of their input records.
+	group: Vec<(SequenceNumber, Transaction)>,
+	/// The number of seconds deposits are held before they clear, if enabled.
+	clearing_period: Option<i64>,
+	/// The deposits that have not cleared yet, with their clients and the time they clear.
+	clearing: HashMap<TransactionId, (ClientId, Timestamp)>,
+	/// The exchange rates of conversions.
+	fx: RateTable,
+	/// The amounts credited by the applied conversions.
+	converted: HashMap<TransactionId, Amount>,
+	/// The funds of each client held by manual holds and not released yet.
+	manual_holds: HashMap<ClientId, Amount>,
+	/// How amounts are read, validated and written, the configuration in scope of the caller
+	/// unless configured.
+	amount_config: Option<AmountConfig>,
+}
+
+/// Writes a checkpoint of the processor state.
+type CheckpointWriter = Box<dyn FnMut(&ProcessorSnapshot) -> std::io::Result<()> + Send + Sync>;
+
+/// Writes a snapshot of the processor state every `interval` input records.
+struct Checkpoints {
+	interval: u64,
+	write: CheckpointWriter,
+}
+
+/// The state the transactions of a group may change, captured before the first of them is applied
+/// so that the whole group can be rolled back. Every map or set keyed by the clients or ids of a
+/// group that [`TransactionProcessor::handle_transaction`] changes must be captured here.
+struct GroupUndo {
+	/// The accounts of the clients and destinations of the group.
+	accounts: Saved<ClientId, AccountEntry>,
+	unsettled: Saved<ClientId, UnsettledBatch>,
+	manual_holds: Saved<ClientId, Amount>,
+	originals: Option<Saved<ClientId, Account>>,
+	global_tx_ids: Members<TransactionId>,
+	applied_at: Saved<TransactionId, Timestamp>,
+	clearing: Saved<TransactionId, (ClientId, Timestamp)>,
+	settled: Saved<TransactionId, Amount>,
+	settled_batches: Members<(ClientId, BatchId)>,
+	converted: Saved<TransactionId, Amount>,
+	resolved: Members<TransactionId>,
+	idempotency_keys: Members<String>,
+	counters: ProcessorCounters,
+	/// Authorizations are only appended while a group is applied.
+	authorizations: usize,
+	timed_authorizations: usize,
+}
+
+/// The values of some keys of a map, `None` for the keys it does not contain.
+struct Saved<K, V>(Vec<(K, Option<V>)>);
+
+impl<K: Eq + Hash + Clone, V: Clone> Saved<K, V> {
+	fn of(map: &HashMap<K, V>, keys: &[K]) -> Self {
+		Self(keys.iter().map(|key| (key.clone(), map.get(key).cloned())).collect())
+	}
+
+	fn restore(self, map: &mut HashMap<K, V>) {
+		for (key, value) in self.0 {
+			restore(map, key, value);
+		}
+	}
+}
+
+/// Whether some values are members of a set.
+struct Members<T>(Vec<(T, bool)>);
+
+impl<T: Eq + Hash + Clone> Members<T> {
+	fn of(set: &HashSet<T>, values: &[T]) -> Self {
+		Self(values.iter().map(|value| (value.clone(), set.contains(value))).collect())
+	}
+
+	fn restore(self, set: &mut HashSet<T>) {
+		for (value, member) in self.0 {
+			if member {
+				set.insert(value);
+			} else {
+				set.remove(&value);
+			}
+		}
+	}
 }
 
 #[derive(Debug)]
 pub enum TransactionProcessorError {
 	TransactionProcessingError(TransactionError),
-	TransactionParsingError(CsvError),
+	TransactionParsingError(SourceError),
+}
+
+/// Represents errors that abort a processing run as a whole, rather than a single transaction.
+#[derive(Debug)]
+pub enum ProcessRunError {
+	/// The transaction source could not be opened or read.
+	SourceIo(std::io::Error),
+	/// The resulting account states could not be written.
+	OutputIo(std::io::Error),
+	/// A resulting account record does not match the declared output schema.
+	InvalidOutput(OutputValidationError),
+	/// A checkpoint could not be read or written.
+	CheckpointIo(std::io::Error),
+}
+
+impl fmt::Display for ProcessRunError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ProcessRunError::SourceIo(err) => write!(f, "Could not read transactions: {err}"),
+			ProcessRunError::OutputIo(err) => write!(f, "Could not write accounts: {err}"),
+			ProcessRunError::InvalidOutput(err) => write!(f, "Invalid account output: {err}"),
+			ProcessRunError::CheckpointIo(err) => {
+				write!(f, "Could not read or write checkpoint: {err}")
+			},
+		}
+	}
 }
 
-trait TransactionProcessorErrorHandler {
-	fn handle(error: TransactionProcessorError);
-}
+impl TransactionProcessor {
+	/// Processes the transactions of a source, such as a CSV reader.
+	///
+	/// This function reads transactions from the provided source, handles each transaction,
+	/// and returns a vector of all the resulting account states with a report of the run.
+	///
+	/// Errors parsing or handling individual transactions are passed to `error_handler` and
+	/// processing continues with the next transaction.
+	///
+	/// # Errors
+	///
+	/// Returns a [`ProcessRunError::SourceIo`] if reading from the source fails.
+	pub async fn process_transactions<F>(
+		source: impl TransactionSource,
+		error_handler: F,
+	) -> Result<(Vec<Account>, ProcessingReport), ProcessRunError>
+	where
+		F: Fn(TransactionProcessorError),
+	{
+		let mut tx_processor = TransactionProcessor::default();
+		tx_processor.process(source, error_handler).await?;
+		Ok((tx_processor.get_accounts().await, tx_processor.report().await))
+	}
+
+	/// Processes the transactions of `source` like [`Self::process_transactions`] and writes the
+	/// resulting accounts to `sink`.
+	///
+	/// # Errors
+	///
+	/// Returns a [`ProcessRunError::SourceIo`] if reading from the source fails, or a
+	/// [`ProcessRunError::OutputIo`] if writing to the sink fails.
+	pub async fn process_transactions_into<F>(
+		source: impl TransactionSource,
+		error_handler: F,
+		sink: impl AccountSink,
+	) -> Result<(), ProcessRunError>
+	where
+		F: Fn(TransactionProcessorError),
+	{
+		let mut tx_processor = TransactionProcessor::default();
+		tx_processor.process(source, error_handler).await?;
+		tx_processor.write_accounts(sink).await.map_err(ProcessRunError::OutputIo)
+	}
+
+	/// Enables the duplicate submission heuristics, comparing transactions whose ids are at most
+	/// `window` apart. The findings are available from [`Self::suspected_duplicates`].
+	pub fn with_duplicate_detector(mut self, window: u32) -> Self {
+		self.duplicate_detector = Some(DuplicateSubmissionDetector::new(window));
+		self
+	}
+
+	/// Enforces the given maximum balances on deposits.
+	pub fn with_balance_cap(mut self, balance_cap: BalanceCap) -> Self {
+		self.balance_cap = balance_cap;
+		self
+	}
+
+	/// Allows withdrawals and holds to take available balances below zero down to the given
+	/// overdraft limits, and makes the accounts of clients with credit lines credit accounts.
+	pub fn with_overdraft(mut self, overdraft: Overdraft) -> Self {
+		self.overdraft = overdraft;
+		self
+	}
+
+	/// Restores the state of a snapshot, such as a checkpoint written by a previous run.
+	///
+	/// Input is then numbered from the start again and the records up to the snapshot's sequence
+	/// number are skipped, so the previous run's input can be processed again from its beginning
+	/// to resume it. Counters and duplicate heuristics start afresh.
+	pub fn with_snapshot(self, snapshot: ProcessorSnapshot) -> Self {
+		let sequence = snapshot.sequence;
+		let mut processor = self.with_state(snapshot);
+		processor.resume_after = sequence;
+		processor
+	}
+
+	/// Restores the state of a snapshot like [`Self::with_snapshot`], for input that was seeked to
+	/// the snapshot's offset with [`CsvSource::seek_to`] rather than read from its beginning. Its
+	/// first record is then the last one processed before the snapshot was taken, which is
+	/// skipped, and the records after it are numbered on from it.
+	///
+	/// [`CsvSource::seek_to`]: domain::source::CsvSource::seek_to
+	pub fn with_snapshot_at_offset(self, snapshot: ProcessorSnapshot) -> Self {
+		let sequence = snapshot.sequence;
+		let mut processor = self.with_snapshot(snapshot);
+		processor.last_sequence = sequence.saturating_sub(1);
+		processor
+	}
+
+	/// Starts from the accounts and transaction histories of a snapshot, such as the final state
+	/// of a previous run, to process new input incrementally. Unlike [`Self::with_snapshot`], no
+	/// input records are skipped.
+	pub fn with_state(mut self, snapshot: ProcessorSnapshot) -> Self {
+		let mut global_tx_ids: HashSet<TransactionId> =
+			snapshot.retired_tx_ids.into_iter().collect();
+		let accounts: Accounts = snapshot
+			.accounts
+			.into_iter()
+			.map(|account| {
+				let txs: HashMap<TransactionId, Transaction> = account
+					.transactions
+					.iter()
+					.map(|tx| (tx.tx, tx.transaction(account.client)))
+					.collect();
+				global_tx_ids.extend(txs.keys());
+				if let Some(unsettled) = &account.unsettled {
+					self.unsettled.insert(account.client, unsettled.clone());
+				}
+				if let Some(held) = &account.manual_hold {
+					self.manual_holds.insert(account.client, held.clone());
+				}
+				self.settled_batches
+					.extend(account.settled_batches.iter().map(|batch| (account.client, *batch)));
+				self.applied_at.extend(
+					account.transactions.iter().filter_map(|tx| Some((tx.tx, tx.applied_at?))),
+				);
+				self.clearing.extend(
+					account
+						.transactions
+						.iter()
+						.filter_map(|tx| Some((tx.tx, (account.client, tx.clears_at?)))),
+				);
+				self.resolved
+					.extend(account.transactions.iter().filter(|tx| tx.resolved).map(|tx| tx.tx));
+				(account.client, (account.account(), txs))
+			})
+			.collect();
+		self.idempotency_keys = snapshot.idempotency_keys.into_iter().collect();
+		self.restored_accounts = accounts.len();
+		self.accounts = Arc::new(Mutex::new(accounts));
+		self.global_tx_ids = Arc::new(Mutex::new(global_tx_ids));
+		self
+	}
+
+	/// Tracks which accounts change, so that only those can be written with
+	/// [`Self::write_changed_accounts`].
+	pub fn with_change_tracking(mut self) -> Self {
+		self.originals = Some(HashMap::new());
+		self
+	}
+
+	/// Calls `write` with a snapshot of the processor state every `interval` input records, so
+	/// that an interrupted run can be resumed with [`Self::with_snapshot`].
+	pub fn with_checkpoints(
+		mut self,
+		interval: u64,
+		write: impl FnMut(&ProcessorSnapshot) -> std::io::Result<()> + Send + Sync + 'static,
+	) -> Self {
+		self.checkpoints = Some(Checkpoints { interval: interval.max(1), write: Box::new(write) });
+		self
+	}
+
+	/// Records every applied transaction with the resulting balances of its account, available
+	/// from [`Self::ledger`].
+	pub fn with_ledger(mut self) -> Self {
+		self.ledger = Some(Vec::new());
+		self
+	}
+
+	/// Records every skipped input record with the reason of its rejection, available from
+	/// [`Self::rejects`].
+	pub fn with_rejects(mut self) -> Self {
+		self.rejects = Some(Vec::new());
+		self
+	}
+
+	/// Releases the holds of authorizations that are not captured within `records` further input
+	/// records. With [`Self::with_authorization_window`], authorizations with a timestamp expire by
+	/// time instead. Authorizations restored from a snapshot do not expire.
+	pub fn with_authorization_expiry(mut self, records: u64) -> Self {
+		self.authorization_expiry = Some(records);
+		self
+	}
+
+	/// Releases the holds of authorizations with a timestamp that are not captured within `seconds`
+	/// of it, as [`Self::advance_clock`] advances the clock. An authorization whose timestamp is
+	/// earlier than that of an authorization before it expires no earlier than that one.
+	/// Authorizations restored from a snapshot do not expire.
+	pub fn with_authorization_window(mut self, seconds: i64) -> Self {
+		self.authorization_window = Some(seconds);
+		self
+	}
+
+	/// Enables interest accrual, driven by [`Self::advance_clock`].
+	pub fn with_interest(mut self, accrual: InterestAccrual) -> Self {
+		self.interest = Some(accrual);
+		self
+	}
+
+	/// Rejects disputes of deposits and withdrawals applied more than `seconds` before the current
+	/// time of [`Self::advance_clock`]. Transactions applied while the clock was not set can always
+	/// be disputed.
+	pub fn with_dispute_window(mut self, seconds: i64) -> Self {
+		self.dispute_window = Some(seconds);
+		self
+	}
+
+	/// Holds deposits applied while the clock is set until `seconds` later, when
+	/// [`Self::advance_clock`] releases them to the available balance. Deposits applied while the
+	/// clock was not set clear immediately.
+	pub fn with_clearing_period(mut self, seconds: i64) -> Self {
+		self.clearing_period = Some(seconds);
+		self
+	}
+
+	/// Converts between currency sub-balances at the rates of the given table, see [`RateTable`].
+	pub fn with_fx_rates(mut self, rates: RateTable) -> Self {
+		self.fx = rates;
+		self
+	}
+
+	/// Enables recurring payments, applied as [`Self::advance_clock`] makes them due.
+	pub fn with_recurring_payments(mut self, scheduler: Scheduler) -> Self {
+		self.recurring = Some(scheduler);
+		self
+	}
+
+	/// Applies the dispute rules of the given policy instead of the default ones.
+	pub fn with_dispute_policy(mut self, policy: DisputePolicy) -> Self {
+		self.dispute_policy = policy;
+		self
+	}
+
+	/// Reads, validates and writes amounts as configured by `config`, such as in another base
+	/// currency or with more decimal places, instead of by the configuration in scope. It is in
+	/// scope while the processor reads its sources, applies transactions and writes its sinks.
+	pub fn with_amount_config(mut self, config: AmountConfig) -> Self {
+		self.amount_config = Some(config);
+		self
+	}
+
+	/// Returns the amount configuration of the processor, or the one in scope of the caller if not
+	/// configured.
+	pub fn amount_config(&self) -> AmountConfig {
+		self.amount_config.unwrap_or_else(AmountConfig::current)
+	}
+
+	/// Returns a handle over this processor that can only submit the transaction types allowed
+	/// by `permissions`.
+	pub fn restricted(&mut self, permissions: Permissions) -> RestrictedHandle<'_> {
+		RestrictedHandle::new(self, permissions)
+	}
+
+	/// Returns the suspected duplicate submissions, if the duplicate heuristics are enabled.
+	pub fn suspected_duplicates(&self) -> Option<&[SuspectedDuplicate]> {
+		self.duplicate_detector.as_ref().map(DuplicateSubmissionDetector::findings)
+	}
+
+	/// Returns the running ledger of applied transactions, if the ledger is enabled.
+	pub fn ledger(&self) -> Option<&[LedgerEntry]> {
+		self.ledger.as_deref()
+	}
+
+	/// Returns the skipped input records, if the rejects report is enabled.
+	pub fn rejects(&self) -> Option<&[RejectedRecord]> {
+		self.rejects.as_deref()
+	}
+
+	/// Returns the sequence number of the last input record processed, 0 if none.
+	pub fn last_sequence(&self) -> SequenceNumber {
+		self.last_sequence
+	}
+
+	/// Processes the transactions of a source, such as a CSV reader, into this processor's
+	/// accounts.
+	///
+	/// Records are applied one at a time in the order assigned by the [`Sequencer`], which is the
+	/// input order, continuing the numbering of previously processed input.
+	///
+	/// Errors parsing or handling individual transactions are passed to `error_handler` and
+	/// processing continues with the next transaction.
+	///
+	/// Consecutive records with the same group id are applied together or not at all, see
+	/// [`Self::apply_group`]. No checkpoint is written while a group is being read.
+	///
+	/// # Errors
+	///
+	/// Returns a [`ProcessRunError::SourceIo`] if reading from the source fails.
+	pub async fn process<F>(
+		&mut self,
+		source: impl TransactionSource,
+		error_handler: F,
+	) -> Result<(), ProcessRunError>
+	where
+		F: Fn(TransactionProcessorError),
+	{
+		let config = self.amount_config();
+		config.scope(self.process_records(source, error_handler)).await
+	}
+
+	/// Processes the records of `source` like [`Self::process`], with the amount configuration
+	/// already in scope.
+	async fn process_records<F>(
+		&mut self,
+		source: impl TransactionSource,
+		error_handler: F,
+	) -> Result<(), ProcessRunError>
+	where
+		F: Fn(TransactionProcessorError),
+	{
+		let sequencer = Sequencer::starting_at(self.last_sequence + 1);
+		let mut tx_stream = sequencer.sequence(source.into_offset_stream());
+		while let Some((sequence, (offset, tx_result))) = tx_stream.next().await {
+			self.last_sequence = sequence;
+			self.last_offset = offset;
+			if sequence <= self.resume_after {
+				continue;
+			}
+			self.expire_authorizations(sequence).await;
+			match tx_result {
+				Ok(tx) if tx.group_id().is_some() => {
+					if self.group.first().is_some_and(|(_, leg)| leg.group_id() != tx.group_id()) {
+						self.apply_group(&error_handler).await;
+					}
+					self.group.push((sequence, tx));
+				},
+				Ok(tx) => {
+					self.apply_group(&error_handler).await;
+					let original = (self.duplicate_detector.is_some()
+						|| self.rejects.is_some()
+						|| self.ledger.is_some())
+					.then(|| tx.clone());
+					match self.handle_transaction(tx).await {
+						Ok(()) => {
+							if let (Some(detector), Some(tx)) =
+								(&mut self.duplicate_detector, &original)
+							{
+								detector.observe(tx);
+							}
+							if let Some(tx) = &original {
+								self.record_ledger_entry(sequence, tx).await;
+							}
+						},
+						Err(e) => {
+							if let (Some(rejects), Some(tx)) = (&mut self.rejects, &original) {
+								rejects.push(RejectedRecord::rejected(sequence, tx, &e));
+							}
+							error_handler(TransactionProcessorError::TransactionProcessingError(e))
+						},
+					}
+				},
+				Err(SourceError::Io(e)) => return Err(ProcessRunError::SourceIo(e)),
+				Err(e) => {
+					self.apply_group(&error_handler).await;
+					self.counters.parse_errors += 1;
+					if let Some(rejects) = &mut self.rejects {
+						rejects.push(RejectedRecord::unreadable(sequence, &e));
+					}
+					error_handler(TransactionProcessorError::TransactionParsingError(e))
+				},
+			};
+			if self.group.is_empty()
+				&& self.checkpoints.as_ref().is_some_and(|c| sequence % c.interval == 0)
+			{
+				self.checkpoint().await?;
+			}
+		}
+		self.apply_group(&error_handler).await;
+		Ok(())
+	}
+
+	/// Applies the transactions of the group read so far in order. If one of them is rejected, the
+	/// changes of those applied before it are rolled back, and the whole group is rejected: the
+	/// failing transaction with its own reason and the others as `GroupRejected`.
+	async fn apply_group<F>(&mut self, error_handler: &F)
+	where
+		F: Fn(TransactionProcessorError),
+	{
+		let legs = std::mem::take(&mut self.group);
+		if legs.is_empty() {
+			return;
+		}
+		// Advancing the clock up front keeps the interest accrued meanwhile out of the rollback.
+		if let Some(timestamp) = legs.iter().filter_map(|(_, tx)| tx.timestamp()).max() {
+			self.advance_clock(timestamp).await;
+		}
+		let undo = self.group_undo(&legs).await;
+		let mut failure = None;
+		for (index, (_, tx)) in legs.iter().enumerate() {
+			if let Err(e) = self.handle_transaction(tx.clone()).await {
+				failure = Some((index, e));
+				break;
+			}
+		}
+		let Some((failed, error)) = failure else {
+			for (sequence, tx) in &legs {
+				if let Some(detector) = &mut self.duplicate_detector {
+					detector.observe(tx);
+				}
+				self.record_ledger_entry(*sequence, tx).await;
+			}
+			return;
+		};
+
+		debug!("Rolling back group {:?}", legs[failed].1.group_id());
+		self.roll_back(undo).await;
+		let mut error = Some(error);
+		for (index, (sequence, tx)) in legs.into_iter().enumerate() {
+			let e = match error.take_if(|_| index == failed) {
+				Some(e) => e,
+				None => GroupRejected(tx.clone()),
+			};
+			self.counters.rejected += 1;
+			*self.counters.rejected_by_reason.entry(e.code()).or_default() += 1;
+			if let Some(rejects) = &mut self.rejects {
+				rejects.push(RejectedRecord::rejected(sequence, &tx, &e));
+			}
+			error_handler(TransactionProcessorError::TransactionProcessingError(e));
+		}
+	}
+
+	/// Captures the state the transactions of a group may change, see [`GroupUndo`].
+	async fn group_undo(&self, legs: &[(SequenceNumber, Transaction)]) -> GroupUndo {
+		let clients = legs
+			.iter()
+			.flat_map(|(_, tx)| [Some(tx.client_id()), tx.destination()])
+			.flatten()
+			.copied()
+			.unique()
+			.collect_vec();
+		let ids = legs.iter().map(|(_, tx)| tx.id()).unique().collect_vec();
+		let batches = legs
+			.iter()
+			.filter_map(|(_, tx)| match tx {
+				Transaction::Settlement { client, batch, .. } => Some((*client, *batch)),
+				_ => None,
+			})
+			.collect_vec();
+		let keys = legs
+			.iter()
+			.filter_map(|(_, tx)| tx.idempotency_key())
+			.map(String::from)
+			.collect_vec();
+		GroupUndo {
+			accounts: Saved::of(&*self.accounts.lock().await, &clients),
+			unsettled: Saved::of(&self.unsettled, &clients),
+			manual_holds: Saved::of(&self.manual_holds, &clients),
+			originals: self.originals.as_ref().map(|originals| Saved::of(originals, &clients)),
+			global_tx_ids: Members::of(&*self.global_tx_ids.lock().await, &ids),
+			applied_at: Saved::of(&self.applied_at, &ids),
+			clearing: Saved::of(&self.clearing, &ids),
+			settled: Saved::of(&self.settled, &ids),
+			settled_batches: Members::of(&self.settled_batches, &batches),
+			converted: Saved::of(&self.converted, &ids),
+			resolved: Members::of(&self.resolved, &ids),
+			idempotency_keys: Members::of(&self.idempotency_keys, &keys),
+			counters: self.counters.clone(),
+			authorizations: self.authorizations.len(),
+			timed_authorizations: self.timed_authorizations.len(),
+		}
+	}
+
+	/// Restores the state captured before the transactions of a group were applied.
+	async fn roll_back(&mut self, undo: GroupUndo) {
+		undo.accounts.restore(&mut *self.accounts.lock().await);
+		undo.unsettled.restore(&mut self.unsettled);
+		undo.manual_holds.restore(&mut self.manual_holds);
+		if let (Some(saved), Some(originals)) = (undo.originals, &mut self.originals) {
+			saved.restore(originals);
+		}
+		undo.global_tx_ids.restore(&mut *self.global_tx_ids.lock().await);
+		undo.applied_at.restore(&mut self.applied_at);
+		undo.clearing.restore(&mut self.clearing);
+		undo.settled.restore(&mut self.settled);
+		undo.settled_batches.restore(&mut self.settled_batches);
+		undo.converted.restore(&mut self.converted);
+		undo.resolved.restore(&mut self.resolved);
+		undo.idempotency_keys.restore(&mut self.idempotency_keys);
+		self.counters = undo.counters;
+		self.authorizations.truncate(undo.authorizations);
+		self.timed_authorizations.truncate(undo.timed_authorizations);
+	}
+
+	/// Appends the entry of the applied transaction `tx` to the ledger, if the ledger is enabled.
+	/// A transfer has an entry for each account.
+	async fn record_ledger_entry(&mut self, sequence: SequenceNumber, tx: &Transaction) {
+		let accounts = self.accounts.lock().await;
+		let Some(ledger) = &mut self.ledger else {
+			return;
+		};
+		let clients = [Some(tx.client_id()), tx.destination()];
+		for (account, txs) in clients.into_iter().flatten().filter_map(|c| accounts.get(c)) {
+			// The recorded amount may differ from the input, e.g. of a capped deposit.
+			let amount = match tx {
+				Transaction::Settlement { id, .. } => self.settled.get(id).cloned(),
+				Transaction::Convert { conversion, .. } => Some(conversion.amount.clone()),
+				_ => tx.amount().map(|amount| {
+					txs.get(&tx.id()).and_then(Transaction::amount).unwrap_or(amount).clone()
+				}),
+			};
+			let mut entry = LedgerEntry::new(sequence, tx.type_name(), tx.id(), amount, account);
+			entry.reason = tx.reason().map(String::from);
+			entry.reference = tx.reference();
+			entry.timestamp = tx.timestamp();
+			if let (Some(conversion), Some(converted)) =
+				(tx.conversion(), self.converted.get(&tx.id()))
+			{
+				let (from, to) = (conversion.from(), conversion.to);
+				entry.conversion = Some(format!(
+					"{} {} -> {converted} {}",
+					conversion.amount, from.iso_alpha_code, to.iso_alpha_code
+				));
+			}
+			let annotations = tx.annotations();
+			entry.memo = annotations.memo;
+			entry.merchant = annotations.merchant;
+			entry.external_reference = annotations.external_reference;
+			ledger.push(entry);
+		}
+	}
+
+	/// Advances the clock to `timestamp`, which never moves backwards.
+	///
+	/// With recurring payments enabled, first applies the payments that became due in the order
+	/// they became due, skipping those whose ids were already applied. With a clearing period, then
+	/// releases the deposits that cleared in the order they cleared, and with an authorization
+	/// window the holds of the authorizations that expired. With interest accrual
+	/// enabled, then accrues interest onto the available balances of the unlocked accounts for each
+	/// period that ended since the previous time. Both are recorded in the ledger and rejects after
+	/// the last input record processed.
+	pub async fn advance_clock(&mut self, timestamp: Timestamp) {
+		let config = self.amount_config();
+		config.scope(self.advance_clock_to(timestamp)).await
+	}
+
+	/// Advances the clock like [`Self::advance_clock`], with the amount configuration already in
+	/// scope.
+	async fn advance_clock_to(&mut self, timestamp: Timestamp) {
+		self.clock = Some(self.clock.map_or(timestamp, |clock| clock.max(timestamp)));
+		let due = self.recurring.as_mut().map(|scheduler| scheduler.due(timestamp));
+		for (_, tx) in due.into_iter().flatten() {
+			self.apply_scheduled(tx).await;
+		}
+		self.clear_deposits(timestamp).await;
+		self.expire_timed_authorizations(timestamp).await;
+		let Some(accrual) = &mut self.interest else {
+			return;
+		};
+		let periods = accrual.advance(timestamp);
+		let mut accounts = self.accounts.lock().await;
+		for start in periods {
+			for client in accounts.keys().copied().sorted().collect_vec() {
+				let (account, _) =
+					accounts.get_mut(&client).expect("client should have an account");
+				if account.locked || account.closed {
+					continue;
+				}
+				let Some(interest) = accrual.interest(&account.available, start) else {
+					continue;
+				};
+				if let Some(originals) = &mut self.originals {
+					originals.entry(client).or_insert_with(|| account.clone());
+				}
+				if let Err(e) = account.adjust_credit(&interest) {
+					warn!("Could not accrue interest {interest:?} to client {client}: {e:?}");
+					continue;
+				}
+				debug!("Accrued interest {interest} to client {client}");
+				self.counters.interest.saturating_add_assign(&interest);
+				if let Some(ledger) = &mut self.ledger {
+					ledger.push(LedgerEntry::accrual(self.last_sequence, interest, account));
+				}
+			}
+		}
+	}
+
+	/// Releases the deposits that cleared by `timestamp` to the available balance, recording them
+	/// in the ledger after the last input record processed. The disputed or charged back portion of
+	/// a deposit stays held, and only the rest is released.
+	async fn clear_deposits(&mut self, timestamp: Timestamp) {
+		let cleared = self
+			.clearing
+			.iter()
+			.filter(|(_, (_, clears_at))| *clears_at <= timestamp)
+			.map(|(id, (client, clears_at))| (*clears_at, *id, *client))
+			.sorted()
+			.collect_vec();
+		let mut accounts = self.accounts.lock().await;
+		for (_, id, client) in cleared {
+			self.clearing.remove(&id);
+			let Some((account, txs)) = accounts.get_mut(&client) else {
+				continue;
+			};
+			let Some(referenced) = txs.get(&id) else {
+				continue;
+			};
+			let (Some(full), Some(state)) = (referenced.amount(), referenced.state()) else {
+				continue;
+			};
+			let charged_back = *state == ChargedBack;
+			let amount = if state.is_disputed() || charged_back {
+				full.minus_checked(referenced.disputed_portion().unwrap_or(full))
+			} else {
+				Ok(full.clone())
+			};
+			// A charged back deposit is only kept in the history until its undisputed part cleared.
+			if charged_back && !self.dispute_policy.reopen_chargebacks {
+				txs.remove(&id);
+			}
+			let amount = match amount {
+				Ok(amount) if amount.value().is_zero() => continue,
+				Ok(amount) => amount,
+				Err(e) => {
+					warn!("Could not clear deposit {id}: {e:?}");
+					continue;
+				},
+			};
+			if let Some(originals) = &mut self.originals {
+				originals.entry(client).or_insert_with(|| account.clone());
+			}
+			if let Err(e) = account.clear(&amount) {
+				warn!("Could not clear deposit {id}: {e:?}");
+				continue;
+			}
+			debug!("Cleared deposit {id} of client {client}");
+			if let Some(ledger) = &mut self.ledger {
+				let entry =
+					LedgerEntry::new(self.last_sequence, "clearing", id, Some(amount), account);
+				ledger.push(entry);
+			}
+		}
+	}
+
+	/// Applies a transaction generated by the processor rather than read from the input, unless its
+	/// id was already applied, recording it like an input record after the last one.
+	async fn apply_scheduled(&mut self, tx: Transaction) {
+		if self.global_tx_ids.lock().await.contains(&tx.id()) {
+			debug!("Skipping already applied scheduled transaction {}", tx.id());
+			return;
+		}
+		let sequence = self.last_sequence;
+		let original = (self.rejects.is_some() || self.ledger.is_some()).then(|| tx.clone());
+		match self.handle_at_current_time(tx).await {
+			Ok(()) => {
+				if let Some(tx) = &original {
+					self.record_ledger_entry(sequence, tx).await;
+				}
+			},
+			Err(e) => {
+				warn!("Could not apply scheduled transaction: {e:?}");
+				if let (Some(rejects), Some(tx)) = (&mut self.rejects, &original) {
+					rejects.push(RejectedRecord::rejected(sequence, tx, &e));
+				}
+			},
+		}
+	}
+
+	/// Releases the holds of the authorizations that were not captured within the configured number
+	/// of input records before `sequence`, removing them from the history.
+	async fn expire_authorizations(&mut self, sequence: SequenceNumber) {
+		let Some(expiry) = self.authorization_expiry else {
+			return;
+		};
+		let mut expired = Vec::new();
+		while let Some(&(authorized, client, id)) = self.authorizations.front() {
+			if sequence - authorized <= expiry {
+				break;
+			}
+			self.authorizations.pop_front();
+			expired.push((client, id));
+		}
+		self.release_authorizations(expired).await;
+	}
+
+	/// Releases the holds of the authorizations with a timestamp that were not captured within the
+	/// configured number of seconds before `timestamp`, removing them from the history.
+	async fn expire_timed_authorizations(&mut self, timestamp: Timestamp) {
+		let Some(window) = self.authorization_window else {
+			return;
+		};
+		let mut expired = Vec::new();
+		while let Some(&(authorized, client, id)) = self.timed_authorizations.front() {
+			if timestamp - authorized <= window {
+				break;
+			}
+			self.timed_authorizations.pop_front();
+			expired.push((client, id));
+		}
+		self.release_authorizations(expired).await;
+	}
+
+	/// Releases the holds of the given authorizations that are still uncaptured, removing them from
+	/// the history.
+	async fn release_authorizations(&mut self, expired: Vec<(ClientId, TransactionId)>) {
+		let mut accounts = self.accounts.lock().await;
+		for (client, id) in expired {
+			let Some((account, txs)) = accounts.get_mut(&client) else {
+				continue;
+			};
+			let Some(Transaction::Authorization { amount, captured: None, .. }) = txs.get(&id)
+			else {
+				continue;
+			};
+			match account.release(amount) {
+				Ok(()) => {
+					debug!("Expired authorization {id} of client {client}");
+					txs.remove(&id);
+					self.counters.expired_authorizations += 1;
+				},
+				Err(e) => warn!("Could not expire authorization {id} of client {client}: {e:?}"),
+			}
+		}
+	}
+
+	/// Writes a checkpoint of the current state, if checkpoints are enabled.
+	async fn checkpoint(&mut self) -> Result<(), ProcessRunError> {
+		let snapshot = self.snapshot().await;
+		match &mut self.checkpoints {
+			Some(checkpoints) => {
+				(checkpoints.write)(&snapshot).map_err(ProcessRunError::CheckpointIo)
+			},
+			None => Ok(()),
+		}
+	}
+
+	/// Returns the effective configuration, counters and build information of this processor,
+	/// for diagnostics.
+	pub async fn info(&self) -> ProcessorInfo {
+		let accounts = self.accounts.lock().await;
+		ProcessorInfo {
+			version: env!("CARGO_PKG_VERSION"),
+			build_hash: option_env!("ENGINE_BUILD_HASH").unwrap_or("unknown"),
+			base_currency: self.amount_config().base_currency,
+			balance_cap: self.balance_cap.clone(),
+			duplicate_window: self.duplicate_detector.as_ref().map(|d| d.window()),
+			counters: ProcessorCounters {
+				records_read: self.last_sequence,
+				..self.counters.clone()
+			},
+			accounts: accounts.len(),
+			locked_accounts: accounts.values().filter(|(account, _)| account.locked).count(),
+			held: accounts.values().map(|(account, _)| &account.held).sum(),
+		}
+	}
+
+	/// Returns the summary statistics of the input processed so far.
+	pub async fn report(&self) -> ProcessingReport {
+		let accounts = self.accounts.lock().await;
+		ProcessingReport {
+			records_read: self.last_sequence,
+			parse_errors: self.counters.parse_errors,
+			applied: self.counters.applied,
+			rejected_by_reason: self.counters.rejected_by_reason.clone(),
+			accounts_created: accounts.len().saturating_sub(self.restored_accounts),
+			accounts_locked: accounts.values().filter(|(account, _)| account.locked).count(),
+			deposited: self.counters.deposited.clone(),
+			withdrawn: self.counters.withdrawn.clone(),
+			refunded: self.counters.refunded.clone(),
+			expired_authorizations: self.counters.expired_authorizations,
+			interest: self.counters.interest.clone(),
+			open_disputes_by_stage: accounts
+				.values()
+				.flat_map(|(_, txs)| txs.values())
+				.filter_map(Transaction::state)
+				.filter(|state| state.is_disputed())
+				.counts_by(TransactionState::name)
+				.into_iter()
+				.map(|(stage, count)| (stage, count as u64))
+				.collect(),
+		}
+	}
+
+	/// Handles a single transaction by applying its effect to the relevant account.
+	///
+	/// A transaction with a timestamp first advances the clock to it, see
+	/// [`Self::advance_clock`].
+	///
+	/// # Arguments
+	///
+	/// * `tx` - The `Transaction` to process.
+	///
+	/// # Errors
+	///
+	/// Returns a `TransactionError` if an error occurs during processing, such as:
+	/// - DuplicateGlobalTransactionId: If the transaction ID is already in the global set.
+	/// - DuplicateIdempotencyKey: If a record with the same idempotency key was applied before.
+	/// - AccountFrozen: If the account associated with the transaction is frozen.
+	/// - InsufficientFunds: If a withdrawal, transfer or chargeback would result in a negative
+	///   balance.
+	/// - IllegalStateChange: If the transaction attempts an invalid state transition, such as
+	///   unlocking an account that is not locked, or one the dispute policy does not allow, or
+	///   refunds a transaction that is not in the okay state, or disputes or reverses a refunded
+	///   one.
+	/// - InvalidTransactionId: If the transaction ID is invalid for the operation, such as a
+	///   dispute of a fee.
+	/// - TransactionNotFound: If a dispute, resolve, chargeback, reopen or refund references a
+	///   non-existent transaction.
+	/// - RefundExceedsOriginal: If the refunds of a transaction would exceed its amount.
+	/// - DisputeExceedsOriginal: If a partial dispute exceeds the amount of the disputed transaction.
+	/// - DisputeWindowExpired: If a dispute references a transaction applied before the dispute
+	///   window.
+	/// - AccountClosed: If the account, or either account of a transfer, is closed.
+	/// - HeldFundsRemaining: If an account is closed while it holds funds.
+	/// - CurrencyMismatch: If a dispute or refund references a transaction in a different currency.
+	pub async fn handle_transaction(&mut self, tx: Transaction) -> Result<(), TransactionError> {
+		let config = self.amount_config();
+		config
+			.scope(async move {
+				if let Some(timestamp) = tx.timestamp() {
+					self.advance_clock_to(timestamp).await;
+				}
+				self.handle_at_current_time(tx).await
+			})
+			.await
+	}
+
+	/// Handles a single transaction at the current time of the clock, see
+	/// [`Self::handle_transaction`].
+	async fn handle_at_current_time(&mut self, tx: Transaction) -> Result<(), TransactionError> {
+		let tx_type = tx.type_name();
+		let disputable = matches!(
+			tx,
+			Transaction::Deposit { .. }
+				| Transaction::Withdrawal { .. }
+				| Transaction::Capture { .. }
+		)
+		.then(|| tx.id());
+		// A retry is skipped if the first submission of its key was applied, and the key is only
+		// kept once it was, so that a rejected submission may be retried.
+		let key = tx.idempotency_key().map(str::to_string);
+		let result = match &key {
+			Some(key) if self.idempotency_keys.contains(key) => Err(DuplicateIdempotencyKey(tx)),
+			_ => self.apply(tx).await,
+		};
+		match &result {
+			Ok(()) => {
+				if let Some(key) = key {
+					self.idempotency_keys.insert(key);
+				}
+				if let (Some(id), Some(now), Some(_)) =
+					(disputable, self.clock, self.dispute_window)
+				{
+					self.applied_at.insert(id, now);
+				}
+				self.counters.applied += 1;
+				*self.counters.applied_by_type.entry(tx_type).or_default() += 1;
+			},
+			Err(e) => {
+				self.counters.rejected += 1;
+				*self.counters.rejected_by_reason.entry(e.code()).or_default() += 1;
+			},
+		}
+		result
+	}
+
+	/// Applies a single transaction to the relevant account, see [`Self::handle_transaction`].
+	async fn apply(&mut self, tx: Transaction) -> Result<(), TransactionError> {
+		debug!("Processing transaction: {:?}", &tx);
+		let mut accounts = self.accounts.lock().await;
+		let mut global_tx_ids = self.global_tx_ids.lock().await;
+
+		let (account, account_txs) = accounts.entry(*tx.client_id()).or_insert_with(|| {
+			(
+				Account::new(*tx.client_id(), Amount::default(), Amount::default(), false),
+				HashMap::new(),
+			)
+		});
+		self.overdraft.configure(account);
+		if let Some(originals) = &mut self.originals {
+			originals.entry(account.client_id).or_insert_with(|| account.clone());
+		}
+		if account.closed {
+			return Err(AccountClosed(tx));
+		}
+
+		// Account operations either succeed or leave the account unchanged, so balances are changed
+		// in place, and the history and global id bookkeeping follow only once they succeeded. Only
+		// transactions of several operations that may fail after the first prepare a copy.
+		// The transaction is only moved into the history once applied, so the success path
+		// neither clones the transaction nor its amount; error paths hand over ownership instead.
+		match tx {
+			Transaction::Deposit { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				// Deposits to a credit account repay the outstanding balance, but cannot exceed it.
+				let credit_cap = (account.kind == AccountKind::Credit).then(Amount::default);
+				let cap =
+					credit_cap.as_ref().or_else(|| self.balance_cap.cap_for(&account.client_id));
+				// Deposits applied while the clock is set are held until they clear. The deposit
+				// and its hold are prepared on a copy, so that a failing hold leaves no deposit.
+				let clears_at =
+					self.clearing_period.zip(self.clock).map(|(period, now)| now + period);
+				let mut uncleared = clears_at.map(|_| account.clone());
+				let prepared = uncleared.as_mut().unwrap_or(&mut *account);
+				let tx = match cap {
+					Some(cap) => {
+						match prepared.deposit_capped(amount, cap, self.balance_cap.policy) {
+							Ok(deposited) => {
+								let mut tx = tx;
+								if let Transaction::Deposit { amount, .. } = &mut tx {
+									if *amount != deposited {
+										warn!("Capped deposit {id} of {amount:?} to {deposited:?}");
+										*amount = deposited;
+									}
+								}
+								tx
+							},
+							Err(e) => return Err((e, tx).into()),
+						}
+					},
+					None => match prepared.deposit(amount) {
+						Ok(()) => tx,
+						Err(e) => return Err((e, tx).into()),
+					},
+				};
+				if let (Some(clears_at), Some(amount)) = (clears_at, tx.amount()) {
+					if let Err(e) = prepared.hold(amount) {
+						return Err((e, tx).into());
+					}
+					self.clearing.insert(id, (prepared.client_id, clears_at));
+				}
+				if let Some(prepared) = uncleared {
+					*account = prepared;
+				}
+				account.activity.deposits += 1;
+				// Counters and settlement batches are kept in the base currency only. As the
+				// transaction is already applied, their totals saturate rather than fail it.
+				if let Some(amount) = tx.amount().filter(|amount| amount.is_base_currency()) {
+					self.counters.deposited.saturating_add_assign(amount);
+					self.unsettled
+						.entry(account.client_id)
+						.or_default()
+						.credits
+						.saturating_add_assign(amount);
+				}
+				account_txs.insert(id, tx);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			Transaction::Withdrawal { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.withdraw(amount) {
+					return Err((e, tx).into());
+				}
+				account.activity.withdrawals += 1;
+				if amount.is_base_currency() {
+					self.counters.withdrawn.saturating_add_assign(amount);
+					self.unsettled
+						.entry(account.client_id)
+						.or_default()
+						.debits
+						.saturating_add_assign(amount);
+				}
+				account_txs.insert(id, tx);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			Transaction::Fee { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.withdraw(amount) {
+					return Err((e, tx).into());
+				}
+				if amount.is_base_currency() {
+					self.unsettled
+						.entry(account.client_id)
+						.or_default()
+						.debits
+						.saturating_add_assign(amount);
+				}
+				account_txs.insert(id, tx);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			Transaction::Refund { ref amount, id, original, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				let Some(refunded) = account_txs.get(&original) else {
+					return Err(TransactionNotFound(tx));
+				};
+				let Some(original_amount) =
+					refunded.amount().filter(|_| refunded.state().is_some())
+				else {
+					return Err(InvalidTransactionId(tx));
+				};
+				// Disputed, reversed and charged back funds were held or returned already.
+				if refunded.state() != Some(&Okay) {
+					return Err(IllegalStateChange(tx));
+				}
+				if original_amount.value().currency() != amount.value().currency() {
+					return Err(CurrencyMismatch(tx));
+				}
+				let mut refunds = Amount::zero(amount.value().currency());
+				if refunds_of(account_txs, original)
+					.chain([amount])
+					.try_for_each(|refund| refunds.add_assign(refund))
+					.is_err() || &refunds > original_amount
+				{
+					return Err(RefundExceedsOriginal(tx));
+				}
+				if let Err(e) = account.deposit(amount) {
+					return Err((e, tx).into());
+				}
+				if amount.is_base_currency() {
+					self.counters.refunded.saturating_add_assign(amount);
+					self.unsettled
+						.entry(account.client_id)
+						.or_default()
+						.credits
+						.saturating_add_assign(amount);
+				}
+				account_txs.insert(id, tx);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			Transaction::Authorization { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.hold(amount) {
+					return Err((e, tx).into());
+				}
+				let timestamp = tx.timestamp();
+				account_txs.insert(id, tx);
+				global_tx_ids.insert(id);
+				match (timestamp, self.authorization_window) {
+					(Some(timestamp), Some(_)) => {
+						self.timed_authorizations.push_back((timestamp, account.client_id, id));
+					},
+					_ if self.authorization_expiry.is_some() => {
+						self.authorizations.push_back((self.last_sequence, account.client_id, id));
+					},
+					_ => {},
+				}
+				Ok(())
+			},
+			// The authorization stays in the history, captured, so that it can be disputed and
+			// refunded like a withdrawal.
+			Transaction::Capture { id, .. } => {
+				let Some(authorization) = account_txs.get_mut(&id) else {
+					return Err(TransactionNotFound(tx));
+				};
+				let Transaction::Authorization { amount, captured: captured @ None, .. } =
+					authorization
+				else {
+					return Err(InvalidTransactionId(tx));
+				};
+				if let Err(e) = account.capture(amount) {
+					return Err((e, tx).into());
+				}
+				*captured = Some(Okay);
+				let amount = amount.clone();
+				account.activity.withdrawals += 1;
+				if amount.is_base_currency() {
+					self.counters.withdrawn.saturating_add_assign(&amount);
+					self.unsettled
+						.entry(account.client_id)
+						.or_default()
+						.debits
+						.saturating_add_assign(&amount);
+				}
+				Ok(())
+			},
+
+			Transaction::CreditAdjustment { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.adjust_credit(amount) {
+					return Err((e, tx).into());
+				}
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			Transaction::DebitAdjustment { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.adjust_debit(amount) {
+					return Err((e, tx).into());
+				}
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			Transaction::Settlement { id, batch: batch_id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if self.settled_batches.contains(&(account.client_id, batch_id)) {
+					return Err(BatchAlreadySettled(tx));
+				}
+				let batch = self.unsettled.get(&account.client_id).cloned().unwrap_or_default();
+				// A collection returns funds the batch already debited, so the balance cap does not
+				// apply to it.
+				let (result, amount) = match batch.net() {
+					NetAmount::Payout(amount) => (account.withdraw(&amount), amount),
+					NetAmount::Collection(amount) => (account.deposit(&amount), amount),
+				};
+				if let Err(e) = result {
+					return Err((e, tx).into());
+				}
+				self.unsettled.remove(&account.client_id);
+				self.settled.insert(id, amount);
+				self.settled_batches.insert((account.client_id, batch_id));
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			Transaction::CloseAccount { id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.close() {
+					return Err((e, tx).into());
+				}
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			Transaction::Hold { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.hold(amount) {
+					return Err((e, tx).into());
+				}
+				self.manual_holds
+					.entry(account.client_id)
+					.or_default()
+					.saturating_add_assign(amount);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			// Only manually held funds may be released, not those of disputes or authorizations.
+			Transaction::Release { ref amount, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				let held = self.manual_holds.get(&account.client_id).cloned().unwrap_or_default();
+				let Ok(remaining) = held.minus_checked(amount) else {
+					return Err(ReleaseExceedsHold(tx));
+				};
+				if let Err(e) = account.release(amount) {
+					return Err((e, tx).into());
+				}
+				self.manual_holds.insert(account.client_id, remaining);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			Transaction::Unlock { id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				if let Err(e) = account.unlock() {
+					return Err((e, tx).into());
+				}
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+
+			// A disputed withdrawal is provisionally credited to the held balance rather than held
+			// from the available balance, which it already left.
+			Transaction::Dispute { id, .. } => {
+				// The refunded part of a transaction was returned already, and cannot be charged
+				// back as well.
+				if refunds_of(account_txs, id).next().is_some() {
+					return Err(IllegalStateChange(tx));
+				}
+				// A dispute that does not state a currency disputes the transaction in its own.
+				let currency = account_txs.get(&id).and_then(Transaction::currency);
+				if let (Some(currency), Some(stated)) = (currency, tx.currency()) {
+					if stated != currency {
+						return Err(CurrencyMismatch(tx));
+					}
+				}
+				if let (Some(window), Some(now), Some(applied)) =
+					(self.dispute_window, self.clock, self.applied_at.get(&id))
+				{
+					if now - applied > window {
+						return Err(DisputeWindowExpired(tx));
+					}
+				}
+				// An uncleared deposit is already held, and clearing then releases only the rest.
+				let operation: Operation = match account_txs.get(&id) {
+					Some(referenced) if referenced.is_withdrawal() => Account::hold_withdrawal,
+					_ if self.clearing.contains_key(&id) => |_, _| Ok(()),
+					_ => Account::hold,
+				};
+				transition(account, account_txs, id, tx, Disputed, operation)
+			},
+			// A resolved deposit that has not cleared yet stays held until it clears.
+			Transaction::Resolve { id, .. } => {
+				let operation: Operation = match account_txs.get(&id) {
+					Some(referenced) if referenced.is_withdrawal() => Account::release_withdrawal,
+					_ if self.clearing.contains_key(&id) => |_, _| Ok(()),
+					_ => Account::release,
+				};
+				transition(account, account_txs, id, tx, Okay, operation)?;
+				self.resolved.insert(id);
+				Ok(())
+			},
+			// Escalations only advance the dispute, whose funds remain held.
+			Transaction::Representment { id, .. } => {
+				transition(account, account_txs, id, tx, Representment, |_, _| Ok(()))
+			},
+			Transaction::PreArbitration { id, .. } => {
+				transition(account, account_txs, id, tx, PreArbitration, |_, _| Ok(()))
+			},
+			Transaction::Chargeback { id, .. } => {
+				if !self.dispute_policy.chargeback_after_redispute && self.resolved.contains(&id) {
+					return Err(IllegalStateChange(tx));
+				}
+				let operation = match account_txs.get(&id) {
+					Some(referenced) if referenced.is_withdrawal() => {
+						Account::chargeback_withdrawal
+					},
+					_ => Account::chargeback,
+				};
+				let returned = returned_amount(account_txs.get(&id), true);
+				transition(account, account_txs, id, tx, ChargedBack, operation)?;
+				if let Some((amount, withdrawal)) = returned {
+					let batch = self.unsettled.entry(account.client_id).or_default();
+					batch.add_return(&amount, withdrawal);
+				}
+				// Charged back transactions are only kept in the history if they may be re-opened,
+				// or until the rest of an uncleared deposit clears.
+				if !self.dispute_policy.reopen_chargebacks && !self.clearing.contains_key(&id) {
+					account_txs.remove(&id);
+				}
+				Ok(())
+			},
+			Transaction::Reopen { id, .. } => {
+				if !self.dispute_policy.reopen_chargebacks {
+					return Err(IllegalStateChange(tx));
+				}
+				let Some(referenced) = account_txs.get_mut(&id) else {
+					return Err(TransactionNotFound(tx));
+				};
+				let (Some(full), Some(ChargedBack)) = (referenced.amount(), referenced.state())
+				else {
+					return Err(IllegalStateChange(tx));
+				};
+				// The re-opened transaction is known to be charged back, so once the balances changed
+				// neither its state change nor lifting the lock can fail.
+				let amount = referenced.disputed_portion().unwrap_or(full).clone();
+				if referenced.is_withdrawal() {
+					if let Err(e) = account.reopen_withdrawal(&amount) {
+						return Err((e, tx).into());
+					}
+				} else {
+					if let Err(e) = account.reopen(&amount) {
+						return Err((e, tx).into());
+					}
+				}
+				referenced.reopen_chargeback()?;
+				// Undoing the chargeback that locked the account also lifts its lock.
+				if account.activity.locked_by == Some(id) {
+					account.unlock().map_err(|e| (e, tx))?;
+				}
+				account.activity.open_disputes += 1;
+				Ok(())
+			},
+			Transaction::Reversal { id, .. } => {
+				// As for disputes, the refunded part of a transaction cannot be returned again.
+				if refunds_of(account_txs, id).next().is_some() {
+					return Err(IllegalStateChange(tx));
+				}
+				// A deposit that has not cleared yet is taken from the held balance instead.
+				let uncleared = self.clearing.contains_key(&id);
+				let operation: Operation = match account_txs.get(&id) {
+					Some(referenced) if referenced.is_withdrawal() => Account::deposit,
+					_ if uncleared => Account::capture,
+					_ => Account::withdraw,
+				};
+				let returned = returned_amount(account_txs.get(&id), false);
+				transition(account, account_txs, id, tx, Reversed, operation)?;
+				if let Some((amount, withdrawal)) = returned {
+					let batch = self.unsettled.entry(account.client_id).or_default();
+					batch.add_return(&amount, withdrawal);
+				}
+				if uncleared {
+					self.clearing.remove(&id);
+				}
+				Ok(())
+			},
+			Transaction::Convert { ref conversion, id, .. } => {
+				if global_tx_ids.contains(&id) {
+					return Err(DuplicateGlobalTransactionId(tx));
+				}
+				let converted = match self.fx.convert(conversion) {
+					Ok(converted) => converted,
+					Err(FxError::RateUnavailable) => return Err(FxRateUnavailable(tx)),
+					Err(FxError::SlippageExceeded) => return Err(FxSlippageExceeded(tx)),
+					Err(FxError::Overflow) => {
+						return Err(InternalError(tx, "Converted amount out of range".into()))
+					},
+				};
+				// The balance cap is checked on the converted balances, which are prepared on a copy.
+				let mut prepared = account.clone();
+				if let Err(e) = prepared.convert(&conversion.amount, &converted) {
+					return Err((e, tx).into());
+				}
+				// Conversions into the base currency are capped like deposits, but never partially.
+				let credit_cap = (account.kind == AccountKind::Credit).then(Amount::default);
+				let cap =
+					credit_cap.as_ref().or_else(|| self.balance_cap.cap_for(&account.client_id));
+				if conversion.to == base_currency()
+					&& cap.is_some_and(|cap| {
+						!prepared.checked_total().is_ok_and(|total| total.value() <= cap.value())
+					}) {
+					return Err(BalanceCapExceeded(tx));
+				}
+				*account = prepared;
+				self.converted.insert(id, converted);
+				global_tx_ids.insert(id);
+				Ok(())
+			},
+			Transaction::Transfer { to, .. } => {
+				if let Some(originals) = &mut self.originals {
+					originals.entry(to).or_insert_with(|| match accounts.get(&to) {
+						Some((account, _)) => account.clone(),
+						None => Account::new(to, Amount::default(), Amount::default(), false),
+					});
+				}
+				let limits = (&self.balance_cap, &self.overdraft);
+				transfer(&mut accounts, &mut global_tx_ids, limits, tx)
+			},
+		}
+	}
+
+	/// Returns the full state of this processor, every account with its recorded transactions.
+	pub async fn snapshot(&self) -> ProcessorSnapshot {
+		let accounts = self.accounts.lock().await;
+		let recorded: HashSet<&TransactionId> =
+			accounts.values().flat_map(|(_, txs)| txs.keys()).collect();
+		let retired_tx_ids = self
+			.global_tx_ids
+			.lock()
+			.await
+			.iter()
+			.filter(|id| !recorded.contains(id))
+			.copied()
+			.sorted()
+			.collect();
+		let accounts = accounts
+			.values()
+			.map(|(account, txs)| {
+				let client = account.client_id;
+				let mut snapshot = AccountSnapshot::new(
+					account,
+					txs.values(),
+					&self.applied_at,
+					&self.clearing,
+					&self.resolved,
+					self.unsettled.get(&client),
+				);
+				snapshot.manual_hold = self.manual_holds.get(&client).cloned();
+				snapshot.settled_batches = self
+					.settled_batches
+					.iter()
+					.filter(|(settled, _)| *settled == client)
+					.map(|(_, batch)| *batch)
+					.sorted()
+					.collect();
+				snapshot
+			})
+			.sorted_by_key(|account| account.client)
+			.collect();
+		let idempotency_keys = self.idempotency_keys.iter().cloned().sorted().collect();
+		ProcessorSnapshot {
+			sequence: self.last_sequence,
+			offset: self.last_offset,
+			accounts,
+			retired_tx_ids,
+			idempotency_keys,
+		}
+	}
+
+	/// Retrieves all accounts resolved from the input transactions.
+	pub async fn get_accounts(&self) -> Vec<Account> {
+		let accounts = self.accounts.lock().await;
+		accounts.values().map(|a| a.0.clone()).collect_vec()
+	}
+
+	/// Writes the accounts to `sink` in batches of [`SINK_BATCH_SIZE`] and finishes it.
+	pub async fn write_accounts(&self, sink: impl AccountSink) -> std::io::Result<()> {
+		self.write_accounts_where(sink, |_| true).await
+	}
+
+	/// Writes only the accounts whose balances or locked status changed during this run to `sink`,
+	/// like [`Self::write_accounts`]. Without [`Self::with_change_tracking`] no account is written.
+	pub async fn write_changed_accounts(&self, sink: impl AccountSink) -> std::io::Result<()> {
+		let Some(originals) = &self.originals else {
+			return self.write_accounts_where(sink, |_| false).await;
+		};
+		self.write_accounts_where(sink, |account| {
+			originals.get(&account.client_id).is_some_and(|original| {
+				original.available != account.available
+					|| original.held != account.held
+					|| original.balances != account.balances
+					|| original.locked != account.locked
+					|| original.closed != account.closed
+			})
+		})
+		.await
+	}
+
+	async fn write_accounts_where(
+		&self,
+		mut sink: impl AccountSink,
+		filter: impl Fn(&Account) -> bool,
+	) -> std::io::Result<()> {
+		let accounts = self.accounts.lock().await;
+		let selected = accounts.values().map(|(account, _)| account).filter(|a| filter(a));
+		let config = self.amount_config();
+		config
+			.scope(async move {
+				for batch in &selected.chunks(SINK_BATCH_SIZE) {
+					let batch = batch.cloned().collect_vec();
+					sink.write(&batch).await?;
+				}
+				sink.finish().await
+			})
+			.await
+	}
+
+	/// Passes the accounts to `visit` without copying them, so that large results can be written
+	/// out one account at a time instead of first being collected. The accounts stay locked until
+	/// `visit` returns, which runs with the amount configuration of the processor in scope.
+	pub async fn visit_accounts<R>(
+		&self,
+		visit: impl FnOnce(&mut dyn Iterator<Item = &Account>) -> R,
+	) -> R {
+		let accounts = self.accounts.lock().await;
+		self.amount_config()
+			.sync_scope(|| visit(&mut accounts.values().map(|(account, _)| account)))
+	}
+}
+/// Applies `operation` with the amount of the referenced transaction `id` to the account and
+/// validates the referenced transaction's change to `state`. Only if both succeed are the account
+/// and the transaction history updated, a charged back transaction leaving the history.
+fn transition(
+	account: &mut Account,
+	account_txs: &mut HashMap<TransactionId, Transaction>,
+	id: TransactionId,
+	tx: Transaction,
+	state: TransactionState,
+	operation: Operation,
+) -> Result<(), TransactionError> {
+	let Some(referenced) = account_txs.get_mut(&id) else {
+		return Err(TransactionNotFound(tx));
+	};
+	let (Some(full), Some(_)) = (referenced.amount(), referenced.state()) else {
+		return Err(InvalidTransactionId(referenced.clone()));
+	};
+	// A partial dispute holds only its portion, which a resolve or chargeback then moves.
+	let portion = match &tx {
+		Transaction::Dispute { amount: Some(portion), .. } if portion.value() > full.value() => {
+			return Err(DisputeExceedsOriginal(tx));
+		},
+		Transaction::Dispute { amount: Some(portion), .. } if portion != full => Some(portion),
+		Transaction::Dispute { .. } | Transaction::Reversal { .. } => None,
+		_ => referenced.disputed_portion(),
+	};
+	let amount = portion.unwrap_or(full).clone();
+	let portion = portion.cloned();
+	// The operation only changes the balances in the currency of the amount and the lock, which
+	// are restored if the state change turns out to be illegal.
+	let code = amount.value().currency().iso_alpha_code;
+	let before =
+		(account.available.clone(), account.held.clone(), account.balances.get(code).cloned());
+	let locked = account.locked;
+	operation(account, &amount).map_err(|e| (e, referenced.clone()))?;
+	if let Err(e) = referenced.check_state_change(state) {
+		let (available, held, balance) = before;
+		(account.available, account.held, account.locked) = (available, held, locked);
+		match balance {
+			Some(balance) => account.balances.insert(code, balance),
+			None => account.balances.remove(code),
+		};
+		return Err(e);
+	}
+	referenced.change_state(state)?;
+	// A charged back transaction keeps its portion, which a reopen holds again.
+	if state != ChargedBack {
+		referenced.set_disputed_portion(if state.is_disputed() { portion } else { None });
+	}
+	let activity = &mut account.activity;
+	match state {
+		Disputed => activity.open_disputes += 1,
+		Representment | PreArbitration => {},
+		Okay => activity.open_disputes = activity.open_disputes.saturating_sub(1),
+		ChargedBack => {
+			activity.open_disputes = activity.open_disputes.saturating_sub(1);
+			activity.locked_by = Some(id);
+		},
+		Reversed => {},
+	}
+	Ok(())
+}
+/// Returns the amount of `original` that a reversal, or a chargeback of its disputed portion if
+/// `disputed`, returns to or from the unsettled batch, and whether `original` is a withdrawal.
+/// Batches are kept in the base currency only.
+fn returned_amount(original: Option<&Transaction>, disputed: bool) -> Option<(Amount, bool)> {
+	let original = original?;
+	let full = original.amount()?;
+	let amount = if disputed { original.disputed_portion().unwrap_or(full) } else { full };
+	let withdrawal = original.is_withdrawal();
+	amount.is_base_currency().then(|| (amount.clone(), withdrawal))
+}
+
+/// Returns the amounts of the refunds of the transaction `original`.
+fn refunds_of(
+	account_txs: &HashMap<TransactionId, Transaction>,
+	original: TransactionId,
+) -> impl Iterator<Item = &Amount> {
+	account_txs.values().filter_map(move |earlier| match earlier {
+		Transaction::Refund { amount, original: o, .. } if *o == original => Some(amount),
+		_ => None,
+	})
+}
+
+/// Sets the value of `key` to `value`, removing it if `value` is `None`.
+fn restore<K: Eq + Hash, V>(map: &mut HashMap<K, V>, key: K, value: Option<V>) {
+	match value {
+		Some(value) => map.insert(key, value),
+		None => map.remove(&key),
+	};
+}
+
+/// Moves the amount of a transfer from the available balance of its source account to that of its
+/// destination account, which is created if needed. Both accounts are prepared on copies and only
+/// updated if the withdrawal and the deposit succeed. A balance cap of the destination, or the
+/// outstanding balance of a destination credit account, rejects the whole transfer, and so does a
+/// destination that is the source itself. Transfers take their id but are not recorded in the
+/// history, as they cannot be disputed.
+fn transfer(
+	accounts: &mut Accounts,
+	global_tx_ids: &mut HashSet<TransactionId>,
+	(balance_cap, overdraft): (&BalanceCap, &Overdraft),
+	tx: Transaction,
+) -> Result<(), TransactionError> {
+	let Transaction::Transfer { ref amount, id, client_id, to, .. } = tx else {
+		return Err(InternalError(tx, "Not a transfer".into()));
+	};
+	if global_tx_ids.contains(&id) {
+		return Err(DuplicateGlobalTransactionId(tx));
+	}
+	if client_id == to {
+		return Err(SelfTransfer(tx));
+	}
+	let prepare = |client| match accounts.get(&client) {
+		Some((account, _)) => account.clone(),
+		None => Account::new(client, Amount::default(), Amount::default(), false),
+	};
+	let (mut source, mut destination) = (prepare(client_id), prepare(to));
+	if source.closed || destination.closed {
+		return Err(AccountClosed(tx));
+	}
+	overdraft.configure(&mut source);
+	overdraft.configure(&mut destination);
+	if let Err(e) = source.withdraw(amount) {
+		return Err((e, tx).into());
+	}
+	let credit_cap = (destination.kind == AccountKind::Credit).then(Amount::default);
+	let deposited = match credit_cap.as_ref().or_else(|| balance_cap.cap_for(&to)) {
+		Some(cap) => destination.deposit_capped(amount, cap, CapPolicy::Reject).map(|_| ()),
+		None => destination.deposit(amount),
+	};
+	if let Err(e) = deposited {
+		return Err((e, tx).into());
+	}
+
+	for account in [source, destination] {
+		let txs = accounts.remove(&account.client_id).map(|(_, txs)| txs).unwrap_or_default();
+		accounts.insert(account.client_id, (account, txs));
+	}
+	global_tx_ids.insert(id);
+	Ok(())
+}
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::pin::Pin;
+	use std::task::{Context, Poll};
+
+	use itertools::Itertools;
+	use log::error;
+	use rust_decimal::Decimal;
+	use tempfile::NamedTempFile;
+
+	use domain::account::{Account, AccountActivity, CapPolicy};
+	use domain::amount::Amount;
+	use domain::config::{find_currency, AmountConfig};
+	use domain::output::AccountSerializer;
+	use domain::sink::CsvSink;
+	use domain::source::{CsvSource, SourceError, StreamSource};
+	use domain::transaction::TransactionError::{
+		BalanceCapExceeded, BatchAlreadySettled, SelfTransfer,
+	};
+	use domain::transaction::{AsyncRead, File};
+	use domain::transaction::{CsvFormat, Transaction};
+
+	use crate::fx::RateTable;
+	use crate::info::ProcessorCounters;
+	use crate::interest::{InterestAccrual, RateSchedule};
+	use crate::limits::{BalanceCap, Overdraft};
+	use crate::policy::DisputePolicy;
+	use crate::processor::{
+		ProcessRunError, TransactionProcessor, TransactionProcessorError, SINK_BATCH_SIZE,
+	};
+	use crate::recurring::{RecurringPayment, Scheduler};
+	use crate::snapshot::{ProcessorSnapshot, RecordedType};
+
+	struct TestTransactionsCsvBuilder<'a> {
+		temp_file: NamedTempFile,
+		transactions: Vec<Vec<&'a str>>,
+	}
+
+	const TYPE: &str = "type";
+	const CLIENT: &str = "client";
+	const TX: &str = "tx";
+	const AMOUNT: &str = "amount";
+	const DEPOSIT: &str = "deposit";
+	const WITHDRAWAL: &str = "withdrawal";
+	const DISPUTE: &str = "dispute";
+	const RESOLVE: &str = "resolve";
+	const CHARGEBACK: &str = "chargeback";
+	const EMPTY: &str = "";
+
+	impl<'a> TestTransactionsCsvBuilder<'a> {
+		fn new() -> Self {
+			Self {
+				temp_file: NamedTempFile::new().unwrap(),
+				transactions: vec![vec![TYPE, CLIENT, TX, AMOUNT]],
+			}
+		}
+		fn deposit(mut self, client_id: &'a str, tx_id: &'a str, amount: &'a str) -> Self {
+			self.transactions.push(vec![DEPOSIT, client_id, tx_id, amount]);
+			self
+		}
+		fn withdrawal(mut self, client_id: &'a str, tx_id: &'a str, amount: &'a str) -> Self {
+			self.transactions.push(vec![WITHDRAWAL, client_id, tx_id, amount]);
+			self
+		}
+		fn dispute(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
+			self.transactions.push(vec![DISPUTE, client_id, tx_id, EMPTY]);
+			self
+		}
+		fn resolve(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
+			self.transactions.push(vec![RESOLVE, client_id, tx_id, EMPTY]);
+			self
+		}
+		fn chargeback(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
+			self.transactions.push(vec![CHARGEBACK, client_id, tx_id, EMPTY]);
+			self
+		}
+
+		async fn write(self) -> Self {
+			tokio::fs::write(
+				self.temp_file.path(),
+				self.transactions
+					.iter()
+					.map(|row| row.join(","))
+					.collect::<Vec<String>>()
+					.join("\n"),
+			)
+			.await
+			.unwrap();
+			self
+		}
+
+		async fn reader(&self) -> File {
+			File::open(self.temp_file.path()).await.unwrap()
+		}
+	}
+
+	fn amount(value: &str) -> Amount {
+		Amount::try_from(value).unwrap()
+	}
+
+	fn error_handler(e: TransactionProcessorError) {
+		error!("{e:?}");
+	}
+
+	/// A reader whose every read fails, simulating an I/O failure of the transaction source.
+	struct FailingReader;
+
+	impl AsyncRead for FailingReader {
+		fn poll_read(
+			self: Pin<&mut Self>,
+			_cx: &mut Context<'_>,
+			_buf: &mut [u8],
+		) -> Poll<std::io::Result<usize>> {
+			Poll::Ready(Err(std::io::Error::other("disk on fire")))
+		}
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_fails_on_source_io_error() {
+		let result = TransactionProcessor::process_transactions(FailingReader, error_handler).await;
+
+		assert!(matches!(result, Err(ProcessRunError::SourceIo(_))));
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_simple() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "1")
+			.deposit("1", "3", "1")
+			.withdrawal("1", "4", "1")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let (accounts, report) =
+			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+
+		assert_eq!(accounts.len(), 1);
+		assert_eq!((report.records_read, report.applied, report.accounts_created), (4, 4, 1));
+		assert_eq!((&report.deposited, &report.withdrawn), (&amount("3"), &amount("1")));
+
+		let account = &accounts[0];
+		assert_eq!(account.client_id, 1);
+		assert_eq!(account.available, amount("2"));
+		assert_eq!(account.held, amount("0"));
+		assert_eq!(account.total(), amount("2"));
+		assert!(!account.locked);
+	}
+	#[tokio::test]
+	async fn test_process_transactions_with_disputes() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "1")
+			.deposit("1", "3", "1")
+			.withdrawal("1", "4", "1")
+			.dispute("1", "3")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let (accounts, _) =
+			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+
+		assert_eq!(accounts.len(), 1);
+
+		let account = &accounts[0];
+		assert_eq!(account.client_id, 1);
+		assert_eq!(account.available, amount("1"));
+		assert_eq!(account.held, amount("1"));
+		assert_eq!(account.total(), amount("2"));
+		assert!(!account.locked);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_with_dispute_and_resolve() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "1")
+			.deposit("1", "3", "1")
+			.withdrawal("1", "4", "1")
+			.dispute("1", "3")
+			.resolve("1", "3")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let (accounts, _) =
+			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+
+		assert_eq!(accounts.len(), 1);
+
+		let account = &accounts[0];
+		assert_eq!(account.client_id, 1);
+		assert_eq!(account.available, amount("2"));
+		assert_eq!(account.held, amount("0"));
+		assert_eq!(account.total(), amount("2"));
+		assert!(!account.locked);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_with_dispute_and_chargeback() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "1")
+			.deposit("1", "3", "1")
+			.withdrawal("1", "4", "1")
+			.dispute("1", "3")
+			.resolve("1", "3")
+			.dispute("1", "3")
+			.chargeback("1", "3")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let (accounts, _) =
+			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+
+		assert_eq!(accounts.len(), 1);
+
+		let account = &accounts[0];
+		assert_eq!(account.client_id, 1);
+		assert_eq!(account.available, amount("1"));
+		assert_eq!(account.held, amount("0"));
+		assert_eq!(account.total(), amount("1"));
+		assert!(account.locked);
+		assert_eq!(
+			account.activity,
+			AccountActivity { deposits: 3, withdrawals: 1, open_disputes: 0, locked_by: Some(3) }
+		);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_with_dispute_and_resolve_and_further_dispute_and_chargeback()
+	{
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "1")
+			.deposit("1", "3", "1")
+			.withdrawal("1", "4", "1")
+			.dispute("1", "3")
+			.chargeback("1", "3")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let (accounts, _) =
+			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+
+		assert_eq!(accounts.len(), 1);
+
+		let account = &accounts[0];
+		assert_eq!(account.client_id, 1);
+		assert_eq!(account.available, amount("1"));
+		assert_eq!(account.held, amount("0"));
+		assert_eq!(account.total(), amount("1"));
+		assert!(account.locked);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_rejected_dispute_leaves_account_unchanged() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "1")
+			.deposit("1", "2", "2")
+			.dispute("1", "1")
+			.dispute("1", "1")
+			.resolve("1", "2")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let errors = std::sync::Mutex::new(Vec::new());
+		let mut processor = TransactionProcessor::default();
+		processor.process(reader, |e| errors.lock().unwrap().push(e)).await.unwrap();
+
+		let account = &processor.get_accounts().await[0];
+		assert_eq!(account.available, amount("2"));
+		assert_eq!(account.held, amount("1"));
+		assert_eq!(errors.lock().unwrap().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_process_multiple_readers_into_shared_state() {
+		enable_debug_logs();
+
+		let first_csv = TestTransactionsCsvBuilder::new().deposit("1", "1", "2").write().await;
+		let second_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "2", "1")
+			.dispute("1", "1")
+			.write()
+			.await;
+
+		let mut processor = TransactionProcessor::default();
+		processor.process(first_csv.reader().await, error_handler).await.unwrap();
+		processor.process(second_csv.reader().await, error_handler).await.unwrap();
+
+		assert_eq!(processor.last_sequence(), 3);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!(account.available, amount("1"));
+		assert_eq!(account.held, amount("2"));
+	}
+
+	#[tokio::test]
+	async fn test_process_stream_source() {
+		enable_debug_logs();
+
+		let source = StreamSource(tokio_stream::iter([
+			Ok(Transaction::deposit(1, amount("2"), 1)),
+			Err(SourceError::InvalidRecord("unreadable".into())),
+			Ok(Transaction::withdrawal(2, amount("0.5"), 1)),
+		]));
+		let mut processor = TransactionProcessor::default();
+		processor.process(source, error_handler).await.unwrap();
+
+		assert_eq!(processor.info().await.counters.parse_errors, 1);
+		assert_eq!(processor.get_accounts().await[0].available, amount("1.5"));
+	}
+
+	#[tokio::test]
+	async fn test_visit_accounts() {
+		let source = StreamSource(tokio_stream::iter([
+			Ok::<_, SourceError>(Transaction::deposit(1, amount("2"), 1)),
+			Ok(Transaction::deposit(2, amount("3"), 2)),
+		]));
+		let mut processor = TransactionProcessor::default();
+		processor.process(source, error_handler).await.unwrap();
+
+		let clients = processor
+			.visit_accounts(|accounts| {
+				accounts.map(|account| account.client_id).sorted().collect_vec()
+			})
+			.await;
+		assert_eq!(clients, vec![1, 2]);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_into_sink() {
+		let source =
+			StreamSource(tokio_stream::iter((1..=SINK_BATCH_SIZE as i16 + 1).map(|client| {
+				Ok::<_, SourceError>(Transaction::deposit(client.into(), amount("1"), client))
+			})));
+		let mut accounts = Vec::new();
+		TransactionProcessor::process_transactions_into(source, error_handler, &mut accounts)
+			.await
+			.unwrap();
+
+		assert_eq!(accounts.len(), SINK_BATCH_SIZE + 1);
+	}
+
+	#[tokio::test]
+	async fn test_process_reports_suspected_duplicates() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "5")
+			.deposit("1", "2", "5")
+			.withdrawal("1", "3", "1")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let mut processor = TransactionProcessor::default().with_duplicate_detector(1);
+		processor.process(reader, error_handler).await.unwrap();
+
+		assert_eq!(processor.last_sequence(), 3);
+		let duplicates = processor.suspected_duplicates().unwrap();
+		assert_eq!(duplicates.len(), 1);
+		assert_eq!((duplicates[0].tx_id, duplicates[0].previous_tx_id), (2, 1));
+
+		let account = &processor.get_accounts().await[0];
+		assert_eq!(account.available, amount("9"));
+	}
+
+	#[tokio::test]
+	async fn test_process_records_ledger() {
+		enable_debug_logs();
+
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "5")
+			.withdrawal("1", "2", "9")
+			.dispute("1", "1")
+			.write()
+			.await;
+
+		let reader = transactions_csv.reader().await;
+		let mut processor = TransactionProcessor::default().with_ledger();
+		processor.process(reader, error_handler).await.unwrap();
+
+		let ledger = processor.ledger().unwrap();
+		assert_eq!(ledger.len(), 2);
+		assert_eq!((ledger[0].sequence, ledger[0].tx_type), (1, "deposit"));
+		assert_eq!(ledger[0].amount, Some(amount("5")));
+		assert_eq!((ledger[1].sequence, ledger[1].tx_type), (3, "dispute"));
+		assert_eq!((&ledger[1].available, &ledger[1].held), (&amount("0"), &amount("5")));
+	}
+
+	#[tokio::test]
+	async fn test_process_transfers() {
+		let input = "type,client,tx,amount,to
+deposit,1,1,10,
+deposit,3,2,1,
+dispute,3,2,,
+chargeback,3,2,,
+transfer,1,3,4,2
+transfer,1,4,7,2
+transfer,1,3,1,2
+transfer,1,5,1,3
+dispute,1,3,,";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("insufficient_funds", Some(4)),
+				("duplicate_tx", Some(3)),
+				("account_frozen", Some(5)),
+				("unknown_tx", Some(3)),
+			]
+		);
+		let accounts = processor.get_accounts().await;
+		let balance = |client| {
+			let account = accounts.iter().find(|a| a.client_id == client).unwrap();
+			(account.available.clone(), account.held.clone())
+		};
+		assert_eq!(balance(1), (amount("6"), amount("0")));
+		assert_eq!(balance(2), (amount("4"), amount("0")));
+		assert_eq!(balance(3), (amount("0"), amount("0")));
+
+		let transfers = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.filter(|entry| entry.tx_type == "transfer")
+			.map(|entry| (entry.client, entry.tx, entry.available.clone()))
+			.collect_vec();
+		assert_eq!(transfers, vec![(1, Some(3), amount("6")), (2, Some(3), amount("4"))]);
+	}
+
+	#[tokio::test]
+	async fn test_process_self_transfer() {
+		let mut processor = TransactionProcessor::default();
+		processor
+			.handle_transaction(Transaction::deposit(1, amount("10"), 1))
+			.await
+			.unwrap();
+
+		let result =
+			processor.handle_transaction(Transaction::transfer(2, amount("4"), 1, 1)).await;
+		assert!(matches!(result, Err(SelfTransfer(_))));
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, account.total()), (&amount("10"), amount("10")));
+	}
+
+	#[tokio::test]
+	async fn test_process_fees() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+fee,1,2,1.5
+dispute,1,2,
+fee,1,3,20
+fee,1,2,1";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("invalid_tx_reference", Some(2)),
+				("insufficient_funds", Some(3)),
+				("duplicate_tx", Some(2)),
+			]
+		);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("8.5"), &amount("0")));
+		assert_eq!(account.activity.withdrawals, 0);
+
+		let snapshot = processor.snapshot().await;
+		let restored = TransactionProcessor::default().with_state(snapshot);
+		assert_eq!(restored.snapshot().await.accounts, processor.snapshot().await.accounts);
+	}
+
+	#[tokio::test]
+	async fn test_process_refunds() {
+		let input = "type,client,tx,amount,ref
+deposit,1,1,10,
+withdrawal,1,2,4,
+refund,1,3,3,2
+refund,1,4,1.5,2
+refund,2,5,1,2
+refund,1,6,1,9
+refund,1,7,1,3
+dispute,1,3,,
+refund,1,8,1,2";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("refund_exceeds_original", Some(4)),
+				("unknown_tx", Some(5)),
+				("unknown_tx", Some(6)),
+				("invalid_tx_reference", Some(7)),
+				("invalid_tx_reference", Some(3)),
+			]
+		);
+		let account = processor.get_accounts().await.into_iter().find(|a| a.client_id == 1);
+		assert_eq!(account.unwrap().available, amount("10"));
+		assert_eq!(processor.report().await.refunded, amount("4"));
+
+		let snapshot = processor.snapshot().await;
+		let restored = TransactionProcessor::default().with_state(snapshot);
+		assert_eq!(restored.snapshot().await.accounts, processor.snapshot().await.accounts);
+	}
+
+	#[tokio::test]
+	async fn test_refund_charged_back() {
+		let input = "type,client,tx,amount,ref
+deposit,1,1,10,
+deposit,1,2,5,
+dispute,1,2,,
+refund,1,3,1,2
+dispute,1,1,,
+chargeback,1,1,,
+refund,1,4,1,1";
+		let policy = DisputePolicy { reopen_chargebacks: true, ..DisputePolicy::default() };
+		let mut processor =
+			TransactionProcessor::default().with_dispute_policy(policy).with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![("illegal_state_change", Some(3)), ("illegal_state_change", Some(4))]
+		);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("0"), &amount("5")));
+		assert_eq!(processor.report().await.refunded, amount("0"));
+	}
+
+	#[tokio::test]
+	async fn test_process_withdrawal_disputes() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,4
+dispute,1,2,
+resolve,1,2,
+dispute,1,2,
+chargeback,1,2,";
+		let mut processor = TransactionProcessor::default().with_ledger();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let balances = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.map(|entry| (entry.available.clone(), entry.held.clone()))
+			.collect_vec();
+		assert_eq!(
+			balances,
+			vec![
+				(amount("10"), amount("0")),
+				(amount("6"), amount("0")),
+				(amount("6"), amount("4")),
+				(amount("6"), amount("0")),
+				(amount("6"), amount("4")),
+				(amount("10"), amount("0")),
+			]
+		);
+		assert!(processor.get_accounts().await[0].locked);
+	}
+
+	#[tokio::test]
+	async fn test_process_partial_disputes() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+dispute,1,1,15
+dispute,1,1,4
+resolve,1,1,
+dispute,1,1,3
+chargeback,1,1,";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("dispute_exceeds_original", Some(1))]);
+		let balances = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.map(|entry| (entry.available.clone(), entry.held.clone()))
+			.collect_vec();
+		assert_eq!(
+			balances,
+			vec![
+				(amount("10"), amount("0")),
+				(amount("6"), amount("4")),
+				(amount("10"), amount("0")),
+				(amount("7"), amount("3")),
+				(amount("7"), amount("0")),
+			]
+		);
+		assert!(processor.get_accounts().await[0].locked);
+	}
+
+	#[tokio::test]
+	async fn test_dispute_window() {
+		const DAY: i64 = 24 * 60 * 60;
+		let mut processor = TransactionProcessor::default().with_dispute_window(90 * DAY);
+		processor
+			.handle_transaction(Transaction::deposit(1, amount("10"), 1))
+			.await
+			.unwrap();
+		processor.advance_clock(0).await;
+		processor
+			.handle_transaction(Transaction::deposit(2, amount("10"), 1))
+			.await
+			.unwrap();
+		processor.advance_clock(10 * DAY).await;
+		processor
+			.handle_transaction(Transaction::deposit(3, amount("10"), 1))
+			.await
+			.unwrap();
+		processor.advance_clock(100 * DAY).await;
+
+		let input = "type,client,tx,amount
+dispute,1,1,
+dispute,1,2,
+dispute,1,3,";
+		let mut processor = processor.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("dispute_window_expired", Some(2))]);
+		assert_eq!(processor.get_accounts().await[0].held, amount("20"));
+	}
+
+	#[tokio::test]
+	async fn test_process_timestamps_advance_clock() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount,timestamp
+deposit,1,1,10,0
+deposit,1,2,10,
+deposit,1,3,10,864000
+dispute,1,1,,7862400
+dispute,1,3,,4320000
+dispute,1,4,,";
+		let mut processor = TransactionProcessor::default()
+			.with_dispute_window(90 * DAY)
+			.with_ledger()
+			.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		// The clock never moves backwards, so the third deposit is disputed at day 91.
+		let rejects = processor.rejects().unwrap();
+		assert_eq!(
+			rejects.iter().map(|r| (r.reason, r.tx, r.timestamp)).collect_vec(),
+			vec![
+				("dispute_window_expired", Some(1), Some(91 * DAY)),
+				("unknown_tx", Some(4), None)
+			]
+		);
+		assert_eq!(
+			processor.ledger().unwrap().iter().map(|e| e.timestamp).collect_vec(),
+			vec![Some(0), None, Some(10 * DAY), Some(50 * DAY)]
+		);
+		assert_eq!(processor.get_accounts().await[0].held, amount("10"));
+	}
+
+	#[tokio::test]
+	async fn test_process_clearing_period() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount,timestamp
+deposit,1,1,100,0
+withdrawal,1,2,10,3600
+deposit,1,3,50,7200
+dispute,1,3,,10800
+withdrawal,1,4,10,86400
+resolve,1,3,,90000
+deposit,1,5,5,90000";
+		let mut processor = TransactionProcessor::default()
+			.with_clearing_period(DAY)
+			.with_ledger()
+			.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("insufficient_funds", Some(2))]);
+		let ledger = processor.ledger().unwrap().iter().map(|e| (e.tx_type, e.tx)).collect_vec();
+		assert_eq!(
+			ledger,
+			vec![
+				("deposit", Some(1)),
+				("deposit", Some(3)),
+				("dispute", Some(3)),
+				("clearing", Some(1)),
+				("withdrawal", Some(4)),
+				("resolve", Some(3)),
+				("deposit", Some(5)),
+			]
+		);
+		// The resolved deposit stays held until it clears.
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("90"), &amount("55")));
+
+		// Deposits that have not cleared yet clear after a restore.
+		let snapshot = ProcessorSnapshot::from_yaml(&processor.snapshot().await.to_yaml().unwrap());
+		let mut processor = TransactionProcessor::default().with_state(snapshot.unwrap());
+		processor.advance_clock(90000 + DAY).await;
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("145"), &amount("0")));
+	}
+
+	#[tokio::test]
+	async fn test_process_dispute_uncleared_deposit() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount,timestamp
+deposit,1,1,100,0
+dispute,1,1,10,3600
+withdrawal,1,2,1,7200
+withdrawal,1,3,90,86400
+withdrawal,1,4,1,86400";
+		let mut processor = TransactionProcessor::default()
+			.with_clearing_period(DAY)
+			.with_ledger()
+			.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		// Only the undisputed part clears, and none of it before the clearing period.
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("insufficient_funds", Some(2)), ("insufficient_funds", Some(4))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("0"), &amount("10")));
+		let cleared = processor.ledger().unwrap().iter().find(|e| e.tx_type == "clearing");
+		assert_eq!(cleared.and_then(|e| e.amount.clone()), Some(amount("90")));
+	}
+
+	#[tokio::test]
+	async fn test_process_groups() {
+		let input = "type,client,tx,amount,group_id
+deposit,1,1,10,
+withdrawal,1,2,5,7
+deposit,2,3,5,7
+fee,1,4,1,7
+withdrawal,1,5,3,8
+deposit,2,6,3,8
+fee,1,7,5,8
+deposit,1,5,1,";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("group_rejected", Some(5)),
+				("group_rejected", Some(6)),
+				("insufficient_funds", Some(7)),
+			]
+		);
+		let ledger = processor.ledger().unwrap().iter().map(|e| (e.sequence, e.tx)).collect_vec();
+		assert_eq!(
+			ledger,
+			vec![(1, Some(1)), (2, Some(2)), (3, Some(3)), (4, Some(4)), (8, Some(5))]
+		);
+		let balances = processor
+			.get_accounts()
+			.await
+			.into_iter()
+			.sorted_by_key(|a| a.client_id)
+			.map(|a| a.available)
+			.collect_vec();
+		assert_eq!(balances, vec![amount("5"), amount("5")]);
+		let report = processor.report().await;
+		assert_eq!((report.applied, report.rejected_by_reason["group_rejected"]), (5, 2));
+	}
+
+	#[tokio::test]
+	async fn test_process_group_rollback() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount,group_id,timestamp,reason
+deposit,1,1,10,,0,
+deposit,1,2,5,7,90000,
+fee,1,3,2,7,90000,
+hold,1,4,4,7,90000,risk case 12
+withdrawal,1,5,20,7,90000,
+release,1,6,1,,90000,risk case 12 closed
+deposit,1,7,1,,180000,";
+		let mut processor = TransactionProcessor::default()
+			.with_clearing_period(DAY)
+			.with_ledger()
+			.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		// The manual hold of the group was rolled back, so there is nothing to release.
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("group_rejected", Some(2)),
+				("group_rejected", Some(3)),
+				("group_rejected", Some(4)),
+				("insufficient_funds", Some(5)),
+				("release_exceeds_hold", Some(6)),
+			]
+		);
+		// The deposit of the group never clears.
+		let ledger = processor.ledger().unwrap().iter().map(|e| (e.tx_type, e.tx)).collect_vec();
+		assert_eq!(ledger, vec![("deposit", Some(1)), ("clearing", Some(1)), ("deposit", Some(7))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("10"), &amount("1")));
+		assert_eq!(processor.snapshot().await.accounts[0].manual_hold, None);
+		let report = processor.report().await;
+		assert_eq!((report.applied, report.rejected_by_reason["group_rejected"]), (2, 3));
+	}
+
+	#[tokio::test]
+	async fn test_process_overdrafts() {
+		let input = "type,client,tx,amount,to
+deposit,1,1,10,
+withdrawal,1,2,30,
+transfer,1,3,1,2
+deposit,2,4,10,
+withdrawal,2,5,30,
+dispute,2,4,,
+transfer,2,6,5,1
+withdrawal,2,7,0.01,";
+		let overdraft = Overdraft {
+			global: Some(amount("20")),
+			per_client: HashMap::from([(2, amount("25"))]),
+			..Overdraft::default()
+		};
+		let mut processor =
+			TransactionProcessor::default().with_overdraft(overdraft.clone()).with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("insufficient_funds", Some(3)),
+				("insufficient_funds", Some(4)),
+				("insufficient_funds", Some(7)),
+			]
+		);
+		let balances = |accounts: Vec<Account>| {
+			accounts
+				.iter()
+				.sorted_by_key(|a| a.client_id)
+				.map(|a| (a.available.to_string(), a.total().to_string()))
+				.collect_vec()
+		};
+		assert_eq!(
+			balances(processor.get_accounts().await),
+			vec![("-15".into(), "-15".into()), ("-25".into(), "-25".into())]
+		);
+
+		// Overdrawn balances are restored from a snapshot.
+		let snapshot = ProcessorSnapshot::from_yaml(&processor.snapshot().await.to_yaml().unwrap());
+		let mut processor = TransactionProcessor::default()
+			.with_state(snapshot.unwrap())
+			.with_overdraft(overdraft);
+		let input = "type,client,tx,amount\ndeposit,1,8,5";
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		assert_eq!(balances(processor.get_accounts().await)[0].0, "-10");
+	}
+
+	#[tokio::test]
+	async fn test_process_credit_lines() {
+		let input = "type,client,tx,amount,to
+withdrawal,1,1,80,
+withdrawal,1,2,30,
+deposit,1,3,50,
+deposit,1,4,40,
+deposit,2,5,50,
+transfer,2,6,40,1
+transfer,2,7,10,1
+dispute,1,3,,";
+		let overdraft =
+			Overdraft { credit_lines: HashMap::from([(1, amount("100"))]), ..Overdraft::default() };
+		let mut processor =
+			TransactionProcessor::default().with_overdraft(overdraft).with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("insufficient_funds", Some(2)),
+				("balance_cap_exceeded", Some(4)),
+				("balance_cap_exceeded", Some(6)),
+			]
+		);
+		let accounts = processor.get_accounts().await;
+		let account = accounts.iter().find(|a| a.client_id == 1).unwrap();
+		assert_eq!(account.available.to_string(), "-70");
+		assert_eq!(account.held, amount("50"));
+		assert_eq!(account.outstanding(), amount("20"));
+		assert_eq!(account.credit_limit(), Some(&amount("100")));
+	}
+
+	#[tokio::test]
+	async fn test_process_conversions() {
+		let input = "type,client,tx,amount,currency,to_currency,rate
+deposit,1,1,100,,,
+convert,1,2,50,,EUR,0.92
+convert,1,3,10,EUR,USD,
+convert,1,4,10,,GBP,
+convert,1,5,10,,EUR,0.99
+convert,1,6,100,EUR,USD,
+convert,1,2,1,,EUR,";
+		let rates = RateTable {
+			rates: HashMap::from([(("USD".into(), "EUR".into()), Decimal::new(92, 2))]),
+			max_slippage: Some(Decimal::new(1, 2)),
+		};
+		let mut processor = TransactionProcessor::default()
+			.with_fx_rates(rates)
+			.with_ledger()
+			.with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("fx_rate_unavailable", Some(4)),
+				("fx_slippage_exceeded", Some(5)),
+				("insufficient_funds", Some(6)),
+				("duplicate_tx", Some(2)),
+			]
+		);
+		let conversions = processor.ledger().unwrap().iter().filter_map(|e| e.conversion.clone());
+		assert_eq!(conversions.collect_vec(), vec!["50 USD -> 46.00 EUR", "10 EUR -> 10.86 USD"]);
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!(account.available.to_string(), "60.86");
+		assert_eq!(account.balances.get("EUR").unwrap().available.to_string(), "36.00");
+
+		let snapshot = ProcessorSnapshot::from_yaml(&processor.snapshot().await.to_yaml().unwrap());
+		let restored = TransactionProcessor::default().with_state(snapshot.unwrap());
+		let account = restored.get_accounts().await.pop().unwrap();
+		assert_eq!(account.balances.get("EUR").unwrap().available.to_string(), "36.00");
+	}
+
+	#[tokio::test]
+	async fn test_process_overflow() {
+		let input = "type,client,tx,amount
+deposit,1,1,79228162514264337593543950335
+deposit,1,2,1
+deposit,2,3,1";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("internal_error", Some(2))]);
+		let accounts = processor.get_accounts().await;
+		assert_eq!(accounts.len(), 2);
+		assert!(accounts.iter().all(|account| account.activity.deposits == 1));
+	}
+
+	#[tokio::test]
+	async fn test_process_currencies() {
+		let input = "type,client,tx,amount,currency,ref
+deposit,1,1,100,EUR,
+deposit,1,2,50,,
+dispute,1,1,,USD,
+dispute,1,1,40,EUR,
+withdrawal,1,3,70,EUR,
+withdrawal,1,4,20,EUR,
+refund,1,5,10,,1";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("currency_mismatch", Some(1)),
+				("insufficient_funds", Some(3)),
+				("currency_mismatch", Some(5)),
+			]
+		);
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!((account.available, account.held), (amount("50"), Amount::default()));
+		assert_eq!(account.balances.get("EUR").unwrap().available.to_string(), "40");
+		assert_eq!(account.balances.get("EUR").unwrap().held.to_string(), "40");
+		assert_eq!(processor.report().await.deposited, amount("50"));
+
+		// The currencies of the balances and the disputed deposit survive a snapshot.
+		let snapshot = ProcessorSnapshot::from_yaml(&processor.snapshot().await.to_yaml().unwrap());
+		let mut restored = TransactionProcessor::default().with_state(snapshot.unwrap());
+		let input = "type,client,tx,amount,currency\nresolve,1,1,,";
+		restored.process(input.as_bytes(), error_handler).await.unwrap();
+		let account = restored.get_accounts().await.pop().unwrap();
+		assert_eq!(account.balances.get("EUR").unwrap().available.to_string(), "80");
+		assert_eq!(account.balances.get("EUR").unwrap().held.to_string(), "0");
+
+		// Refunds in other currencies count toward neither the report nor the settlement.
+		let eur = find_currency("EUR").unwrap();
+		let refund = Transaction::refund(6, amount("10").in_currency(eur), 1, 1);
+		restored.handle_transaction(refund).await.unwrap();
+		assert_eq!(restored.report().await.refunded, Amount::default());
+		let snapshot = restored.snapshot().await;
+		assert_eq!(snapshot.accounts[0].unsettled.as_ref().unwrap().credits, amount("50"));
+
+		// Restoring a snapshot with a currency that is not known fails.
+		let yaml = snapshot.to_yaml().unwrap().replace("EUR", "XYZ");
+		assert!(ProcessorSnapshot::from_yaml(&yaml).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_dispute_without_currency() {
+		let input = "type,client,tx,amount,currency
+deposit,1,1,100,EUR
+dispute,1,1,,
+resolve,1,1,,
+dispute,1,1,,EUR
+chargeback,1,1,,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		assert!(processor.rejects().unwrap().is_empty());
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!(account.balances.get("EUR").unwrap().available.to_string(), "0");
+		assert!(account.locked);
+	}
+
+	#[tokio::test]
+	async fn test_process_amount_config() {
+		let input = "type,client,tx,amount,currency
+deposit,1,1,100.12345678,
+deposit,1,2,20,USD
+withdrawal,1,3,0.00000001,
+fee,1,4,1,EUR";
+		let eur = find_currency("EUR").unwrap();
+		let config = AmountConfig::default().with_base_currency(eur).with_max_decimal_places(8);
+		let mut processor = TransactionProcessor::default().with_amount_config(config);
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		assert_eq!(processor.report().await.parse_errors, 0);
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!(account.available.value().currency(), eur);
+		assert_eq!(account.balances.get("USD").unwrap().available.to_string(), "20");
+		assert_eq!(processor.info().await.base_currency, eur);
+		for (serializer, row) in [
+			(AccountSerializer::default(), "1,99.12345677,0.0,99.12345677,false"),
+			(AccountSerializer::default().with_max_decimal_places(2), "1,99.12,0.0,99.12,false"),
+		] {
+			let mut csv = Vec::new();
+			processor.write_accounts(CsvSink::new(&mut csv, serializer)).await.unwrap();
+			assert_eq!(
+				String::from_utf8(csv).unwrap(),
+				format!("client,available,held,total,locked\n{row}\n")
+			);
+		}
+
+		// With the default configuration, the amounts with 8 decimal places and the EUR fee
+		// cannot be read.
+		let mut processor = TransactionProcessor::default();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		assert_eq!(processor.report().await.parse_errors, 3);
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!(account.available, amount("20"));
+	}
+
+	#[tokio::test]
+	async fn test_process_dispute_escalation() {
+		let input = "type,client,tx,amount
+deposit,1,1,100
+deposit,1,2,50
+dispute,1,1,
+representment,1,1,
+pre_arbitration,1,1,
+representment,1,2,
+dispute,1,2,
+pre_arbitration,1,2,
+representment,1,2,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![("illegal_state_change", Some(2)), ("illegal_state_change", Some(2))]
+		);
+		assert_eq!(
+			processor.report().await.open_disputes_by_stage,
+			[("pre_arbitration", 1), ("representment", 1)].into()
+		);
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!((account.available, account.held), (amount("0"), amount("150")));
+		assert_eq!(account.activity.open_disputes, 2);
+
+		let input = "type,client,tx,amount\nresolve,1,2,\nchargeback,1,1,";
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		assert!(processor.report().await.open_disputes_by_stage.is_empty());
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!((account.available, account.held), (amount("50"), amount("0")));
+		assert!(account.locked);
+		assert_eq!(account.activity.open_disputes, 0);
+	}
+
+	#[tokio::test]
+	async fn test_process_manual_holds() {
+		let input = "type,client,tx,amount,reason
+deposit,1,1,100,
+deposit,1,2,50,
+dispute,1,2,,
+hold,1,3,30,risk case 12
+release,1,4,40,risk case 12 closed
+release,1,5,20,risk case 12 partially closed
+hold,1,6,100,risk case 13";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![("release_exceeds_hold", Some(4)), ("insufficient_funds", Some(6))]
+		);
+		let entry = processor.ledger().unwrap().iter().find(|e| e.tx == Some(3)).unwrap();
+		assert_eq!(entry.reason.as_deref(), Some("risk case 12"));
+		let account = processor.get_accounts().await.pop().unwrap();
+		assert_eq!((account.available, account.held), (amount("90"), amount("60")));
+		assert_eq!(account.activity.open_disputes, 1);
+
+		// The remaining manual hold survives a snapshot.
+		let snapshot = ProcessorSnapshot::from_yaml(&processor.snapshot().await.to_yaml().unwrap());
+		let mut restored =
+			TransactionProcessor::default().with_state(snapshot.unwrap()).with_rejects();
+		let input = "type,client,tx,amount,reason\nrelease,1,7,10,closed\nrelease,1,8,0.01,closed";
+		restored.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = restored.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("release_exceeds_hold", Some(8))]);
+		let account = restored.get_accounts().await.pop().unwrap();
+		assert_eq!((account.available, account.held), (amount("100"), amount("50")));
+	}
+
+	#[tokio::test]
+	async fn test_process_idempotency_keys() {
+		let input = "type,client,tx,amount,idempotency_key
+deposit,1,1,10,a
+deposit,1,2,10,a
+withdrawal,1,3,50,b
+withdrawal,1,4,5,b
+withdrawal,1,8,5,b
+deposit,1,5,1,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("duplicate_idempotency_key", Some(2)),
+				("insufficient_funds", Some(3)),
+				("duplicate_idempotency_key", Some(8)),
+			]
+		);
+		// The retry of the rejected withdrawal is applied.
+		assert_eq!(processor.get_accounts().await[0].available, amount("6"));
+
+		// The keys are restored from a snapshot.
+		let snapshot = processor.snapshot().await;
+		assert_eq!(snapshot.idempotency_keys, vec!["a", "b"]);
+		let mut processor = TransactionProcessor::default().with_state(snapshot).with_rejects();
+		let input = "type,client,tx,amount,idempotency_key
+deposit,1,6,10,a
+deposit,1,7,10,c";
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("duplicate_idempotency_key", Some(6))]);
+		assert_eq!(processor.get_accounts().await[0].available, amount("16"));
+	}
+
+	#[tokio::test]
+	async fn test_process_unlocks() {
+		let input = "type,client,tx,amount,reason
+deposit,1,1,10,
+deposit,1,2,5,
+dispute,1,2,,
+chargeback,1,2,,
+deposit,1,3,1,
+unlock,1,4,,investigation 17 concluded by ops
+deposit,1,5,1,
+unlock,1,6,,again";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("account_frozen", Some(3)), ("illegal_state_change", Some(6))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!(account.available, amount("11"));
+		assert!(!account.locked);
+		assert_eq!(account.activity.locked_by, None);
+
+		let ledger = processor.ledger().unwrap();
+		let unlock = ledger.iter().find(|entry| entry.tx_type == "unlock").unwrap();
+		assert_eq!(unlock.reason.as_deref(), Some("investigation 17 concluded by ops"));
+	}
+
+	#[tokio::test]
+	async fn test_chargeback_after_redispute_policy() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+dispute,1,1,
+resolve,1,1,
+dispute,1,1,
+chargeback,1,1,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		assert!(processor.rejects().unwrap().is_empty());
+		assert!(processor.get_accounts().await[0].locked);
+
+		let policy =
+			DisputePolicy { chargeback_after_redispute: false, ..DisputePolicy::default() };
+		let mut processor =
+			TransactionProcessor::default().with_rejects().with_dispute_policy(policy);
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("illegal_state_change", Some(1))]);
+
+		// The resolution is restored from a snapshot.
+		let snapshot = processor.snapshot().await;
+		assert!(snapshot.accounts[0].transactions[0].resolved);
+		let mut processor = TransactionProcessor::default()
+			.with_state(snapshot)
+			.with_dispute_policy(policy)
+			.with_rejects();
+		let input = "type,client,tx,amount
+chargeback,1,1,
+resolve,1,1,";
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("illegal_state_change", Some(1))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, account.locked), (&amount("10"), false));
+	}
+
+	#[tokio::test]
+	async fn test_process_reopened_chargebacks() {
+		let input = "type,client,tx,amount,reason
+deposit,1,1,10,
+deposit,1,2,5,
+withdrawal,1,3,4,
+dispute,1,2,,
+chargeback,1,2,,
+reopen,1,2,,arbitration case 9
+resolve,1,2,,
+reopen,1,2,,again
+dispute,1,3,,
+chargeback,1,3,,
+reopen,1,3,,arbitration case 10";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("illegal_state_change", Some(2)),
+				("unknown_tx", Some(2)),
+				("illegal_state_change", Some(2)),
+				("account_frozen", Some(3)),
+				("account_frozen", Some(3)),
+				("illegal_state_change", Some(3)),
+			]
+		);
+
+		let policy = DisputePolicy { reopen_chargebacks: true, ..DisputePolicy::default() };
+		let mut processor =
+			TransactionProcessor::default().with_rejects().with_dispute_policy(policy);
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("illegal_state_change", Some(2))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("11"), &amount("4")));
+		assert!(!account.locked);
+		assert_eq!(account.activity.open_disputes, 1);
+	}
+
+	#[tokio::test]
+	async fn test_process_account_closure() {
+		let input = "type,client,tx,amount,to
+deposit,1,1,10,
+deposit,2,2,5,
+dispute,2,2,,
+close_account,2,3,,
+resolve,2,2,,
+close_account,2,4,,
+deposit,2,5,1,
+transfer,1,6,1,2
+close_account,2,7,,";
+		let mut processor = TransactionProcessor::default().with_rejects().with_change_tracking();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("held_funds_remaining", Some(3)),
+				("account_closed", Some(5)),
+				("account_closed", Some(6)),
+				("account_closed", Some(7)),
+			]
+		);
+		let accounts = processor.get_accounts().await;
+		let closed = accounts.iter().find(|account| account.client_id == 2).unwrap();
+		assert!(closed.closed);
+		assert_eq!(closed.available, amount("5"));
 
-impl TransactionProcessor {
-	/// Processes a stream of transactions from a CSV reader.
-	///
-	/// This function reads and parses transactions from the provided reader, handles each transaction,
-	/// and returns a vector of all the resulting account states.
-	///
-	/// # Errors
-	///
-	/// Returns a `TransactionError` if an error occurs while parsing transactions or handling individual transactions.
-	pub async fn process_transactions<F>(
-		reader: impl domain::transaction::AsyncRead + Unpin + Send + 'static,
-		error_handler: F,
-	) -> Result<Vec<Account>, TransactionError>
-	where
-		F: Fn(TransactionProcessorError),
-	{
-		let mut tx_stream = Transaction::tx_stream(reader);
-		let mut tx_processor = TransactionProcessor::default();
-		while let Some(tx_result) = tx_stream.next().await {
-			match tx_result.map_err(TransactionProcessorError::TransactionParsingError) {
-				Ok(tx) => tx_processor
-					.handle_transaction(tx)
-					.await
-					.map_err(TransactionProcessorError::TransactionProcessingError)
-					.unwrap_or_else(|e| error_handler(e)),
-				Err(e) => error_handler(e),
-			};
-		}
-		let accounts = tx_processor.get_accounts();
-		Ok(accounts.await)
+		let restored = TransactionProcessor::default().with_state(processor.snapshot().await);
+		assert!(restored.get_accounts().await.iter().any(|account| account.closed));
 	}
 
-	/// Handles a single transaction by applying its effect to the relevant account.
-	///
-	/// # Arguments
-	///
-	/// * `tx` - The `Transaction` to process.
-	///
-	/// # Errors
-	///
-	/// Returns a `TransactionError` if an error occurs during processing, such as:
-	/// - DuplicateGlobalTransactionId: If the transaction ID is already in the global set.
-	/// - AccountFrozen: If the account associated with the transaction is frozen.
-	/// - InsufficientFunds: If a withdrawal or chargeback would result in a negative balance.
-	/// - IllegalStateChange: If the transaction attempts an invalid state transition.
-	/// - InvalidTransactionId: If the transaction ID is invalid for the operation.
-	/// - TransactionNotFound: If a dispute, resolve, or chargeback references a non-existent transaction.
-	async fn handle_transaction(&mut self, tx: Transaction) -> Result<(), TransactionError> {
-		debug!("Processing transaction: {:?}", &tx);
-		let mut accounts = self.accounts.lock().await;
-		let mut global_tx_ids = self.global_tx_ids.lock().await;
+	#[tokio::test]
+	async fn test_process_settlements() {
+		let input = "type,client,tx,amount,ref,reason
+deposit,1,1,10,,
+deposit,1,2,5,,
+withdrawal,1,3,2,,
+fee,1,4,0.5,,
+settlement,1,5,,900,
+credit_adjustment,1,6,5,,reinstate
+withdrawal,1,7,2,,
+settlement,1,8,,901,
+settlement,1,9,,902,";
+		let mut processor = TransactionProcessor::default().with_ledger();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
 
-		let (account, account_txs) = accounts.entry(*tx.client_id()).or_insert_with(|| {
-			(
-				Account::new(*tx.client_id(), Amount::default(), Amount::default(), false),
-				HashMap::new(),
-			)
-		});
+		let settlements = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.filter(|entry| entry.tx_type == "settlement")
+			.map(|entry| (entry.reference, entry.amount.clone(), entry.available.clone()))
+			.collect_vec();
+		assert_eq!(
+			settlements,
+			vec![
+				(Some(900), Some(amount("12.5")), amount("0")),
+				(Some(901), Some(amount("2")), amount("5")),
+				(Some(902), Some(amount("0")), amount("5")),
+			]
+		);
 
-		let result: Result<(), TransactionError> = match &tx {
-			Transaction::Deposit { amount, id, .. } => {
-				if global_tx_ids.contains(id) {
-					Err(DuplicateGlobalTransactionId(tx.clone()))
-				} else {
-					account.deposit(amount.clone()).map_err(|e| (e, tx.clone()))?;
-					let tx_id = tx.id();
-					account_txs.insert(tx_id, tx);
-					global_tx_ids.insert(tx_id);
-					Ok(())
-				}
-			},
+		processor
+			.handle_transaction(Transaction::deposit(10, amount("3"), 1))
+			.await
+			.unwrap();
+		let mut restored = TransactionProcessor::default().with_state(processor.snapshot().await);
+		restored.handle_transaction(Transaction::settlement(11, 1, 903)).await.unwrap();
+		assert_eq!(restored.get_accounts().await[0].available, amount("5"));
 
-			Transaction::Withdrawal { amount, id, .. } => {
-				if global_tx_ids.contains(id) {
-					Err(DuplicateGlobalTransactionId(tx.clone()))
-				} else {
-					account.withdraw(amount.clone()).map_err(|e| (e, tx.clone()))?;
-					let tx_id = tx.id();
-					account_txs.insert(tx_id, tx);
-					global_tx_ids.insert(tx_id);
-					Ok(())
-				}
-			},
+		// A batch settled before the snapshot cannot be settled again.
+		let result = restored.handle_transaction(Transaction::settlement(12, 1, 901)).await;
+		assert!(matches!(result, Err(BatchAlreadySettled(_))));
+	}
 
-			Transaction::Dispute { id, .. } => {
-				match account_txs.get_mut(id) {
-					Some(tx) => match tx.amount() {
-						Some(amount) => {
-							//improve: these should be atomic
-							account.hold(amount).map_err(|e| (e, tx.clone()))?;
-							tx.set_disputed()?;
-							Ok(())
-						},
-						None => Err(InvalidTransactionId(tx.clone())),
-					},
-					None => Err(TransactionNotFound(tx.clone())),
-				}
-			},
-			Transaction::Resolve { id, .. } => match account_txs.get_mut(id) {
-				Some(tx) => match tx.amount() {
-					Some(amount) => {
-						//improve: these should be atomic
-						account.release(amount).map_err(|e| (e, tx.clone()))?;
-						tx.set_resolved()?;
-						Ok(())
-					},
-					None => Err(InvalidTransactionId(tx.clone())),
-				},
-				None => Err(TransactionNotFound(tx.clone())),
-			},
+	#[tokio::test]
+	async fn test_settle_after_reversal() {
+		let input = "type,client,tx,amount,ref
+deposit,1,1,10,
+deposit,1,2,5,
+reversal,1,2,,
+settlement,1,3,,900
+deposit,1,4,12,
+reversal,1,1,,
+settlement,1,5,,901
+deposit,2,7,10,
+withdrawal,2,8,4,
+dispute,2,8,,
+chargeback,2,8,,";
+		let mut processor = TransactionProcessor::default().with_ledger();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
 
-			Transaction::Chargeback { id, .. } => match account_txs.get_mut(id) {
-				Some(tx) => match tx.amount() {
-					Some(amount) => {
-						//improve: these should be atomic
-						account.chargeback(amount).map_err(|e| (e, tx.clone()))?;
-						tx.set_chargeback()?;
-						account_txs.remove(id);
-						Ok(())
-					},
-					None => Err(InvalidTransactionId(tx.clone())),
-				},
-				None => Err(TransactionNotFound(tx.clone())),
-			},
-		};
+		let settlements = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.filter(|entry| entry.tx_type == "settlement")
+			.map(|entry| (entry.reference, entry.amount.clone(), entry.available.clone()))
+			.collect_vec();
+		assert_eq!(
+			settlements,
+			vec![
+				(Some(900), Some(amount("10")), amount("0")),
+				(Some(901), Some(amount("2")), amount("0"))
+			]
+		);
 
-		result
+		// A charged back withdrawal is netted like a deposit.
+		let snapshot = processor.snapshot().await;
+		let batch = snapshot.accounts[1].unsettled.as_ref().unwrap();
+		assert_eq!((&batch.credits, &batch.debits), (&amount("14"), &amount("4")));
 	}
 
-	/// Retrieves all accounts resolved from the input transactions.
-	async fn get_accounts(&self) -> Vec<Account> {
-		let accounts = self.accounts.lock().await;
-		accounts.values().map(|a| a.0.clone()).collect_vec()
+	#[tokio::test]
+	async fn test_recurring_payments() {
+		const DAY: i64 = 24 * 60 * 60;
+		let subscription = RecurringPayment {
+			client: 1,
+			amount: amount("4"),
+			start: 0,
+			interval: 30 * DAY,
+			end: None,
+			first_id: 1000,
+		};
+		let mut processor = TransactionProcessor::default()
+			.with_recurring_payments(Scheduler::new([subscription.clone()]))
+			.with_rejects();
+		processor
+			.handle_transaction(Transaction::deposit(1, amount("10"), 1))
+			.await
+			.unwrap();
+		processor.advance_clock(61 * DAY).await;
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("insufficient_funds", Some(1002))]);
+		assert_eq!(processor.get_accounts().await[0].available, amount("2"));
+
+		// Replaying the schedule from a snapshot does not charge the applied payments again.
+		let mut restored = TransactionProcessor::default()
+			.with_state(processor.snapshot().await)
+			.with_recurring_payments(Scheduler::new([subscription]));
+		restored
+			.handle_transaction(Transaction::deposit(2, amount("10"), 1))
+			.await
+			.unwrap();
+		restored.advance_clock(61 * DAY).await;
+		assert_eq!(restored.get_accounts().await[0].available, amount("8"));
+		assert_eq!(restored.report().await.applied, 2);
 	}
-}
-#[cfg(test)]
-mod tests {
-	use log::error;
-	use tempfile::NamedTempFile;
 
-	use domain::amount::Amount;
-	use domain::transaction::File;
+	#[tokio::test]
+	async fn test_process_reversals() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,4
+deposit,1,3,1
+reversal,1,1,
+reversal,1,2,
+dispute,1,3,
+reversal,1,3,
+reversal,1,2,
+dispute,1,2,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
 
-	use crate::processor::{TransactionProcessor, TransactionProcessorError};
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("insufficient_funds", Some(1)),
+				("illegal_state_change", Some(3)),
+				("illegal_state_change", Some(2)),
+				("illegal_state_change", Some(2)),
+			]
+		);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("10"), &amount("1")));
+		assert_eq!(account.activity.open_disputes, 1);
+	}
 
-	struct TestTransactionsCsvBuilder<'a> {
-		temp_file: NamedTempFile,
-		transactions: Vec<Vec<&'a str>>,
+	#[tokio::test]
+	async fn test_reverse_refunded() {
+		let input = "type,client,tx,amount,ref
+deposit,1,1,10,
+withdrawal,1,2,4,
+refund,1,3,3,2
+reversal,1,2,,
+dispute,1,2,,
+reversal,1,3,,
+refund,1,4,1,2";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("illegal_state_change", Some(2)),
+				("illegal_state_change", Some(2)),
+				("invalid_tx_reference", Some(3)),
+			]
+		);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("10"), &amount("0")));
 	}
 
-	const TYPE: &str = "type";
-	const CLIENT: &str = "client";
-	const TX: &str = "tx";
-	const AMOUNT: &str = "amount";
-	const DEPOSIT: &str = "deposit";
-	const WITHDRAWAL: &str = "withdrawal";
-	const DISPUTE: &str = "dispute";
-	const RESOLVE: &str = "resolve";
-	const CHARGEBACK: &str = "chargeback";
-	const EMPTY: &str = "";
+	#[tokio::test]
+	async fn test_process_authorizations() {
+		let input = "type,client,tx,amount
+deposit,1,1,10
+authorize,1,2,4
+authorize,1,3,20
+authorize,1,4,5
+capture,1,2,
+capture,1,2,
+dispute,1,4,
+capture,1,1,
+deposit,1,5,1
+capture,1,4,";
+		let mut processor = TransactionProcessor::default().with_rejects();
+		let mut expiring = TransactionProcessor::default().with_authorization_expiry(5);
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		expiring.process(input.as_bytes(), error_handler).await.unwrap();
 
-	impl<'a> TestTransactionsCsvBuilder<'a> {
-		fn new() -> Self {
-			Self {
-				temp_file: NamedTempFile::new().unwrap(),
-				transactions: vec![vec![TYPE, CLIENT, TX, AMOUNT]],
-			}
-		}
-		fn deposit(mut self, client_id: &'a str, tx_id: &'a str, amount: &'a str) -> Self {
-			self.transactions.push(vec![DEPOSIT, client_id, tx_id, amount]);
-			self
-		}
-		fn withdrawal(mut self, client_id: &'a str, tx_id: &'a str, amount: &'a str) -> Self {
-			self.transactions.push(vec![WITHDRAWAL, client_id, tx_id, amount]);
-			self
-		}
-		fn dispute(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
-			self.transactions.push(vec![DISPUTE, client_id, tx_id, EMPTY]);
-			self
-		}
-		fn resolve(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
-			self.transactions.push(vec![RESOLVE, client_id, tx_id, EMPTY]);
-			self
-		}
-		fn chargeback(mut self, client_id: &'a str, tx_id: &'a str) -> Self {
-			self.transactions.push(vec![CHARGEBACK, client_id, tx_id, EMPTY]);
-			self
-		}
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				("insufficient_funds", Some(3)),
+				("invalid_tx_reference", Some(2)),
+				("invalid_tx_reference", Some(4)),
+				("invalid_tx_reference", Some(1)),
+			]
+		);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("2"), &amount("0")));
+		assert_eq!(account.activity.withdrawals, 2);
 
-		async fn write(self) -> Self {
-			tokio::fs::write(
-				self.temp_file.path(),
-				self.transactions
-					.iter()
-					.map(|row| row.join(","))
-					.collect::<Vec<String>>()
-					.join("\n"),
-			)
+		let account = &expiring.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("7"), &amount("0")));
+		assert_eq!(expiring.report().await.expired_authorizations, 1);
+	}
+
+	#[tokio::test]
+	async fn test_process_captured_authorizations() {
+		let input = "type,client,tx,amount,ref
+deposit,1,1,10,
+authorize,1,2,4,
+capture,1,2,,
+capture,1,2,,
+dispute,1,2,,
+resolve,1,2,,
+refund,1,3,1,2";
+		let errors = std::sync::Mutex::new(Vec::new());
+		let mut processor = TransactionProcessor::default();
+		processor
+			.process(input.as_bytes(), |e| errors.lock().unwrap().push(e))
 			.await
 			.unwrap();
-			self
-		}
 
-		async fn reader(self) -> File {
-			File::open(self.temp_file.path()).await.unwrap()
-		}
+		// The second capture is reported, not the authorization it references.
+		let errors = errors
+			.into_inner()
+			.unwrap()
+			.into_iter()
+			.map(|e| match e {
+				TransactionProcessorError::TransactionProcessingError(e) => {
+					(e.code(), e.transaction().type_name())
+				},
+				e => panic!("unexpected error {e:?}"),
+			})
+			.collect_vec();
+		assert_eq!(errors, vec![("invalid_tx_reference", "capture")]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("7"), &amount("0")));
+
+		// The captured authorization is restored from a snapshot as such.
+		let snapshot = processor.snapshot().await;
+		let recorded = snapshot.accounts[0].transactions.iter().find(|t| t.tx == 2).unwrap();
+		assert_eq!(recorded.tx_type, RecordedType::CapturedAuthorization);
+		let restored = TransactionProcessor::default().with_state(snapshot);
+		assert_eq!(restored.snapshot().await.accounts, processor.snapshot().await.accounts);
 	}
 
-	fn amount(value: &str) -> Amount {
-		Amount::try_from(value).unwrap()
+	#[tokio::test]
+	async fn test_process_authorization_window() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount,timestamp
+deposit,1,1,10,0
+authorize,1,2,4,0
+authorize,1,3,5,
+authorize,1,4,1,3600
+deposit,1,5,1,90000";
+		let mut processor = TransactionProcessor::default()
+			.with_authorization_window(DAY)
+			.with_authorization_expiry(100);
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		// Only the authorization a day older than the clock expired, the untimestamped one is
+		// counted in records.
+		let account = &processor.get_accounts().await[0];
+		assert_eq!((&account.available, &account.held), (&amount("5"), &amount("6")));
+		assert_eq!(processor.report().await.expired_authorizations, 1);
 	}
 
-	fn error_handler(e: TransactionProcessorError) {
-		error!("{e:?}");
+	#[tokio::test]
+	async fn test_process_adjustments() {
+		let input = "type,client,tx,amount,reason
+deposit,1,1,10,
+dispute,1,1,,
+chargeback,1,1,,
+credit_adjustment,1,2,3,reinstate settled deposit
+debit_adjustment,1,3,1,fee waived twice
+debit_adjustment,1,4,5,overdraw";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let reasons = processor.rejects().unwrap().iter().map(|r| (r.reason, r.tx)).collect_vec();
+		assert_eq!(reasons, vec![("insufficient_funds", Some(4))]);
+		let account = &processor.get_accounts().await[0];
+		assert_eq!(account.available, amount("2"));
+		assert!(account.locked);
+
+		let ledger = processor.ledger().unwrap();
+		assert_eq!(ledger.len(), 5);
+		assert_eq!(ledger[3].reason.as_deref(), Some("reinstate settled deposit"));
+		assert_eq!((&ledger[4].amount, &ledger[4].available), (&Some(amount("1")), &amount("2")));
 	}
 
 	#[tokio::test]
-	async fn test_process_transactions_simple() {
-		enable_debug_logs();
+	async fn test_accrue_interest() {
+		const DAY: i64 = 24 * 60 * 60;
+		let input = "type,client,tx,amount
+deposit,1,1,1000
+deposit,2,2,2000
+deposit,3,3,10
+dispute,3,3,
+chargeback,3,3,";
+		let schedule = RateSchedule::new([(0, Decimal::new(365, 4)), (2 * DAY, Decimal::ZERO)]);
+		let mut processor = TransactionProcessor::default()
+			.with_ledger()
+			.with_interest(InterestAccrual::new(schedule, DAY));
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+		processor.advance_clock(DAY / 2).await;
+		processor.advance_clock(3 * DAY).await;
 
-		let transactions_csv = TestTransactionsCsvBuilder::new()
-			.deposit("1", "1", "1")
-			.deposit("1", "2", "1")
-			.deposit("1", "3", "1")
-			.withdrawal("1", "4", "1")
-			.write()
-			.await;
+		let balances = processor
+			.get_accounts()
+			.await
+			.into_iter()
+			.sorted_by_key(|account| account.client_id)
+			.map(|account| account.available)
+			.collect_vec();
+		assert_eq!(balances, vec![amount("1000.2"), amount("2000.4"), amount("0")]);
+		assert_eq!(processor.report().await.interest, amount("0.6"));
 
-		let reader = transactions_csv.reader().await;
-		let accounts =
-			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+		let accruals = processor
+			.ledger()
+			.unwrap()
+			.iter()
+			.filter(|entry| entry.tx_type == "interest")
+			.map(|entry| (entry.sequence, entry.client, entry.tx, entry.amount.clone()))
+			.collect_vec();
+		assert_eq!(
+			accruals,
+			vec![
+				(5, 1, None, Some(amount("0.1"))),
+				(5, 2, None, Some(amount("0.2"))),
+				(5, 1, None, Some(amount("0.1"))),
+				(5, 2, None, Some(amount("0.2"))),
+			]
+		);
+	}
 
-		assert_eq!(accounts.len(), 1);
+	#[tokio::test]
+	async fn test_process_records_rejects() {
+		let source = StreamSource(tokio_stream::iter([
+			Ok(Transaction::deposit(1, amount("1"), 1)),
+			Err(SourceError::InvalidRecord("unreadable".into())),
+			Ok(Transaction::withdrawal(2, amount("5"), 1)),
+			Ok(Transaction::deposit(1, amount("1"), 2)),
+		]));
+		let mut processor = TransactionProcessor::default().with_rejects();
+		processor.process(source, error_handler).await.unwrap();
 
-		let account = &accounts[0];
-		assert_eq!(account.client_id, 1);
-		assert_eq!(account.available, amount("2"));
-		assert_eq!(account.held, amount("0"));
-		assert_eq!(account.total(), amount("2"));
-		assert!(!account.locked);
+		let rejects = processor.rejects().unwrap();
+		let reasons = rejects.iter().map(|r| (r.sequence, r.reason, r.tx)).collect_vec();
+		assert_eq!(
+			reasons,
+			vec![
+				(2, "parse_error", None),
+				(3, "insufficient_funds", Some(2)),
+				(4, "duplicate_tx", Some(1))
+			]
+		);
+		assert_eq!(rejects[1].amount, Some(amount("5")));
+		assert_eq!(rejects[0].detail, "Invalid record: unreadable");
 	}
+
 	#[tokio::test]
-	async fn test_process_transactions_with_disputes() {
+	async fn test_process_records_metadata() {
+		let input = "type,client,tx,amount,merchant,reference,memo
+deposit,1,1,10,ACME Ltd,order-81,
+withdrawal,1,2,50,ACME Ltd,order-82,retry
+withdrawal,1,3,5,,,";
+		let mut processor = TransactionProcessor::default().with_ledger().with_rejects();
+		processor.process(input.as_bytes(), error_handler).await.unwrap();
+
+		let metadata = |merchant: &Option<String>, reference: &Option<String>, memo| {
+			(merchant.clone(), reference.clone(), memo)
+		};
+		let ledger = processor.ledger().unwrap();
+		assert_eq!(
+			ledger
+				.iter()
+				.map(|e| metadata(&e.merchant, &e.external_reference, e.memo.clone()))
+				.collect_vec(),
+			vec![(Some("ACME Ltd".into()), Some("order-81".into()), None), (None, None, None)]
+		);
+		let reject = &processor.rejects().unwrap()[0];
+		assert_eq!(
+			metadata(&reject.merchant, &reject.external_reference, reject.memo.clone()),
+			(Some("ACME Ltd".into()), Some("order-82".into()), Some("retry".into()))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_write_changed_accounts() {
+		let source = StreamSource(tokio_stream::iter([
+			Ok::<_, SourceError>(Transaction::deposit(1, amount("1"), 1)),
+			Ok(Transaction::deposit(2, amount("1"), 2)),
+			Ok(Transaction::deposit(3, amount("1"), 3)),
+		]));
+		let mut state = TransactionProcessor::default();
+		state.process(source, error_handler).await.unwrap();
+
+		let source = StreamSource(tokio_stream::iter([
+			Ok::<_, SourceError>(Transaction::deposit(4, amount("1"), 1)),
+			Ok(Transaction::deposit(5, amount("1"), 2)),
+			Ok(Transaction::withdrawal(6, amount("1"), 2)),
+			Ok(Transaction::withdrawal(7, amount("5"), 3)),
+			Ok(Transaction::deposit(8, amount("1"), 4)),
+		]));
+		let mut processor = TransactionProcessor::default()
+			.with_state(state.snapshot().await)
+			.with_change_tracking();
+		processor.process(source, error_handler).await.unwrap();
+
+		let mut changed = Vec::new();
+		processor.write_changed_accounts(&mut changed).await.unwrap();
+		let clients = changed.iter().map(|account| account.client_id).sorted().collect_vec();
+		assert_eq!(clients, vec![1, 4]);
+		assert_eq!(processor.get_accounts().await.len(), 4);
+	}
+
+	#[tokio::test]
+	async fn test_process_transactions_with_balance_cap() {
 		enable_debug_logs();
 
 		let transactions_csv = TestTransactionsCsvBuilder::new()
-			.deposit("1", "1", "1")
-			.deposit("1", "2", "1")
-			.deposit("1", "3", "1")
-			.withdrawal("1", "4", "1")
-			.dispute("1", "3")
+			.deposit("1", "1", "8")
+			.deposit("1", "2", "5")
+			.deposit("2", "3", "8")
+			.deposit("2", "4", "5")
+			.dispute("2", "4")
 			.write()
 			.await;
 
 		let reader = transactions_csv.reader().await;
-		let accounts =
-			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+		let mut processor = TransactionProcessor::default().with_balance_cap(BalanceCap {
+			global: Some(amount("10")),
+			per_client: HashMap::from([(2, amount("10"))]),
+			policy: CapPolicy::PartiallyApply,
+		});
+		processor.process(reader, error_handler).await.unwrap();
 
-		assert_eq!(accounts.len(), 1);
+		let accounts = processor.get_accounts().await;
+		let account = accounts.iter().find(|a| a.client_id == 2).unwrap();
+		assert_eq!(account.available, amount("8"));
+		assert_eq!(account.held, amount("2"));
 
-		let account = &accounts[0];
-		assert_eq!(account.client_id, 1);
-		assert_eq!(account.available, amount("1"));
-		assert_eq!(account.held, amount("1"));
-		assert_eq!(account.total(), amount("2"));
-		assert!(!account.locked);
+		let transactions_csv = TestTransactionsCsvBuilder::new()
+			.deposit("1", "1", "8")
+			.deposit("1", "2", "5")
+			.write()
+			.await;
+		let reader = transactions_csv.reader().await;
+		let mut processor = TransactionProcessor::default()
+			.with_balance_cap(BalanceCap { global: Some(amount("10")), ..BalanceCap::default() });
+		let errors = std::sync::Mutex::new(Vec::new());
+		processor.process(reader, |e| errors.lock().unwrap().push(e)).await.unwrap();
+
+		assert_eq!(processor.get_accounts().await[0].available, amount("8"));
+		assert!(matches!(
+			errors.lock().unwrap().as_slice(),
+			[TransactionProcessorError::TransactionProcessingError(BalanceCapExceeded(_))]
+		));
 	}
 
 	#[tokio::test]
-	async fn test_process_transactions_with_dispute_and_resolve() {
+	async fn test_info_counts_processed_transactions() {
 		enable_debug_logs();
 
 		let transactions_csv = TestTransactionsCsvBuilder::new()
 			.deposit("1", "1", "1")
-			.deposit("1", "2", "1")
-			.deposit("1", "3", "1")
-			.withdrawal("1", "4", "1")
-			.dispute("1", "3")
-			.resolve("1", "3")
+			.deposit("1", "2", "x")
+			.withdrawal("1", "3", "5")
 			.write()
 			.await;
 
 		let reader = transactions_csv.reader().await;
-		let accounts =
-			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
-
-		assert_eq!(accounts.len(), 1);
+		let mut processor = TransactionProcessor::default().with_duplicate_detector(3);
+		processor.process(reader, error_handler).await.unwrap();
 
-		let account = &accounts[0];
-		assert_eq!(account.client_id, 1);
-		assert_eq!(account.available, amount("2"));
-		assert_eq!(account.held, amount("0"));
-		assert_eq!(account.total(), amount("2"));
-		assert!(!account.locked);
+		let info = processor.info().await;
+		assert_eq!(
+			info.counters,
+			ProcessorCounters {
+				records_read: 3,
+				parse_errors: 1,
+				applied: 1,
+				rejected: 1,
+				applied_by_type: [("deposit", 1)].into(),
+				rejected_by_reason: [("insufficient_funds", 1)].into(),
+				deposited: amount("1"),
+				withdrawn: Amount::default(),
+				refunded: Amount::default(),
+				expired_authorizations: 0,
+				interest: amount("0"),
+			}
+		);
+		assert_eq!(info.accounts, 1);
+		assert_eq!(info.duplicate_window, Some(3));
+		assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+		assert!(info.to_string().contains("duplicate_window: 3\n"));
 	}
 
 	#[tokio::test]
-	async fn test_process_transactions_with_dispute_and_chargeback() {
+	async fn test_snapshot_to_yaml() {
 		enable_debug_logs();
 
 		let transactions_csv = TestTransactionsCsvBuilder::new()
-			.deposit("1", "1", "1")
-			.deposit("1", "2", "1")
-			.deposit("1", "3", "1")
-			.withdrawal("1", "4", "1")
-			.dispute("1", "3")
-			.resolve("1", "3")
-			.dispute("1", "3")
-			.chargeback("1", "3")
+			.deposit("2", "3", "1")
+			.deposit("1", "2", "2")
+			.deposit("1", "1", "3")
+			.dispute("1", "2")
 			.write()
 			.await;
 
 		let reader = transactions_csv.reader().await;
-		let accounts =
-			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+		let mut processor = TransactionProcessor::default();
+		processor.process(reader, error_handler).await.unwrap();
 
-		assert_eq!(accounts.len(), 1);
-
-		let account = &accounts[0];
-		assert_eq!(account.client_id, 1);
-		assert_eq!(account.available, amount("1"));
-		assert_eq!(account.held, amount("0"));
-		assert_eq!(account.total(), amount("1"));
-		assert!(account.locked);
+		let expected = "\
+sequence: 4
+offset: 64
+accounts:
+- client: 1
+  available: '3'
+  held: '2'
+  total: '5'
+  locked: false
+  activity:
+    deposits: 2
+    withdrawals: 0
+    open_disputes: 1
+    locked_by: null
+  unsettled:
+    credits: '5'
+    debits: '0.0'
+  transactions:
+  - tx: 1
+    type: deposit
+    amount: '3'
+    state: okay
+  - tx: 2
+    type: deposit
+    amount: '2'
+    state: disputed
+- client: 2
+  available: '1'
+  held: '0.0'
+  total: '1'
+  locked: false
+  activity:
+    deposits: 1
+    withdrawals: 0
+    open_disputes: 0
+    locked_by: null
+  unsettled:
+    credits: '1'
+    debits: '0.0'
+  transactions:
+  - tx: 3
+    type: deposit
+    amount: '1'
+    state: okay
+retired_tx_ids: []
+";
+		assert_eq!(processor.snapshot().await.to_yaml().unwrap(), expected);
 	}
 
 	#[tokio::test]
-	async fn test_process_transactions_with_dispute_and_resolve_and_further_dispute_and_chargeback()
-	{
+	async fn test_resume_from_checkpoint() {
 		enable_debug_logs();
 
 		let transactions_csv = TestTransactionsCsvBuilder::new()
-			.deposit("1", "1", "1")
-			.deposit("1", "2", "1")
-			.deposit("1", "3", "1")
-			.withdrawal("1", "4", "1")
-			.dispute("1", "3")
-			.chargeback("1", "3")
+			.deposit("1", "1", "5")
+			.dispute("1", "1")
+			.chargeback("1", "1")
+			.deposit("2", "2", "3")
+			.deposit("2", "1", "1")
+			.withdrawal("2", "3", "1")
 			.write()
 			.await;
 
-		let reader = transactions_csv.reader().await;
-		let accounts =
-			TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+		let checkpoints = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+		let written = checkpoints.clone();
+		let mut processor = TransactionProcessor::default().with_checkpoints(4, move |snapshot| {
+			written.lock().unwrap().push(snapshot.to_yaml().unwrap());
+			Ok(())
+		});
+		processor.process(transactions_csv.reader().await, error_handler).await.unwrap();
+		let expected = processor.snapshot().await;
 
-		assert_eq!(accounts.len(), 1);
+		let checkpoint = checkpoints.lock().unwrap()[0].clone();
+		let snapshot = ProcessorSnapshot::from_yaml(&checkpoint).unwrap();
+		assert_eq!((snapshot.sequence, snapshot.retired_tx_ids.as_slice()), (4, [1].as_slice()));
+		assert_eq!(snapshot.offset, Some(65));
+		let mut resumed = TransactionProcessor::default().with_snapshot(snapshot);
+		resumed.process(transactions_csv.reader().await, error_handler).await.unwrap();
 
-		let account = &accounts[0];
-		assert_eq!(account.client_id, 1);
-		assert_eq!(account.available, amount("1"));
-		assert_eq!(account.held, amount("0"));
-		assert_eq!(account.total(), amount("1"));
-		assert!(account.locked);
+		assert_eq!(resumed.snapshot().await, expected);
+		assert_eq!(resumed.info().await.counters.applied, 1);
+
+		// Input seeked to the offset of the checkpoint is not read again before it.
+		let snapshot = ProcessorSnapshot::from_yaml(&checkpoint).unwrap();
+		let source = CsvSource::new(transactions_csv.reader().await, CsvFormat::default());
+		let source = source.seek_to(65).await.unwrap();
+		let mut seeked = TransactionProcessor::default().with_snapshot_at_offset(snapshot);
+		seeked.process(source, error_handler).await.unwrap();
+
+		assert_eq!(seeked.snapshot().await, expected);
+		assert_eq!(seeked.info().await.counters.applied, 1);
 	}
 
 	fn enable_debug_logs() {
@@ -0,0 +1,108 @@
+use domain::amount::Amount;
+use domain::config::{ClientId, Timestamp, TransactionId};
+use domain::transaction::Transaction;
+
+/// A payment withdrawn from a client's account at a fixed interval, such as a subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringPayment {
+	pub client: ClientId,
+	pub amount: Amount,
+	/// The time of the first payment.
+	pub start: Timestamp,
+	/// The number of seconds between payments.
+	pub interval: i64,
+	/// The time after which no more payments are made, if any.
+	pub end: Option<Timestamp>,
+	/// The transaction id of the first payment. Each further payment takes the next id, so the
+	/// range of ids must not be used by other transactions.
+	pub first_id: TransactionId,
+}
+
+impl RecurringPayment {
+	/// Returns the time and transaction id of the `n`th payment, if it is made.
+	fn occurrence(&self, n: u32) -> Option<(Timestamp, TransactionId)> {
+		let time = self.start.checked_add(self.interval.checked_mul(n.into())?)?;
+		let id = self.first_id.checked_add(n.try_into().ok()?)?;
+		self.end.is_none_or(|end| time <= end).then_some((time, id))
+	}
+}
+
+/// Expands recurring payments into the withdrawals due as time advances.
+///
+/// Payments have deterministic transaction ids, so that a processor skips the payments it already
+/// applied when a schedule is replayed, rather than charging them twice.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+	/// The payments with the number of their occurrences already due.
+	payments: Vec<(RecurringPayment, u32)>,
+}
+
+impl Scheduler {
+	/// Creates a scheduler of the given payments.
+	///
+	/// # Panics
+	///
+	/// Panics if the interval of a payment is not positive.
+	pub fn new(payments: impl IntoIterator<Item = RecurringPayment>) -> Self {
+		let payments = payments
+			.into_iter()
+			.inspect(|payment| assert!(payment.interval > 0, "payment interval must be positive"))
+			.map(|payment| (payment, 0))
+			.collect();
+		Self { payments }
+	}
+
+	/// Returns the withdrawals that became due up to and including `until` since the previous
+	/// call, ordered by time and then transaction id.
+	pub(crate) fn due(&mut self, until: Timestamp) -> Vec<(Timestamp, Transaction)> {
+		let mut due = Vec::new();
+		for (payment, next) in &mut self.payments {
+			while let Some((time, id)) =
+				payment.occurrence(*next).filter(|(time, _)| *time <= until)
+			{
+				due.push((
+					time,
+					Transaction::withdrawal(id, payment.amount.clone(), payment.client),
+				));
+				*next += 1;
+			}
+		}
+		due.sort_by_key(|(time, tx)| (*time, tx.id()));
+		due
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DAY: i64 = 24 * 60 * 60;
+
+	fn payment(
+		client: ClientId,
+		start: Timestamp,
+		end: Option<Timestamp>,
+		first_id: i32,
+	) -> RecurringPayment {
+		let amount = Amount::try_from("9.99").unwrap();
+		RecurringPayment { client, amount, start, interval: 30 * DAY, end, first_id }
+	}
+
+	#[test]
+	fn test_due() {
+		let mut scheduler =
+			Scheduler::new([payment(1, 0, Some(60 * DAY), 100), payment(2, 10 * DAY, None, 200)]);
+		let ids = |due: Vec<(Timestamp, Transaction)>| {
+			due.iter().map(|(_, tx)| tx.id()).collect::<Vec<_>>()
+		};
+
+		assert_eq!(ids(scheduler.due(-1)), Vec::<TransactionId>::new());
+		assert_eq!(ids(scheduler.due(0)), vec![100]);
+		assert_eq!(ids(scheduler.due(0)), Vec::<TransactionId>::new());
+		assert_eq!(ids(scheduler.due(40 * DAY)), vec![200, 101, 201]);
+		assert_eq!(
+			ids(scheduler.due(365 * DAY)),
+			[102].into_iter().chain(202..=211).collect::<Vec<_>>()
+		);
+	}
+}
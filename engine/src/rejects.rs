@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+use domain::amount::Amount;
+use domain::config::{ClientId, Timestamp, TransactionId};
+use domain::source::SourceError;
+use domain::transaction::{Transaction, TransactionError};
+
+use crate::sequencer::SequenceNumber;
+
+/// An input record that was skipped, with the reason code of the rejection, for reconciling with
+/// the sender.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RejectedRecord {
+	/// The sequence number of the input record.
+	pub sequence: SequenceNumber,
+	/// The reason code, `parse_error` or one of [`TransactionError::code`].
+	pub reason: &'static str,
+	/// The fields of the original record, empty for records that could not be read.
+	#[serde(rename = "type")]
+	pub tx_type: Option<&'static str>,
+	pub client: Option<ClientId>,
+	pub tx: Option<TransactionId>,
+	pub amount: Option<Amount>,
+	pub timestamp: Option<Timestamp>,
+	/// The memo, merchant and reference of the original record.
+	pub memo: Option<String>,
+	pub merchant: Option<String>,
+	#[serde(rename = "reference")]
+	pub external_reference: Option<String>,
+	/// Further details, such as the parse error of an unreadable record.
+	pub detail: String,
+}
+
+impl RejectedRecord {
+	/// Creates the record of the transaction `tx`, rejected with `error`.
+	pub fn rejected(sequence: SequenceNumber, tx: &Transaction, error: &TransactionError) -> Self {
+		let annotations = tx.annotations();
+		Self {
+			sequence,
+			reason: error.code(),
+			tx_type: Some(tx.type_name()),
+			client: Some(*tx.client_id()),
+			tx: Some(tx.id()),
+			amount: tx.amount().cloned(),
+			timestamp: tx.timestamp(),
+			memo: annotations.memo,
+			merchant: annotations.merchant,
+			external_reference: annotations.external_reference,
+			detail: match error {
+				TransactionError::InternalError(_, detail) => detail.to_string(),
+				_ => String::new(),
+			},
+		}
+	}
+
+	/// Creates the record of an input record that could not be read as a transaction.
+	pub fn unreadable(sequence: SequenceNumber, error: &SourceError) -> Self {
+		Self {
+			sequence,
+			reason: "parse_error",
+			tx_type: None,
+			client: None,
+			tx: None,
+			amount: None,
+			timestamp: None,
+			memo: None,
+			merchant: None,
+			external_reference: None,
+			detail: error.to_string(),
+		}
+	}
+}
@@ -0,0 +1,56 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use domain::amount::Amount;
+
+/// Summary statistics of a processing run, answering how many records failed and why, and how
+/// much money moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingReport {
+	/// Input records read, including those that could not be parsed.
+	pub records_read: u64,
+	/// Input records that could not be parsed into a transaction.
+	pub parse_errors: u64,
+	/// Transactions applied to an account.
+	pub applied: u64,
+	/// Transactions rejected, by [`TransactionError::code`](domain::transaction::TransactionError::code).
+	pub rejected_by_reason: BTreeMap<&'static str, u64>,
+	/// Accounts created by this run's transactions, excluding those restored from a snapshot.
+	pub accounts_created: usize,
+	/// Accounts locked at the end of the run.
+	pub accounts_locked: usize,
+	/// The sum of the applied deposits.
+	pub deposited: Amount,
+	/// The sum of the applied withdrawals.
+	pub withdrawn: Amount,
+	/// The sum of the applied refunds.
+	pub refunded: Amount,
+	/// Authorizations released for not being captured in time.
+	pub expired_authorizations: u64,
+	/// The sum of the interest accrued.
+	pub interest: Amount,
+	/// The transactions disputed at the end of the run, by stage of the dispute.
+	pub open_disputes_by_stage: BTreeMap<&'static str, u64>,
+}
+
+impl fmt::Display for ProcessingReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "records_read: {}", self.records_read)?;
+		writeln!(f, "parse_errors: {}", self.parse_errors)?;
+		writeln!(f, "applied: {}", self.applied)?;
+		writeln!(f, "rejected: {}", self.rejected_by_reason.values().sum::<u64>())?;
+		for (reason, count) in &self.rejected_by_reason {
+			writeln!(f, "rejected.{reason}: {count}")?;
+		}
+		writeln!(f, "accounts_created: {}", self.accounts_created)?;
+		writeln!(f, "accounts_locked: {}", self.accounts_locked)?;
+		for (stage, count) in &self.open_disputes_by_stage {
+			writeln!(f, "open_disputes.{stage}: {count}")?;
+		}
+		writeln!(f, "deposited: {}", self.deposited)?;
+		writeln!(f, "withdrawn: {}", self.withdrawn)?;
+		writeln!(f, "refunded: {}", self.refunded)?;
+		writeln!(f, "expired_authorizations: {}", self.expired_authorizations)?;
+		writeln!(f, "interest: {}", self.interest)
+	}
+}
@@ -0,0 +1,225 @@
+use core::fmt;
+
+use itertools::Itertools;
+use serde::Deserialize;
+
+use domain::account::Account;
+use domain::amount::Amount;
+use domain::config::ClientId;
+use domain::transaction::{Transaction, TransactionRow};
+
+use crate::processor::TransactionProcessor;
+
+/// A scripted sequence of transactions together with the expected resulting account states,
+/// written in TOML:
+///
+/// ```toml
+/// name = "chargeback locks the account"
+///
+/// [[steps]]
+/// type = "deposit"
+/// client = 1
+/// tx = 1
+/// amount = "1.0"
+///
+/// [[steps]]
+/// type = "dispute"
+/// client = 1
+/// tx = 1
+///
+/// [[steps]]
+/// type = "chargeback"
+/// client = 1
+/// tx = 1
+///
+/// [[expect]]
+/// client = 1
+/// available = "0"
+/// held = "0"
+/// locked = true
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+	#[serde(default)]
+	pub name: String,
+	/// The transactions to process, in order.
+	#[serde(default)]
+	steps: Vec<TransactionRow>,
+	/// The assertions on the resulting accounts.
+	#[serde(default, rename = "expect")]
+	expectations: Vec<AccountExpectation>,
+}
+
+/// The expected state of a single account. Omitted fields are not checked.
+#[derive(Debug, Deserialize)]
+struct AccountExpectation {
+	client: ClientId,
+	available: Option<Amount>,
+	held: Option<Amount>,
+	total: Option<Amount>,
+	locked: Option<bool>,
+}
+
+/// The outcome of running a [`Scenario`].
+#[derive(Debug, PartialEq)]
+pub struct ScenarioReport {
+	pub name: String,
+	/// The number of steps that were rejected while processing.
+	pub rejected_steps: usize,
+	/// A description of every expectation that did not hold.
+	pub failures: Vec<String>,
+}
+
+impl ScenarioReport {
+	/// Returns whether all expectations held.
+	pub fn passed(&self) -> bool {
+		self.failures.is_empty()
+	}
+}
+
+impl fmt::Display for ScenarioReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let status = if self.passed() { "PASS" } else { "FAIL" };
+		writeln!(f, "{status}: {} ({} rejected steps)", self.name, self.rejected_steps)?;
+		self.failures.iter().try_for_each(|failure| writeln!(f, "  {failure}"))
+	}
+}
+
+impl Scenario {
+	/// Parses a scenario from its TOML representation.
+	///
+	/// # Errors
+	///
+	/// Returns a `toml::de::Error` if the input is not a valid scenario.
+	pub fn from_toml(input: &str) -> Result<Self, toml::de::Error> {
+		toml::from_str(input)
+	}
+
+	/// Processes the scenario steps and checks the expectations against the resulting accounts.
+	///
+	/// Steps that are invalid or rejected by the processor are counted rather than failing the
+	/// scenario, so scenarios can exercise error handling as well.
+	pub async fn run(self) -> ScenarioReport {
+		let mut processor = TransactionProcessor::default();
+		let mut rejected_steps = 0;
+		for step in self.steps {
+			let applied = match Transaction::try_from(Ok(step)) {
+				Ok(tx) => processor.handle_transaction(tx).await.is_ok(),
+				Err(_) => false,
+			};
+			if !applied {
+				rejected_steps += 1;
+			}
+		}
+
+		let accounts = processor.get_accounts().await;
+		let failures = self
+			.expectations
+			.iter()
+			.flat_map(|expectation| {
+				match accounts.iter().find(|account| account.client_id == expectation.client) {
+					Some(account) => expectation.check(account),
+					None => vec![format!("client {}: account not found", expectation.client)],
+				}
+			})
+			.collect_vec();
+
+		ScenarioReport { name: self.name, rejected_steps, failures }
+	}
+}
+
+impl AccountExpectation {
+	/// Returns a description of every field of `account` that does not match the expectation.
+	fn check(&self, account: &Account) -> Vec<String> {
+		let total = account.total();
+		let amounts = [
+			("available", &self.available, &account.available),
+			("held", &self.held, &account.held),
+			("total", &self.total, &total),
+		];
+		let mut failures = amounts
+			.into_iter()
+			.filter_map(|(field, expected, actual)| match expected {
+				Some(expected) if expected != actual => Some(format!(
+					"client {}: expected {field} {expected}, got {actual}",
+					self.client
+				)),
+				_ => None,
+			})
+			.collect_vec();
+		if let Some(locked) = self.locked.filter(|locked| *locked != account.locked) {
+			failures.push(format!(
+				"client {}: expected locked {locked}, got {}",
+				self.client, account.locked
+			));
+		}
+		failures
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SCENARIO: &str = r#"
+name = "dispute and chargeback"
+
+[[steps]]
+type = "deposit"
+client = 1
+tx = 1
+amount = "2.0"
+
+[[steps]]
+type = "withdrawal"
+client = 1
+tx = 2
+amount = "5.0"
+
+[[steps]]
+type = "dispute"
+client = 1
+tx = 1
+
+[[steps]]
+type = "chargeback"
+client = 1
+tx = 1
+
+[[expect]]
+client = 1
+available = "0"
+held = "0"
+total = "0"
+locked = true
+"#;
+
+	#[tokio::test]
+	async fn test_run_passing_scenario() {
+		let report = Scenario::from_toml(SCENARIO).unwrap().run().await;
+
+		assert!(report.passed(), "{report}");
+		assert_eq!(report.name, "dispute and chargeback");
+		assert_eq!(report.rejected_steps, 1);
+	}
+
+	#[tokio::test]
+	async fn test_run_failing_scenario() {
+		let scenario =
+			SCENARIO.replace("locked = true", "locked = false\n\n[[expect]]\nclient = 2");
+		let report = Scenario::from_toml(&scenario).unwrap().run().await;
+
+		assert_eq!(
+			report.failures,
+			vec![
+				"client 1: expected locked false, got true".to_string(),
+				"client 2: account not found".to_string()
+			]
+		);
+	}
+
+	#[test]
+	fn test_from_toml_rejects_invalid_steps() {
+		assert!(Scenario::from_toml("[[steps]]\ntype = \"rebate\"\nclient = 1\ntx = 1").is_err());
+	}
+}
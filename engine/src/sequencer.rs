@@ -0,0 +1,65 @@
+use domain::transaction::{Stream, StreamExt};
+
+/// The position of an input record in the authoritative apply order, starting at 1.
+pub type SequenceNumber = u64;
+
+/// Assigns the authoritative apply order to input records.
+///
+/// For batch input this is the input order: records are numbered as they are read and the
+/// processor applies each one before reading the next, so replaying the same input applies
+/// the same transactions in the same order.
+#[derive(Debug)]
+pub struct Sequencer {
+	next: SequenceNumber,
+}
+
+impl Default for Sequencer {
+	fn default() -> Self {
+		Self { next: 1 }
+	}
+}
+
+impl Sequencer {
+	/// Creates a sequencer whose next assigned sequence number is `next`.
+	pub fn starting_at(next: SequenceNumber) -> Self {
+		Self { next }
+	}
+
+	/// Assigns the next sequence number.
+	pub fn assign(&mut self) -> SequenceNumber {
+		let sequence = self.next;
+		self.next += 1;
+		sequence
+	}
+
+	/// Numbers every item of `stream` in the order it is produced.
+	pub fn sequence<S: Stream>(
+		mut self,
+		stream: S,
+	) -> impl Stream<Item = (SequenceNumber, S::Item)> {
+		stream.map(move |item| (self.assign(), item))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_sequence_numbers_follow_input_order() {
+		let sequenced: Vec<_> = Sequencer::default()
+			.sequence(tokio_stream::iter(["a", "b", "c"]))
+			.collect()
+			.await;
+
+		assert_eq!(sequenced, vec![(1, "a"), (2, "b"), (3, "c")]);
+	}
+
+	#[test]
+	fn test_starting_at() {
+		let mut sequencer = Sequencer::starting_at(42);
+
+		assert_eq!(sequencer.assign(), 42);
+		assert_eq!(sequencer.assign(), 43);
+	}
+}
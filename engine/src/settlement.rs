@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use domain::amount::{Amount, SignedAmount};
+
+/// The deposits, withdrawals, fees, refunds and captures of a client applied since its last
+/// settlement, and the reversals and chargebacks returning them, netted as they are applied.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnsettledBatch {
+	/// The sum of the deposits and refunds, and of the returned withdrawals.
+	pub credits: Amount,
+	/// The sum of the withdrawals, fees and captures, and of the returned deposits.
+	pub debits: Amount,
+}
+
+/// The direction and amount of money moved by a settlement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetAmount {
+	/// The credits exceed the debits, so the net amount is paid out of the available balance.
+	Payout(Amount),
+	/// The debits exceed the credits, so the net amount is collected into the available balance.
+	Collection(Amount),
+}
+
+impl UnsettledBatch {
+	/// Returns the net position of the batch, the credits less the debits.
+	pub fn net_position(&self) -> SignedAmount {
+		SignedAmount::difference(&self.credits, &self.debits)
+			.expect("difference of two amounts should not overflow")
+	}
+
+	/// Nets the return of `amount` of an earlier deposit, or withdrawal if `withdrawal`, by a
+	/// reversal or chargeback. The deposit may have been paid out by an earlier settlement, so the
+	/// return is netted against the next one rather than the deposit.
+	pub fn add_return(&mut self, amount: &Amount, withdrawal: bool) {
+		let side = if withdrawal { &mut self.credits } else { &mut self.debits };
+		side.saturating_add_assign(amount);
+	}
+
+	/// Returns the net amount of the batch.
+	pub fn net(&self) -> NetAmount {
+		let position = self.net_position();
+		match Amount::try_from(position.clone()) {
+			Ok(payout) => NetAmount::Payout(payout),
+			Err(_) => NetAmount::Collection(position.abs()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_net() {
+		let amount = |value| Amount::try_from(value).unwrap();
+		let batch =
+			|credits, debits| UnsettledBatch { credits: amount(credits), debits: amount(debits) };
+
+		assert_eq!(batch("10", "2.5").net(), NetAmount::Payout(amount("7.5")));
+		assert_eq!(batch("1", "3").net(), NetAmount::Collection(amount("2")));
+		assert_eq!(UnsettledBatch::default().net(), NetAmount::Payout(amount("0")));
+		assert_eq!(batch("1", "3").net_position().to_string(), "-2");
+	}
+}
@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use rusty_money::Money;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use domain::account::{Account, AccountActivity};
+use domain::amount::Amount;
+use domain::config::{find_currency, BatchId, ClientId, Timestamp, TransactionId};
+use domain::transaction::{RecordMetadata, Transaction, TransactionState};
+
+use crate::sequencer::SequenceNumber;
+use crate::settlement::UnsettledBatch;
+
+/// The full state of a processor: every account with the states of its recorded transactions.
+///
+/// Accounts are ordered by client and transactions by id, so that snapshots of the same state are
+/// identical and differences between snapshots diff readably.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProcessorSnapshot {
+	/// The sequence number of the last input record processed, 0 if none.
+	pub sequence: SequenceNumber,
+	/// The byte offset of the last input record processed in its source, if the source knows it,
+	/// to resume reading the source from it.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub offset: Option<u64>,
+	pub accounts: Vec<AccountSnapshot>,
+	/// The ids of applied transactions no longer recorded in an account, such as charged back
+	/// ones, which remain taken.
+	pub retired_tx_ids: Vec<TransactionId>,
+	/// The idempotency keys of the records handled, which retries may not reuse.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub idempotency_keys: Vec<String>,
+}
+
+/// The state of a single account and its recorded transactions.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AccountSnapshot {
+	pub client: ClientId,
+	/// Negative if the account is overdrawn.
+	#[serde(deserialize_with = "Amount::deserialize_balance")]
+	pub available: Amount,
+	pub held: Amount,
+	#[serde(deserialize_with = "Amount::deserialize_balance")]
+	pub total: Amount,
+	pub locked: bool,
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub closed: bool,
+	#[serde(default)]
+	pub activity: AccountActivity,
+	/// The available sub-balances in other currencies than the base currency, by currency code.
+	#[serde(
+		default,
+		skip_serializing_if = "BTreeMap::is_empty",
+		deserialize_with = "deserialize_balances"
+	)]
+	pub balances: BTreeMap<String, Decimal>,
+	/// The held sub-balances in other currencies than the base currency, by currency code.
+	#[serde(
+		default,
+		skip_serializing_if = "BTreeMap::is_empty",
+		deserialize_with = "deserialize_balances"
+	)]
+	pub held_balances: BTreeMap<String, Decimal>,
+	/// The transactions applied since the last settlement, if any.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub unsettled: Option<UnsettledBatch>,
+	/// The funds held by manual holds and not released yet, if any.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub manual_hold: Option<Amount>,
+	/// The batches settled, which cannot be settled again.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub settled_batches: Vec<BatchId>,
+	pub transactions: Vec<TransactionSnapshot>,
+}
+
+/// The types of recorded transactions.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedType {
+	Deposit,
+	Withdrawal,
+	Fee,
+	Refund,
+	Authorization,
+	CapturedAuthorization,
+}
+
+/// The state of a recorded deposit, withdrawal, fee, refund or authorization. Only deposits,
+/// withdrawals and captured authorizations can be disputed, so the state of the others is always
+/// `okay`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransactionSnapshot {
+	pub tx: TransactionId,
+	#[serde(rename = "type")]
+	pub tx_type: RecordedType,
+	pub amount: Amount,
+	/// The currency of the amount, if not the base currency.
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "deserialize_currency"
+	)]
+	pub currency: Option<String>,
+	pub state: TransactionState,
+	/// The original transaction of a refund.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub original: Option<TransactionId>,
+	/// The disputed part of a partially disputed deposit or withdrawal.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub disputed: Option<Amount>,
+	/// The time a deposit or withdrawal was applied, if tracked for the dispute window.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub applied_at: Option<Timestamp>,
+	/// The time a deposit that has not cleared yet clears.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub clears_at: Option<Timestamp>,
+	/// Whether a dispute of the deposit or withdrawal was resolved before.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub resolved: bool,
+}
+
+impl AccountSnapshot {
+	pub(crate) fn new<'a>(
+		account: &Account,
+		transactions: impl Iterator<Item = &'a Transaction>,
+		applied_at: &HashMap<TransactionId, Timestamp>,
+		clearing: &HashMap<TransactionId, (ClientId, Timestamp)>,
+		resolved: &HashSet<TransactionId>,
+		unsettled: Option<&UnsettledBatch>,
+	) -> Self {
+		let mut transactions = transactions
+			.filter_map(|tx| {
+				let id = tx.id();
+				let clears_at = clearing.get(&id).map(|(_, clears_at)| *clears_at);
+				let applied_at = applied_at.get(&id).copied();
+				TransactionSnapshot::new(tx, applied_at, clears_at, resolved.contains(&id))
+			})
+			.collect::<Vec<_>>();
+		transactions.sort_by_key(|tx| tx.tx);
+		AccountSnapshot {
+			client: account.client_id,
+			available: account.available.clone(),
+			held: account.held.clone(),
+			total: account.total(),
+			locked: account.locked,
+			closed: account.closed,
+			activity: account.activity.clone(),
+			balances: decimals(&account.available_balances()),
+			held_balances: decimals(&account.held_balances()),
+			unsettled: unsettled.cloned(),
+			manual_hold: None,
+			settled_batches: Vec::new(),
+			transactions,
+		}
+	}
+
+	/// Returns the account this snapshot was taken of.
+	pub(crate) fn account(&self) -> Account {
+		let mut account =
+			Account::new(self.client, self.available.clone(), self.held.clone(), self.locked);
+		account.closed = self.closed;
+		account.activity = self.activity.clone();
+		for available in amounts(&self.balances) {
+			account.balance_in(available.value().currency()).available = available;
+		}
+		for held in amounts(&self.held_balances) {
+			account.balance_in(held.value().currency()).held = held;
+		}
+		account
+	}
+}
+
+/// Returns the values of currency sub-balances by currency code.
+fn decimals(balances: &BTreeMap<&'static str, &Amount>) -> BTreeMap<String, Decimal> {
+	balances
+		.iter()
+		.map(|(currency, balance)| (currency.to_string(), *balance.value().amount()))
+		.collect()
+}
+
+/// Returns the currency sub-balances of the given values, skipping unknown currencies, which
+/// deserialized snapshots do not contain.
+fn amounts(balances: &BTreeMap<String, Decimal>) -> impl Iterator<Item = Amount> + '_ {
+	balances.iter().filter_map(|(code, balance)| {
+		let currency = find_currency(code)?;
+		Amount::try_from(Money::from_decimal(*balance, currency)).ok()
+	})
+}
+
+/// Deserializes currency sub-balances, failing on a currency that is neither an ISO currency nor
+/// registered, so that a snapshot cannot be restored without its balances in that currency.
+fn deserialize_balances<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<BTreeMap<String, Decimal>, D::Error> {
+	let balances = BTreeMap::<String, Decimal>::deserialize(deserializer)?;
+	match balances.keys().find(|code| find_currency(code).is_none()) {
+		Some(code) => Err(de::Error::custom(format!("Unknown currency {code}"))),
+		None => Ok(balances),
+	}
+}
+
+/// Deserializes the currency of a recorded transaction, failing on an unknown currency like
+/// [`deserialize_balances`].
+fn deserialize_currency<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Option<String>, D::Error> {
+	let currency = Option::<String>::deserialize(deserializer)?;
+	match currency {
+		Some(code) if find_currency(&code).is_none() => {
+			Err(de::Error::custom(format!("Unknown currency {code}")))
+		},
+		currency => Ok(currency),
+	}
+}
+
+impl TransactionSnapshot {
+	fn new(
+		tx: &Transaction,
+		applied_at: Option<Timestamp>,
+		clears_at: Option<Timestamp>,
+		resolved: bool,
+	) -> Option<Self> {
+		let (tx_type, original) = match tx {
+			Transaction::Deposit { .. } => (RecordedType::Deposit, None),
+			Transaction::Withdrawal { .. } => (RecordedType::Withdrawal, None),
+			Transaction::Fee { .. } => (RecordedType::Fee, None),
+			Transaction::Refund { original, .. } => (RecordedType::Refund, Some(*original)),
+			Transaction::Authorization { captured: None, .. } => {
+				(RecordedType::Authorization, None)
+			},
+			Transaction::Authorization { .. } => (RecordedType::CapturedAuthorization, None),
+			_ => return None,
+		};
+		let amount = tx.amount()?;
+		Some(TransactionSnapshot {
+			tx: tx.id(),
+			tx_type,
+			amount: amount.clone(),
+			currency: (!amount.is_base_currency())
+				.then(|| amount.value().currency().iso_alpha_code.to_string()),
+			state: tx.state().copied().unwrap_or(TransactionState::Okay),
+			original,
+			disputed: tx.disputed_portion().cloned(),
+			applied_at,
+			clears_at,
+			resolved,
+		})
+	}
+
+	/// Returns the transaction of the given client this snapshot was taken of.
+	pub(crate) fn transaction(&self, client_id: ClientId) -> Transaction {
+		let (id, mut amount, state, mut disputed) =
+			(self.tx, self.amount.clone(), self.state, self.disputed.clone());
+		if let Some(currency) = self.currency.as_deref().and_then(find_currency) {
+			amount = amount.in_currency(currency);
+			disputed = disputed.map(|disputed| disputed.in_currency(currency));
+		}
+		let metadata = RecordMetadata::default();
+		match self.tx_type {
+			RecordedType::Deposit => {
+				Transaction::Deposit { id, amount, client_id, state, disputed, metadata }
+			},
+			RecordedType::Withdrawal => {
+				Transaction::Withdrawal { id, amount, client_id, state, disputed, metadata }
+			},
+			RecordedType::Fee => Transaction::Fee { id, amount, client_id, metadata },
+			RecordedType::Authorization => Transaction::Authorization {
+				id,
+				amount,
+				client_id,
+				captured: None,
+				disputed: None,
+				metadata,
+			},
+			RecordedType::CapturedAuthorization => Transaction::Authorization {
+				id,
+				amount,
+				client_id,
+				captured: Some(state),
+				disputed,
+				metadata,
+			},
+			RecordedType::Refund => Transaction::Refund {
+				id,
+				amount,
+				client_id,
+				original: self.original.unwrap_or_default(),
+				metadata,
+			},
+		}
+	}
+}
+
+impl ProcessorSnapshot {
+	/// Serializes the snapshot as YAML.
+	///
+	/// # Errors
+	///
+	/// Returns a `serde_yaml::Error` if serialization fails.
+	pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+		serde_yaml::to_string(self)
+	}
+
+	/// Deserializes a snapshot from YAML.
+	///
+	/// # Errors
+	///
+	/// Returns a `serde_yaml::Error` if the input is not a valid snapshot.
+	pub fn from_yaml(input: &str) -> Result<Self, serde_yaml::Error> {
+		serde_yaml::from_str(input)
+	}
+}
@@ -0,0 +1,65 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use itertools::Itertools;
+use rusty_money::iso::EUR;
+
+use domain::amount::Amount;
+use domain::transaction::Transaction;
+use engine::processor::TransactionProcessor;
+
+/// Counts every allocation made by this test binary, so the hot path can be checked for
+/// per-transaction allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const TRANSACTIONS: i32 = 10_000;
+/// Leaves room for the amortized growth of the account and transaction id maps only.
+const MAX_ALLOCATIONS: usize = 64;
+
+#[test]
+fn test_deposits_and_withdrawals_do_not_allocate_per_transaction() {
+	let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+	runtime.block_on(async {
+		let mut processor = TransactionProcessor::default();
+		let amount = Amount::try_from("1.0").unwrap();
+		let euros = amount.in_currency(EUR);
+		// A sub-balance in another currency makes copies of the account allocate.
+		let opening = Transaction::deposit(TRANSACTIONS + 1, euros.clone(), 1);
+		processor.handle_transaction(opening).await.unwrap();
+		let transactions = (1..=TRANSACTIONS)
+			.map(|id| match id % 4 {
+				1 => Transaction::deposit(id, amount.clone(), 1),
+				2 => Transaction::withdrawal(id, amount.clone(), 1),
+				3 => Transaction::deposit(id, euros.clone(), 1),
+				_ => Transaction::withdrawal(id, euros.clone(), 1),
+			})
+			.collect_vec();
+
+		let before = ALLOCATIONS.load(Ordering::Relaxed);
+		for tx in transactions {
+			processor.handle_transaction(tx).await.unwrap();
+		}
+		let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+		assert!(
+			allocations <= MAX_ALLOCATIONS,
+			"{allocations} allocations for {TRANSACTIONS} transactions"
+		);
+	});
+}
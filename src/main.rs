@@ -1,98 +1,1178 @@
 extern crate core;
 
+use std::borrow::Borrow;
 use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
+use async_compression::futures::bufread::{GzipDecoder, ZstdDecoder};
+use clap::{Parser, Subcommand, ValueEnum};
 use csv::WriterBuilder;
 
-use domain::account::Account;
+use domain::account::{Account, CapPolicy};
+use domain::amount::Amount;
+#[cfg(feature = "amqp")]
+use domain::amqp::AmqpSource;
+use domain::config::{
+	base_currency, find_currency, register_currency, AmountConfig, AmountParsing, ClientId,
+	DEFAULT_MAX_DECIMAL_PLACES,
+};
+#[cfg(feature = "kafka")]
+use domain::kafka::{self, KafkaSource};
+#[cfg(feature = "object-store")]
+use domain::object_store;
+use domain::output::{
+	AccountColumn, AccountSerializer, OutputConfigError, OutputValidationError, ReportingCurrency,
+};
+#[cfg(feature = "parquet")]
+use domain::parquet::ParquetSink;
+use domain::sink::{AccountSink, CsvSink, CurrencyRowsSink, JsonLinesSink, JsonSink, TableSink};
+use domain::source::{CsvSource, StreamSource};
+#[cfg(feature = "postgres")]
+use domain::sql::{SqlSink, SqlSource};
 use domain::transaction::TransactionError::{
-	AccountFrozen, DuplicateGlobalTransactionId, IllegalStateChange, InsufficientFunds,
-	InvalidTransactionId, TransactionNotFound,
+	AccountClosed, AccountFrozen, BalanceCapExceeded, BatchAlreadySettled, CurrencyMismatch,
+	DisputeExceedsOriginal, DisputeWindowExpired, DuplicateGlobalTransactionId,
+	DuplicateIdempotencyKey, FxRateUnavailable, FxSlippageExceeded, GroupRejected,
+	HeldFundsRemaining, IllegalStateChange, InsufficientFunds, InvalidTransactionId,
+	RefundExceedsOriginal, ReleaseExceedsHold, SelfTransfer, TransactionNotFound,
 };
-use domain::transaction::{File, TransactionError};
-use engine::processor::{TransactionProcessor, TransactionProcessorError};
-use log::error;
+use domain::transaction::{AsyncRead, CsvFormat, File, InputColumn, Transaction, TransactionError};
+use engine::fx::RateTable;
+use engine::limits::{BalanceCap, Overdraft};
+use engine::policy::DisputePolicy;
+use engine::processor::{ProcessRunError, TransactionProcessor, TransactionProcessorError};
+use engine::scenario::Scenario;
+use engine::snapshot::ProcessorSnapshot;
+use rust_decimal::{Decimal, RoundingStrategy};
+use rusty_money::iso::Currency;
+use rusty_money::Money;
+use serde::Serialize;
+use tokio::sync::Mutex;
 use TransactionError::InternalError;
 use TransactionProcessorError::{TransactionParsingError, TransactionProcessingError};
 
+mod metrics;
+mod repl;
+mod serve;
+mod watch;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+	#[command(subcommand)]
+	command: Option<Command>,
+	/// Write a report of likely accidental double submissions to this file
+	#[arg(long)]
+	duplicate_report: Option<String>,
+	/// Write the running ledger, every applied transaction with the resulting balances, to this file
+	#[arg(long)]
+	ledger: Option<String>,
+	/// Write every skipped input record with the reason code of its rejection to this file
+	#[arg(long)]
+	rejects: Option<String>,
+	/// The maximal transaction id distance between two submissions considered duplicates
+	#[arg(long, default_value_t = 10)]
+	duplicate_window: u32,
+	/// The account output columns in order, e.g. `client,locked,available` to omit `held` and `total`
+	#[arg(long, value_delimiter = ',')]
+	columns: Option<Vec<AccountColumn>>,
+	/// The format of the account output
+	#[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+	output_format: OutputFormat,
+	/// Rename an account output column header, e.g. `--rename client=client_id`
+	#[arg(long, value_parser = parse_rename)]
+	rename: Vec<(AccountColumn, String)>,
+	/// The currency of the account balances and of amounts without a currency, e.g. `EUR`
+	#[arg(long, default_value = "USD")]
+	base_currency: String,
+	/// Define a currency outside the ISO set with its decimal places, e.g. `--currency BTC=8`
+	#[arg(long, value_parser = parse_currency_definition)]
+	currency: Vec<(String, u32)>,
+	/// The maximum decimal places of input amounts, to which output balances are rounded, e.g. 8
+	/// for crypto currencies
+	#[arg(
+		long,
+		default_value_t = DEFAULT_MAX_DECIMAL_PLACES,
+		value_parser = clap::value_parser!(u8).range(0..=28)
+	)]
+	max_decimal_places: u8,
+	/// How output balances are rounded to the maximum decimal places
+	#[arg(long, value_enum, default_value_t = Rounding::HalfAwayFromZero)]
+	rounding: Rounding,
+	/// Accept input amounts with thousands separators and the base currency's symbol or code,
+	/// such as `"$1,234.56"`
+	#[arg(long)]
+	lenient_amounts: bool,
+	/// Read input amounts as integers of minor units of their currency, such as `12345` cents for
+	/// 123.45 USD
+	#[arg(long, conflicts_with = "lenient_amounts")]
+	minor_units: bool,
+	/// The maximum total balance of every account
+	#[arg(long, value_parser = parse_amount)]
+	max_balance: Option<Decimal>,
+	/// The maximum total balance of a single account, e.g. `--client-max-balance 1=500.0`
+	#[arg(long, value_parser = parse_client_amount)]
+	client_max_balance: Vec<(ClientId, Decimal)>,
+	/// How far below zero withdrawals and holds may take the available balance of every account
+	#[arg(long, value_parser = parse_amount)]
+	overdraft: Option<Decimal>,
+	/// The overdraft limit of a single account, e.g. `--client-overdraft 1=50.0`
+	#[arg(long, value_parser = parse_client_amount)]
+	client_overdraft: Vec<(ClientId, Decimal)>,
+	/// Makes an account a credit line with the given credit limit, e.g. `--credit-line 1=1000.0`
+	#[arg(long, value_parser = parse_client_amount)]
+	credit_line: Vec<(ClientId, Decimal)>,
+	/// Apply deposits exceeding the maximum balance up to the maximum instead of rejecting them
+	#[arg(long)]
+	partial_deposits: bool,
+	/// Release the holds of authorizations not captured within this many further input records
+	#[arg(long)]
+	authorization_expiry: Option<u64>,
+	/// Release the holds of timestamped authorizations not captured within this many seconds
+	#[arg(long)]
+	authorization_window: Option<i64>,
+	/// Hold deposits for this many seconds of input timestamps before they become available
+	#[arg(long)]
+	clearing_period: Option<i64>,
+	/// The exchange rate of `convert` rows between two currencies, e.g. `--fx-rate USD/EUR=0.92`
+	#[arg(long, value_parser = parse_fx_rate)]
+	fx_rate: Vec<((String, String), Decimal)>,
+	/// Load exchange rates from a CSV file of `from,to,rate` rows, overridden by `--fx-rate`
+	#[arg(long, value_parser = parse_fx_rates_file)]
+	fx_rates: Option<RateTable>,
+	/// Convert all balances into this currency for the `reporting_total` column, at the exchange
+	/// rates of `--fx-rate` and `--fx-rates`
+	#[arg(long)]
+	reporting_currency: Option<String>,
+	/// Reject `convert` rows whose rate deviates from `--fx-rate` by more than this fraction
+	#[arg(long)]
+	fx_max_slippage: Option<Decimal>,
+	/// Reject chargebacks of transactions that were disputed, resolved and disputed again
+	#[arg(long)]
+	no_chargeback_after_redispute: bool,
+	/// Allow `reopen` rows to re-open the disputes of charged back transactions
+	#[arg(long)]
+	reopen_chargebacks: bool,
+	/// In `serve` and `watch` modes, expose Prometheus metrics at `/metrics` on this address
+	#[arg(long)]
+	metrics: Option<String>,
+	/// Do not report rejected and unreadable records or the run summary on stderr, only fatal
+	/// errors
+	#[arg(long, short)]
+	quiet: bool,
+	/// Print the effective configuration, counters and build information to stderr
+	#[arg(long)]
+	print_config: bool,
+	/// Write the full processor state, accounts and their transaction states, as YAML to this file
+	#[arg(long)]
+	snapshot_yaml: Option<String>,
+	/// The format of the transactions file, inferred from its extension by default
+	#[arg(long, value_enum)]
+	input_format: Option<InputFormat>,
+	/// The compression of the transactions file, inferred from a `.gz` or `.zst` extension by
+	/// default
+	#[arg(long, value_enum)]
+	input_compression: Option<InputCompression>,
+	/// The column order of CSV input without a header row, e.g. `tx,type,client,amount`, with `_`
+	/// for columns to skip
+	#[arg(long, value_delimiter = ',')]
+	input_columns: Option<Vec<InputColumn>>,
+	/// The field delimiter of CSV input, a single character or `tab`
+	#[arg(long, value_parser = parse_delimiter)]
+	delimiter: Option<u8>,
+	/// Periodically write the processor state to this file, to resume an interrupted run from
+	#[arg(long)]
+	checkpoint: Option<String>,
+	/// The number of input records between checkpoints
+	#[arg(long, default_value_t = 100_000)]
+	checkpoint_interval: u64,
+	/// Resume an interrupted run from its checkpoint file, skipping the records processed before it
+	#[arg(long)]
+	resume: Option<String>,
+	/// Start from the final state of a previous run, as written by `--snapshot-yaml`, to process
+	/// new transactions files incrementally
+	#[arg(long, conflicts_with = "resume")]
+	state: Option<String>,
+	/// Only output the accounts whose balances or locked status changed during this run
+	#[arg(long)]
+	changed_only: bool,
+	/// Write one output row per client and currency, the base currency row followed by a row for
+	/// each currency sub-balance, with a `currency` column
+	#[arg(long)]
+	currency_rows: bool,
+	/// After the transactions files, process the messages of `--amqp-queue` on this AMQP broker,
+	/// e.g. `amqp://localhost:5672/%2f`, until it cancels the consumer
+	#[cfg(feature = "amqp")]
+	#[arg(long, requires = "amqp_queue")]
+	amqp: Option<String>,
+	/// The queue of transaction messages to consume with `--amqp`, each a JSON object like a line
+	/// of `jsonl` input
+	#[cfg(feature = "amqp")]
+	#[arg(long, requires = "amqp")]
+	amqp_queue: Option<String>,
+	/// Requeue the messages that cannot be read as transactions, rather than leaving them to the
+	/// dead letter exchange of the queue
+	#[cfg(feature = "amqp")]
+	#[arg(long, requires = "amqp")]
+	amqp_requeue_invalid: bool,
+	/// After the transactions files, and the AMQP messages, process the messages of
+	/// `--kafka-topic` on these Kafka brokers, e.g. `localhost:9092`, until reading them fails
+	#[cfg(feature = "kafka")]
+	#[arg(long, requires = "kafka_topic")]
+	kafka: Option<String>,
+	/// The topic of transaction messages to consume with `--kafka`, each a JSON object like a line
+	/// of `jsonl` input
+	#[cfg(feature = "kafka")]
+	#[arg(long, requires = "kafka")]
+	kafka_topic: Option<String>,
+	/// The consumer group to consume `--kafka-topic` in, whose committed offsets it resumes from
+	#[cfg(feature = "kafka")]
+	#[arg(long, requires = "kafka", default_value = kafka::DEFAULT_GROUP)]
+	kafka_group: String,
+	/// After the transactions files, process the rows of this SQL query on `--database-url`, with
+	/// columns named like the CSV columns
+	#[cfg(feature = "postgres")]
+	#[arg(long, requires = "database_url")]
+	sql_query: Option<String>,
+	/// Also upsert the resulting accounts into this table of `--database-url`, with the columns
+	/// `client`, `available`, `held`, `total` and `locked`
+	#[cfg(feature = "postgres")]
+	#[arg(long, requires = "database_url")]
+	sql_table: Option<String>,
+	/// The number of accounts upserted into `--sql-table` per database transaction [default: 1000]
+	#[cfg(feature = "postgres")]
+	#[arg(long, requires = "sql_table")]
+	sql_batch_size: Option<usize>,
+	/// The Postgres database of `--sql-query` and `--sql-table`, e.g.
+	/// `postgres://user@localhost/payments`
+	#[cfg(feature = "postgres")]
+	#[arg(long)]
+	database_url: Option<String>,
+	/// The transactions files, processed in order into a single account state
 	extra: Vec<String>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+	/// Enter transactions line by line and see the resulting account states immediately
+	Repl,
+	/// Listen for transaction records on a TCP address and answer with account states on demand
+	Serve {
+		#[arg(default_value = "127.0.0.1:7878")]
+		address: String,
+	},
+	/// Process new transactions files as they appear in a directory, keeping the account state
+	Watch {
+		directory: String,
+		/// Seconds between checks for new files
+		#[arg(long, default_value_t = 5)]
+		interval: u64,
+	},
+	/// Work with scripted transaction scenarios
+	Scenario {
+		#[command(subcommand)]
+		action: ScenarioAction,
+	},
+}
+
+/// The supported transactions file formats.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum InputFormat {
+	/// Comma separated values with a header row
+	Csv,
+	/// Newline-delimited JSON records
+	Jsonl,
+	/// Length-delimited protobuf records, see `proto/transaction.proto`
+	Protobuf,
+	/// An XML document of `tx` elements
+	Xml,
+	/// A Parquet file with a column per CSV column
+	#[cfg(feature = "parquet")]
+	Parquet,
+	/// An Avro object container file of records with a field per CSV column
+	#[cfg(feature = "avro")]
+	Avro,
+	/// The first sheet of an XLSX workbook, with a header row like CSV input
+	#[cfg(feature = "xlsx")]
+	Xlsx,
+}
+
+impl InputFormat {
+	/// Infers the format from the file extension, defaulting to CSV. The format of a compressed
+	/// file is the extension before the compression's, as in `transactions.csv.gz`.
+	fn from_path(path: &str) -> Self {
+		let path = match InputCompression::from_path(path) {
+			Some(_) => path.rsplit_once('.').map_or(path, |(stem, _)| stem),
+			None => path,
+		};
+		match Path::new(path).extension().and_then(|e| e.to_str()) {
+			Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+			Some("pb" | "binpb") => InputFormat::Protobuf,
+			Some("xml") => InputFormat::Xml,
+			#[cfg(feature = "parquet")]
+			Some("parquet") => InputFormat::Parquet,
+			#[cfg(feature = "avro")]
+			Some("avro") => InputFormat::Avro,
+			#[cfg(feature = "xlsx")]
+			Some("xlsx") => InputFormat::Xlsx,
+			_ => InputFormat::Csv,
+		}
+	}
+}
+
+/// The supported compressions of transactions files.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum InputCompression {
+	/// gzip, including concatenated members
+	Gzip,
+	/// Zstandard
+	Zstd,
+}
+
+impl InputCompression {
+	/// Infers the compression from the file extension, if compressed.
+	fn from_path(path: &str) -> Option<Self> {
+		match Path::new(path).extension().and_then(|e| e.to_str()) {
+			Some("gz") => Some(InputCompression::Gzip),
+			Some("zst") => Some(InputCompression::Zstd),
+			_ => None,
+		}
+	}
+
+	/// Returns a reader of the decompressed content of `reader`.
+	fn decode(
+		compression: Option<Self>,
+		reader: impl AsyncRead + Unpin + Send + 'static,
+	) -> Box<dyn AsyncRead + Unpin + Send> {
+		let reader = futures::io::BufReader::new(reader);
+		match compression {
+			Some(InputCompression::Gzip) => {
+				let mut decoder = GzipDecoder::new(reader);
+				decoder.multiple_members(true);
+				Box::new(decoder)
+			},
+			Some(InputCompression::Zstd) => Box::new(ZstdDecoder::new(reader)),
+			None => Box::new(reader),
+		}
+	}
+}
+
+/// The supported account output formats.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+	/// Comma separated values with a header row
+	Csv,
+	/// A JSON array of account objects
+	Json,
+	/// One JSON account object per line
+	Jsonl,
+	/// An aligned table with a row of balance totals, for reading in a terminal
+	Table,
+	/// A Parquet file with integer, boolean and decimal columns
+	#[cfg(feature = "parquet")]
+	Parquet,
+}
+
+/// The supported roundings of output balances.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Rounding {
+	/// Round half away from zero, e.g. 0.00005 to 0.0001
+	HalfAwayFromZero,
+	/// Round half to the nearest even digit, banker's rounding, e.g. 0.00005 to 0.0000
+	HalfEven,
+	/// Truncate toward zero, e.g. 0.00009 to 0.0000
+	Truncate,
+}
+
+impl From<Rounding> for RoundingStrategy {
+	fn from(rounding: Rounding) -> Self {
+		match rounding {
+			Rounding::HalfAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+			Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+			Rounding::Truncate => RoundingStrategy::ToZero,
+		}
+	}
+}
+
+/// Configures how the transactions files are read.
+struct InputConfig {
+	/// The format of every file, inferred from each file's extension if `None`.
+	format: Option<InputFormat>,
+	/// The compression of every file, inferred from each file's extension if `None`.
+	compression: Option<InputCompression>,
+	csv: CsvFormat,
+}
+
+impl InputConfig {
+	/// Returns the format of `path`, the configured one or the one of its extension.
+	fn format(&self, path: &str) -> InputFormat {
+		self.format.unwrap_or_else(|| InputFormat::from_path(path))
+	}
+
+	/// Returns the compression of `path`, the configured one or the one of its extension.
+	fn compression(&self, path: &str) -> Option<InputCompression> {
+		self.compression.or_else(|| InputCompression::from_path(path))
+	}
+}
+
+#[derive(Subcommand, Debug)]
+enum ScenarioAction {
+	/// Run a TOML scenario file and check its expected account states
+	Run { file: String },
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
 	let args = Args::parse();
+	for (code, exponent) in &args.currency {
+		if let Err(known) = register_currency(code, *exponent) {
+			eprintln!("Error: {code} already has {} decimal places", known.exponent);
+			return ExitCode::FAILURE;
+		}
+	}
+	let amount_config = match amount_config(&args) {
+		Ok(config) => config,
+		Err(e) => {
+			eprintln!("Error: {e}");
+			return ExitCode::FAILURE;
+		},
+	};
+	if let Some(Err(e)) = args.reporting_currency.as_deref().map(parse_currency) {
+		eprintln!("Error: {e}");
+		return ExitCode::FAILURE;
+	}
+	// Everything the run reads, processes and writes uses the configured amounts.
+	amount_config.scope(run(args)).await
+}
+
+/// Runs the command of `args`, or processes its transactions files.
+async fn run(args: Args) -> ExitCode {
+	match &args.command {
+		Some(Command::Repl) => {
+			let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+			if let Err(e) = repl::run(stdin, std::io::stdout()).await {
+				eprintln!("Error: could not run the session: {e}");
+				return ExitCode::FAILURE;
+			}
+			return ExitCode::SUCCESS;
+		},
+		Some(Command::Serve { address }) => {
+			let processor = match shared_processor(&args).await {
+				Ok(processor) => processor,
+				Err(e) => {
+					eprintln!("Error: could not serve metrics: {e}");
+					return ExitCode::FAILURE;
+				},
+			};
+			let result = match tokio::net::TcpListener::bind(address).await {
+				Ok(listener) => serve::serve(listener, processor).await,
+				Err(e) => Err(e),
+			};
+			if let Err(e) = result {
+				eprintln!("Error: could not serve on {address}: {e}");
+				return ExitCode::FAILURE;
+			}
+			return ExitCode::SUCCESS;
+		},
+		Some(Command::Scenario { action: ScenarioAction::Run { file } }) => {
+			return run_scenario(file).await;
+		},
+		Some(Command::Watch { .. }) | None => {},
+	}
+
+	let serializer = match account_serializer(&args) {
+		Ok(serializer) => serializer,
+		Err(e) => {
+			eprintln!("Error: {e}");
+			return ExitCode::FAILURE;
+		},
+	};
+	let input = match input_config(&args) {
+		Ok(input) => input,
+		Err(e) => {
+			eprintln!("Error: {e}");
+			return ExitCode::FAILURE;
+		},
+	};
+	if let Some(Command::Watch { directory, interval }) = &args.command {
+		let interval = Duration::from_secs(*interval);
+		let processor = match shared_processor(&args).await {
+			Ok(processor) => processor,
+			Err(e) => {
+				eprintln!("Error: could not serve metrics: {e}");
+				return ExitCode::FAILURE;
+			},
+		};
+		let watched =
+			watch::watch(directory.as_ref(), interval, processor, &input, &serializer, args.quiet);
+		if let Err(e) = watched.await {
+			eprintln!("Error: {e}");
+		}
+		return ExitCode::FAILURE;
+	}
+	if !has_input(&args) {
+		if args.print_config {
+			eprint!("{}", processor(&args).info().await);
+			return ExitCode::SUCCESS;
+		}
+		eprintln!("Error: no transactions file provided");
+		return ExitCode::FAILURE;
+	}
+	match process_files(&args.extra, &args, &input, &serializer).await {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("Error: {e}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+/// Returns whether there is any input to process: transactions files or, with their features, a
+/// SQL query or a message queue.
+fn has_input(args: &Args) -> bool {
+	#[cfg(feature = "postgres")]
+	if args.sql_query.is_some() {
+		return true;
+	}
+	#[cfg(feature = "amqp")]
+	if args.amqp.is_some() {
+		return true;
+	}
+	#[cfg(feature = "kafka")]
+	if args.kafka.is_some() {
+		return true;
+	}
+	!args.extra.is_empty()
+}
+
+async fn run_scenario(file: &str) -> ExitCode {
+	let scenario = match std::fs::read_to_string(file) {
+		Ok(input) => Scenario::from_toml(&input).map_err(|e| e.to_string()),
+		Err(e) => Err(e.to_string()),
+	};
+	match scenario {
+		Ok(scenario) => {
+			let report = scenario.run().await;
+			print!("{report}");
+			if report.passed() {
+				ExitCode::SUCCESS
+			} else {
+				ExitCode::FAILURE
+			}
+		},
+		Err(e) => {
+			eprintln!("Error: could not load scenario {file}: {e}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+/// Builds the amount configuration from the amount options.
+fn amount_config(args: &Args) -> Result<AmountConfig, String> {
+	let parsing = match (args.lenient_amounts, args.minor_units) {
+		(true, _) => AmountParsing::Lenient,
+		(_, true) => AmountParsing::MinorUnits,
+		_ => AmountParsing::Strict,
+	};
+	Ok(AmountConfig::default()
+		.with_base_currency(parse_currency(&args.base_currency)?)
+		.with_max_decimal_places(args.max_decimal_places)
+		.with_rounding(args.rounding.into())
+		.with_parsing(parsing))
+}
+
+/// Builds the processor configured by the command line options, keeping the amount configuration
+/// in scope also in the tasks serving it.
+fn processor(args: &Args) -> TransactionProcessor {
+	let mut processor = TransactionProcessor::default()
+		.with_amount_config(AmountConfig::current())
+		.with_balance_cap(BalanceCap {
+			global: args.max_balance.map(amount),
+			per_client: client_amounts(&args.client_max_balance),
+			policy: if args.partial_deposits {
+				CapPolicy::PartiallyApply
+			} else {
+				CapPolicy::Reject
+			},
+		});
+	if args.overdraft.is_some() || !args.client_overdraft.is_empty() || !args.credit_line.is_empty()
+	{
+		processor = processor.with_overdraft(Overdraft {
+			global: args.overdraft.map(amount),
+			per_client: client_amounts(&args.client_overdraft),
+			credit_lines: client_amounts(&args.credit_line),
+		});
+	}
+	if args.duplicate_report.is_some() {
+		processor = processor.with_duplicate_detector(args.duplicate_window);
+	}
+	if let Some(records) = args.authorization_expiry {
+		processor = processor.with_authorization_expiry(records);
+	}
+	if let Some(seconds) = args.authorization_window {
+		processor = processor.with_authorization_window(seconds);
+	}
+	if let Some(seconds) = args.clearing_period {
+		processor = processor.with_clearing_period(seconds);
+	}
+	processor = processor.with_fx_rates(rate_table(args));
+	processor = processor.with_dispute_policy(DisputePolicy {
+		chargeback_after_redispute: !args.no_chargeback_after_redispute,
+		reopen_chargebacks: args.reopen_chargebacks,
+	});
+	if args.ledger.is_some() {
+		processor = processor.with_ledger();
+	}
+	if args.rejects.is_some() {
+		processor = processor.with_rejects();
+	}
+	processor
+}
+
+/// Builds the processor shared by the long running modes and starts serving its metrics, if
+/// `--metrics` is given.
+async fn shared_processor(args: &Args) -> Result<Arc<Mutex<TransactionProcessor>>, std::io::Error> {
+	let processor = Arc::new(Mutex::new(processor(args)));
+	if let Some(address) = &args.metrics {
+		let listener = tokio::net::TcpListener::bind(address).await?;
+		let processor = processor.clone();
+		tokio::spawn(async move {
+			if let Err(e) = metrics::serve_metrics(listener, processor).await {
+				eprintln!("Error: metrics endpoint failed: {e}");
+			}
+		});
+	}
+	Ok(processor)
+}
+
+/// Processes the transactions files in order into a single account state and writes the results.
+async fn process_files(
+	transactions_files: &[String],
+	args: &Args,
+	input: &InputConfig,
+	serializer: &AccountSerializer,
+) -> Result<(), ProcessRunError> {
+	let mut processor = processor(args);
+	let mut resume_offset = None;
+	if let Some(path) = &args.resume {
+		let snapshot = read_snapshot(path).map_err(ProcessRunError::CheckpointIo)?;
+		// A single CSV file is seeked to the checkpoint rather than read again up to it.
+		resume_offset = match transactions_files {
+			[file] if input.format(file) == InputFormat::Csv => snapshot.offset,
+			_ => None,
+		};
+		processor = match resume_offset {
+			Some(_) => processor.with_snapshot_at_offset(snapshot),
+			None => processor.with_snapshot(snapshot),
+		};
+	}
+	if let Some(path) = &args.state {
+		processor = processor.with_state(read_snapshot(path).map_err(ProcessRunError::SourceIo)?);
+	}
+	if args.changed_only {
+		processor = processor.with_change_tracking();
+	}
+	if let Some(path) = &args.checkpoint {
+		let path = path.clone();
+		processor = processor.with_checkpoints(args.checkpoint_interval, move |snapshot| {
+			write_checkpoint(&path, snapshot)
+		});
+	}
+	for transactions_file in transactions_files {
+		let offset = resume_offset.take();
+		process_file(&mut processor, transactions_file, input, offset, args.quiet).await?;
+	}
+	#[cfg(feature = "postgres")]
+	if let (Some(query), Some(url)) = (&args.sql_query, &args.database_url) {
+		let source = SqlSource::connect(url, query).await.map_err(ProcessRunError::SourceIo)?;
+		processor.process(source, error_handler(args.quiet)).await?;
+	}
+	#[cfg(feature = "amqp")]
+	if let (Some(uri), Some(queue)) = (&args.amqp, &args.amqp_queue) {
+		let source = AmqpSource::connect(uri, queue).await.map_err(ProcessRunError::SourceIo)?;
+		let source = source.with_requeue_invalid(args.amqp_requeue_invalid);
+		let (rejections, handler) = (source.rejections(), error_handler(args.quiet));
+		let handler = move |e: TransactionProcessorError| {
+			if let TransactionProcessingError(e) = &e {
+				rejections.reject(e.transaction().id());
+			}
+			handler(e)
+		};
+		processor.process(source, handler).await?;
+	}
+	#[cfg(feature = "kafka")]
+	if let (Some(servers), Some(topic)) = (&args.kafka, &args.kafka_topic) {
+		let source = KafkaSource::connect(servers, topic, &args.kafka_group)
+			.map_err(ProcessRunError::SourceIo)?;
+		processor.process(source, error_handler(args.quiet)).await?;
+	}
+	if args.print_config {
+		eprint!("{}", processor.info().await);
+	}
+
+	if let (Some(path), Some(duplicates)) =
+		(&args.duplicate_report, processor.suspected_duplicates())
+	{
+		let file = std::fs::File::create(path).map_err(ProcessRunError::OutputIo)?;
+		write_records(duplicates, file).map_err(ProcessRunError::OutputIo)?;
+	}
+
+	if let (Some(path), Some(rejects)) = (&args.rejects, processor.rejects()) {
+		let file = std::fs::File::create(path).map_err(ProcessRunError::OutputIo)?;
+		write_records(rejects, file).map_err(ProcessRunError::OutputIo)?;
+	}
+
+	if let (Some(path), Some(ledger)) = (&args.ledger, processor.ledger()) {
+		let file = std::fs::File::create(path).map_err(ProcessRunError::OutputIo)?;
+		write_records(ledger, file).map_err(ProcessRunError::OutputIo)?;
+	}
+
+	if let Some(path) = &args.snapshot_yaml {
+		let yaml = processor
+			.snapshot()
+			.await
+			.to_yaml()
+			.map_err(|e| ProcessRunError::OutputIo(std::io::Error::other(e)))?;
+		std::fs::write(path, yaml).map_err(ProcessRunError::OutputIo)?;
+	}
+
+	// The accounts are validated and then written in batches, never collected into a copy.
+	processor
+		.visit_accounts(|accounts| validate_accounts(accounts, serializer, args.currency_rows))
+		.await
+		.map_err(ProcessRunError::InvalidOutput)?;
+	#[cfg(feature = "postgres")]
+	if let (Some(table), Some(url)) = (&args.sql_table, &args.database_url) {
+		let sink = SqlSink::connect(url, table, serializer.clone())
+			.await
+			.map_err(ProcessRunError::OutputIo)?;
+		let sink = match args.sql_batch_size {
+			Some(batch_size) => sink.with_batch_size(batch_size),
+			None => sink,
+		};
+		// One row per client, as the table is keyed by client.
+		let written = if args.changed_only {
+			processor.write_changed_accounts(sink).await
+		} else {
+			processor.write_accounts(sink).await
+		};
+		written.map_err(ProcessRunError::OutputIo)?;
+	}
+	let (stdout, serializer) = (std::io::stdout(), serializer.clone());
+	match args.output_format {
+		OutputFormat::Csv => write_output(&processor, args, CsvSink::new(stdout, serializer)).await,
+		OutputFormat::Json => {
+			write_output(&processor, args, JsonSink::new(stdout, serializer)).await
+		},
+		OutputFormat::Jsonl => {
+			write_output(&processor, args, JsonLinesSink::new(stdout, serializer)).await
+		},
+		OutputFormat::Table => {
+			write_output(&processor, args, TableSink::new(stdout, serializer)).await
+		},
+		#[cfg(feature = "parquet")]
+		OutputFormat::Parquet => match ParquetSink::new(stdout, serializer) {
+			Ok(sink) => write_output(&processor, args, sink).await,
+			Err(e) => Err(e),
+		},
+	}
+	.map_err(ProcessRunError::OutputIo)?;
+	if !args.quiet {
+		eprint!("{}", processor.report().await);
+	}
+	Ok(())
+}
+
+/// Writes all accounts, or only the changed ones with `--changed-only`, to `sink`, as one row per
+/// currency with `--currency-rows`.
+async fn write_output(
+	processor: &TransactionProcessor,
+	args: &Args,
+	sink: impl AccountSink + Send,
+) -> Result<(), std::io::Error> {
+	match (args.changed_only, args.currency_rows) {
+		(true, true) => processor.write_changed_accounts(CurrencyRowsSink(sink)).await,
+		(true, false) => processor.write_changed_accounts(sink).await,
+		(false, true) => processor.write_accounts(CurrencyRowsSink(sink)).await,
+		(false, false) => processor.write_accounts(sink).await,
+	}
+}
 
-	let transactions_csv = args.extra.first().expect("No transactions file provided");
-	let reader = File::open(transactions_csv).await.unwrap();
+/// Reads a processor snapshot from a YAML file.
+fn read_snapshot(path: &str) -> Result<ProcessorSnapshot, std::io::Error> {
+	let yaml = std::fs::read_to_string(path)?;
+	ProcessorSnapshot::from_yaml(&yaml).map_err(std::io::Error::other)
+}
 
-	let output_accounts =
-		TransactionProcessor::process_transactions(reader, error_handler).await.unwrap();
+/// Writes a checkpoint through a temporary file, so that an interruption never leaves a partial
+/// checkpoint behind.
+fn write_checkpoint(path: &str, snapshot: &ProcessorSnapshot) -> Result<(), std::io::Error> {
+	let temporary = format!("{path}.tmp");
+	std::fs::write(&temporary, snapshot.to_yaml().map_err(std::io::Error::other)?)?;
+	std::fs::rename(temporary, path)
+}
+
+/// Processes a single transactions file into `processor`, as configured by `input`, warning about
+/// rejected and unreadable records unless `quiet`. Compressed files are decompressed as they are
+/// read. A CSV file is read from the record at byte `offset` of its content, if given.
+///
+/// With the `object-store` feature, the file may also be the URL of an object, such as
+/// `s3://bucket/transactions.csv`, which is streamed as it is read.
+async fn process_file(
+	processor: &mut TransactionProcessor,
+	transactions_file: &str,
+	input: &InputConfig,
+	offset: Option<u64>,
+	quiet: bool,
+) -> Result<(), ProcessRunError> {
+	let error_handler = error_handler(quiet);
+	let format = input.format(transactions_file);
+	let compression = input.compression(transactions_file);
+	#[cfg(feature = "object-store")]
+	if object_store::is_object_url(transactions_file) {
+		let object =
+			object_store::open(transactions_file).await.map_err(ProcessRunError::SourceIo)?;
+		let reader = InputCompression::decode(compression, object);
+		return process_reader(processor, reader, format, input, offset, error_handler).await;
+	}
+	let file = File::open(transactions_file).await.map_err(ProcessRunError::SourceIo)?;
+	match (format, compression, offset) {
+		// Uncompressed CSV files are seeked to the offset, compressed ones are read up to it.
+		(InputFormat::Csv, None, Some(offset)) => {
+			let source = CsvSource::new(file, input.csv.clone());
+			let source = source.seek_to(offset).await.map_err(ProcessRunError::SourceIo)?;
+			return processor.process(source, error_handler).await;
+		},
+		// Parquet files are read a row group at a time, which needs a seekable file.
+		#[cfg(feature = "parquet")]
+		(InputFormat::Parquet, None, _) => {
+			let source = StreamSource(Transaction::tx_stream_parquet(file));
+			return processor.process(source, error_handler).await;
+		},
+		_ => {},
+	}
+	let reader = InputCompression::decode(compression, file);
+	process_reader(processor, reader, format, input, offset, error_handler).await
+}
 
-	let stdout = std::io::stdout();
-	write_accounts(output_accounts, stdout).unwrap();
+/// Processes the content of a transactions file read from `reader`, in the given `format`, from
+/// the record at byte `offset` of CSV content if given.
+async fn process_reader(
+	processor: &mut TransactionProcessor,
+	reader: impl AsyncRead + Unpin + Send + 'static,
+	format: InputFormat,
+	input: &InputConfig,
+	offset: Option<u64>,
+	error_handler: impl Fn(TransactionProcessorError),
+) -> Result<(), ProcessRunError> {
+	match format {
+		InputFormat::Csv => {
+			let source = CsvSource::new(reader, input.csv.clone());
+			let source = match offset {
+				Some(offset) => source.skip_to(offset).await.map_err(ProcessRunError::SourceIo)?,
+				None => source,
+			};
+			processor.process(source, error_handler).await
+		},
+		InputFormat::Jsonl => {
+			let source = StreamSource(Transaction::tx_stream_json(reader));
+			processor.process(source, error_handler).await
+		},
+		InputFormat::Protobuf => {
+			let source = StreamSource(Transaction::tx_stream_protobuf(reader));
+			processor.process(source, error_handler).await
+		},
+		InputFormat::Xml => {
+			processor
+				.process(StreamSource(Transaction::tx_stream_xml(reader)), error_handler)
+				.await
+		},
+		#[cfg(feature = "avro")]
+		InputFormat::Avro => {
+			processor
+				.process(StreamSource(Transaction::tx_stream_avro(reader)), error_handler)
+				.await
+		},
+		#[cfg(feature = "xlsx")]
+		InputFormat::Xlsx => {
+			processor
+				.process(StreamSource(Transaction::tx_stream_xlsx(reader)), error_handler)
+				.await
+		},
+		#[cfg(feature = "parquet")]
+		InputFormat::Parquet => Err(ProcessRunError::SourceIo(std::io::Error::other(
+			"Parquet files can only be read from uncompressed local files, which can be seeked",
+		))),
+	}
 }
 
-fn error_handler(e: TransactionProcessorError) {
-	match e {
-		TransactionProcessingError(e) => {
-			match e {
+/// Returns the handler of rejected and unreadable records, which reports them on stderr as
+/// warnings, or not at all with `--quiet`. Neither stops processing.
+fn error_handler(quiet: bool) -> impl Fn(TransactionProcessorError) {
+	move |e| {
+		if quiet {
+			return;
+		}
+		let message = match e {
+			TransactionProcessingError(e) => match e {
 				TransactionNotFound(tx) => {
-					error!("Ignoring transaction referencing unknown transaction {:?}: ", &tx);
+					format!("Ignoring transaction referencing unknown transaction {tx:?}")
 				},
 				DuplicateGlobalTransactionId(tx) => {
-					// or panic, depending on the meaning of "Likewise, transaction IDs (tx) are globally unique", as in, should it be guaranteed ot is it guaranteed.
-					error!("Found duplicate global transaction id in: {:?}: ", &tx);
+					format!("Ignoring duplicate global transaction id in {tx:?}")
+				},
+				DuplicateIdempotencyKey(tx) => {
+					format!("Ignoring retried submission with a seen idempotency key {tx:?}")
 				},
 				InvalidTransactionId(tx) => {
-					panic!("Error: Transaction reference is wrong for transaction {:?}", &tx);
+					format!("Ignoring transaction referencing a {} {tx:?}", tx.type_name())
+				},
+				InsufficientFunds(tx) => format!("Insufficient funds for transaction {tx:?}"),
+				IllegalStateChange(tx) => format!("Illegal state change for transaction {tx:?}"),
+				AccountFrozen(tx) => format!("Account frozen for transaction {tx:?}"),
+				BalanceCapExceeded(tx) => format!("Balance cap exceeded for transaction {tx:?}"),
+				RefundExceedsOriginal(tx) => {
+					format!("Refunds would exceed the original amount for transaction {tx:?}")
+				},
+				DisputeExceedsOriginal(tx) => {
+					format!("Disputed portion exceeds the original amount for transaction {tx:?}")
 				},
-				InsufficientFunds(tx) => {
-					error!("Insufficient funds for transaction {:?}: ", &tx);
+				DisputeWindowExpired(tx) => {
+					format!("Dispute window expired for transaction {tx:?}")
 				},
-				IllegalStateChange(tx) => {
-					panic!("Error: Illegal state change for transaction {:?}: ", &tx);
+				AccountClosed(tx) => format!("Account closed for transaction {tx:?}"),
+				HeldFundsRemaining(tx) => {
+					format!("Cannot close account holding funds for transaction {tx:?}")
 				},
-				AccountFrozen(tx) => {
-					error!("Account frozen for transaction {:?}: ", &tx);
+				GroupRejected(tx) => {
+					format!("Ignoring transaction of a rejected group {tx:?}")
 				},
+				ReleaseExceedsHold(tx) => {
+					format!("Release exceeds the manually held funds for transaction {tx:?}")
+				},
+				CurrencyMismatch(tx) => {
+					format!("Referenced transaction is in a different currency for {tx:?}")
+				},
+				FxRateUnavailable(tx) => format!("No exchange rate for conversion {tx:?}"),
+				FxSlippageExceeded(tx) => {
+					format!("Rate deviates from the rate table for conversion {tx:?}")
+				},
+				SelfTransfer(tx) => format!("Ignoring transfer to its own client {tx:?}"),
+				BatchAlreadySettled(tx) => format!("Ignoring settlement of a settled batch {tx:?}"),
 				InternalError(tx, s) => {
-					panic!("Internal Error processing transaction {:?}: {}", &tx, s);
+					format!("Internal error processing transaction {tx:?}: {s}")
 				},
-			}
-		},
-		TransactionParsingError(e) => {
-			eprintln!("Error parsing transaction: {:?}", e);
-		},
+			},
+			TransactionParsingError(e) => format!("Could not parse transaction: {e}"),
+		};
+		eprintln!("Warning: {message}");
 	}
 }
 
-fn write_accounts(accounts: Vec<Account>, writer: impl Write) -> Result<(), std::io::Error> {
-	let mut csv_writer = WriterBuilder::new().has_headers(true).from_writer(writer);
+fn write_accounts(
+	accounts: impl IntoIterator<Item = impl Borrow<Account>>,
+	serializer: &AccountSerializer,
+	writer: impl Write,
+) -> Result<(), std::io::Error> {
+	let mut csv_writer = WriterBuilder::new().from_writer(writer);
+	csv_writer.write_record(serializer.header())?;
 	for account in accounts {
-		match csv_writer.serialize(account) {
-			Ok(()) => {},
-			Err(err) => {
-				eprintln!("Error serializing account: {err}");
-				let _ = std::io::stderr().write_all(err.to_string().as_bytes());
-			},
-		}
+		csv_writer.write_record(serializer.record(account.borrow()))?;
 	}
 	csv_writer.flush()?;
 	Ok(())
 }
 
+/// Checks every account record against the output schema before any of them is written, so that
+/// a malformed record fails the run instead of producing a partial or malformed file. With
+/// `currency_rows`, the rows of the currency sub-balances are checked too.
+fn validate_accounts<'a>(
+	mut accounts: impl Iterator<Item = &'a Account>,
+	serializer: &AccountSerializer,
+	currency_rows: bool,
+) -> Result<(), OutputValidationError> {
+	accounts.try_for_each(|account| {
+		serializer.validate(&serializer.record(account))?;
+		if currency_rows {
+			for row in account.currency_accounts() {
+				serializer.validate(&serializer.record(&row))?;
+			}
+		}
+		Ok(())
+	})
+}
+
+/// Builds the accounts serializer from the output options.
+fn account_serializer(args: &Args) -> Result<AccountSerializer, OutputConfigError> {
+	let mut serializer = match &args.columns {
+		Some(columns) => AccountSerializer::with_columns(columns.clone())?,
+		None if args.currency_rows => {
+			let mut columns = AccountColumn::ALL.to_vec();
+			columns.insert(1, AccountColumn::Currency);
+			AccountSerializer::with_columns(columns)?
+		},
+		None => AccountSerializer::default(),
+	};
+	for (column, header) in &args.rename {
+		serializer = serializer.with_header(*column, header);
+	}
+	if args.overdraft.is_some() || !args.client_overdraft.is_empty() || !args.credit_line.is_empty()
+	{
+		serializer = serializer.with_overdrafts();
+	}
+	if let Some(currency) =
+		args.reporting_currency.as_deref().and_then(|code| parse_currency(code).ok())
+	{
+		let rates = rate_table(args).rates_to(currency);
+		serializer = serializer.with_reporting_currency(ReportingCurrency { currency, rates });
+	}
+	Ok(serializer)
+}
+
+/// Builds the exchange rates table from the rates file and the `--fx-rate` options.
+fn rate_table(args: &Args) -> RateTable {
+	let mut table = args.fx_rates.clone().unwrap_or_default();
+	table.rates.extend(args.fx_rate.iter().cloned());
+	table.max_slippage = args.fx_max_slippage;
+	table
+}
+
+/// Builds the input configuration from the input options.
+fn input_config(args: &Args) -> Result<InputConfig, String> {
+	let mut csv = CsvFormat::default();
+	if let Some(delimiter) = args.delimiter {
+		csv = csv.with_delimiter(delimiter);
+	}
+	if let Some(columns) = &args.input_columns {
+		csv = csv.with_columns(columns.clone())?;
+	}
+	Ok(InputConfig { format: args.input_format, compression: args.input_compression, csv })
+}
+
+/// Parses a single byte field delimiter, accepting `tab` and `\t` for tabs.
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+	match value {
+		"tab" | "\\t" => Ok(b'\t'),
+		_ if value.len() == 1 => Ok(value.as_bytes()[0]),
+		_ => Err(format!("Expected a single character delimiter, got {value}")),
+	}
+}
+
+/// Parses a `column=header` pair.
+fn parse_rename(value: &str) -> Result<(AccountColumn, String), String> {
+	let (column, header) = value
+		.split_once('=')
+		.ok_or_else(|| format!("Expected column=header, got {value}"))?;
+	Ok((column.parse().map_err(|e: OutputConfigError| e.to_string())?, header.to_string()))
+}
+
+/// Parses an ISO or `--currency` code, such as `EUR`.
+fn parse_currency(value: &str) -> Result<&'static Currency, String> {
+	find_currency(&value.trim().to_uppercase()).ok_or_else(|| format!("Unknown currency {value}"))
+}
+
+/// Parses a `code=decimal places` currency definition, such as `BTC=8`.
+fn parse_currency_definition(value: &str) -> Result<(String, u32), String> {
+	let (code, exponent) = value
+		.split_once('=')
+		.ok_or_else(|| format!("Expected code=decimal places, got {value}"))?;
+	let code = code.trim().to_uppercase();
+	if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+		return Err(format!("Invalid currency code {code}"));
+	}
+	let exponent = exponent
+		.trim()
+		.parse::<u32>()
+		.ok()
+		.filter(|exponent| *exponent <= 18)
+		.ok_or_else(|| format!("Expected up to 18 decimal places, got {exponent}"))?;
+	Ok((code, exponent))
+}
+
+/// Parses a non-negative amount. It is only converted to an [`Amount`] by [`amount`] once the
+/// base currency is configured.
+fn parse_amount(value: &str) -> Result<Decimal, String> {
+	let decimal = Decimal::from_str_exact(value.trim())
+		.map_err(|e| format!("Invalid amount {value}: {e}"))?;
+	if decimal < Decimal::ZERO {
+		return Err(format!("Amount cannot be negative: {value}"));
+	}
+	Ok(decimal)
+}
+
+/// Converts an amount parsed by [`parse_amount`] to the base currency.
+fn amount(value: Decimal) -> Amount {
+	Amount::try_from(Money::from_decimal(value, base_currency())).unwrap_or_default()
+}
+
+/// Converts `client=amount` pairs parsed by [`parse_client_amount`] to the base currency.
+fn client_amounts<T: FromIterator<(ClientId, Amount)>>(values: &[(ClientId, Decimal)]) -> T {
+	values.iter().map(|(client, value)| (*client, amount(*value))).collect()
+}
+
+/// Parses a `client=amount` pair.
+fn parse_client_amount(value: &str) -> Result<(ClientId, Decimal), String> {
+	let (client, amount) = value
+		.split_once('=')
+		.ok_or_else(|| format!("Expected client=amount, got {value}"))?;
+	Ok((
+		client.trim().parse().map_err(|e| format!("Invalid client {client}: {e}"))?,
+		parse_amount(amount)?,
+	))
+}
+
+/// Parses a `FROM/TO=rate` exchange rate, such as `USD/EUR=0.92`.
+fn parse_fx_rate(value: &str) -> Result<((String, String), Decimal), String> {
+	let (pair, rate) = value
+		.split_once('=')
+		.ok_or_else(|| format!("Expected FROM/TO=rate, got {value}"))?;
+	let (from, to) = pair
+		.split_once('/')
+		.ok_or_else(|| format!("Expected FROM/TO=rate, got {value}"))?;
+	let rate =
+		Decimal::from_str_exact(rate.trim()).map_err(|e| format!("Invalid rate {rate}: {e}"))?;
+	if rate <= Decimal::ZERO {
+		return Err(format!("Rate must be positive, got {rate}"));
+	}
+	Ok(((from.trim().to_uppercase(), to.trim().to_uppercase()), rate))
+}
+
+/// Loads a `from,to,rate` exchange rates file.
+fn parse_fx_rates_file(path: &str) -> Result<RateTable, String> {
+	let file = std::fs::File::open(path).map_err(|e| format!("Could not open {path}: {e}"))?;
+	RateTable::from_csv(file).map_err(|e| format!("Invalid rates file {path}: {e}"))
+}
+
+/// Writes serializable records as CSV with a header row.
+fn write_records(records: &[impl Serialize], writer: impl Write) -> Result<(), std::io::Error> {
+	let mut csv_writer = WriterBuilder::new().has_headers(true).from_writer(writer);
+	for record in records {
+		csv_writer.serialize(record)?;
+	}
+	csv_writer.flush()
+}
+
 #[cfg(test)]
 mod tests {
 	use std::io::BufWriter;
 
+	use async_compression::futures::bufread::{GzipEncoder, ZstdEncoder};
 	use domain::account::Account;
 	use domain::amount::Amount;
+	use domain::output::{AccountColumn, AccountSerializer};
+	use domain::transaction::CsvFormat;
+	use engine::processor::TransactionProcessor;
+	use futures::AsyncReadExt;
+	use rust_decimal::Decimal;
+	use rusty_money::iso::EUR;
 
-	use crate::write_accounts;
+	use crate::{
+		parse_client_amount, parse_currency, parse_delimiter, parse_rename, process_file,
+		write_accounts, InputCompression, InputConfig, InputFormat,
+	};
 
 	#[test]
 	fn test_write_accounts() {
@@ -102,10 +1182,127 @@ mod tests {
 		let accounts = vec![account];
 		let mut out = Vec::new();
 		let writer = BufWriter::new(&mut out);
-		write_accounts(accounts, writer).unwrap();
+		write_accounts(accounts, &AccountSerializer::default(), writer).unwrap();
 
 		let expected = "client,available,held,total,locked\n1,1.1001,2.1001,3.2002,false\n";
 		let result = String::from_utf8(out).unwrap();
 		assert_eq!(expected, result);
 	}
+
+	#[test]
+	fn test_write_accounts_with_configured_columns() {
+		let account = Account::new(1, Amount::try_from("1").unwrap(), Amount::default(), true);
+		let (column, header) = parse_rename("locked=frozen").unwrap();
+		let serializer = AccountSerializer::with_columns(vec![
+			AccountColumn::Client,
+			AccountColumn::Locked,
+			AccountColumn::Available,
+			AccountColumn::Held,
+			AccountColumn::Total,
+		])
+		.unwrap()
+		.with_header(column, header);
+		let mut out = Vec::new();
+		write_accounts(vec![account], &serializer, &mut out).unwrap();
+
+		let expected = "client,frozen,available,held,total\n1,true,1.00,0.0,1.00\n";
+		assert_eq!(expected, String::from_utf8(out).unwrap());
+	}
+
+	#[test]
+	fn test_parse_client_amount() {
+		assert_eq!(parse_client_amount("1=500.0"), Ok((1, Decimal::new(5000, 1))));
+		assert!(parse_client_amount("1=-5").is_err());
+		assert!(parse_client_amount("x=5").is_err());
+	}
+
+	#[test]
+	fn test_parse_currency() {
+		assert_eq!(parse_currency("eur"), Ok(EUR));
+		assert!(parse_currency("XYZ").is_err());
+	}
+
+	#[test]
+	fn test_parse_currency_definition() {
+		assert_eq!(parse_currency_definition("btc=8"), Ok(("BTC".to_string(), 8)));
+		assert_eq!(parse_currency_definition("ETH = 18"), Ok(("ETH".to_string(), 18)));
+		assert!(parse_currency_definition("BTC").is_err());
+		assert!(parse_currency_definition("BTC=19").is_err());
+		assert!(parse_currency_definition("=8").is_err());
+	}
+
+	#[test]
+	fn test_parse_delimiter() {
+		assert_eq!(parse_delimiter("|"), Ok(b'|'));
+		assert_eq!(parse_delimiter("tab"), Ok(b'\t'));
+		assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+		assert!(parse_delimiter("||").is_err());
+		assert!(parse_delimiter("é").is_err());
+	}
+
+	#[test]
+	fn test_input_format_from_path() {
+		assert_eq!(InputFormat::from_path("transactions.jsonl"), InputFormat::Jsonl);
+		assert_eq!(InputFormat::from_path("transactions.ndjson"), InputFormat::Jsonl);
+		assert_eq!(InputFormat::from_path("transactions.pb"), InputFormat::Protobuf);
+		assert_eq!(InputFormat::from_path("transactions.xml"), InputFormat::Xml);
+		assert_eq!(InputFormat::from_path("transactions.csv"), InputFormat::Csv);
+		assert_eq!(InputFormat::from_path("transactions"), InputFormat::Csv);
+		assert_eq!(InputFormat::from_path("transactions.jsonl.gz"), InputFormat::Jsonl);
+		assert_eq!(InputFormat::from_path("transactions.csv.zst"), InputFormat::Csv);
+		#[cfg(feature = "parquet")]
+		assert_eq!(InputFormat::from_path("transactions.parquet"), InputFormat::Parquet);
+		#[cfg(feature = "avro")]
+		assert_eq!(InputFormat::from_path("transactions.avro"), InputFormat::Avro);
+		#[cfg(feature = "xlsx")]
+		assert_eq!(InputFormat::from_path("transactions.xlsx"), InputFormat::Xlsx);
+	}
+
+	#[test]
+	fn test_input_compression_from_path() {
+		assert_eq!(
+			InputCompression::from_path("transactions.csv.gz"),
+			Some(InputCompression::Gzip)
+		);
+		assert_eq!(InputCompression::from_path("transactions.zst"), Some(InputCompression::Zstd));
+		assert_eq!(InputCompression::from_path("transactions.csv"), None);
+	}
+
+	#[tokio::test]
+	async fn test_process_compressed_file() {
+		let input = b"type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,2\n";
+		let mut gzip = Vec::new();
+		GzipEncoder::new(&input[..]).read_to_end(&mut gzip).await.unwrap();
+		let mut zstd = Vec::new();
+		ZstdEncoder::new(&input[..]).read_to_end(&mut zstd).await.unwrap();
+		let directory = tempfile::tempdir().unwrap();
+		let input_config =
+			InputConfig { format: None, compression: None, csv: CsvFormat::default() };
+
+		for (name, content, offset, available) in [
+			("transactions.csv.gz", &gzip, None, "3.5"),
+			("transactions.csv.zst", &zstd, None, "3.5"),
+			// A checkpoint offset is one of the decompressed content.
+			("transactions.csv.gz", &gzip, Some(38), "2"),
+		] {
+			let path = directory.path().join(name);
+			std::fs::write(&path, content).unwrap();
+			let mut processor = TransactionProcessor::default();
+			process_file(&mut processor, path.to_str().unwrap(), &input_config, offset, true)
+				.await
+				.unwrap();
+			let accounts = processor.get_accounts().await;
+			assert_eq!(accounts[0].available.to_string(), available);
+		}
+	}
+
+	#[test]
+	fn test_parse_rename() {
+		assert_eq!(
+			parse_rename("client=client_id"),
+			Ok((AccountColumn::Client, "client_id".into()))
+		);
+		assert!(parse_rename("client").is_err());
+		assert!(parse_rename("owner=client_id").is_err());
+	}
 }
@@ -0,0 +1,159 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use engine::info::ProcessorInfo;
+use engine::processor::TransactionProcessor;
+use log::warn;
+
+/// Answers `GET /metrics` on every connection to `listener` with the counters and gauges of
+/// `processor` in the Prometheus text format, until accepting fails.
+///
+/// # Errors
+///
+/// Returns an `std::io::Error` if accepting a connection fails.
+pub(crate) async fn serve_metrics(
+	listener: TcpListener,
+	processor: Arc<Mutex<TransactionProcessor>>,
+) -> Result<(), std::io::Error> {
+	let config = processor.lock().await.amount_config();
+	loop {
+		let (socket, peer) = listener.accept().await?;
+		let processor = processor.clone();
+		tokio::spawn(config.scope(async move {
+			if let Err(e) = handle_request(socket, processor).await {
+				warn!("Metrics request from {peer} failed: {e}");
+			}
+		}));
+	}
+}
+
+async fn handle_request(
+	socket: TcpStream,
+	processor: Arc<Mutex<TransactionProcessor>>,
+) -> Result<(), std::io::Error> {
+	let (input, mut output) = socket.into_split();
+	let mut lines = BufReader::new(input).lines();
+	let request = lines.next_line().await?.unwrap_or_default();
+	while lines.next_line().await?.is_some_and(|header| !header.is_empty()) {}
+
+	let response = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+		["GET", "/metrics"] => {
+			let body = render(&processor.lock().await.info().await);
+			format!(
+				"HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+				Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+				body.len()
+			)
+		},
+		_ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into(),
+	};
+	output.write_all(response.as_bytes()).await?;
+	output.shutdown().await
+}
+
+/// Renders the counters and gauges of a processor in the Prometheus text format.
+fn render(info: &ProcessorInfo) -> String {
+	let counters = &info.counters;
+	let mut out = String::new();
+	let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+		let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+		for (labels, value) in samples {
+			let _ = writeln!(out, "{name}{labels} {value}");
+		}
+	};
+	metric(
+		"payments_records_read_total",
+		"counter",
+		"Input records read, including unparseable ones.",
+		vec![(String::new(), counters.records_read.to_string())],
+	);
+	metric(
+		"payments_parse_errors_total",
+		"counter",
+		"Input records that could not be parsed into a transaction.",
+		vec![(String::new(), counters.parse_errors.to_string())],
+	);
+	metric(
+		"payments_transactions_applied_total",
+		"counter",
+		"Transactions applied, by type.",
+		counters
+			.applied_by_type
+			.iter()
+			.map(|(tx_type, count)| (format!("{{type=\"{tx_type}\"}}"), count.to_string()))
+			.collect(),
+	);
+	metric(
+		"payments_transactions_rejected_total",
+		"counter",
+		"Transactions rejected, by reason.",
+		counters
+			.rejected_by_reason
+			.iter()
+			.map(|(reason, count)| (format!("{{reason=\"{reason}\"}}"), count.to_string()))
+			.collect(),
+	);
+	metric(
+		"payments_accounts",
+		"gauge",
+		"Accounts tracked.",
+		vec![(String::new(), info.accounts.to_string())],
+	);
+	metric(
+		"payments_locked_accounts",
+		"gauge",
+		"Accounts locked by a chargeback.",
+		vec![(String::new(), info.locked_accounts.to_string())],
+	);
+	metric(
+		"payments_held",
+		"gauge",
+		"Sum of the held balances of all accounts.",
+		vec![(String::new(), info.held.to_string())],
+	);
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use tokio::io::AsyncReadExt;
+
+	use domain::amount::Amount;
+	use domain::transaction::Transaction;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_serve_metrics() {
+		let mut processor = TransactionProcessor::default();
+		let deposit = Transaction::deposit(1, Amount::try_from("2").unwrap(), 1);
+		processor.handle_transaction(deposit.clone()).await.unwrap();
+		processor.handle_transaction(deposit).await.unwrap_err();
+		let withdrawal = Transaction::withdrawal(2, Amount::try_from("0.5").unwrap(), 1);
+		processor.handle_transaction(withdrawal).await.unwrap();
+
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap();
+		tokio::spawn(serve_metrics(listener, Arc::new(Mutex::new(processor))));
+
+		let mut client = TcpStream::connect(address).await.unwrap();
+		client
+			.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+			.await
+			.unwrap();
+		let mut response = String::new();
+		client.read_to_string(&mut response).await.unwrap();
+
+		assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+		assert!(response.contains("\npayments_transactions_applied_total{type=\"deposit\"} 1\n"));
+		assert!(response.contains("\npayments_transactions_applied_total{type=\"withdrawal\"} 1\n"));
+		assert!(response
+			.contains("\npayments_transactions_rejected_total{reason=\"duplicate_tx\"} 1\n"));
+		assert!(response.contains("\npayments_accounts 1\n"));
+		assert!(response.contains("\npayments_held 0.0\n"));
+	}
+}
@@ -0,0 +1,185 @@
+use std::io::Write;
+
+use futures::io::Cursor;
+use itertools::Itertools;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use domain::output::AccountSerializer;
+use domain::transaction::{CsvError, StreamExt, Transaction};
+use engine::processor::TransactionProcessor;
+
+use crate::write_accounts;
+
+/// Header used to parse single rows and to save the session as a transactions file.
+const HEADER: &str = "type,client,tx,amount";
+const PROMPT: &str = "> ";
+const HELP: &str = "\
+Enter one transaction per line, either as CSV or space separated:
+  deposit,1,1,1.5      withdrawal 1 2 0.5      dispute 1 1
+Commands:
+  accounts             print the current account states
+  save <path>          save the session as a replayable transactions file
+  help                 print this message
+  quit                 exit the repl
+";
+
+/// A single line of repl input.
+#[derive(Debug, PartialEq)]
+enum ReplCommand<'a> {
+	Empty,
+	Help,
+	Accounts,
+	Save(&'a str),
+	Quit,
+	Transaction(String),
+}
+
+impl<'a> From<&'a str> for ReplCommand<'a> {
+	fn from(line: &'a str) -> Self {
+		let line = line.trim();
+		match line.split_whitespace().collect_vec().as_slice() {
+			[] => ReplCommand::Empty,
+			["help"] => ReplCommand::Help,
+			["accounts"] => ReplCommand::Accounts,
+			["save", path] => ReplCommand::Save(path),
+			["quit"] | ["exit"] => ReplCommand::Quit,
+			_ => ReplCommand::Transaction(normalize_row(line)),
+		}
+	}
+}
+
+/// Normalizes a CSV or space separated row into a CSV row with all four columns.
+pub(crate) fn normalize_row(line: &str) -> String {
+	let mut fields = if line.contains(',') {
+		line.split(',').map(str::trim).collect_vec()
+	} else {
+		line.split_whitespace().collect_vec()
+	};
+	while fields.len() < HEADER.split(',').count() {
+		fields.push("");
+	}
+	fields.join(",")
+}
+
+/// Parses a single normalized row into a transaction, applying the same validation as file input.
+pub(crate) async fn parse_row(row: &str) -> Option<Result<Transaction, CsvError>> {
+	let csv = format!("{HEADER}\n{row}");
+	Transaction::tx_stream(Cursor::new(csv.into_bytes())).next().await
+}
+
+/// Runs an interactive session, reading transactions and commands from `input` line by line
+/// and writing the resulting account states to `output` after every transaction.
+///
+/// # Errors
+///
+/// Returns an `std::io::Error` if reading the input or writing the output fails.
+pub(crate) async fn run(
+	input: impl AsyncBufRead + Unpin,
+	mut output: impl Write,
+) -> Result<(), std::io::Error> {
+	let mut processor = TransactionProcessor::default();
+	let mut session: Vec<String> = Vec::new();
+	let mut lines = input.lines();
+
+	write!(output, "{PROMPT}")?;
+	output.flush()?;
+	while let Some(line) = lines.next_line().await? {
+		match ReplCommand::from(line.as_str()) {
+			ReplCommand::Empty => {},
+			ReplCommand::Help => write!(output, "{HELP}")?,
+			ReplCommand::Accounts => print_accounts(&processor, &mut output).await?,
+			ReplCommand::Save(path) => {
+				let mut contents =
+					std::iter::once(HEADER).chain(session.iter().map(String::as_str));
+				match std::fs::write(path, contents.join("\n") + "\n") {
+					Ok(()) => writeln!(output, "Saved {} transactions to {path}", session.len())?,
+					Err(e) => writeln!(output, "Could not save session to {path}: {e}")?,
+				}
+			},
+			ReplCommand::Quit => break,
+			ReplCommand::Transaction(row) => match parse_row(&row).await {
+				Some(Ok(tx)) => {
+					match processor.handle_transaction(tx).await {
+						Ok(()) => print_accounts(&processor, &mut output).await?,
+						Err(e) => writeln!(output, "Rejected: {e:?}")?,
+					}
+					session.push(row);
+				},
+				Some(Err(e)) => writeln!(output, "Could not parse transaction: {e}")?,
+				None => writeln!(output, "Could not parse transaction: {row}")?,
+			},
+		}
+		write!(output, "{PROMPT}")?;
+		output.flush()?;
+	}
+	Ok(())
+}
+
+/// Writes the current account states as CSV, sorted by client.
+pub(crate) async fn print_accounts(
+	processor: &TransactionProcessor,
+	output: &mut impl Write,
+) -> Result<(), std::io::Error> {
+	let accounts = processor.get_accounts().await;
+	let accounts = accounts.into_iter().sorted_by_key(|a| a.client_id);
+	write_accounts(accounts, &AccountSerializer::default(), output)
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::NamedTempFile;
+
+	use super::*;
+
+	async fn run_session(input: &str) -> String {
+		let mut out = Vec::new();
+		run(input.as_bytes(), &mut out).await.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn test_parse_command() {
+		assert_eq!(ReplCommand::from("  "), ReplCommand::Empty);
+		assert_eq!(ReplCommand::from("accounts"), ReplCommand::Accounts);
+		assert_eq!(ReplCommand::from("save out.csv"), ReplCommand::Save("out.csv"));
+		assert_eq!(ReplCommand::from("exit"), ReplCommand::Quit);
+		assert_eq!(
+			ReplCommand::from("deposit 1 2 1.5"),
+			ReplCommand::Transaction("deposit,1,2,1.5".to_string())
+		);
+		assert_eq!(
+			ReplCommand::from("dispute, 1, 2"),
+			ReplCommand::Transaction("dispute,1,2,".to_string())
+		);
+	}
+
+	#[tokio::test]
+	async fn test_run_prints_accounts_after_each_transaction() {
+		let output = run_session("deposit 1 1 2.0\nwithdrawal,1,2,0.5\ndispute 1 1\n").await;
+
+		assert!(output.contains("1,2.0,0.0,"));
+		assert!(output.contains("1,1.5,0.0,"));
+		assert!(output.contains("Rejected: InsufficientFunds"));
+	}
+
+	#[tokio::test]
+	async fn test_run_reports_parse_errors() {
+		let output = run_session("deposit 1 1\n").await;
+
+		assert!(output.contains("Could not parse transaction"));
+	}
+
+	#[tokio::test]
+	async fn test_run_saves_session() {
+		let file = NamedTempFile::new().unwrap();
+		let path = file.path().to_str().unwrap();
+
+		run_session(&format!(
+			"deposit 1 1 2.0\ndeposit 1 2\ndispute 1 1\nsave {path}\nquit\ndeposit 1 3 1\n"
+		))
+		.await;
+
+		let saved = std::fs::read_to_string(path).unwrap();
+		assert_eq!(saved, "type,client,tx,amount\ndeposit,1,1,2.0\ndispute,1,1,\n");
+	}
+}
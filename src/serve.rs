@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use domain::transaction::{CsvError, StreamExt, Transaction};
+use engine::processor::TransactionProcessor;
+use log::warn;
+
+use crate::repl::{normalize_row, parse_row, print_accounts};
+
+/// Serves `processor` to every client connecting to `listener`, until accepting fails.
+///
+/// Clients send one record per line, as a CSV row (`deposit,1,1,1.5`) or a JSON object, and
+/// receive `ok`, `rejected: <reason>` or `error: <reason>` for each. The line `accounts` is
+/// answered with the current account states as CSV, terminated by an empty line.
+///
+/// # Errors
+///
+/// Returns an `std::io::Error` if accepting a connection fails.
+pub(crate) async fn serve(
+	listener: TcpListener,
+	processor: Arc<Mutex<TransactionProcessor>>,
+) -> Result<(), std::io::Error> {
+	// Records are parsed in the connection tasks, with the amount configuration of the processor.
+	let config = processor.lock().await.amount_config();
+	loop {
+		let (socket, peer) = listener.accept().await?;
+		let processor = processor.clone();
+		tokio::spawn(config.scope(async move {
+			if let Err(e) = handle_connection(socket, processor).await {
+				warn!("Connection from {peer} failed: {e}");
+			}
+		}));
+	}
+}
+
+async fn handle_connection(
+	socket: TcpStream,
+	processor: Arc<Mutex<TransactionProcessor>>,
+) -> Result<(), std::io::Error> {
+	let (input, mut output) = socket.into_split();
+	let mut lines = BufReader::new(input).lines();
+	while let Some(line) = lines.next_line().await? {
+		let line = line.trim();
+		let response = match line {
+			"" => continue,
+			"accounts" => {
+				let mut accounts = Vec::new();
+				print_accounts(&*processor.lock().await, &mut accounts).await?;
+				accounts.push(b'\n');
+				accounts
+			},
+			_ => {
+				let tx = if line.starts_with('{') {
+					parse_json_row(line).await
+				} else {
+					parse_row(&normalize_row(line)).await
+				};
+				match tx {
+					Some(Ok(tx)) => match processor.lock().await.handle_transaction(tx).await {
+						Ok(()) => "ok\n".to_string(),
+						Err(e) => format!("rejected: {e:?}\n"),
+					},
+					Some(Err(e)) => format!("error: {e}\n"),
+					None => format!("error: could not parse {line}\n"),
+				}
+				.into_bytes()
+			},
+		};
+		output.write_all(&response).await?;
+	}
+	Ok(())
+}
+
+/// Parses a single JSON record into a transaction, applying the same validation as file input.
+async fn parse_json_row(line: &str) -> Option<Result<Transaction, CsvError>> {
+	let input = futures::io::Cursor::new(line.as_bytes().to_vec());
+	Transaction::tx_stream_json(input).next().await
+}
+
+#[cfg(test)]
+mod tests {
+	use tokio::io::AsyncReadExt;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_serve() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap();
+		tokio::spawn(serve(listener, Arc::new(Mutex::new(TransactionProcessor::default()))));
+
+		let mut client = TcpStream::connect(address).await.unwrap();
+		client
+			.write_all(
+				b"deposit,1,1,2.0\n{\"type\": \"withdrawal\", \"client\": 1, \"tx\": 2, \"amount\": \"0.5\"}\n\
+				withdrawal 1 3 5\ndeposit 1\naccounts\n",
+			)
+			.await
+			.unwrap();
+		client.shutdown().await.unwrap();
+		let mut response = String::new();
+		client.read_to_string(&mut response).await.unwrap();
+
+		let lines: Vec<&str> = response.lines().collect();
+		assert_eq!(lines[..2], ["ok", "ok"]);
+		assert!(lines[2].starts_with("rejected: InsufficientFunds"));
+		assert!(lines[3].starts_with("error: "));
+		assert_eq!(lines[4..], ["client,available,held,total,locked", "1,1.5,0.0,1.5,false", ""]);
+	}
+}
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use itertools::Itertools;
+use tokio::sync::Mutex;
+
+use domain::output::AccountSerializer;
+use engine::processor::{ProcessRunError, TransactionProcessor};
+
+use crate::{process_file, write_accounts, InputConfig};
+
+/// Watches `directory` for new transactions files, checking every `interval`, and processes each
+/// new file in name order into `processor`, writing the cumulative account states to stdout
+/// after every file. A file that cannot be processed is reported on stderr and not retried, and
+/// watching continues with the next one.
+///
+/// Hidden files are skipped, so producers can write `.name` and rename it once complete.
+///
+/// # Errors
+///
+/// Returns a [`ProcessRunError`] if listing the directory or writing the accounts fails.
+pub(crate) async fn watch(
+	directory: &Path,
+	interval: Duration,
+	processor: Arc<Mutex<TransactionProcessor>>,
+	input: &InputConfig,
+	serializer: &AccountSerializer,
+	quiet: bool,
+) -> Result<(), ProcessRunError> {
+	let mut processed = HashSet::new();
+	let mut ticker = tokio::time::interval(interval);
+	loop {
+		ticker.tick().await;
+		for file in new_files(directory, &mut processed).map_err(ProcessRunError::SourceIo)? {
+			let mut processor = processor.lock().await;
+			let path = file.to_string_lossy();
+			if let Err(e) = process_file(&mut processor, &path, input, None, quiet).await {
+				eprintln!("Error: could not process {path}: {e}");
+				continue;
+			}
+			let accounts = processor
+				.get_accounts()
+				.await
+				.into_iter()
+				.sorted_by_key(|account| account.client_id);
+			write_accounts(accounts, serializer, std::io::stdout())
+				.map_err(ProcessRunError::OutputIo)?;
+		}
+	}
+}
+
+/// Lists the files in `directory` that are not in `processed` in name order, and adds them to it.
+fn new_files(
+	directory: &Path,
+	processed: &mut HashSet<PathBuf>,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+	let mut files = Vec::new();
+	for entry in std::fs::read_dir(directory)? {
+		let entry = entry?;
+		let hidden = entry.file_name().to_string_lossy().starts_with('.');
+		if !hidden && entry.file_type()?.is_file() && !processed.contains(&entry.path()) {
+			files.push(entry.path());
+		}
+	}
+	files.sort();
+	processed.extend(files.iter().cloned());
+	Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	use domain::transaction::CsvFormat;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_watch_continues_after_failed_file() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = directory.path();
+		std::fs::write(path.join("a.csv"), "type,client,tx,amount\ndeposit,1,1,5\ndeposit,1,2,5")
+			.unwrap();
+		std::fs::write(path.join("b.csv"), "type,client,tx,amount\ndeposit,2,3,7").unwrap();
+		// The first checkpoint fails, which fails the first file after its first record.
+		let failed = AtomicBool::new(false);
+		let processor = TransactionProcessor::default().with_checkpoints(1, move |_| {
+			if failed.swap(true, Ordering::SeqCst) {
+				Ok(())
+			} else {
+				Err(std::io::Error::other("disk full"))
+			}
+		});
+		let processor = Arc::new(Mutex::new(processor));
+		let input = InputConfig { format: None, compression: None, csv: CsvFormat::default() };
+		let serializer = AccountSerializer::default();
+
+		let interval = Duration::from_millis(10);
+		let watched = watch(path, interval, processor.clone(), &input, &serializer, true);
+		assert!(tokio::time::timeout(Duration::from_millis(200), watched).await.is_err());
+		let balances = processor
+			.lock()
+			.await
+			.get_accounts()
+			.await
+			.into_iter()
+			.map(|account| (account.client_id, account.available.to_string()))
+			.sorted()
+			.collect_vec();
+		assert_eq!(balances, vec![(1, "5".to_string()), (2, "7".to_string())]);
+	}
+
+	#[test]
+	fn test_new_files() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = directory.path();
+		std::fs::write(path.join("b.csv"), "").unwrap();
+		std::fs::write(path.join("a.csv"), "").unwrap();
+		std::fs::write(path.join(".c.csv"), "").unwrap();
+		std::fs::create_dir(path.join("d")).unwrap();
+		let mut processed = HashSet::new();
+
+		assert_eq!(
+			new_files(path, &mut processed).unwrap(),
+			vec![path.join("a.csv"), path.join("b.csv")]
+		);
+		std::fs::rename(path.join(".c.csv"), path.join("c.csv")).unwrap();
+		assert_eq!(new_files(path, &mut processed).unwrap(), vec![path.join("c.csv")]);
+		assert!(new_files(path, &mut processed).unwrap().is_empty());
+	}
+}